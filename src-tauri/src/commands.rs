@@ -23,9 +23,12 @@ use crate::optimizer::prompt_generator::{EnhancedPrompt, EnhancedPromptRequest};
 use crate::parser::{
     extractor::{ExportFormat, ExtractionEngine, ExtractionLevel},
     jsonl::JsonlParser,
-    tree::{ConversationTree, MessageTreeBuilder},
+    tree::{
+        ConversationTree, MessageMetadata, MessageNode, MessageTreeBuilder, NodeIndex,
+        SidechainCursor,
+    },
 };
-use crate::session_type_detector::SessionFileType;
+use crate::session_type_detector::{detect_session_type_by_filename, SessionFileType};
 use crate::tokenizer::{TokenCounter, TokenEncodingType};
 
 // ==================== 性能基准测试模块（内联） ====================
@@ -782,7 +785,6 @@ pub async fn scan_sessions(
     _manager: State<'_, LLMClientManager>,
 ) -> std::result::Result<Vec<SessionMeta>, CommandError> {
     use crate::database::repository::{MonitoredDirectoryRepository, SessionRepository};
-    use crate::monitor::scanner;
 
     // 获取用户配置的监控目录列表
     let dir_repo = MonitoredDirectoryRepository::from_default_db().map_err(|e| CommandError {
@@ -804,7 +806,7 @@ pub async fn scan_sessions(
     let mut all_sessions = Vec::new();
     for directory in directories {
         let path = std::path::PathBuf::from(&directory.path);
-        match scanner::scan_directory(&path) {
+        match directory.source.adapter().scan(&path) {
             Ok(mut sessions) => {
                 all_sessions.append(&mut sessions);
             }
@@ -833,6 +835,9 @@ pub async fn scan_sessions(
         );
     }
 
+    // 后台同步全文索引和消息索引
+    crate::monitor::watcher::submit_scanned_sessions(&all_sessions);
+
     // 转换为返回格式
     let result: Vec<SessionMeta> = all_sessions
         .into_iter()
@@ -850,21 +855,22 @@ pub async fn scan_sessions(
     Ok(result)
 }
 
-/// 扫描指定目录的 Claude Code 会话文件
+/// 扫描指定目录的会话文件
 ///
-/// 扫描用户选择的目录，查找所有 .jsonl 会话文件并提取元数据
+/// 扫描用户选择的目录，按会话来源查找会话文件并提取元数据
 ///
 /// # 参数
 /// - `directory`: 要扫描的目录路径
+/// - `source`: 会话来源（默认 Claude Code）
 ///
 /// # 返回
 /// 返回会话元数据列表
 #[tauri::command]
 pub async fn scan_directory(
     directory: String,
+    source: Option<crate::parser::source::SessionSourceKind>,
 ) -> std::result::Result<Vec<SessionMeta>, CommandError> {
     use crate::database::repository::SessionRepository;
-    use crate::monitor::scanner;
 
     let path = PathBuf::from(&directory);
 
@@ -882,9 +888,13 @@ pub async fn scan_directory(
     }
 
     // 扫描指定目录的会话文件
-    let sessions_metadata = scanner::scan_directory(&path).map_err(|e| CommandError {
-        message: format!("扫描目录失败: {}", e),
-    })?;
+    let sessions_metadata = source
+        .unwrap_or_default()
+        .adapter()
+        .scan(&path)
+        .map_err(|e| CommandError {
+            message: format!("扫描目录失败: {}", e),
+        })?;
 
     // 获取数据库连接并创建 SessionRepository
     let conn = crate::database::init::get_connection_shared().map_err(|e| CommandError {
@@ -904,6 +914,9 @@ pub async fn scan_directory(
         );
     }
 
+    // 后台同步全文索引和消息索引
+    crate::monitor::watcher::submit_scanned_sessions(&sessions_metadata);
+
     // 转换为返回格式
     let result: Vec<SessionMeta> = sessions_metadata
         .into_iter()
@@ -1020,8 +1033,14 @@ pub struct ParseSessionResponse {
     /// 会话 ID
     pub session_id: String,
 
-    /// 消息树
-    pub tree: ConversationTree,
+    /// 消息树（增量更新时为 None，新增节点见 `appended`）
+    pub tree: Option<ConversationTree>,
+
+    /// 增量更新时新增的子树（全量解析时为空）
+    pub appended: Vec<AppendedSubtree>,
+
+    /// 增量更新时内容发生变化的已有节点（全量解析时为空）
+    pub updated: Vec<UpdatedNode>,
 
     /// 解析耗时（毫秒）
     pub parse_duration_ms: f64,
@@ -1031,6 +1050,101 @@ pub struct ParseSessionResponse {
 
     /// 最大深度
     pub max_depth: usize,
+
+    /// 已解析到的字节偏移量
+    pub next_offset: u64,
+
+    /// 本次新增挂载的节点数量（全量解析时为 0）
+    pub appended_count: usize,
+
+    /// 是否为增量更新（false 表示整棵树被重建）
+    pub incremental: bool,
+
+    /// 本次新拼接的子代理会话数量
+    pub sidechain_count: usize,
+
+    /// 本次解析中无法解析的行
+    pub diagnostics: Vec<crate::parser::jsonl::LineDiagnostic>,
+}
+
+/// 增量更新中新增的子树
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppendedSubtree {
+    /// 挂载点节点 ID（None 表示新的根节点）
+    pub parent_id: Option<String>,
+
+    /// 新增的子树
+    pub node: MessageNode,
+}
+
+/// 增量更新中内容发生变化的已有节点
+///
+/// 新条目中的工具结果会回填到已有节点的工具调用上，新分支也会改变已有兄弟节点的
+/// 分支标记（分支数、存活分支），前端按 ID 替换即可
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatedNode {
+    /// 节点 ID
+    pub node_id: String,
+
+    /// 更新后的分支标记
+    pub branch: Option<crate::parser::branch::BranchLabel>,
+
+    /// 更新后的元数据
+    pub metadata: Option<MessageMetadata>,
+}
+
+/// 会话树缓存条目
+///
+/// 保存解析器的偏移量状态和已构建的消息树，供增量解析复用
+struct SessionTreeCacheEntry {
+    parser: JsonlParser,
+    tree: ConversationTree,
+    index: NodeIndex,
+    sidechains: SidechainCursor,
+    last_access: Instant,
+}
+
+/// 会话树缓存的最大条目数
+const SESSION_TREE_CACHE_LIMIT: usize = 8;
+
+/// 会话树缓存（按文件路径索引）
+static SESSION_TREE_CACHE: once_cell::sync::Lazy<
+    std::sync::Mutex<std::collections::HashMap<String, SessionTreeCacheEntry>>,
+> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(std::collections::HashMap::new()));
+
+/// 从缓存中取出会话树条目（取出后立即释放锁）
+fn take_cached_session_tree(
+    file_path: &str,
+) -> std::result::Result<Option<SessionTreeCacheEntry>, CommandError> {
+    let mut cache = SESSION_TREE_CACHE.lock().map_err(|e| CommandError {
+        message: format!("获取会话树缓存锁失败: {}", e),
+    })?;
+    Ok(cache.remove(file_path))
+}
+
+/// 将会话树条目放回缓存，超出容量时淘汰最久未访问的条目
+fn store_cached_session_tree(
+    file_path: String,
+    entry: SessionTreeCacheEntry,
+) -> std::result::Result<(), CommandError> {
+    let mut cache = SESSION_TREE_CACHE.lock().map_err(|e| CommandError {
+        message: format!("获取会话树缓存锁失败: {}", e),
+    })?;
+
+    if cache.len() >= SESSION_TREE_CACHE_LIMIT && !cache.contains_key(&file_path) {
+        if let Some(oldest) = cache
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_access)
+            .map(|(key, _)| key.clone())
+        {
+            cache.remove(&oldest);
+        }
+    }
+
+    cache.insert(file_path, entry);
+    Ok(())
 }
 
 /// 解析会话文件并构建消息树
@@ -1040,14 +1154,17 @@ pub struct ParseSessionResponse {
 ///
 /// # 参数
 /// * `file_path` - JSONL 会话文件的完整路径
+/// * `incremental` - (可选) 是否增量解析。为 true 时复用缓存的消息树，只解析新追加的行
 ///
 /// # 返回
-/// 返回完整的消息树结构，包含所有消息的嵌套关系
+/// 全量解析时返回完整的消息树；增量更新时 `tree` 为 None，
+/// 只在 `appended` 中返回新增的子树及其挂载点，在 `updated` 中返回发生变化的已有节点
 ///
 /// # 算法特点
 /// - **迭代算法**：使用迭代而非递归构建树，避免深层嵌套导致栈溢出
 /// - **根节点识别**：自动识别 User 消息作为根节点
 /// - **深度计算**：自动计算每个节点的树深度
+/// - **增量更新**：缓存解析偏移量，文件追加时只把新条目挂载到已有的树上
 ///
 /// # 示例
 /// ```javascript
@@ -1060,8 +1177,15 @@ pub struct ParseSessionResponse {
 #[tauri::command]
 pub async fn parse_session_tree(
     file_path: String,
+    incremental: Option<bool>,
 ) -> std::result::Result<ParseSessionResponse, CommandError> {
-    let path = PathBuf::from(&file_path);
+    // 已压缩归档的会话读取解压后的缓存文件
+    let path =
+        crate::session_archive::resolve_session_path(&PathBuf::from(&file_path)).map_err(|e| {
+            CommandError {
+                message: format!("读取归档会话失败: {}", e),
+            }
+        })?;
 
     // 验证文件存在
     if !path.exists() {
@@ -1072,34 +1196,221 @@ pub async fn parse_session_tree(
 
     let start = std::time::Instant::now();
 
-    // 创建 JSONL 解析器并解析所有条目
-    let mut parser = JsonlParser::new(path).map_err(|e| CommandError {
-        message: format!("创建 JSONL 解析器失败: {}", e),
-    })?;
+    // 其他来源的会话需要整体转换，不参与增量缓存
+    let adapter = crate::parser::source::adapter_for_path(&path);
+    if adapter.kind() != crate::parser::source::SessionSourceKind::ClaudeCode {
+        return parse_foreign_session_tree(adapter, &path, start);
+    }
 
-    let entries = parser.parse_all().map_err(|e| CommandError {
-        message: format!("解析 JSONL 文件失败: {}", e),
-    })?;
+    // 取出缓存条目后释放锁，文件读取和树构建都在锁外进行
+    // 非增量模式：丢弃缓存，重新构建
+    let cached = take_cached_session_tree(&file_path)?.filter(|_| incremental.unwrap_or(false));
 
-    // 构建消息树
-    let tree = MessageTreeBuilder::build_from_entries(&entries).map_err(|e| CommandError {
-        message: format!("构建消息树失败: {}", e),
-    })?;
+    let mut is_incremental = false;
+
+    let (mut cached, appended_count, diagnostics) = match cached {
+        Some(mut cached) => {
+            // 只解析新追加的条目
+            let result = cached
+                .parser
+                .parse_incremental()
+                .map_err(|e| CommandError {
+                    message: format!("增量解析 JSONL 文件失败: {}", e),
+                })?;
+
+            if result.reset {
+                // 文件被截断或替换，重建消息树并重新拼接子代理会话
+                cached.tree =
+                    MessageTreeBuilder::build_from_entries(&result.entries).map_err(|e| {
+                        CommandError {
+                            message: format!("构建消息树失败: {}", e),
+                        }
+                    })?;
+                cached.index = NodeIndex::build(&cached.tree);
+                cached.sidechains = SidechainCursor::default();
+                (cached, 0, result.diagnostics)
+            } else {
+                is_incremental = true;
+                let appended = MessageTreeBuilder::append_entries(
+                    &mut cached.tree,
+                    &mut cached.index,
+                    &result.entries,
+                )
+                .map_err(|e| CommandError {
+                    message: format!("合并新消息失败: {}", e),
+                })?;
+                (cached, appended, result.diagnostics)
+            }
+        }
+        None => {
+            // 创建 JSONL 解析器并解析所有条目
+            let mut parser = JsonlParser::new(path).map_err(|e| CommandError {
+                message: format!("创建 JSONL 解析器失败: {}", e),
+            })?;
+
+            let result = parser.parse_incremental().map_err(|e| CommandError {
+                message: format!("解析 JSONL 文件失败: {}", e),
+            })?;
+
+            // 构建消息树
+            let tree = MessageTreeBuilder::build_from_entries(&result.entries).map_err(|e| {
+                CommandError {
+                    message: format!("构建消息树失败: {}", e),
+                }
+            })?;
+            let index = NodeIndex::build(&tree);
+
+            (
+                SessionTreeCacheEntry {
+                    parser,
+                    tree,
+                    index,
+                    sidechains: SidechainCursor::default(),
+                    last_access: Instant::now(),
+                },
+                0,
+                result.diagnostics,
+            )
+        }
+    };
+
+    // 拼接同目录下属于该会话的子代理会话（只读取 agent 文件新追加的条目）
+    let sidechain_count = if detect_session_type_by_filename(&file_path).is_main() {
+        MessageTreeBuilder::attach_sidechains(
+            &mut cached.tree,
+            &mut cached.index,
+            std::path::Path::new(&file_path),
+            &mut cached.sidechains,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("⚠️  拼接子代理会话失败: {}", e);
+            0
+        })
+    } else {
+        0
+    };
+
+    // 本次新挂载的子树（主会话追加的条目和子代理会话），
+    // 挂在其他新子树内部的侧链已包含在外层子树中
+    let attached = cached.index.take_attached();
+
+    // 标记编辑、回退或重试产生的兄弟分支，增量更新时只重新标记受影响的分叉点
+    if is_incremental {
+        crate::parser::branch::BranchDetector::relabel(
+            &mut cached.tree,
+            &mut cached.index,
+            &attached,
+        );
+    } else {
+        crate::parser::branch::BranchDetector::annotate(&mut cached.tree);
+    }
+
+    let updated_ids = cached.index.take_updated();
+    let (tree, appended, updated) = if is_incremental {
+        let attached_ids: std::collections::HashSet<&str> =
+            attached.iter().map(|id| id.as_str()).collect();
+        let appended = attached
+            .iter()
+            .filter(|id| {
+                !cached
+                    .index
+                    .ancestors(id)
+                    .any(|ancestor| attached_ids.contains(ancestor))
+            })
+            .filter_map(|id| {
+                Some(AppendedSubtree {
+                    parent_id: cached.index.parent_id(id).map(|s| s.to_string()),
+                    node: cached.index.get(&cached.tree, id)?.clone(),
+                })
+            })
+            .collect();
+        let updated = updated_ids
+            .iter()
+            .filter_map(|id| {
+                let node = cached.index.get(&cached.tree, id)?;
+                Some(UpdatedNode {
+                    node_id: id.clone(),
+                    branch: node.branch.clone(),
+                    metadata: node.metadata.clone(),
+                })
+            })
+            .collect();
+        (None, appended, updated)
+    } else {
+        (Some(cached.tree.clone()), Vec::new(), Vec::new())
+    };
+
+    let message_count = cached.tree.total_count;
+    let max_depth = cached.tree.max_depth;
+    let next_offset = cached.parser.consumed_offset();
+    cached.last_access = Instant::now();
+    store_cached_session_tree(file_path.clone(), cached)?;
 
     let duration = start.elapsed();
 
     // 提取会话 ID（从文件路径或第一条消息）
     let session_id = extract_session_id(&file_path);
 
-    let message_count = tree.total_count;
-    let max_depth = tree.max_depth;
-
     Ok(ParseSessionResponse {
         session_id,
         tree,
+        appended,
+        updated,
         parse_duration_ms: duration.as_secs_f64() * 1000.0,
         message_count,
         max_depth,
+        next_offset,
+        appended_count,
+        incremental: is_incremental,
+        sidechain_count,
+        diagnostics,
+    })
+}
+
+/// 读取任意来源的会话文件，返回 Claude Code 兼容的条目（已压缩归档的会话读取解压缓存）
+fn read_session_entries(
+    file_path: &str,
+) -> std::result::Result<Vec<crate::parser::jsonl::JsonlEntry>, CommandError> {
+    let path = crate::session_archive::resolve_session_path(std::path::Path::new(file_path))
+        .map_err(|e| CommandError {
+            message: format!("读取归档会话失败: {}", e),
+        })?;
+    crate::parser::source::read_entries(&path).map_err(|e| CommandError {
+        message: format!("解析会话文件失败: {}", e),
+    })
+}
+
+/// 通过会话来源适配器构建消息树（Codex CLI、Aider 等）
+fn parse_foreign_session_tree(
+    adapter: &dyn crate::parser::source::SessionSourceAdapter,
+    path: &std::path::Path,
+    start: Instant,
+) -> std::result::Result<ParseSessionResponse, CommandError> {
+    let entries = adapter.read_entries(path).map_err(|e| CommandError {
+        message: format!("读取会话文件失败: {}", e),
+    })?;
+    let mut tree = MessageTreeBuilder::build_from_entries(&entries).map_err(|e| CommandError {
+        message: format!("构建消息树失败: {}", e),
+    })?;
+    crate::parser::branch::BranchDetector::annotate(&mut tree);
+
+    let next_offset = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let message_count = tree.total_count;
+    let max_depth = tree.max_depth;
+
+    Ok(ParseSessionResponse {
+        session_id: crate::parser::source::session_id_from_entries(path, &entries),
+        tree: Some(tree),
+        appended: Vec::new(),
+        updated: Vec::new(),
+        parse_duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+        message_count,
+        max_depth,
+        next_offset,
+        appended_count: 0,
+        incremental: false,
+        sidechain_count: 0,
+        diagnostics: Vec::new(),
     })
 }
 
@@ -1589,7 +1900,13 @@ pub async fn extract_session_log(
     file_path: String,
     level: String,
 ) -> std::result::Result<ExtractSessionResponse, CommandError> {
-    let path = PathBuf::from(&file_path);
+    // 已压缩归档的会话读取解压后的缓存文件
+    let path =
+        crate::session_archive::resolve_session_path(&PathBuf::from(&file_path)).map_err(|e| {
+            CommandError {
+                message: format!("读取归档会话失败: {}", e),
+            }
+        })?;
 
     // 验证文件存在
     if !path.exists() {
@@ -1613,13 +1930,9 @@ pub async fn extract_session_log(
         }
     };
 
-    // 创建 JSONL 解析器并解析
-    let mut parser = JsonlParser::new(path).map_err(|e| CommandError {
-        message: format!("创建 JSONL 解析器失败: {}", e),
-    })?;
-
-    let entries = parser.parse_all().map_err(|e| CommandError {
-        message: format!("解析 JSONL 文件失败: {}", e),
+    // 读取会话条目（其他来源的会话由适配器转换为兼容条目）
+    let entries = crate::parser::source::read_entries(&path).map_err(|e| CommandError {
+        message: format!("解析会话文件失败: {}", e),
     })?;
 
     // 构建消息树
@@ -1648,13 +1961,15 @@ pub async fn extract_session_log(
 
 /// 导出会话日志
 ///
-/// 提取会话内容并导出为文件（Markdown 或 JSON）。
+/// 提取会话内容并导出为文件（Markdown、JSON 或微调数据集格式）。
 ///
 /// # 参数
 /// - `file_path`: JSONL 会话文件路径
-/// - `level`: 提取等级
-/// - `format`: 导出格式（markdown 或 json）
+/// - `level`: 提取等级（数据集格式下 l3_prompt_only 不包含工具调用）
+/// - `format`: 导出格式（markdown、json、openai、sharegpt 或 anthropic）
 /// - `output_dir`: 输出目录（可选）
+/// - `redact`: 是否脱敏（可选，默认使用脱敏配置中的 `enabled`）
+/// - `segment_scope`: 压缩分段范围（可选），`all`（默认）或 `current`
 ///
 /// # 返回
 /// 返回导出文件的路径和大小
@@ -1674,8 +1989,16 @@ pub async fn export_session_log(
     level: String,
     format: String,
     output_dir: Option<String>,
+    redact: Option<bool>,
+    segment_scope: Option<crate::parser::segment::SegmentScope>,
 ) -> std::result::Result<ExportSessionResponse, CommandError> {
-    let path = PathBuf::from(&file_path);
+    // 已压缩归档的会话读取解压后的缓存文件
+    let path =
+        crate::session_archive::resolve_session_path(&PathBuf::from(&file_path)).map_err(|e| {
+            CommandError {
+                message: format!("读取归档会话失败: {}", e),
+            }
+        })?;
 
     // 验证文件存在
     if !path.exists() {
@@ -1700,9 +2023,15 @@ pub async fn export_session_log(
     let export_format = match format.as_str() {
         "markdown" => ExportFormat::Markdown,
         "json" => ExportFormat::Json,
+        "openai" => ExportFormat::OpenAi,
+        "sharegpt" => ExportFormat::ShareGpt,
+        "anthropic" => ExportFormat::Anthropic,
         _ => {
             return Err(CommandError {
-                message: format!("无效的导出格式: {}，可选值：markdown, json", format),
+                message: format!(
+                    "无效的导出格式: {}，可选值：markdown, json, openai, sharegpt, anthropic",
+                    format
+                ),
             });
         }
     };
@@ -1724,25 +2053,27 @@ pub async fn export_session_log(
         message: format!("创建输出目录失败: {}", e),
     })?;
 
-    // 创建 JSONL 解析器并解析
-    let mut parser = JsonlParser::new(path.clone()).map_err(|e| CommandError {
-        message: format!("创建 JSONL 解析器失败: {}", e),
-    })?;
-
-    let entries = parser.parse_all().map_err(|e| CommandError {
-        message: format!("解析 JSONL 文件失败: {}", e),
+    // 读取会话条目（其他来源的会话由适配器转换为兼容条目）
+    let entries = crate::parser::source::read_entries(&path).map_err(|e| CommandError {
+        message: format!("解析会话文件失败: {}", e),
     })?;
 
-    // 构建消息树
-    let mut tree = MessageTreeBuilder::build_from_entries(&entries).map_err(|e| CommandError {
+    // 构建消息树，按压缩分段范围截取
+    let tree = MessageTreeBuilder::build_from_entries(&entries).map_err(|e| CommandError {
         message: format!("构建消息树失败: {}", e),
     })?;
+    let mut tree = crate::parser::segment::SegmentDetector::scope_tree(
+        tree,
+        segment_scope.unwrap_or_default(),
+    );
 
-    // 提取元数据
-    crate::parser::extractor::MetadataExtractor::extract_tree_metadata(&mut tree).map_err(|e| {
-        CommandError {
-            message: format!("提取元数据失败: {}", e),
-        }
+    // 脱敏（可选）并提取元数据
+    let mut redactor =
+        crate::redaction::Redactor::for_export(redact).map_err(|e| CommandError {
+            message: format!("加载脱敏配置失败: {}", e),
+        })?;
+    ExtractionEngine::prepare_tree(&mut tree, redactor.as_mut()).map_err(|e| CommandError {
+        message: format!("提取元数据失败: {}", e),
     })?;
 
     // 确定输出文件名
@@ -1751,13 +2082,24 @@ pub async fn export_session_log(
         .and_then(|s| s.to_str())
         .unwrap_or("session");
 
-    let ext = match export_format {
-        ExportFormat::Markdown => "md",
-        ExportFormat::Json => "json",
+    // 数据集格式在文件名中带上格式名，避免 openai 与 anthropic 的 .jsonl 互相覆盖
+    let output_path = if export_format.is_dataset() {
+        output_dir.join(format!(
+            "{}_{}_{}.{}",
+            file_stem,
+            level,
+            format,
+            export_format.extension()
+        ))
+    } else {
+        output_dir.join(format!(
+            "{}_{}.{}",
+            file_stem,
+            level,
+            export_format.extension()
+        ))
     };
 
-    let output_path = output_dir.join(format!("{}_{}.{}", file_stem, level, ext));
-
     // 导出文件
     match export_format {
         ExportFormat::Markdown => {
@@ -1779,6 +2121,12 @@ pub async fn export_session_log(
                 }
             })?;
         }
+        ExportFormat::OpenAi | ExportFormat::ShareGpt | ExportFormat::Anthropic => {
+            ExtractionEngine::export_dataset(&tree, extraction_level, export_format, &output_path)
+                .map_err(|e| CommandError {
+                    message: format!("导出数据集文件失败: {}", e),
+                })?;
+        }
     }
 
     // 获取文件大小
@@ -2145,11 +2493,12 @@ pub fn get_monitored_directories(
 
 /// 添加监控目录
 ///
-/// 添加新的监控目录到配置列表
+/// 添加新的监控目录到配置列表，`source` 指定目录中的会话来源（默认 Claude Code）
 #[tauri::command]
 pub fn add_monitored_directory(
     path: String,
     name: String,
+    source: Option<crate::parser::source::SessionSourceKind>,
 ) -> Result<crate::database::models::MonitoredDirectory, CommandError> {
     use crate::database::repository::MonitoredDirectoryRepository;
 
@@ -2157,7 +2506,8 @@ pub fn add_monitored_directory(
         message: format!("创建目录仓库失败: {}", e),
     })?;
 
-    let directory = crate::database::models::MonitoredDirectory::new(path, name);
+    let mut directory = crate::database::models::MonitoredDirectory::new(path, name);
+    directory.source = source.unwrap_or_default();
     repo.create_directory(directory).map_err(|e| CommandError {
         message: format!("添加监控目录失败: {}", e),
     })
@@ -2690,7 +3040,8 @@ pub async fn sync_embeddings_now(manager: State<'_, LLMClientManager>) -> Result
 // 多等级日志读取 Commands (Multi-Level Log Reading)
 // ============================================================================
 
-use crate::parser::view_level::{MessageFilter, QAPair, ViewLevel};
+use crate::parser::segment::SegmentScope;
+use crate::parser::view_level::{CustomViewLevel, MessageFilter, QAPair, ViewLevel};
 
 /// 根据等级获取会话消息
 ///
@@ -2698,6 +3049,8 @@ use crate::parser::view_level::{MessageFilter, QAPair, ViewLevel};
 /// - `session_id`: 会话 ID
 /// - `view_level`: 视图等级
 /// - `file_path`: (可选) 会话文件路径。如果提供，直接使用文件路径而不从数据库查询
+/// - `segment_scope`: (可选) 压缩分段范围，`all`（默认）或 `current`（仅最后一次压缩之后）
+/// - `custom_level`: (可选) 自定义等级名称。提供时忽略 `view_level`，按完整消息求值该等级的谓词
 ///
 /// # 返回
 /// 过滤后的消息列表
@@ -2706,27 +3059,143 @@ pub async fn cmd_get_messages_by_level(
     session_id: String,
     view_level: ViewLevel,
     file_path: Option<String>,
+    segment_scope: Option<SegmentScope>,
+    custom_level: Option<String>,
 ) -> Result<Vec<crate::database::models::Message>, String> {
+    let result = load_messages_by_level(
+        &session_id,
+        view_level,
+        file_path,
+        None,
+        segment_scope,
+        custom_level,
+    )?;
+    Ok(result.messages)
+}
+
+/// 增量获取消息响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppendedMessagesResponse {
+    /// 新追加的消息（`reset` 为 true 时为从头解析的全部消息）
+    pub messages: Vec<crate::database::models::Message>,
+
+    /// 已解析到的字节偏移量，作为下次调用的 `from_offset`
+    pub next_offset: u64,
+
+    /// `next_offset` 之前内容的指纹，作为下次调用的 `from_fingerprint`
+    ///
+    /// 以十六进制字符串传递，避免超出 JavaScript 的安全整数范围；其他来源的会话为 None
+    pub next_fingerprint: Option<String>,
+
+    /// 文件已被截断或重写（或 `Current` 范围下出现了新的压缩分段），
+    /// 调用方应丢弃已有消息并用 `messages` 替换
+    pub reset: bool,
+}
+
+/// 根据等级获取 `from_offset` 之后追加的会话消息
+///
+/// # 参数
+/// - `from_offset`: 上次调用返回的 `next_offset`（首次调用传 0）
+/// - `from_fingerprint`: 上次调用返回的 `next_fingerprint`（首次调用不传）
+/// - 其余参数与 `cmd_get_messages_by_level` 相同
+///
+/// # 返回
+/// 新追加的消息、下次的起始偏移量和指纹；
+/// 偏移量超出文件长度或偏移量之前的内容与指纹不一致时从头解析并设置 `reset`
+#[tauri::command]
+pub async fn cmd_get_appended_messages_by_level(
+    session_id: String,
+    view_level: ViewLevel,
+    file_path: Option<String>,
+    from_offset: u64,
+    from_fingerprint: Option<String>,
+    segment_scope: Option<SegmentScope>,
+    custom_level: Option<String>,
+) -> Result<AppendedMessagesResponse, String> {
+    let from_fingerprint = from_fingerprint
+        .map(|fingerprint| {
+            u64::from_str_radix(&fingerprint, 16)
+                .map_err(|e| format!("无效的偏移量指纹 {}: {}", fingerprint, e))
+        })
+        .transpose()?;
+
+    let result = load_messages_by_level(
+        &session_id,
+        view_level,
+        file_path,
+        Some((from_offset, from_fingerprint)),
+        segment_scope,
+        custom_level,
+    )?;
+    Ok(AppendedMessagesResponse {
+        messages: result.messages,
+        next_offset: result.next_offset,
+        next_fingerprint: result
+            .next_fingerprint
+            .map(|fingerprint| format!("{:016x}", fingerprint)),
+        reset: result.reset,
+    })
+}
+
+/// 确定会话文件路径：提供了 `file_path` 时直接使用，否则从数据库查询
+fn session_file_path(session_id: &str, file_path: Option<String>) -> Result<String, String> {
     use crate::database::repository::SessionRepository;
+
+    if let Some(fp) = file_path {
+        return Ok(fp);
+    }
+
+    let repo = SessionRepository::from_default_db()
+        .map_err(|e| format!("创建 SessionRepository 失败: {}", e))?;
+    let session = repo
+        .get_session_by_id(session_id)
+        .map_err(|e| format!("获取会话失败: {}", e))?
+        .ok_or_else(|| format!("会话不存在: {}", session_id))?;
+    Ok(session.file_path)
+}
+
+/// 按名称加载自定义等级（名称为 None 时返回 None）
+fn load_custom_level(name: Option<String>) -> Result<Option<CustomViewLevel>, String> {
+    use crate::database::repository::ViewLevelPreferenceRepository;
+
+    match name {
+        Some(name) => ViewLevelPreferenceRepository::new()
+            .get_custom_level(&name)
+            .map_err(|e| format!("获取自定义等级失败: {}", e))?
+            .ok_or_else(|| format!("自定义等级不存在: {}", name))
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+/// 按等级解析会话消息（`from_checkpoint` 为 None 时全量解析）
+///
+/// `from_checkpoint` 为上次返回的偏移量及其指纹，提供时只解析新追加的部分
+fn load_messages_by_level(
+    session_id: &str,
+    view_level: ViewLevel,
+    file_path: Option<String>,
+    from_checkpoint: Option<(u64, Option<u64>)>,
+    segment_scope: Option<SegmentScope>,
+    custom_level: Option<String>,
+) -> Result<crate::session_parser::SessionParseResult, String> {
     use crate::session_parser::{SessionParserConfig, SessionParserService};
 
-    // 确定文件路径
-    let final_file_path = if let Some(fp) = file_path {
-        // 如果提供了文件路径，直接使用
-        fp
+    let custom_level = load_custom_level(custom_level)?;
+    let view_level = if custom_level.is_some() {
+        ViewLevel::Full
     } else {
-        // 否则从数据库查询会话信息
-        let repo = SessionRepository::from_default_db()
-            .map_err(|e| format!("创建 SessionRepository 失败: {}", e))?;
-        let session = repo
-            .get_session_by_id(&session_id)
-            .map_err(|e| format!("获取会话失败: {}", e))?
-            .ok_or_else(|| format!("会话不存在: {}", session_id))?;
-        session.file_path
+        view_level
     };
 
-    // 检查会话文件是否存在
-    let path_buf = std::path::PathBuf::from(&final_file_path);
+    // 确定文件路径
+    let final_file_path = session_file_path(session_id, file_path)?;
+
+    // 检查会话文件是否存在（已压缩归档的会话由解析器读取解压后的缓存文件）
+    let path_buf =
+        crate::session_archive::resolve_session_path(std::path::Path::new(&final_file_path))
+            .map_err(|e| format!("读取归档会话失败: {}", e))?;
     if !path_buf.exists() {
         return Err(format!("会话文件不存在: {}", final_file_path));
     }
@@ -2736,15 +3205,26 @@ pub async fn cmd_get_messages_by_level(
         enable_content_filter: true, // ✅ 启用内容过滤
         view_level: view_level.clone(),
         debug: cfg!(debug_assertions),
+        segment_scope: segment_scope.unwrap_or_default(),
     };
 
     // 创建解析服务
     let parser = SessionParserService::new(config);
 
-    // 解析会话
-    let result = parser
-        .parse_session(&final_file_path, &session_id)
-        .map_err(|e| format!("解析会话失败: {}", e))?;
+    // 解析会话（提供偏移量时只解析新追加的部分）
+    let mut result = match from_checkpoint {
+        Some((offset, fingerprint)) => {
+            parser.parse_session_from_offset(&final_file_path, session_id, offset, fingerprint)
+        }
+        None => parser.parse_session(&final_file_path, session_id),
+    }
+    .map_err(|e| format!("解析会话失败: {}", e))?;
+
+    // 应用自定义等级
+    if let Some(custom_level) = custom_level {
+        result.messages =
+            MessageFilter::with_custom_level(custom_level).filter_messages(result.messages);
+    }
 
     // 输出调试信息
     #[cfg(debug_assertions)]
@@ -2806,7 +3286,7 @@ pub async fn cmd_get_messages_by_level(
         }
     }
 
-    Ok(result.messages)
+    Ok(result)
 }
 
 /// 根据等级提取问答对
@@ -2815,6 +3295,8 @@ pub async fn cmd_get_messages_by_level(
 /// - `session_id`: 会话 ID
 /// - `view_level`: 视图等级（必须是 QAPairs）
 /// - `file_path`: (可选) 会话文件路径。如果提供，直接使用文件路径而不从数据库查询
+/// - `segment_scope`: (可选) 压缩分段范围，`all`（默认）或 `current`
+/// - `custom_level`: (可选) 自定义等级名称。提供时只保留问题或答案满足该等级谓词的问答对
 ///
 /// # 返回
 /// 问答对列表
@@ -2823,14 +3305,16 @@ pub async fn cmd_get_qa_pairs_by_level(
     session_id: String,
     view_level: ViewLevel,
     file_path: Option<String>,
+    segment_scope: Option<SegmentScope>,
+    custom_level: Option<String>,
 ) -> Result<Vec<QAPair>, String> {
     use crate::database::repository::SessionRepository;
-    use crate::session_parser::{SessionParserConfig, SessionParserService};
 
     // 验证等级必须是 QAPairs
     if view_level != ViewLevel::QAPairs {
         return Err("问答对提取仅在 QAPairs 等级下可用".to_string());
     }
+    let custom_level = load_custom_level(custom_level)?;
 
     // 确定文件路径
     let final_file_path = if let Some(fp) = file_path {
@@ -2847,36 +3331,15 @@ pub async fn cmd_get_qa_pairs_by_level(
         session.file_path
     };
 
-    // 检查会话文件是否存在
-    let path_buf = std::path::PathBuf::from(&final_file_path);
+    // 检查会话文件是否存在（已压缩归档的会话由解析器读取解压后的缓存文件）
+    let path_buf =
+        crate::session_archive::resolve_session_path(std::path::Path::new(&final_file_path))
+            .map_err(|e| format!("读取归档会话失败: {}", e))?;
     if !path_buf.exists() {
         return Err(format!("会话文件不存在: {}", final_file_path));
     }
 
-    // 使用 SessionParserService 解析会话（在 Full 视图等级下获取所有消息）
-    let config = SessionParserConfig {
-        enable_content_filter: false, // 问答对提取不过滤内容
-        view_level: ViewLevel::Full,  // 获取所有消息，后续由 extract_qa_pairs 处理
-        debug: cfg!(debug_assertions),
-    };
-
-    let parser = SessionParserService::new(config);
-    let result = parser
-        .parse_session(&final_file_path, &session_id)
-        .map_err(|e| format!("解析会话失败: {}", e))?;
-
-    #[cfg(debug_assertions)]
-    {
-        eprintln!("[DEBUG] 解析统计: {:?}", result.stats);
-        eprintln!(
-            "[DEBUG] 返回 {} 个消息用于问答对提取",
-            result.messages.len()
-        );
-    }
-
-    // 提取问答对
-    let filter = MessageFilter::new(view_level);
-    let qa_pairs = filter.extract_qa_pairs(result.messages);
+    let qa_pairs = qa_pairs_for_file(&final_file_path, &session_id, segment_scope, custom_level)?;
 
     // 调试日志：检查提取的问答对
     #[cfg(debug_assertions)]
@@ -2906,25 +3369,124 @@ pub async fn cmd_get_qa_pairs_by_level(
     Ok(qa_pairs)
 }
 
-/// 保存视图等级偏好
-///
-/// # 参数
-/// - `session_id`: 会话 ID
-/// - `view_level`: 视图等级
+/// 解析会话文件并提取问答对
 ///
-/// # 返回
-/// 成功返回 Ok(())
-#[tauri::command]
-pub async fn cmd_save_view_level_preference(
-    session_id: String,
-    view_level: ViewLevel,
-) -> Result<(), String> {
-    use crate::database::repository::ViewLevelPreferenceRepository;
-
-    let mut repo = ViewLevelPreferenceRepository::new();
-    repo.save_preference(&session_id, view_level)
-        .map_err(|e| format!("保存偏好失败: {}", e))
-}
+/// `cmd_get_qa_pairs_by_level` 和按问答对下标续接会话共用，保证相同参数下的下标一致
+fn qa_pairs_for_file(
+    file_path: &str,
+    session_id: &str,
+    segment_scope: Option<SegmentScope>,
+    custom_level: Option<CustomViewLevel>,
+) -> Result<Vec<QAPair>, String> {
+    use crate::session_parser::{SessionParserConfig, SessionParserService};
+
+    // 使用 SessionParserService 解析会话（在 Full 视图等级下获取所有消息）
+    let config = SessionParserConfig {
+        enable_content_filter: false, // 问答对提取不过滤内容
+        view_level: ViewLevel::Full,  // 获取所有消息，后续由 extract_qa_pairs 处理
+        debug: cfg!(debug_assertions),
+        segment_scope: segment_scope.unwrap_or_default(),
+    };
+
+    let parser = SessionParserService::new(config);
+    let result = parser
+        .parse_session(file_path, session_id)
+        .map_err(|e| format!("解析会话失败: {}", e))?;
+
+    #[cfg(debug_assertions)]
+    {
+        eprintln!("[DEBUG] 解析统计: {:?}", result.stats);
+        eprintln!(
+            "[DEBUG] 返回 {} 个消息用于问答对提取",
+            result.messages.len()
+        );
+    }
+
+    // 提取问答对
+    let filter = match custom_level {
+        Some(custom_level) => MessageFilter::with_custom_level(custom_level),
+        None => MessageFilter::new(ViewLevel::QAPairs),
+    };
+    Ok(filter.extract_qa_pairs(result.messages))
+}
+
+/// 保存视图等级偏好
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+/// - `view_level`: 视图等级
+/// - `custom_level`: (可选) 自定义等级名称，提供时保存为该会话的自定义等级
+///
+/// # 返回
+/// 成功返回 Ok(())
+#[tauri::command]
+pub async fn cmd_save_view_level_preference(
+    session_id: String,
+    view_level: ViewLevel,
+    custom_level: Option<String>,
+) -> Result<(), String> {
+    use crate::database::repository::ViewLevelPreferenceRepository;
+
+    let mut repo = ViewLevelPreferenceRepository::new();
+    match custom_level {
+        Some(name) => repo.save_custom_preference(&session_id, &name),
+        None => repo.save_preference(&session_id, view_level),
+    }
+    .map_err(|e| format!("保存偏好失败: {}", e))
+}
+
+/// 获取会话选择的自定义等级
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+///
+/// # 返回
+/// 自定义等级名称，会话使用预设等级时返回 None
+#[tauri::command]
+pub async fn cmd_get_custom_view_level_preference(
+    session_id: String,
+) -> Result<Option<String>, String> {
+    use crate::database::repository::ViewLevelPreferenceRepository;
+
+    ViewLevelPreferenceRepository::new()
+        .get_custom_preference(&session_id)
+        .map_err(|e| format!("获取偏好失败: {}", e))
+}
+
+/// 获取所有自定义等级
+#[tauri::command]
+pub async fn cmd_list_custom_view_levels() -> Result<Vec<CustomViewLevel>, String> {
+    use crate::database::repository::ViewLevelPreferenceRepository;
+
+    ViewLevelPreferenceRepository::new()
+        .list_custom_levels()
+        .map_err(|e| format!("获取自定义等级失败: {}", e))
+}
+
+/// 保存自定义等级（同名等级会被覆盖）
+///
+/// # 参数
+/// - `level`: 等级定义（名称、描述和消息谓词）
+#[tauri::command]
+pub async fn cmd_save_custom_view_level(level: CustomViewLevel) -> Result<(), String> {
+    use crate::database::repository::ViewLevelPreferenceRepository;
+
+    ViewLevelPreferenceRepository::new()
+        .save_custom_level(&level)
+        .map_err(|e| format!("保存自定义等级失败: {}", e))
+}
+
+/// 删除自定义等级
+///
+/// 使用该等级的会话偏好会回退到完整模式
+#[tauri::command]
+pub async fn cmd_delete_custom_view_level(name: String) -> Result<bool, String> {
+    use crate::database::repository::ViewLevelPreferenceRepository;
+
+    ViewLevelPreferenceRepository::new()
+        .delete_custom_level(&name)
+        .map_err(|e| format!("删除自定义等级失败: {}", e))
+}
 
 /// 获取视图等级偏好
 ///
@@ -2952,6 +3514,12 @@ pub enum ExportFormatType {
     Markdown,
     #[serde(rename = "json")]
     Json,
+    #[serde(rename = "openai")]
+    OpenAi,
+    #[serde(rename = "sharegpt")]
+    ShareGpt,
+    #[serde(rename = "anthropic")]
+    Anthropic,
 }
 
 /// 根据等级导出会话
@@ -2959,8 +3527,15 @@ pub enum ExportFormatType {
 /// # 参数
 /// - `session_id`: 会话 ID
 /// - `view_level`: 视图等级
-/// - `format`: 导出格式（markdown 或 json）
+/// - `format`: 导出格式（markdown、json、openai、sharegpt 或 anthropic）
 /// - `file_path`: (可选) 会话文件路径。如果提供，直接使用文件路径而不从数据库查询
+/// - `redact`: (可选) 是否脱敏，默认使用脱敏配置中的 `enabled`
+/// - `segment_scope`: (可选) 压缩分段范围，`all`（默认）或 `current`
+/// - `custom_level`: (可选) 自定义等级名称。提供时忽略 `view_level`，按该等级的谓词过滤消息
+///
+/// 数据集格式下，问答对等级每个问答对导出为一条样本；
+/// 其他等级按消息树中每个根节点的存活路径导出样本，只保留过滤后的消息，
+/// 完整等级（full）保留 tool_use / tool_result 内容块
 ///
 /// # 返回
 /// 导出的内容字符串
@@ -2970,12 +3545,35 @@ pub async fn cmd_export_session_by_level(
     view_level: ViewLevel,
     format: ExportFormatType,
     file_path: Option<String>,
+    redact: Option<bool>,
+    segment_scope: Option<SegmentScope>,
+    custom_level: Option<String>,
 ) -> Result<String, String> {
+    // 自定义等级基于完整消息求值
+    let view_level = if custom_level.is_some() {
+        ViewLevel::Full
+    } else {
+        view_level
+    };
+    let level_name = custom_level
+        .clone()
+        .unwrap_or_else(|| view_level.display_name().to_string());
+
+    // 问答对等级保留原始问答对，供数据集格式按样本导出
+    let mut qa_pairs_for_dataset = None;
+
     // 获取过滤后的消息
     let messages = if view_level == ViewLevel::QAPairs {
         // 对于 QAPairs，先获取问答对
-        let qa_pairs =
-            cmd_get_qa_pairs_by_level(session_id.clone(), view_level, file_path.clone()).await?;
+        let qa_pairs = cmd_get_qa_pairs_by_level(
+            session_id.clone(),
+            view_level,
+            file_path.clone(),
+            segment_scope,
+            None,
+        )
+        .await?;
+        qa_pairs_for_dataset = Some(qa_pairs.clone());
 
         // 将问答对转换为可导出的格式
         let export_messages: Vec<crate::database::models::Message> = qa_pairs
@@ -2992,9 +3590,34 @@ pub async fn cmd_export_session_by_level(
         export_messages
     } else {
         // 其他等级直接获取消息
-        cmd_get_messages_by_level(session_id.clone(), view_level, file_path.clone()).await?
+        cmd_get_messages_by_level(
+            session_id.clone(),
+            view_level,
+            file_path.clone(),
+            segment_scope,
+            custom_level.clone(),
+        )
+        .await?
     };
 
+    // 脱敏（可选）：同一次导出共用一个脱敏引擎，保证占位符一致
+    let mut messages = messages;
+    let mut redactor = crate::redaction::Redactor::for_export(redact)
+        .map_err(|e| format!("加载脱敏配置失败: {}", e))?;
+    if let Some(redactor) = redactor.as_mut() {
+        messages
+            .iter_mut()
+            .for_each(|msg| redactor.redact_message(msg));
+        if let Some(pairs) = qa_pairs_for_dataset.as_mut() {
+            for pair in pairs.iter_mut() {
+                redactor.redact_message(&mut pair.question);
+                if let Some(answer) = pair.answer.as_mut() {
+                    redactor.redact_message(answer);
+                }
+            }
+        }
+    }
+
     // 保存 file_path 的引用供后续使用
     let file_path_ref = file_path.as_deref();
 
@@ -3006,7 +3629,7 @@ pub async fn cmd_export_session_by_level(
             if let Some(fp) = file_path_ref {
                 markdown.push_str(&format!("**文件路径**: {}\n", fp));
             }
-            markdown.push_str(&format!("**视图等级**: {}\n\n", view_level.display_name()));
+            markdown.push_str(&format!("**视图等级**: {}\n\n", level_name));
             markdown.push_str("---\n\n");
 
             for msg in &messages {
@@ -3040,8 +3663,9 @@ pub async fn cmd_export_session_by_level(
                 },
                 "view_level": {
                     "value": view_level.to_string(),
-                    "display_name": view_level.display_name(),
+                    "display_name": level_name,
                     "description": view_level.description(),
+                    "custom_level": custom_level,
                 },
                 "messages": messages,
                 "exported_at": chrono::Utc::now().to_rfc3339()
@@ -3050,6 +3674,38 @@ pub async fn cmd_export_session_by_level(
             serde_json::to_string_pretty(&export_data)
                 .map_err(|e| format!("JSON 序列化失败: {}", e))
         }
+        ExportFormatType::OpenAi | ExportFormatType::ShareGpt | ExportFormatType::Anthropic => {
+            use crate::parser::dataset::DatasetBuilder;
+
+            let conversations = match &qa_pairs_for_dataset {
+                Some(pairs) => DatasetBuilder::from_qa_pairs(pairs),
+                None => {
+                    // 在消息树上按存活路径构建样本，保留工具调用内容块
+                    let final_file_path = session_file_path(&session_id, file_path.clone())?;
+                    let entries = read_session_entries(&final_file_path).map_err(|e| e.message)?;
+                    let mut tree = MessageTreeBuilder::build_from_entries(&entries)
+                        .map_err(|e| format!("构建消息树失败: {}", e))?;
+                    if let Some(redactor) = redactor.as_mut() {
+                        redactor.redact_tree(&mut tree);
+                    }
+
+                    let kept: std::collections::HashSet<&str> =
+                        messages.iter().map(|msg| msg.uuid.as_str()).collect();
+                    let include_tools = view_level == ViewLevel::Full;
+                    DatasetBuilder::from_tree_filtered(&tree, include_tools, |node| {
+                        kept.contains(node.id.as_str())
+                    })
+                }
+            };
+            let dataset_format = match format {
+                ExportFormatType::OpenAi => ExportFormat::OpenAi,
+                ExportFormatType::ShareGpt => ExportFormat::ShareGpt,
+                _ => ExportFormat::Anthropic,
+            };
+
+            ExtractionEngine::render_dataset(&conversations, dataset_format)
+                .map_err(|e| format!("导出数据集失败: {}", e))
+        }
     }
 }
 
@@ -3526,40 +4182,36 @@ pub async fn cmd_analyze_opening_intent(
 pub async fn cmd_detect_qa_pairs(
     session_file_path: String,
 ) -> Result<Vec<DecisionQAPair>, CommandError> {
-    // 1. 解析会话文件
-    let events = crate::optimizer::PromptOptimizer::parse_session_file(&session_file_path)
+    use crate::session_parser::{SessionParserConfig, SessionParserService};
+
+    // 1. 解析会话文件（完整等级，保留 parentUuid 链路和工具往返）
+    let session_id = std::path::Path::new(&session_file_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let config = SessionParserConfig {
+        enable_content_filter: false,
+        view_level: ViewLevel::Full,
+        debug: cfg!(debug_assertions),
+        segment_scope: SegmentScope::All,
+    };
+    let result = SessionParserService::new(config)
+        .parse_session(&session_file_path, &session_id)
         .map_err(|e| CommandError {
             message: format!("解析会话文件失败: {}", e),
         })?;
 
-    if events.is_empty() {
+    if result.messages.is_empty() {
         return Ok(Vec::new());
     }
 
-    // 2. 将 ParsedEvent 转换为 Message
-    let messages: Vec<crate::database::models::Message> = events
-        .into_iter()
-        .enumerate()
-        .map(|(idx, event)| crate::database::models::Message {
-            id: None,
-            session_id: String::new(),
-            uuid: format!("msg-{}", idx),
-            parent_uuid: None,
-            msg_type: event.role.clone(),
-            content_type: Some("text".to_string()),
-            timestamp: event.time.clone(),
-            offset: 0,
-            length: event.content.len() as i64,
-            summary: None,
-            content: Some(event.content),
-            parent_idx: None,
-            created_at: event.time,
-        })
-        .collect();
+    // 2. 沿 parentUuid 链路提取问答轮次
+    let turns = MessageFilter::new(ViewLevel::QAPairs).extract_qa_turns(result.messages);
 
     // 3. 使用 QAPairDetector 检测问答对
     let detector = crate::intent_analyzer::qa_detector::QAPairDetector::new();
-    let qa_pairs = detector.detect_decision_qa_pairs(messages);
+    let qa_pairs = detector.detect_from_qa_turns(&turns);
 
     #[cfg(debug_assertions)]
     eprintln!(
@@ -3954,3 +4606,1327 @@ pub async fn cmd_import_decision_keywords(
 
     Ok(count)
 }
+
+// ==================== Token 用量与成本命令 ====================
+
+use crate::database::{ModelPrice, ModelPriceRepository};
+use crate::parser::usage::{MessageUsage, PriceTable, TokenUsage, UsageCost, UsageExtractor};
+
+/// 会话成本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCost {
+    /// 会话文件路径
+    pub file_path: String,
+
+    /// 按模型计算的成本
+    pub cost: UsageCost,
+
+    /// 计入统计的 API 响应数量
+    pub response_count: usize,
+
+    /// 每条消息的用量
+    pub messages: Vec<MessageUsage>,
+}
+
+/// 项目内单个会话的成本摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCostSummary {
+    /// 会话 ID
+    pub session_id: String,
+
+    /// 会话文件路径
+    pub file_path: String,
+
+    /// 总 token 数（含缓存）
+    pub total_tokens: u64,
+
+    /// 成本（美元）
+    pub cost_usd: f64,
+}
+
+/// 项目成本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectCost {
+    /// 项目路径
+    pub project_path: String,
+
+    /// 按模型计算的成本合计
+    pub cost: UsageCost,
+
+    /// 各会话成本（按成本降序）
+    pub sessions: Vec<SessionCostSummary>,
+}
+
+/// 单日成本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyCost {
+    /// 日期（本地时区，YYYY-MM-DD）
+    pub date: String,
+
+    /// 按模型计算的成本
+    pub cost: UsageCost,
+}
+
+/// 从数据库加载价格表
+fn load_price_table() -> Result<PriceTable, CommandError> {
+    let repo = ModelPriceRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建数据库仓库失败: {}", e),
+    })?;
+    let prices = repo.get_all_prices().map_err(|e| CommandError {
+        message: format!("获取模型价格失败: {}", e),
+    })?;
+    Ok(PriceTable::new(prices))
+}
+
+/// 获取会话列表（可按项目路径过滤）
+fn load_sessions_for_cost(
+    project_path: Option<&str>,
+) -> Result<Vec<crate::database::models::Session>, CommandError> {
+    let repo = crate::database::repository::SessionRepository::from_default_db().map_err(|e| {
+        CommandError {
+            message: format!("创建数据库仓库失败: {}", e),
+        }
+    })?;
+    let sessions = repo.get_all_sessions().map_err(|e| CommandError {
+        message: format!("获取会话列表失败: {}", e),
+    })?;
+
+    Ok(sessions
+        .into_iter()
+        .filter(|s| project_path.is_none_or(|p| s.project_path == p))
+        .collect())
+}
+
+/// 获取单个会话的 token 用量与成本
+///
+/// 读取 assistant 条目中的 `message.usage` 和 `message.model`，
+/// 按模型价格表计算成本（包括缓存写入和缓存读取）
+///
+/// # 参数
+/// - `file_path`: 会话文件路径
+#[tauri::command]
+pub async fn cmd_get_session_cost(file_path: String) -> Result<SessionCost, CommandError> {
+    let table = load_price_table()?;
+
+    let mut parser = JsonlParser::new(PathBuf::from(&file_path)).map_err(|e| CommandError {
+        message: format!("创建 JSONL 解析器失败: {}", e),
+    })?;
+    let entries = parser.parse_all().map_err(|e| CommandError {
+        message: format!("解析 JSONL 文件失败: {}", e),
+    })?;
+
+    let messages = UsageExtractor::extract_from_entries(&entries);
+    let usage = UsageExtractor::aggregate(&messages);
+
+    Ok(SessionCost {
+        file_path,
+        cost: table.price_usage(&usage.by_model),
+        response_count: usage.response_count,
+        messages,
+    })
+}
+
+/// 获取项目的 token 用量与成本
+///
+/// # 参数
+/// - `project_path`: 项目路径（与 sessions 表中的 project_path 一致）
+#[tauri::command]
+pub async fn cmd_get_project_cost(project_path: String) -> Result<ProjectCost, CommandError> {
+    let table = load_price_table()?;
+    let sessions = load_sessions_for_cost(Some(&project_path))?;
+
+    let mut by_model: std::collections::HashMap<String, TokenUsage> =
+        std::collections::HashMap::new();
+    let mut summaries = Vec::new();
+
+    // 恢复或分叉的会话会复制之前的 assistant 条目，跨会话按消息 ID 去重
+    let mut seen_ids = std::collections::HashSet::new();
+    for session in sessions {
+        let usage = match UsageExtractor::from_file_unseen(
+            std::path::Path::new(&session.file_path),
+            &mut seen_ids,
+        ) {
+            Ok(usage) => usage,
+            Err(e) => {
+                log::warn!("读取会话用量失败 {}: {}", session.file_path, e);
+                continue;
+            }
+        };
+
+        for (model, model_usage) in &usage.by_model {
+            by_model.entry(model.clone()).or_default().add(model_usage);
+        }
+
+        summaries.push(SessionCostSummary {
+            session_id: session.session_id,
+            file_path: session.file_path,
+            total_tokens: usage.total.total_tokens(),
+            cost_usd: table.price_usage(&usage.by_model).cost_usd,
+        });
+    }
+
+    summaries.sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd));
+
+    Ok(ProjectCost {
+        project_path,
+        cost: table.price_usage(&by_model),
+        sessions: summaries,
+    })
+}
+
+/// 获取每日 token 用量与成本
+///
+/// # 参数
+/// - `project_path`: (可选) 只统计该项目的会话
+/// - `start_date`: (可选) 起始日期（YYYY-MM-DD，含）
+/// - `end_date`: (可选) 结束日期（YYYY-MM-DD，含）
+///
+/// # 返回
+/// 按日期升序排列的每日成本
+#[tauri::command]
+pub async fn cmd_get_daily_cost(
+    project_path: Option<String>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<Vec<DailyCost>, CommandError> {
+    let table = load_price_table()?;
+    let sessions = load_sessions_for_cost(project_path.as_deref())?;
+
+    let mut by_day: std::collections::BTreeMap<
+        String,
+        std::collections::HashMap<String, TokenUsage>,
+    > = std::collections::BTreeMap::new();
+
+    // 恢复或分叉的会话会复制之前的 assistant 条目，跨会话按消息 ID 去重
+    let mut seen_ids = std::collections::HashSet::new();
+    for session in sessions {
+        let usage = match UsageExtractor::from_file_unseen(
+            std::path::Path::new(&session.file_path),
+            &mut seen_ids,
+        ) {
+            Ok(usage) => usage,
+            Err(e) => {
+                log::warn!("读取会话用量失败 {}: {}", session.file_path, e);
+                continue;
+            }
+        };
+
+        for (day, models) in usage.by_day {
+            let day_entry = by_day.entry(day).or_default();
+            for (model, model_usage) in models {
+                day_entry.entry(model).or_default().add(&model_usage);
+            }
+        }
+    }
+
+    Ok(by_day
+        .into_iter()
+        .filter(|(day, _)| {
+            start_date
+                .as_deref()
+                .is_none_or(|start| day.as_str() >= start)
+        })
+        .filter(|(day, _)| end_date.as_deref().is_none_or(|end| day.as_str() <= end))
+        .map(|(date, models)| DailyCost {
+            date,
+            cost: table.price_usage(&models),
+        })
+        .collect())
+}
+
+/// 获取模型价格表
+#[tauri::command]
+pub async fn cmd_get_model_prices() -> Result<Vec<ModelPrice>, CommandError> {
+    let repo = ModelPriceRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建数据库仓库失败: {}", e),
+    })?;
+
+    repo.get_all_prices().map_err(|e| CommandError {
+        message: format!("获取模型价格失败: {}", e),
+    })
+}
+
+/// 保存模型价格（按模型名称模式新增或更新）
+///
+/// # 返回
+/// 保存的记录 ID
+#[tauri::command]
+pub async fn cmd_save_model_price(price: ModelPrice) -> Result<i64, CommandError> {
+    let repo = ModelPriceRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建数据库仓库失败: {}", e),
+    })?;
+
+    repo.upsert_price(&price).map_err(|e| CommandError {
+        message: format!("保存模型价格失败: {}", e),
+    })
+}
+
+/// 删除模型价格
+#[tauri::command]
+pub async fn cmd_delete_model_price(id: i64) -> Result<bool, CommandError> {
+    let repo = ModelPriceRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建数据库仓库失败: {}", e),
+    })?;
+
+    repo.delete_price(id).map_err(|e| CommandError {
+        message: format!("删除模型价格失败: {}", e),
+    })
+}
+
+// ==================== 文件变更时间线命令 ====================
+
+use crate::parser::file_timeline::{FileTimeline, FileTimelineBuilder};
+
+/// 获取会话中的文件变更时间线
+///
+/// 从 Write/Edit/MultiEdit 工具调用的输入重建每个文件的 unified diff，
+/// 并附带引起变更的消息 UUID 和时间戳
+///
+/// # 参数
+/// - `file_path`: 会话文件路径
+/// - `target_file`: (可选) 只返回该文件的时间线（完整路径或路径后缀）
+///
+/// # 返回
+/// 按文件路径排序的时间线列表
+#[tauri::command]
+pub async fn cmd_get_file_change_timeline(
+    file_path: String,
+    target_file: Option<String>,
+) -> Result<Vec<FileTimeline>, CommandError> {
+    let entries = read_session_entries(&file_path)?;
+    let tree = MessageTreeBuilder::build_from_entries(&entries).map_err(|e| CommandError {
+        message: format!("构建消息树失败: {}", e),
+    })?;
+
+    let timelines = FileTimelineBuilder::build(&tree);
+
+    Ok(match target_file {
+        Some(target) => timelines
+            .into_iter()
+            .filter(|t| t.file_path == target || t.file_path.ends_with(&target))
+            .collect(),
+        None => timelines,
+    })
+}
+
+/// 重建会话中某条消息时刻的文件内容
+///
+/// 以完整的 Read 结果或 Write 内容为快照，依次应用之后的编辑；
+/// 无法重建时在结果中报告缺口，而不会猜测内容
+///
+/// # 参数
+/// - `file_path`: 会话文件路径
+/// - `message_uuid`: 目标消息 UUID
+/// - `target_file`: (可选) 只返回该文件（完整路径或路径后缀）
+#[tauri::command]
+pub async fn cmd_replay_files_at_message(
+    file_path: String,
+    message_uuid: String,
+    target_file: Option<String>,
+) -> Result<Vec<crate::parser::file_replay::ReconstructedFile>, CommandError> {
+    let entries = read_session_entries(&file_path)?;
+    let tree = MessageTreeBuilder::build_from_entries(&entries).map_err(|e| CommandError {
+        message: format!("构建消息树失败: {}", e),
+    })?;
+
+    let files =
+        crate::parser::file_replay::FileReplayer::replay_at(&tree, &message_uuid).map_err(|e| {
+            CommandError {
+                message: format!("重建文件内容失败: {}", e),
+            }
+        })?;
+
+    Ok(match target_file {
+        Some(target) => files
+            .into_iter()
+            .filter(|f| f.file_path == target || f.file_path.ends_with(&target))
+            .collect(),
+        None => files,
+    })
+}
+
+// ==================== 脱敏命令 ====================
+
+/// 获取脱敏配置
+#[tauri::command]
+pub fn cmd_get_redaction_config() -> Result<crate::redaction::RedactionConfig, CommandError> {
+    use crate::redaction::RedactionConfigManager;
+
+    let manager = RedactionConfigManager::with_default_path().map_err(|e| CommandError {
+        message: format!("加载脱敏配置失败: {}", e),
+    })?;
+
+    Ok(manager.get_config().clone())
+}
+
+/// 更新脱敏配置
+#[tauri::command]
+pub fn cmd_update_redaction_config(
+    config: crate::redaction::RedactionConfig,
+) -> Result<(), CommandError> {
+    use crate::redaction::RedactionConfigManager;
+
+    let mut manager = RedactionConfigManager::with_default_path().map_err(|e| CommandError {
+        message: format!("加载脱敏配置失败: {}", e),
+    })?;
+
+    manager.update_config(config).map_err(|e| CommandError {
+        message: format!("更新脱敏配置失败: {}", e),
+    })
+}
+
+/// 预览脱敏结果（不修改文件）
+///
+/// 按当前脱敏配置扫描整个会话文件，列出将被替换的内容。
+/// 预览中只包含原文的遮挡片段，不会返回完整的敏感信息。
+///
+/// # 参数
+/// - `file_path`: JSONL 会话文件路径
+#[tauri::command]
+pub async fn cmd_preview_redaction(
+    file_path: String,
+) -> Result<Vec<crate::redaction::RedactionFinding>, CommandError> {
+    use crate::redaction::{RedactionConfigManager, Redactor};
+
+    let manager = RedactionConfigManager::with_default_path().map_err(|e| CommandError {
+        message: format!("加载脱敏配置失败: {}", e),
+    })?;
+    let mut redactor = Redactor::new(manager.get_config()).map_err(|e| CommandError {
+        message: format!("创建脱敏引擎失败: {}", e),
+    })?;
+
+    let mut parser = JsonlParser::new(PathBuf::from(&file_path)).map_err(|e| CommandError {
+        message: format!("创建 JSONL 解析器失败: {}", e),
+    })?;
+    let entries = parser.parse_all().map_err(|e| CommandError {
+        message: format!("解析 JSONL 文件失败: {}", e),
+    })?;
+
+    for mut entry in entries {
+        redactor.redact_value(&mut entry.data);
+    }
+
+    Ok(redactor.findings())
+}
+
+// ==================== 会话分段命令 ====================
+
+/// 获取会话的压缩分段
+///
+/// 以 Claude Code 的压缩边界划分会话，按时间先后返回各分段及其摘要
+///
+/// # 参数
+/// - `file_path`: JSONL 会话文件路径
+#[tauri::command]
+pub async fn cmd_get_session_segments(
+    file_path: String,
+) -> Result<Vec<crate::parser::segment::SessionSegment>, CommandError> {
+    let entries = read_session_entries(&file_path)?;
+    let tree = MessageTreeBuilder::build_from_entries(&entries).map_err(|e| CommandError {
+        message: format!("构建消息树失败: {}", e),
+    })?;
+
+    Ok(crate::parser::segment::SegmentDetector::segments(&tree))
+}
+
+// ==================== 会话格式清单命令 ====================
+
+/// 生成会话格式清单报告
+///
+/// 扫描目录下所有会话文件，统计条目类型、内容块类型、顶层字段和
+/// Claude Code 版本，并列出解析器未知的结构，用于发现格式漂移。
+///
+/// # 参数
+/// - `directory`: (可选) 扫描目录，默认为 `~/.claude/projects`
+#[tauri::command]
+pub async fn cmd_get_schema_report(
+    directory: Option<String>,
+) -> Result<crate::parser::schema::SchemaReport, CommandError> {
+    use crate::parser::schema::SchemaInventory;
+
+    let directory = match directory {
+        Some(dir) => PathBuf::from(dir),
+        None => crate::monitor::scanner::get_claude_projects_dir().map_err(|e| CommandError {
+            message: format!("获取 Claude 项目目录失败: {}", e),
+        })?,
+    };
+
+    let pattern = directory.join("**").join("*.jsonl");
+    let pattern_str = pattern.to_str().ok_or_else(|| CommandError {
+        message: "无效的路径模式".to_string(),
+    })?;
+    let paths = glob::glob(pattern_str).map_err(|e| CommandError {
+        message: format!("无效的路径模式: {}", e),
+    })?;
+
+    let mut inventory = SchemaInventory::new();
+    for path in paths.flatten() {
+        if let Err(e) = inventory.scan_file(&path) {
+            eprintln!("⚠️  扫描会话文件失败 {:?}: {}", path, e);
+        }
+    }
+
+    Ok(inventory.finish())
+}
+
+// ==================== 损坏行诊断命令 ====================
+
+/// 会话文件诊断结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionFileDiagnostics {
+    /// 成功解析的条目数
+    pub valid_entries: usize,
+    /// 无法解析的行
+    pub diagnostics: Vec<crate::parser::jsonl::LineDiagnostic>,
+}
+
+/// 列出会话文件中无法解析的行
+///
+/// # 参数
+/// - `file_path`: JSONL 会话文件路径
+#[tauri::command]
+pub async fn cmd_get_parse_diagnostics(
+    file_path: String,
+) -> Result<SessionFileDiagnostics, CommandError> {
+    let mut parser = JsonlParser::new(PathBuf::from(&file_path)).map_err(|e| CommandError {
+        message: format!("创建 JSONL 解析器失败: {}", e),
+    })?;
+    let result = parser.parse_with_diagnostics().map_err(|e| CommandError {
+        message: format!("解析 JSONL 文件失败: {}", e),
+    })?;
+
+    Ok(SessionFileDiagnostics {
+        valid_entries: result.entries.len(),
+        diagnostics: result.diagnostics,
+    })
+}
+
+/// 写出去除损坏行后的会话文件副本
+///
+/// 原文件不会被修改。默认写入应用数据目录，避免副本被扫描器、监控器和
+/// `claude --resume` 当作新会话。
+///
+/// # 参数
+/// - `file_path`: JSONL 会话文件路径
+/// - `output_path`: (可选) 输出路径（不能已存在），默认为 `~/.prism-forge/repaired/{文件名}.repaired.jsonl`
+#[tauri::command]
+pub async fn cmd_repair_session_file(
+    file_path: String,
+    output_path: Option<String>,
+) -> Result<crate::parser::jsonl::RepairReport, CommandError> {
+    let path = PathBuf::from(&file_path);
+    let output = match output_path {
+        Some(output) => PathBuf::from(output),
+        None => default_repair_path(&path).map_err(|e| CommandError {
+            message: format!("确定修复输出路径失败: {}", e),
+        })?,
+    };
+
+    let mut parser = JsonlParser::new(path).map_err(|e| CommandError {
+        message: format!("创建 JSONL 解析器失败: {}", e),
+    })?;
+    parser
+        .write_repaired_copy(&output)
+        .map_err(|e| CommandError {
+            message: format!("修复会话文件失败: {}", e),
+        })
+}
+
+/// 修复副本的默认路径（应用数据目录下的 `repaired` 目录）
+fn default_repair_path(path: &std::path::Path) -> anyhow::Result<PathBuf> {
+    let db_path = crate::database::get_db_path()?;
+    let dir = db_path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("无法获取应用数据目录"))?
+        .join("repaired");
+    std::fs::create_dir_all(&dir)?;
+
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("session");
+    Ok(dir.join(format!("{}.repaired.jsonl", stem)))
+}
+
+// ==================== 全文搜索命令 ====================
+
+use crate::database::{SearchHit, SearchQuery, SearchRepository};
+
+/// 全文索引状态
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchIndexStatus {
+    /// 已索引的会话数
+    pub indexed_sessions: usize,
+    /// 已索引的消息数
+    pub indexed_messages: usize,
+    /// 本次重建的会话数
+    pub reindexed_sessions: usize,
+    /// 索引失败的会话文件
+    pub failed_files: Vec<String>,
+}
+
+/// 同步会话文件到全文索引，并为有变化的会话重新提取会话事实和工具错误分类
+///
+/// # 返回
+/// 返回（重建的会话数，失败的文件列表）
+fn sync_search_index(
+    conn: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sessions: &[crate::monitor::scanner::SessionMetadata],
+    force: bool,
+) -> (usize, Vec<String>) {
+    let search_repo = SearchRepository::with_conn(conn.clone());
+    let mut reindexed = 0;
+    let mut failed = Vec::new();
+
+    for metadata in sessions {
+        match search_repo.sync_session_file(
+            &metadata.session_id,
+            &metadata.project_path,
+            &metadata.file_path,
+            force,
+        ) {
+            Ok(true) => reindexed += 1,
+            Ok(false) => continue,
+            Err(e) => {
+                eprintln!("警告: 索引会话 {:?} 失败: {}", metadata.file_path, e);
+                failed.push(metadata.file_path.to_string_lossy().to_string());
+                continue;
+            }
+        }
+
+        if let Err(e) = crate::session_query::sync_session_facts(
+            conn.clone(),
+            &metadata.session_id,
+            &metadata.project_path,
+            &metadata.file_path,
+        ) {
+            eprintln!("警告: 提取会话事实 {:?} 失败: {}", metadata.file_path, e);
+        }
+    }
+
+    (reindexed, failed)
+}
+
+/// 全文搜索会话消息
+///
+/// 支持短语查询（`"exact phrase"`）、前缀查询（`pars*`）、AND / OR / NOT，
+/// 以及项目、会话、角色和日期过滤。结果中的 `uuid` 和 `filePath`
+/// 可用于跳转到对应消息。
+///
+/// # 参数
+/// - `query`: 搜索条件
+#[tauri::command]
+pub async fn cmd_search_messages(query: SearchQuery) -> Result<Vec<SearchHit>, CommandError> {
+    let search_repo = SearchRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建搜索仓库失败: {}", e),
+    })?;
+
+    search_repo.search(&query).map_err(|e| CommandError {
+        message: format!("搜索消息失败: {}", e),
+    })
+}
+
+/// 重建全文索引
+///
+/// 扫描所有监控目录，强制重新索引每个会话文件
+#[tauri::command]
+pub async fn cmd_rebuild_search_index() -> Result<SearchIndexStatus, CommandError> {
+    use crate::database::repository::MonitoredDirectoryRepository;
+
+    let dir_repo = MonitoredDirectoryRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建目录仓库失败: {}", e),
+    })?;
+    let directories = dir_repo
+        .get_active_directories()
+        .map_err(|e| CommandError {
+            message: format!("获取监控目录失败: {}", e),
+        })?;
+
+    let mut sessions = Vec::new();
+    for directory in directories {
+        let path = PathBuf::from(&directory.path);
+        match directory.source.adapter().scan(&path) {
+            Ok(mut found) => sessions.append(&mut found),
+            Err(e) => eprintln!("警告: 扫描目录 {} 失败: {}", directory.path, e),
+        }
+    }
+
+    let conn = crate::database::init::get_connection_shared().map_err(|e| CommandError {
+        message: format!("获取数据库连接失败: {}", e),
+    })?;
+    let (reindexed_sessions, failed_files) = sync_search_index(conn.clone(), &sessions, true);
+
+    let search_repo = SearchRepository::with_conn(conn);
+
+    let (indexed_sessions, indexed_messages) = search_repo.stats().map_err(|e| CommandError {
+        message: format!("读取索引状态失败: {}", e),
+    })?;
+
+    Ok(SearchIndexStatus {
+        indexed_sessions,
+        indexed_messages,
+        reindexed_sessions,
+        failed_files,
+    })
+}
+
+// ==================== 会话查询命令 ====================
+
+use crate::database::{SavedQuery, SessionQueryRepository};
+
+/// 默认返回的会话数
+const DEFAULT_SESSION_QUERY_LIMIT: usize = 200;
+
+/// 按查询语言筛选会话
+///
+/// 例如 `project:prism date:last-week tool:Bash error:true file:*.rs`，
+/// 语法见 `session_query` 模块。工具、文件、错误等条件依赖全文索引同步时
+/// 提取的会话事实。
+///
+/// # 参数
+/// - `query`: 查询文本（与 `saved_query` 二选一，都为空时返回所有会话）
+/// - `saved_query`: 保存的查询名称
+/// - `limit`: 最多返回的会话数（默认 200）
+#[tauri::command]
+pub async fn cmd_query_sessions(
+    query: Option<String>,
+    saved_query: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<crate::database::Session>, CommandError> {
+    let repo = SessionQueryRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建查询仓库失败: {}", e),
+    })?;
+
+    let query_text = match saved_query {
+        Some(name) => {
+            repo.get_query(&name)
+                .map_err(|e| CommandError {
+                    message: format!("读取保存的查询失败: {}", e),
+                })?
+                .ok_or_else(|| CommandError {
+                    message: format!("保存的查询不存在: {}", name),
+                })?
+                .query
+        }
+        None => query.unwrap_or_default(),
+    };
+
+    let expr = crate::session_query::parse_query(&query_text).map_err(|e| CommandError {
+        message: e.to_string(),
+    })?;
+    let compiled =
+        crate::session_query::compile_query(expr.as_ref(), chrono::Utc::now().date_naive())
+            .map_err(|e| CommandError {
+                message: e.to_string(),
+            })?;
+
+    repo.query_sessions(&compiled, limit.unwrap_or(DEFAULT_SESSION_QUERY_LIMIT))
+        .map_err(|e| CommandError {
+            message: format!("查询会话失败: {}", e),
+        })
+}
+
+/// 保存会话查询（同名查询会被覆盖）
+///
+/// 保存前会校验查询语法
+#[tauri::command]
+pub async fn cmd_save_session_query(
+    name: String,
+    query: String,
+) -> Result<SavedQuery, CommandError> {
+    crate::session_query::parse_query(&query).map_err(|e| CommandError {
+        message: e.to_string(),
+    })?;
+
+    let repo = SessionQueryRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建查询仓库失败: {}", e),
+    })?;
+    repo.save_query(&name, &query).map_err(|e| CommandError {
+        message: format!("保存查询失败: {}", e),
+    })
+}
+
+/// 获取所有保存的会话查询
+#[tauri::command]
+pub async fn cmd_list_session_queries() -> Result<Vec<SavedQuery>, CommandError> {
+    let repo = SessionQueryRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建查询仓库失败: {}", e),
+    })?;
+    repo.list_queries().map_err(|e| CommandError {
+        message: format!("读取保存的查询失败: {}", e),
+    })
+}
+
+/// 删除保存的会话查询
+#[tauri::command]
+pub async fn cmd_delete_session_query(id: i64) -> Result<bool, CommandError> {
+    let repo = SessionQueryRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建查询仓库失败: {}", e),
+    })?;
+    repo.delete_query(id).map_err(|e| CommandError {
+        message: format!("删除查询失败: {}", e),
+    })
+}
+
+// ==================== 会话分支命令 ====================
+
+/// 获取会话中的分支对比
+///
+/// 识别用户编辑提示词、回退或重试助手回复产生的兄弟分支，返回每个分叉点下
+/// 各分支的统计与预览，并标出存活分支，便于并排比较不同的提示词改写。
+///
+/// # 参数
+/// - `file_path`: JSONL 会话文件路径
+#[tauri::command]
+pub async fn cmd_get_conversation_branches(
+    file_path: String,
+) -> Result<Vec<crate::parser::branch::BranchPoint>, CommandError> {
+    let entries = read_session_entries(&file_path)?;
+    let tree = MessageTreeBuilder::build_from_entries(&entries).map_err(|e| CommandError {
+        message: format!("构建消息树失败: {}", e),
+    })?;
+
+    Ok(crate::parser::branch::BranchDetector::detect(&tree))
+}
+
+// ==================== 工具错误统计命令 ====================
+
+/// 按类别统计会话中的工具错误
+///
+/// 错误在全文索引同步时从 tool_result 中分类得到（编译错误、测试失败、
+/// 权限拒绝、文件不存在、超时、用户中断等），按次数降序返回。
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+#[tauri::command]
+pub async fn cmd_get_session_tool_error_stats(
+    session_id: String,
+) -> Result<Vec<crate::database::ToolErrorCount>, CommandError> {
+    let repo =
+        crate::database::ToolErrorRepository::from_default_db().map_err(|e| CommandError {
+            message: format!("创建工具错误仓库失败: {}", e),
+        })?;
+    repo.session_stats(&session_id).map_err(|e| CommandError {
+        message: format!("统计会话工具错误失败: {}", e),
+    })
+}
+
+/// 按项目和类别统计工具错误
+///
+/// # 参数
+/// - `project_path`: 仅统计指定项目，为空时统计所有项目
+#[tauri::command]
+pub async fn cmd_get_project_tool_error_stats(
+    project_path: Option<String>,
+) -> Result<Vec<crate::database::ProjectToolErrorStats>, CommandError> {
+    let repo =
+        crate::database::ToolErrorRepository::from_default_db().map_err(|e| CommandError {
+            message: format!("创建工具错误仓库失败: {}", e),
+        })?;
+    repo.project_stats(project_path.as_deref())
+        .map_err(|e| CommandError {
+            message: format!("统计项目工具错误失败: {}", e),
+        })
+}
+
+// ==================== 扫描缓存命令 ====================
+
+/// 完整重建会话扫描缓存
+///
+/// 清空缓存后重新扫描所有启用的监控目录，用于缓存与文件不一致时手动修复。
+///
+/// # 返回
+/// 返回重新扫描到的会话数量
+#[tauri::command]
+pub async fn cmd_rebuild_scan_cache() -> Result<usize, CommandError> {
+    use crate::database::repository::MonitoredDirectoryRepository;
+    use crate::database::ScanCacheRepository;
+    use crate::monitor::scanner;
+
+    let dir_repo = MonitoredDirectoryRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建目录仓库失败: {}", e),
+    })?;
+    let directories = dir_repo
+        .get_active_directories()
+        .map_err(|e| CommandError {
+            message: format!("获取监控目录失败: {}", e),
+        })?;
+
+    let cache = ScanCacheRepository::from_default_db().map_err(|e| CommandError {
+        message: format!("创建扫描缓存仓库失败: {}", e),
+    })?;
+    cache.clear().map_err(|e| CommandError {
+        message: format!("清空扫描缓存失败: {}", e),
+    })?;
+
+    let mut total = 0;
+    for directory in directories {
+        let path = PathBuf::from(&directory.path);
+        match scanner::rebuild_directory_cache(&path, &cache) {
+            Ok(sessions) => total += sessions.len(),
+            Err(e) => eprintln!("警告: 重建目录 {} 的扫描缓存失败: {}", directory.path, e),
+        }
+    }
+
+    Ok(total)
+}
+
+// ==================== 消息索引命令 ====================
+
+/// 默认每页消息数
+const DEFAULT_MESSAGE_PAGE_SIZE: usize = 100;
+
+/// 按文件偏移分页读取会话消息
+///
+/// 消息由扫描器和文件监控器增量写入 `messages` 表，返回的消息只包含元数据
+/// （类型、时间戳、偏移、长度、摘要），完整内容通过 `cmd_get_message_at_offset` 按需加载。
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+/// - `after_offset`: 上一页返回的 `nextOffset`（第一页为空）
+/// - `limit`: 每页消息数（默认 100）
+#[tauri::command]
+pub async fn cmd_list_session_messages(
+    session_id: String,
+    after_offset: Option<i64>,
+    limit: Option<usize>,
+) -> Result<crate::database::MessagePage, CommandError> {
+    let repo =
+        crate::database::MessageIndexRepository::from_default_db().map_err(|e| CommandError {
+            message: format!("创建消息索引仓库失败: {}", e),
+        })?;
+    repo.list_messages(
+        &session_id,
+        after_offset,
+        limit.unwrap_or(DEFAULT_MESSAGE_PAGE_SIZE),
+    )
+    .map_err(|e| CommandError {
+        message: format!("读取会话消息失败: {}", e),
+    })
+}
+
+/// 按偏移加载单条消息的完整内容
+///
+/// # 参数
+/// - `file_path`: JSONL 会话文件路径
+/// - `offset`: 消息的字节偏移
+/// - `length`: 消息的字节长度
+#[tauri::command]
+pub async fn cmd_get_message_at_offset(
+    file_path: String,
+    offset: u64,
+    length: usize,
+) -> Result<serde_json::Value, CommandError> {
+    ensure_offset_readable(std::path::Path::new(&file_path))?;
+    let path = crate::session_archive::resolve_session_path(std::path::Path::new(&file_path))
+        .map_err(|e| CommandError {
+            message: format!("读取归档会话失败: {}", e),
+        })?;
+    let parser = JsonlParser::new(path).map_err(|e| CommandError {
+        message: format!("创建 JSONL 解析器失败: {}", e),
+    })?;
+    parser
+        .parse_entry_at_offset(offset, length)
+        .map_err(|e| CommandError {
+            message: format!("读取消息失败: {}", e),
+        })
+}
+
+/// 消息窗口中的一条消息
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageWindowItem {
+    /// 消息元数据（类型、时间戳、偏移、长度、摘要）
+    #[serde(flatten)]
+    pub message: crate::database::Message,
+    /// 从文件按偏移读取的完整消息
+    pub data: serde_json::Value,
+}
+
+/// 消息窗口
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageWindow {
+    /// 窗口内的消息（按文件偏移排序）
+    pub messages: Vec<MessageWindowItem>,
+    /// 沿同一方向继续读取时使用的游标偏移（没有更多消息时为 None）
+    pub next_offset: Option<i64>,
+    /// 会话的消息总数
+    pub total: usize,
+}
+
+/// 确认会话文件可以按偏移读取原始 JSONL 行
+///
+/// 只有 Claude Code 会话的条目与原文件中的 JSONL 行一一对应，
+/// 其他来源（Codex CLI、Aider）需通过 `parse_session_tree` 完整解析
+fn ensure_offset_readable(path: &std::path::Path) -> Result<(), CommandError> {
+    use crate::parser::source::{adapter_for_path, SessionSourceKind};
+
+    let kind = adapter_for_path(path).kind();
+    if kind != SessionSourceKind::ClaudeCode {
+        return Err(CommandError {
+            message: format!(
+                "{} 会话不支持按偏移读取消息，请使用完整解析: {}",
+                kind.as_str(),
+                path.display()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// 确保会话文件的消息索引是最新的，返回仓库、会话 ID 和可读的文件路径
+///
+/// 每次都经过 `MessageIndexRepository::sync_session_file`：文件没有变化时它只校验
+/// 偏移指纹后复用已有索引，有追加时增量索引，被重写（即使长度不变）时从头重建。
+/// 已压缩归档的会话读取解压后的缓存文件；非 Claude Code 来源的会话返回错误。
+fn ensure_message_index(
+    file_path: &str,
+) -> Result<(crate::database::MessageIndexRepository, String, PathBuf), CommandError> {
+    use crate::database::repository::SessionRepository;
+
+    let path = PathBuf::from(file_path);
+    ensure_offset_readable(&path)?;
+
+    let repo =
+        crate::database::MessageIndexRepository::from_default_db().map_err(|e| CommandError {
+            message: format!("创建消息索引仓库失败: {}", e),
+        })?;
+
+    let readable_path =
+        crate::session_archive::resolve_session_path(&path).map_err(|e| CommandError {
+            message: format!("读取归档会话失败: {}", e),
+        })?;
+    let state = repo.get_state(file_path).map_err(|e| CommandError {
+        message: format!("读取消息索引状态失败: {}", e),
+    })?;
+
+    let session_id = match state {
+        Some(state) => state.session_id,
+        None => path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .map(|stem| stem.to_string())
+            .ok_or_else(|| CommandError {
+                message: format!("无效的会话文件名: {}", file_path),
+            })?,
+    };
+
+    // 会话记录已存在时沿用其项目信息，否则使用会话目录名称
+    let session = SessionRepository::from_default_db()
+        .and_then(|session_repo| session_repo.get_session_by_id(&session_id))
+        .ok()
+        .flatten();
+    let (project_path, project_name) = match session {
+        Some(session) => (session.project_path, session.project_name),
+        None => {
+            let folder = path
+                .parent()
+                .and_then(|parent| parent.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (folder.clone(), folder)
+        }
+    };
+
+    repo.sync_session_file(&session_id, &project_path, &project_name, &path)
+        .map_err(|e| CommandError {
+            message: format!("索引会话消息失败: {}", e),
+        })?;
+
+    Ok((repo, session_id, readable_path))
+}
+
+/// 获取会话大纲
+///
+/// 返回全部消息的 UUID、角色、时间戳和偏移，用于滚动条导航，不读取消息内容。
+/// 仅支持 Claude Code 会话，其他来源的会话返回错误。
+///
+/// # 参数
+/// - `file_path`: JSONL 会话文件路径
+#[tauri::command]
+pub async fn cmd_get_session_outline(
+    file_path: String,
+) -> Result<Vec<crate::database::MessageOutlineEntry>, CommandError> {
+    let (repo, session_id, _) = ensure_message_index(&file_path)?;
+    repo.outline(&session_id).map_err(|e| CommandError {
+        message: format!("读取会话大纲失败: {}", e),
+    })
+}
+
+/// 按游标读取一个消息窗口
+///
+/// 只按记录的偏移和长度读取窗口内的消息，不解析整个会话文件。
+/// 仅支持 Claude Code 会话，其他来源的会话返回错误。
+///
+/// # 参数
+/// - `file_path`: JSONL 会话文件路径
+/// - `cursor_uuid`: 游标消息的 UUID（优先于 `cursor_offset`）
+/// - `cursor_offset`: 游标偏移，通常为上一次返回的 `nextOffset`
+/// - `page_size`: 每页消息数（默认 100）
+/// - `direction`: 分页方向（默认 `forward`）
+///
+/// 游标本身不包含在结果中；没有游标时向前从会话开头、向后从会话末尾读取
+#[tauri::command]
+pub async fn cmd_get_message_window(
+    file_path: String,
+    cursor_uuid: Option<String>,
+    cursor_offset: Option<i64>,
+    page_size: Option<usize>,
+    direction: Option<crate::database::PageDirection>,
+) -> Result<MessageWindow, CommandError> {
+    let (repo, session_id, readable_path) = ensure_message_index(&file_path)?;
+
+    let cursor = match cursor_uuid {
+        Some(uuid) => {
+            let offset = repo
+                .find_offset(&session_id, &uuid)
+                .map_err(|e| CommandError {
+                    message: format!("查找消息失败: {}", e),
+                })?
+                .ok_or_else(|| CommandError {
+                    message: format!("消息不存在: {}", uuid),
+                })?;
+            Some(offset)
+        }
+        None => cursor_offset,
+    };
+
+    let page = repo
+        .list_window(
+            &session_id,
+            cursor,
+            direction.unwrap_or_default(),
+            page_size.unwrap_or(DEFAULT_MESSAGE_PAGE_SIZE),
+        )
+        .map_err(|e| CommandError {
+            message: format!("读取消息窗口失败: {}", e),
+        })?;
+
+    let parser = JsonlParser::new(readable_path).map_err(|e| CommandError {
+        message: format!("创建 JSONL 解析器失败: {}", e),
+    })?;
+    let mut messages = Vec::with_capacity(page.messages.len());
+    for message in page.messages {
+        let data = parser
+            .parse_entry_at_offset(message.offset as u64, message.length as usize)
+            .map_err(|e| CommandError {
+                message: format!("读取消息 {} 失败: {}", message.uuid, e),
+            })?;
+        messages.push(MessageWindowItem { message, data });
+    }
+
+    Ok(MessageWindow {
+        messages,
+        next_offset: page.next_offset,
+        total: page.total,
+    })
+}
+
+// ==================== 会话压缩归档命令 ====================
+
+/// 压缩归档长期未更新的会话
+///
+/// 将超过指定天数未更新的会话文件 gzip 压缩到应用数据目录并删除原文件，
+/// 同时将会话标记为已归档。活跃会话不会被归档。已归档的会话仍可被查看和导出。
+///
+/// # 参数
+/// - `max_age_days`: 归档年龄（天），默认 90
+///
+/// # 返回
+/// 返回归档结果（归档的会话、跳过数、失败项和节省的空间）
+#[tauri::command]
+pub async fn cmd_compress_old_sessions(
+    max_age_days: Option<u32>,
+) -> Result<crate::session_archive::ArchiveReport, CommandError> {
+    use crate::monitor::scanner;
+    use crate::session_archive::{SessionArchive, DEFAULT_ARCHIVE_AGE_DAYS};
+
+    let archive = SessionArchive::from_default().map_err(|e| CommandError {
+        message: format!("初始化会话归档失败: {}", e),
+    })?;
+    let sessions = scanner::scan_session_files().map_err(|e| CommandError {
+        message: format!("扫描会话文件失败: {}", e),
+    })?;
+
+    let report =
+        archive.archive_older_than(&sessions, max_age_days.unwrap_or(DEFAULT_ARCHIVE_AGE_DAYS));
+
+    let repo = crate::database::repository::SessionRepository::from_default_db().map_err(|e| {
+        CommandError {
+            message: format!("创建 SessionRepository 失败: {}", e),
+        }
+    })?;
+    for entry in &report.archived {
+        if let Err(e) = repo.archive_session(&entry.session_id) {
+            eprintln!("警告: 标记会话 {} 为已归档失败: {}", entry.session_id, e);
+        }
+    }
+
+    Ok(report)
+}
+
+/// 恢复压缩归档的会话
+///
+/// 将归档文件解压回原路径，并将会话恢复到活跃列表
+///
+/// # 参数
+/// - `original_path`: 归档前的会话文件路径（归档清单中的 `originalPath`）
+///
+/// # 返回
+/// 返回恢复后的文件路径
+#[tauri::command]
+pub async fn cmd_restore_compressed_session(original_path: String) -> Result<String, CommandError> {
+    let archive =
+        crate::session_archive::SessionArchive::from_default().map_err(|e| CommandError {
+            message: format!("初始化会话归档失败: {}", e),
+        })?;
+    let entry = archive
+        .restore(std::path::Path::new(&original_path))
+        .map_err(|e| CommandError {
+            message: format!("恢复会话文件失败: {}", e),
+        })?;
+
+    let repo = crate::database::repository::SessionRepository::from_default_db().map_err(|e| {
+        CommandError {
+            message: format!("创建 SessionRepository 失败: {}", e),
+        }
+    })?;
+    repo.unarchive_session(&entry.session_id)
+        .map_err(|e| CommandError {
+            message: format!("取消归档会话失败: {}", e),
+        })?;
+
+    Ok(entry.original_path)
+}
+
+/// 获取压缩归档清单
+#[tauri::command]
+pub async fn cmd_list_compressed_sessions(
+) -> Result<Vec<crate::database::SessionArchiveEntry>, CommandError> {
+    let archive =
+        crate::session_archive::SessionArchive::from_default().map_err(|e| CommandError {
+            message: format!("初始化会话归档失败: {}", e),
+        })?;
+    archive.list().map_err(|e| CommandError {
+        message: format!("获取归档清单失败: {}", e),
+    })
+}
+
+// ==================== 续接会话命令 ====================
+
+use crate::parser::resume::{
+    default_project_dir, qa_path, write_resume_session, ResumeSelection, ResumeSessionFile,
+};
+
+/// 将会话中选中的消息写成新会话，供 `claude --resume` 继续对话
+///
+/// 选中的消息会重新分配 uuid 并重建 parentUuid 链路，写出前用消息树解析校验。
+///
+/// # 参数
+/// - `file_path`: 原会话文件路径
+/// - `selection`: 按消息 uuid 或问答对下标范围选择
+/// - `project_dir`: (可选) 写入目录，默认为会话工作目录对应的 `~/.claude/projects/<目录名>`
+///
+/// # 返回
+/// 返回新会话 ID、文件路径和继续对话的命令
+#[tauri::command]
+pub async fn cmd_write_resume_session(
+    file_path: String,
+    selection: ResumeSelection,
+    project_dir: Option<String>,
+) -> Result<ResumeSessionFile, CommandError> {
+    let entries = read_session_entries(&file_path)?;
+
+    let (selected, skipped_qa_pairs) = match selection {
+        ResumeSelection::Messages { uuids } => (uuids.into_iter().collect(), Vec::new()),
+        ResumeSelection::QaRange {
+            start,
+            end,
+            segment_scope,
+            custom_level,
+        } => qa_range_uuids(
+            &file_path,
+            &entries,
+            start,
+            end,
+            segment_scope,
+            custom_level,
+        )?,
+    };
+
+    let project_dir = match project_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => default_project_dir(std::path::Path::new(&file_path), &entries).map_err(|e| {
+            CommandError {
+                message: format!("确定写入目录失败: {}", e),
+            }
+        })?,
+    };
+
+    let mut written =
+        write_resume_session(&entries, &selected, &project_dir).map_err(|e| CommandError {
+            message: format!("写出续接会话失败: {}", e),
+        })?;
+    written.skipped_qa_pairs = skipped_qa_pairs;
+    Ok(written)
+}
+
+/// 问答对下标范围内（含两端）的消息 uuid，包括问题到答案之间的工具调用往返
+///
+/// 问答对按 `segment_scope` 和 `custom_level` 提取，与 `cmd_get_qa_pairs_by_level`
+/// 使用相同参数时下标一致。被取代的分支上的问答对会被跳过，其下标随 uuid 一起返回
+fn qa_range_uuids(
+    file_path: &str,
+    entries: &[crate::parser::jsonl::JsonlEntry],
+    start: usize,
+    end: usize,
+    segment_scope: Option<SegmentScope>,
+    custom_level: Option<String>,
+) -> Result<(std::collections::HashSet<String>, Vec<usize>), CommandError> {
+    let custom_level =
+        load_custom_level(custom_level).map_err(|message| CommandError { message })?;
+    let session_id =
+        crate::parser::source::session_id_from_entries(std::path::Path::new(file_path), entries);
+    let qa_pairs = qa_pairs_for_file(file_path, &session_id, segment_scope, custom_level)
+        .map_err(|message| CommandError { message })?;
+
+    if start > end || end >= qa_pairs.len() {
+        return Err(CommandError {
+            message: format!(
+                "无效的问答对范围: {}..={}（共 {} 个问答对）",
+                start,
+                end,
+                qa_pairs.len()
+            ),
+        });
+    }
+
+    let mut selected = std::collections::HashSet::new();
+    let mut skipped = Vec::new();
+    for (index, pair) in qa_pairs.iter().enumerate().take(end + 1).skip(start) {
+        if pair.superseded {
+            skipped.push(index);
+            continue;
+        }
+        // extract_qa_pairs 只返回有回答的问答对
+        if let Some(answer) = &pair.answer {
+            selected.extend(qa_path(entries, &pair.question.uuid, &answer.uuid));
+        }
+    }
+    if selected.is_empty() {
+        return Err(CommandError {
+            message: format!(
+                "问答对范围 {}..={} 内没有可续接的问答对（均已被取代）",
+                start, end
+            ),
+        });
+    }
+    Ok((selected, skipped))
+}
@@ -0,0 +1,624 @@
+//! 消息索引仓库
+//!
+//! 将会话文件中的消息（uuid、父消息、类型、时间戳、字节偏移和长度、摘要）
+//! 增量写入 `messages` 表，并在 `message_index_state` 中记录每个文件已索引到的偏移。
+//! 视图可以按偏移向前或向后分页读取消息元数据、获取整个会话的轻量大纲，
+//! 再通过 `JsonlParser::parse_entry_at_offset` 按需加载可见消息，而无需解析整个文件。
+
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::database::models::Message;
+use crate::parser::jsonl::{JsonlEntry, JsonlParser};
+use crate::parser::source::{adapter_for_path, SessionSourceKind};
+
+/// 消息摘要保留的最大字符数
+const MESSAGE_SUMMARY_CHARS: usize = 100;
+
+/// 会话文件的索引状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageIndexState {
+    /// 会话 ID
+    pub session_id: String,
+    /// 已索引到的字节偏移（位于行首）
+    pub end_offset: u64,
+    /// 偏移之前若干字节的指纹（旧版本写入的记录为 None）
+    pub end_fingerprint: Option<u64>,
+    /// 已索引的消息数
+    pub message_count: usize,
+}
+
+/// 分页方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageDirection {
+    /// 读取游标之后的消息
+    #[default]
+    Forward,
+    /// 读取游标之前的消息
+    Backward,
+}
+
+/// 一页消息
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessagePage {
+    /// 本页消息（按文件偏移排序，不含消息内容）
+    pub messages: Vec<Message>,
+    /// 沿同一方向读取下一页时使用的游标偏移（没有更多消息时为 None）
+    pub next_offset: Option<i64>,
+    /// 会话的消息总数
+    pub total: usize,
+}
+
+/// 会话大纲条目（用于滚动条导航）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageOutlineEntry {
+    /// 消息 UUID
+    pub uuid: String,
+    /// 角色（user / assistant）
+    pub role: String,
+    /// 消息时间戳
+    pub timestamp: String,
+    /// 在 JSONL 文件中的字节偏移
+    pub offset: i64,
+    /// 消息的字节长度
+    pub length: i64,
+}
+
+/// 消息索引仓库
+pub struct MessageIndexRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl MessageIndexRepository {
+    /// 使用共享连接创建仓库实例
+    pub fn with_conn(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// 从默认数据库路径创建仓库
+    pub fn from_default_db() -> Result<Self> {
+        let conn = crate::database::init::get_connection_shared()?;
+        Ok(Self::with_conn(conn))
+    }
+
+    /// 辅助方法：获取连接锁
+    fn with_conn_inner<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Connection) -> Result<R>,
+    {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("获取数据库连接锁失败（Mutex 已被毒化）: {}", e))?;
+        f(&mut conn)
+    }
+
+    /// 获取会话文件的索引状态
+    pub fn get_state(&self, file_path: &str) -> Result<Option<MessageIndexState>> {
+        self.with_conn_inner(|conn| {
+            conn.query_row(
+                "SELECT session_id, end_offset, end_fingerprint, message_count
+                 FROM message_index_state WHERE file_path = ?1",
+                params![file_path],
+                |row| {
+                    Ok(MessageIndexState {
+                        session_id: row.get(0)?,
+                        end_offset: row.get::<_, i64>(1)? as u64,
+                        end_fingerprint: row.get::<_, Option<i64>>(2)?.map(|fp| fp as u64),
+                        message_count: row.get::<_, i64>(3)? as usize,
+                    })
+                },
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    /// 增量同步会话文件的消息
+    ///
+    /// 从上次索引到的偏移继续解析，只写入新追加的消息；文件被截断或重写时
+    /// （按文件长度和偏移之前的内容指纹判断）清空该会话的消息后从头索引。
+    /// 会话记录不存在时会先创建。
+    ///
+    /// 消息窗口按偏移直接读取原始 JSONL 行，因此只索引 Claude Code 会话，
+    /// 其他来源的会话文件会被跳过。已压缩归档的会话读取解压后的缓存文件
+    /// （偏移与原文件一致），索引状态仍按原路径记录。
+    ///
+    /// # 返回
+    /// 返回本次写入的消息数
+    pub fn sync_session_file(
+        &self,
+        session_id: &str,
+        project_path: &str,
+        project_name: &str,
+        file_path: &Path,
+    ) -> Result<usize> {
+        if adapter_for_path(file_path).kind() != SessionSourceKind::ClaudeCode {
+            return Ok(0);
+        }
+
+        let file_path_str = file_path.to_string_lossy().to_string();
+        let state = self.get_state(&file_path_str)?;
+
+        let readable_path = crate::session_archive::resolve_session_path(file_path)?;
+        let start_offset = state.as_ref().map_or(0, |s| s.end_offset);
+        let mut parser = match state.as_ref().and_then(|s| s.end_fingerprint) {
+            Some(fingerprint) => {
+                JsonlParser::with_checkpoint(readable_path, start_offset, fingerprint)?
+            }
+            None => JsonlParser::with_offset(readable_path, start_offset)?,
+        };
+        let result = parser.parse_incremental()?;
+        // 没有新内容（指纹已记录时也确认了文件未被重写）
+        if state.as_ref().is_some_and(|s| s.end_fingerprint.is_some())
+            && !result.reset
+            && result.end_offset == start_offset
+        {
+            return Ok(0);
+        }
+        let rebuild = state.is_none() || result.reset;
+
+        let now = Utc::now().to_rfc3339();
+        self.with_conn_inner(|conn| {
+            let tx = conn.transaction()?;
+
+            tx.execute(
+                "INSERT INTO sessions (
+                    session_id, project_path, project_name, file_path,
+                    is_active, created_at, updated_at
+                ) VALUES (?1, ?2, ?3, ?4, 0, ?5, ?5)
+                ON CONFLICT(session_id) DO NOTHING",
+                params![session_id, project_path, project_name, file_path_str, now],
+            )?;
+
+            if rebuild {
+                tx.execute(
+                    "DELETE FROM messages WHERE session_id = ?1",
+                    params![session_id],
+                )?;
+            }
+
+            let mut inserted = 0;
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO messages (
+                        session_id, uuid, parent_uuid, type, content_type, timestamp,
+                        offset, length, summary, created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                    ON CONFLICT(session_id, uuid) DO UPDATE SET
+                        parent_uuid = excluded.parent_uuid,
+                        type = excluded.type,
+                        content_type = excluded.content_type,
+                        timestamp = excluded.timestamp,
+                        offset = excluded.offset,
+                        length = excluded.length,
+                        summary = excluded.summary",
+                )?;
+                for entry in &result.entries {
+                    let row = match IndexedMessage::from_entry(entry) {
+                        Some(row) => row,
+                        None => continue,
+                    };
+                    stmt.execute(params![
+                        session_id,
+                        row.uuid,
+                        row.parent_uuid,
+                        row.msg_type,
+                        row.content_type,
+                        row.timestamp,
+                        entry.offset as i64,
+                        entry.length as i64,
+                        row.summary,
+                        now,
+                    ])?;
+                    inserted += 1;
+                }
+            }
+
+            let message_count: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM messages WHERE session_id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )?;
+            tx.execute(
+                "INSERT INTO message_index_state
+                    (file_path, session_id, end_offset, end_fingerprint, message_count, indexed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(file_path) DO UPDATE SET
+                    session_id = excluded.session_id,
+                    end_offset = excluded.end_offset,
+                    end_fingerprint = excluded.end_fingerprint,
+                    message_count = excluded.message_count,
+                    indexed_at = excluded.indexed_at",
+                params![
+                    file_path_str,
+                    session_id,
+                    result.end_offset as i64,
+                    result.end_fingerprint as i64,
+                    message_count,
+                    now
+                ],
+            )?;
+
+            tx.commit()?;
+            Ok(inserted)
+        })
+    }
+
+    /// 按文件偏移分页读取会话消息
+    ///
+    /// # 参数
+    /// - `session_id`: 会话 ID
+    /// - `after_offset`: 只返回偏移大于该值的消息（第一页传 None）
+    /// - `limit`: 每页消息数
+    pub fn list_messages(
+        &self,
+        session_id: &str,
+        after_offset: Option<i64>,
+        limit: usize,
+    ) -> Result<MessagePage> {
+        self.list_window(session_id, after_offset, PageDirection::Forward, limit)
+    }
+
+    /// 以偏移为游标读取一个消息窗口
+    ///
+    /// # 参数
+    /// - `session_id`: 会话 ID
+    /// - `cursor`: 游标偏移（不包含在结果中）。为 None 时向前从会话开头、向后从会话末尾读取
+    /// - `direction`: 分页方向
+    /// - `limit`: 每页消息数
+    ///
+    /// 无论方向如何，返回的消息都按文件偏移升序排列
+    pub fn list_window(
+        &self,
+        session_id: &str,
+        cursor: Option<i64>,
+        direction: PageDirection,
+        limit: usize,
+    ) -> Result<MessagePage> {
+        let sql = match direction {
+            PageDirection::Forward => {
+                "SELECT id, session_id, uuid, parent_uuid, type, content_type, timestamp,
+                        offset, length, summary, parent_idx, created_at
+                 FROM messages
+                 WHERE session_id = ?1 AND offset > ?2
+                 ORDER BY offset
+                 LIMIT ?3"
+            }
+            PageDirection::Backward => {
+                "SELECT id, session_id, uuid, parent_uuid, type, content_type, timestamp,
+                        offset, length, summary, parent_idx, created_at
+                 FROM messages
+                 WHERE session_id = ?1 AND offset < ?2
+                 ORDER BY offset DESC
+                 LIMIT ?3"
+            }
+        };
+        let cursor = cursor.unwrap_or(match direction {
+            PageDirection::Forward => -1,
+            PageDirection::Backward => i64::MAX,
+        });
+
+        self.with_conn_inner(|conn| {
+            let total: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM messages WHERE session_id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )?;
+
+            let mut stmt = conn.prepare(sql)?;
+            // 多取一条用于判断是否还有下一页
+            let rows = stmt.query_map(params![session_id, cursor, limit as i64 + 1], |row| {
+                Ok(Message {
+                    id: Some(row.get(0)?),
+                    session_id: row.get(1)?,
+                    uuid: row.get(2)?,
+                    parent_uuid: row.get(3)?,
+                    msg_type: row.get(4)?,
+                    content_type: row.get(5)?,
+                    timestamp: row.get(6)?,
+                    offset: row.get(7)?,
+                    length: row.get(8)?,
+                    summary: row.get(9)?,
+                    content: None,
+                    parent_idx: row.get(10)?,
+                    created_at: row.get(11)?,
+                })
+            })?;
+            let mut messages = rows.collect::<Result<Vec<_>, _>>()?;
+
+            let has_more = messages.len() > limit;
+            messages.truncate(limit);
+            if direction == PageDirection::Backward {
+                messages.reverse();
+            }
+
+            let next_offset = if has_more {
+                match direction {
+                    PageDirection::Forward => messages.last().map(|m| m.offset),
+                    PageDirection::Backward => messages.first().map(|m| m.offset),
+                }
+            } else {
+                None
+            };
+
+            Ok(MessagePage {
+                messages,
+                next_offset,
+                total: total as usize,
+            })
+        })
+    }
+
+    /// 查找消息的文件偏移
+    pub fn find_offset(&self, session_id: &str, uuid: &str) -> Result<Option<i64>> {
+        self.with_conn_inner(|conn| {
+            conn.query_row(
+                "SELECT offset FROM messages WHERE session_id = ?1 AND uuid = ?2",
+                params![session_id, uuid],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    /// 获取会话大纲（全部消息的 UUID、角色、时间戳和偏移，按偏移排序）
+    pub fn outline(&self, session_id: &str) -> Result<Vec<MessageOutlineEntry>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT uuid, type, timestamp, offset, length
+                 FROM messages
+                 WHERE session_id = ?1
+                 ORDER BY offset",
+            )?;
+            let entries = stmt.query_map(params![session_id], |row| {
+                Ok(MessageOutlineEntry {
+                    uuid: row.get(0)?,
+                    role: row.get(1)?,
+                    timestamp: row.get(2)?,
+                    offset: row.get(3)?,
+                    length: row.get(4)?,
+                })
+            })?;
+            entries.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// 删除会话文件的索引状态和消息
+    pub fn remove_file(&self, file_path: &str) -> Result<usize> {
+        self.with_conn_inner(|conn| {
+            let tx = conn.transaction()?;
+            let removed = tx.execute(
+                "DELETE FROM messages WHERE session_id IN
+                 (SELECT session_id FROM message_index_state WHERE file_path = ?1)",
+                params![file_path],
+            )?;
+            tx.execute(
+                "DELETE FROM message_index_state WHERE file_path = ?1",
+                params![file_path],
+            )?;
+            tx.commit()?;
+            Ok(removed)
+        })
+    }
+}
+
+unsafe impl Send for MessageIndexRepository {}
+unsafe impl Sync for MessageIndexRepository {}
+
+/// 从 JSONL 条目提取的消息行
+struct IndexedMessage {
+    uuid: String,
+    parent_uuid: Option<String>,
+    msg_type: String,
+    content_type: Option<String>,
+    timestamp: String,
+    summary: Option<String>,
+}
+
+impl IndexedMessage {
+    /// 提取可索引的消息
+    ///
+    /// 只索引带 uuid 的 user / assistant 条目，summary、system、快照等条目被跳过
+    fn from_entry(entry: &JsonlEntry) -> Option<Self> {
+        let data = &entry.data;
+        let msg_type = entry.message_type()?;
+        if msg_type != "user" && msg_type != "assistant" {
+            return None;
+        }
+        let uuid = data.get("uuid")?.as_str()?.to_string();
+
+        let content = data.get("message").and_then(|m| m.get("content"));
+        let content_type = match content {
+            Some(Value::String(_)) => Some("text".to_string()),
+            Some(Value::Array(blocks)) => blocks
+                .first()
+                .and_then(|b| b.get("type"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            _ => None,
+        };
+
+        Some(Self {
+            uuid,
+            parent_uuid: data
+                .get("parentUuid")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            msg_type,
+            content_type,
+            timestamp: data
+                .get("timestamp")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            summary: content.and_then(summary_text),
+        })
+    }
+}
+
+/// 提取消息的文本摘要（第一个文本块的前若干字符）
+fn summary_text(content: &Value) -> Option<String> {
+    let text = match content {
+        Value::String(text) => text.as_str(),
+        Value::Array(blocks) => blocks.iter().find_map(|b| {
+            b.get("text")
+                .or_else(|| b.get("thinking"))
+                .and_then(|v| v.as_str())
+        })?,
+        _ => return None,
+    };
+
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.chars().take(MESSAGE_SUMMARY_CHARS).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn create_repo() -> MessageIndexRepository {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v4(&mut conn).unwrap();
+        migrations::migrate_v5(&mut conn).unwrap();
+        migrations::migrate_v15(&mut conn).unwrap();
+        migrations::migrate_v29(&mut conn).unwrap();
+        migrations::migrate_v32(&mut conn).unwrap();
+        MessageIndexRepository::with_conn(Arc::new(Mutex::new(conn)))
+    }
+
+    fn append(path: &Path, lines: &[&str]) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_incremental_message_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("s1.jsonl");
+        append(
+            &path,
+            &[
+                r#"{"type":"summary","summary":"Fix build","leafUuid":"a1"}"#,
+                r#"{"type":"user","uuid":"u1","timestamp":"2025-03-10T09:00:00Z","message":{"role":"user","content":"fix the build"}}"#,
+                r#"{"type":"assistant","uuid":"a1","parentUuid":"u1","timestamp":"2025-03-10T09:01:00Z","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}"#,
+            ],
+        );
+
+        let repo = create_repo();
+        assert_eq!(
+            repo.sync_session_file("s1", "/app", "app", &path).unwrap(),
+            2
+        );
+        assert_eq!(
+            repo.sync_session_file("s1", "/app", "app", &path).unwrap(),
+            0
+        );
+
+        append(
+            &path,
+            &[
+                r#"{"type":"user","uuid":"r1","parentUuid":"a1","timestamp":"2025-03-10T09:02:00Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
+            ],
+        );
+        assert_eq!(
+            repo.sync_session_file("s1", "/app", "app", &path).unwrap(),
+            1
+        );
+
+        let state = repo.get_state(&path.to_string_lossy()).unwrap().unwrap();
+        assert_eq!(state.message_count, 3);
+        assert_eq!(state.end_offset, std::fs::metadata(&path).unwrap().len());
+
+        // 分页读取，并能按偏移加载单条消息
+        let page = repo.list_messages("s1", None, 2).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.messages.len(), 2);
+        assert_eq!(page.messages[0].summary.as_deref(), Some("fix the build"));
+        assert_eq!(page.messages[1].content_type.as_deref(), Some("tool_use"));
+        let page = repo.list_messages("s1", page.next_offset, 2).unwrap();
+        assert_eq!(page.messages.len(), 1);
+        assert!(page.next_offset.is_none());
+
+        // 从末尾向后翻页，结果仍按偏移升序
+        let tail = repo
+            .list_window("s1", None, PageDirection::Backward, 2)
+            .unwrap();
+        let uuids: Vec<&str> = tail.messages.iter().map(|m| m.uuid.as_str()).collect();
+        assert_eq!(uuids, ["a1", "r1"]);
+        let head = repo
+            .list_window("s1", tail.next_offset, PageDirection::Backward, 2)
+            .unwrap();
+        assert_eq!(head.messages[0].uuid, "u1");
+        assert!(head.next_offset.is_none());
+
+        let cursor = repo.find_offset("s1", "u1").unwrap();
+        let after = repo
+            .list_window("s1", cursor, PageDirection::Forward, 10)
+            .unwrap();
+        assert_eq!(after.messages.len(), 2);
+
+        let outline = repo.outline("s1").unwrap();
+        assert_eq!(outline.len(), 3);
+        assert_eq!(outline[1].role, "assistant");
+        assert_eq!(outline[1].timestamp, "2025-03-10T09:01:00Z");
+
+        let message = &page.messages[0];
+        let parser = JsonlParser::new(path.clone()).unwrap();
+        let value = parser
+            .parse_entry_at_offset(message.offset as u64, message.length as usize)
+            .unwrap();
+        assert_eq!(value["uuid"], "r1");
+
+        // 文件被重写后从头重建
+        std::fs::write(
+            &path,
+            "{\"type\":\"user\",\"uuid\":\"n1\",\"message\":{\"content\":\"new\"}}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            repo.sync_session_file("s1", "/app", "app", &path).unwrap(),
+            1
+        );
+        assert_eq!(repo.list_messages("s1", None, 10).unwrap().total, 1);
+
+        // 文件被重写为相同长度的内容时同样从头重建
+        std::fs::write(
+            &path,
+            "{\"type\":\"user\",\"uuid\":\"m1\",\"message\":{\"content\":\"old\"}}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            repo.sync_session_file("s1", "/app", "app", &path).unwrap(),
+            1
+        );
+        let page = repo.list_messages("s1", None, 10).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.messages[0].uuid, "m1");
+        assert_eq!(
+            repo.sync_session_file("s1", "/app", "app", &path).unwrap(),
+            0
+        );
+    }
+}
@@ -16,8 +16,7 @@ const DB_NAME: &str = "prism_forge.db";
 /// - macOS: ~/.prism-forge/prism_forge.db
 /// - Linux: ~/.prism-forge/prism_forge.db
 pub fn get_db_path() -> Result<PathBuf> {
-    let home_dir = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("无法获取用户目录"))?;
-    let app_dir = home_dir.join(".prism-forge");
+    let app_dir = get_app_dir()?;
 
     // 确保目录存在
     std::fs::create_dir_all(&app_dir)?;
@@ -25,10 +24,19 @@ pub fn get_db_path() -> Result<PathBuf> {
     Ok(app_dir.join(DB_NAME))
 }
 
+/// 获取应用数据目录（`~/.prism-forge`）
+///
+/// 只计算路径，不创建目录；只读取数据的调用方使用此函数，
+/// 避免为不存在的文件创建目录
+pub fn get_app_dir() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("无法获取用户目录"))?;
+    Ok(home_dir.join(".prism-forge"))
+}
+
 /// 数据库版本号
 ///
 /// 每次修改表结构时递增此版本号
-const CURRENT_DB_VERSION: i32 = 22;
+const CURRENT_DB_VERSION: i32 = 33;
 
 /// 初始化数据库
 ///
@@ -91,6 +99,17 @@ pub fn run_migrations(conn: &mut Connection) -> Result<()> {
             20 => migrate_v20(conn)?,
             21 => migrate_v21(conn)?,
             22 => migrate_v22(conn)?,
+            23 => migrate_v23(conn)?,
+            24 => migrate_v24(conn)?,
+            25 => migrate_v25(conn)?,
+            26 => migrate_v26(conn)?,
+            27 => migrate_v27(conn)?,
+            28 => migrate_v28(conn)?,
+            29 => migrate_v29(conn)?,
+            30 => migrate_v30(conn)?,
+            31 => migrate_v31(conn)?,
+            32 => migrate_v32(conn)?,
+            33 => migrate_v33(conn)?,
             _ => anyhow::bail!("未知的数据库版本: {}", version),
         }
 
@@ -1712,6 +1731,472 @@ fn migrate_v22_impl(conn: &mut Connection) -> Result<()> {
     Ok(())
 }
 
+/// 迁移到版本 23: 创建 model_prices 表
+///
+/// # 功能
+/// - 创建 model_prices 表（模型价格表，单位：美元 / 百万 token）
+/// - 按模型名称模式匹配，最长模式优先
+/// - 预置 Claude 系列模型的默认价格，用户可自行修改
+#[cfg(test)]
+pub fn migrate_v23(conn: &mut Connection) -> Result<()> {
+    migrate_v23_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v23(conn: &mut Connection) -> Result<()> {
+    migrate_v23_impl(conn)
+}
+
+fn migrate_v23_impl(conn: &mut Connection) -> Result<()> {
+    // 1. 创建模型价格表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS model_prices (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            model_pattern TEXT NOT NULL UNIQUE,
+            input_price REAL NOT NULL DEFAULT 0,
+            output_price REAL NOT NULL DEFAULT 0,
+            cache_write_price REAL NOT NULL DEFAULT 0,
+            cache_read_price REAL NOT NULL DEFAULT 0,
+            updated_at TEXT NOT NULL DEFAULT (datetime('now', 'localtime'))
+        )",
+        [],
+    )?;
+
+    // 2. 预置默认价格（模式, 输入, 输出, 缓存写入, 缓存读取）
+    let defaults: [(&str, f64, f64, f64, f64); 10] = [
+        ("claude-opus-4-5", 5.0, 25.0, 6.25, 0.5),
+        ("claude-opus-4", 15.0, 75.0, 18.75, 1.5),
+        ("claude-sonnet-4", 3.0, 15.0, 3.75, 0.3),
+        ("claude-haiku-4-5", 1.0, 5.0, 1.25, 0.1),
+        ("claude-3-7-sonnet", 3.0, 15.0, 3.75, 0.3),
+        ("claude-3-5-sonnet", 3.0, 15.0, 3.75, 0.3),
+        ("claude-3-5-haiku", 0.8, 4.0, 1.0, 0.08),
+        ("claude-3-opus", 15.0, 75.0, 18.75, 1.5),
+        ("claude-3-sonnet", 3.0, 15.0, 3.75, 0.3),
+        ("claude-3-haiku", 0.25, 1.25, 0.3, 0.03),
+    ];
+
+    for (pattern, input, output, cache_write, cache_read) in defaults {
+        conn.execute(
+            "INSERT OR IGNORE INTO model_prices
+             (model_pattern, input_price, output_price, cache_write_price, cache_read_price)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![pattern, input, output, cache_write, cache_read],
+        )?;
+    }
+
+    log::info!("✅ 已创建 model_prices 表");
+
+    Ok(())
+}
+
+/// 迁移到版本 24: 创建消息全文索引
+///
+/// # 功能
+/// - 创建 message_fts 虚拟表（FTS5，仅 content 列参与索引）
+/// - 创建 message_fts_sources 表，记录每个会话文件索引时的大小、修改时间和
+///   已索引到的偏移，用于增量同步时跳过未变化的文件、只索引新追加的消息
+#[cfg(test)]
+pub fn migrate_v24(conn: &mut Connection) -> Result<()> {
+    migrate_v24_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v24(conn: &mut Connection) -> Result<()> {
+    migrate_v24_impl(conn)
+}
+
+fn migrate_v24_impl(conn: &mut Connection) -> Result<()> {
+    // 1. 创建全文索引虚拟表
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS message_fts USING fts5(
+            content,
+            uuid UNINDEXED,
+            session_id UNINDEXED,
+            project_path UNINDEXED,
+            role UNINDEXED,
+            timestamp UNINDEXED,
+            file_path UNINDEXED,
+            tokenize = 'unicode61 remove_diacritics 2'
+        )",
+        [],
+    )?;
+
+    // 2. 创建索引来源表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS message_fts_sources (
+            session_id TEXT PRIMARY KEY,
+            project_path TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            file_mtime TEXT NOT NULL,
+            end_offset INTEGER NOT NULL DEFAULT 0,
+            message_count INTEGER NOT NULL DEFAULT 0,
+            indexed_at TEXT NOT NULL DEFAULT (datetime('now', 'localtime'))
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_message_fts_sources_file_path
+         ON message_fts_sources(file_path);",
+        [],
+    )?;
+
+    log::info!("✅ 已创建 message_fts 全文索引");
+
+    Ok(())
+}
+
+/// 迁移到版本 25: 创建会话查询所需的表
+///
+/// # 功能
+/// - 创建 session_facts 表（会话使用的工具、文件、错误、模型、角色和活跃日期）
+/// - 创建 saved_queries 表（保存的会话查询）
+/// - 依赖版本 24 的 message_fts_sources 表
+#[cfg(test)]
+pub fn migrate_v25(conn: &mut Connection) -> Result<()> {
+    migrate_v25_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v25(conn: &mut Connection) -> Result<()> {
+    migrate_v25_impl(conn)
+}
+
+fn migrate_v25_impl(conn: &mut Connection) -> Result<()> {
+    // 1. 创建会话事实表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_facts (
+            session_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (session_id, kind, value)
+        )",
+        [],
+    )?;
+
+    // 2. 创建索引：按类别和值查找会话
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_session_facts_kind_value
+         ON session_facts(kind, value);",
+        [],
+    )?;
+
+    // 3. 创建保存的查询表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS saved_queries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            query TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now', 'localtime'))
+        )",
+        [],
+    )?;
+
+    // 4. 清空全文索引来源，使下次同步时为已索引的会话补充事实
+    conn.execute("DELETE FROM message_fts_sources", [])?;
+
+    log::info!("✅ 已创建 session_facts 和 saved_queries 表");
+
+    Ok(())
+}
+
+/// 迁移到版本 26: 支持自定义视图等级
+///
+/// # 功能
+/// - 创建 custom_view_levels 表（等级名称 + 谓词 JSON）
+/// - 为 view_level_preferences 添加 custom_level 列，选择自定义等级时
+///   view_level 记为 full，custom_level 记为等级名称
+#[cfg(test)]
+pub fn migrate_v26(conn: &mut Connection) -> Result<()> {
+    migrate_v26_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v26(conn: &mut Connection) -> Result<()> {
+    migrate_v26_impl(conn)
+}
+
+fn migrate_v26_impl(conn: &mut Connection) -> Result<()> {
+    // 1. 创建自定义等级表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS custom_view_levels (
+            name TEXT PRIMARY KEY,
+            description TEXT,
+            predicate TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // 2. 为偏好表添加自定义等级列
+    conn.execute(
+        "ALTER TABLE view_level_preferences ADD COLUMN custom_level TEXT;",
+        [],
+    )?;
+
+    log::info!("✅ 已创建 custom_view_levels 表");
+
+    Ok(())
+}
+
+/// 迁移到版本 27: 工具错误分类
+///
+/// # 功能
+/// - 创建 tool_errors 表，保存每个会话中按类别分类的工具错误
+/// - 清空全文索引来源，使下次同步时为已索引的会话补充错误分类
+#[cfg(test)]
+pub fn migrate_v27(conn: &mut Connection) -> Result<()> {
+    migrate_v27_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v27(conn: &mut Connection) -> Result<()> {
+    migrate_v27_impl(conn)
+}
+
+fn migrate_v27_impl(conn: &mut Connection) -> Result<()> {
+    // 1. 创建工具错误表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tool_errors (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            project_path TEXT NOT NULL,
+            uuid TEXT NOT NULL,
+            tool_name TEXT,
+            category TEXT NOT NULL,
+            origin TEXT,
+            message TEXT NOT NULL,
+            timestamp TEXT
+        )",
+        [],
+    )?;
+
+    // 2. 创建索引
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tool_errors_session ON tool_errors(session_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tool_errors_project_category
+         ON tool_errors(project_path, category)",
+        [],
+    )?;
+
+    // 3. 清空全文索引来源，使下次同步时为已索引的会话补充错误分类
+    conn.execute("DELETE FROM message_fts_sources", [])?;
+
+    log::info!("✅ 已创建 tool_errors 表");
+
+    Ok(())
+}
+
+/// 迁移到版本 28: 会话扫描缓存
+///
+/// # 功能
+/// - 创建 session_scan_cache 表，按文件路径缓存扫描得到的会话元数据，
+///   文件大小和修改时间不变时扫描器直接复用，无需重新读取文件
+#[cfg(test)]
+pub fn migrate_v28(conn: &mut Connection) -> Result<()> {
+    migrate_v28_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v28(conn: &mut Connection) -> Result<()> {
+    migrate_v28_impl(conn)
+}
+
+fn migrate_v28_impl(conn: &mut Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_scan_cache (
+            file_path TEXT PRIMARY KEY,
+            file_size INTEGER NOT NULL,
+            file_mtime TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            project_path TEXT NOT NULL,
+            project_name TEXT NOT NULL,
+            message_count INTEGER NOT NULL,
+            display_name TEXT,
+            first_timestamp TEXT,
+            last_timestamp TEXT,
+            file_type TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            scanned_at TEXT NOT NULL DEFAULT (datetime('now', 'localtime'))
+        )",
+        [],
+    )?;
+
+    log::info!("✅ 已创建 session_scan_cache 表");
+
+    Ok(())
+}
+
+/// 迁移到版本 29: 消息索引
+///
+/// # 功能
+/// - 为 messages 表添加 (session_id, uuid) 唯一索引，支持按消息 upsert
+/// - 创建 message_index_state 表，记录每个会话文件已索引到的字节偏移
+#[cfg(test)]
+pub fn migrate_v29(conn: &mut Connection) -> Result<()> {
+    migrate_v29_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v29(conn: &mut Connection) -> Result<()> {
+    migrate_v29_impl(conn)
+}
+
+fn migrate_v29_impl(conn: &mut Connection) -> Result<()> {
+    // 1. 去除重复消息后创建唯一索引
+    conn.execute(
+        "DELETE FROM messages WHERE id NOT IN
+         (SELECT MIN(id) FROM messages GROUP BY session_id, uuid)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_session_uuid
+            ON messages(session_id, uuid)",
+        [],
+    )?;
+
+    // 2. 按会话 + 偏移量分页
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_messages_session_offset
+            ON messages(session_id, offset)",
+        [],
+    )?;
+
+    // 3. 创建索引状态表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS message_index_state (
+            file_path TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            end_offset INTEGER NOT NULL,
+            message_count INTEGER NOT NULL,
+            indexed_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    log::info!("✅ 已创建 message_index_state 表");
+
+    Ok(())
+}
+
+/// 迁移到版本 30: 会话压缩归档
+///
+/// # 功能
+/// - 创建 session_archives 表，记录已压缩归档的会话文件（原路径、归档路径、压缩前后大小）
+/// - 以原路径为主键：子代理文件的会话 ID（文件名）在不同会话下会重复
+#[cfg(test)]
+pub fn migrate_v30(conn: &mut Connection) -> Result<()> {
+    migrate_v30_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v30(conn: &mut Connection) -> Result<()> {
+    migrate_v30_impl(conn)
+}
+
+fn migrate_v30_impl(conn: &mut Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_archives (
+            original_path TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            archive_path TEXT NOT NULL,
+            original_size INTEGER NOT NULL,
+            compressed_size INTEGER NOT NULL,
+            archived_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    log::info!("✅ 已创建 session_archives 表");
+
+    Ok(())
+}
+
+/// 迁移到版本 31: 监控目录的会话来源
+///
+/// # 功能
+/// - 为 monitored_directories 表添加 source 字段（claude_code / codex_cli / aider），
+///   扫描时按来源选择会话适配器，已有目录默认为 Claude Code
+#[cfg(test)]
+pub fn migrate_v31(conn: &mut Connection) -> Result<()> {
+    migrate_v31_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v31(conn: &mut Connection) -> Result<()> {
+    migrate_v31_impl(conn)
+}
+
+fn migrate_v31_impl(conn: &mut Connection) -> Result<()> {
+    conn.execute(
+        "ALTER TABLE monitored_directories ADD COLUMN source TEXT NOT NULL DEFAULT 'claude_code';",
+        [],
+    )?;
+
+    log::info!("✅ 已添加 monitored_directories.source 字段");
+
+    Ok(())
+}
+
+/// 迁移到版本 32: 消息索引偏移指纹
+///
+/// # 功能
+/// - 为 message_index_state 表添加 end_fingerprint 字段（已索引偏移之前若干字节的指纹），
+///   文件被重写为相同或更大的长度时据此从头重建索引；已有记录为 NULL，下次追加时写入
+#[cfg(test)]
+pub fn migrate_v32(conn: &mut Connection) -> Result<()> {
+    migrate_v32_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v32(conn: &mut Connection) -> Result<()> {
+    migrate_v32_impl(conn)
+}
+
+fn migrate_v32_impl(conn: &mut Connection) -> Result<()> {
+    conn.execute(
+        "ALTER TABLE message_index_state ADD COLUMN end_fingerprint INTEGER;",
+        [],
+    )?;
+
+    log::info!("✅ 已添加 message_index_state.end_fingerprint 字段");
+
+    Ok(())
+}
+
+/// 迁移到版本 33: 全文索引偏移指纹
+///
+/// # 功能
+/// - 为 message_fts_sources 表添加 end_fingerprint 字段（已索引偏移之前若干字节的指纹），
+///   文件被重写为相同或更大的长度时据此重建会话索引；已有记录为 NULL，下次同步时写入
+#[cfg(test)]
+pub fn migrate_v33(conn: &mut Connection) -> Result<()> {
+    migrate_v33_impl(conn)
+}
+
+#[cfg(not(test))]
+fn migrate_v33(conn: &mut Connection) -> Result<()> {
+    migrate_v33_impl(conn)
+}
+
+fn migrate_v33_impl(conn: &mut Connection) -> Result<()> {
+    conn.execute(
+        "ALTER TABLE message_fts_sources ADD COLUMN end_fingerprint INTEGER;",
+        [],
+    )?;
+
+    log::info!("✅ 已添加 message_fts_sources.end_fingerprint 字段");
+
+    Ok(())
+}
+
 /// 获取数据库连接（用于运行时）
 ///
 /// 注意: 每个线程应该有自己的连接
@@ -1784,6 +2269,69 @@ mod tests {
         assert_eq!(column_exists, 1);
     }
 
+    #[test]
+    fn test_migrate_v23_seeds_model_prices() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        migrate_v23_impl(&mut conn).unwrap();
+        // 重复执行不应产生重复数据
+        migrate_v23_impl(&mut conn).unwrap();
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM model_prices", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 10);
+    }
+
+    #[test]
+    fn test_migrate_v24_creates_fts_index() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        migrate_v24_impl(&mut conn).unwrap();
+        migrate_v24_impl(&mut conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO message_fts (content, uuid, session_id, project_path, role, timestamp, file_path)
+             VALUES ('fix the flaky watcher test', 'u1', 's1', 'p', 'user', '2025-01-01T00:00:00Z', 'f')",
+            [],
+        )
+        .unwrap();
+
+        let uuid: String = conn
+            .query_row(
+                "SELECT uuid FROM message_fts WHERE message_fts MATCH '\"flaky watcher\"'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(uuid, "u1");
+    }
+
+    #[test]
+    fn test_migrate_v27_creates_tool_errors() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        migrate_v24_impl(&mut conn).unwrap();
+        migrate_v27_impl(&mut conn).unwrap();
+        migrate_v27_impl(&mut conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO tool_errors (session_id, project_path, uuid, category, message)
+             VALUES ('s1', 'p', 'a1', 'compile_error', 'error[E0308]')",
+            [],
+        )
+        .unwrap();
+
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM tool_errors WHERE project_path = 'p' AND category = 'compile_error'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
     #[test]
     fn test_migrate_v19_tables_created() {
         let mut conn = Connection::open_in_memory().unwrap();
@@ -5,21 +5,33 @@
 pub mod init;
 pub mod init_default_prompts;
 pub mod decision_keywords;
+pub mod message_index_repository;
 pub mod migrations;
+pub mod model_price_repository;
 pub mod models;
 pub mod prompt_versions;
 pub mod repository;
 pub mod repositories_tech_stack;
+pub mod scan_cache_repository;
+pub mod search_repository;
+pub mod session_archive_repository;
+pub mod session_query_repository;
+pub mod tool_error_repository;
 pub mod vector_repository;
 pub mod intent_analysis_repository;
 pub mod decision_analysis_repository;
 
 pub use init::{get_connection_shared, get_db_path as get_db_path_init};
-pub use migrations::{get_connection, get_db_path, initialize_database};
+pub use migrations::{get_app_dir, get_connection, get_db_path, initialize_database};
 pub use decision_keywords::{DecisionKeyword, DecisionKeywordRepository};
 pub use repositories_tech_stack::{ProjectTechStack, ProjectTechStackRepository};
 pub use intent_analysis_repository::{IntentAnalysisHistory, IntentAnalysisRepository};
 pub use decision_analysis_repository::{DecisionAnalysisHistory, DecisionAnalysisRepository};
+pub use message_index_repository::{
+    MessageIndexRepository, MessageOutlineEntry, MessagePage, PageDirection,
+};
+pub use session_archive_repository::{SessionArchiveEntry, SessionArchiveRepository};
+pub use model_price_repository::{ModelPrice, ModelPriceRepository};
 pub use models::{
     validate_timestamp,
     ApiProvider,
@@ -53,4 +65,8 @@ pub use models::{
 };
 pub use prompt_versions::PromptVersionRepository;
 pub use repository::{ApiProviderRepository, PromptHistoryRepository};
+pub use scan_cache_repository::{ScanCacheEntry, ScanCacheRepository};
+pub use search_repository::{SearchHit, SearchQuery, SearchRepository};
+pub use session_query_repository::{SavedQuery, SessionQueryRepository};
+pub use tool_error_repository::{ProjectToolErrorStats, ToolErrorCount, ToolErrorRepository};
 pub use vector_repository::VectorRepository;
@@ -0,0 +1,224 @@
+//! 模型价格数据仓库
+//!
+//! 提供 model_prices 表的 CRUD 操作，用于 token 成本计算
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use std::sync::{Arc, Mutex};
+use ts_rs::TS;
+
+/// 模型价格（单位：美元 / 百万 token）
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(rename_all = "camelCase")]
+pub struct ModelPrice {
+    /// 数据库 ID（新建时为 None）
+    #[ts(type = "number | null")]
+    pub id: Option<i64>,
+    /// 模型名称模式（模型名包含该字符串即匹配，最长模式优先）
+    pub model_pattern: String,
+    /// 输入价格
+    pub input_price: f64,
+    /// 输出价格
+    pub output_price: f64,
+    /// 缓存写入价格
+    pub cache_write_price: f64,
+    /// 缓存读取价格
+    pub cache_read_price: f64,
+    /// 更新时间
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+/// 模型价格数据仓库
+pub struct ModelPriceRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl ModelPriceRepository {
+    /// 使用共享连接创建仓库实例
+    pub fn with_conn(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// 从默认数据库路径创建仓库
+    pub fn from_default_db() -> Result<Self> {
+        let conn = crate::database::init::get_connection_shared()?;
+        Ok(Self::with_conn(conn))
+    }
+
+    /// 辅助方法：获取连接锁
+    fn with_conn_inner<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&rusqlite::Connection) -> Result<R>,
+    {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("获取数据库连接锁失败: {}", e))?;
+        f(&conn)
+    }
+
+    /// 获取所有模型价格（按模式排序）
+    pub fn get_all_prices(&self) -> Result<Vec<ModelPrice>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, model_pattern, input_price, output_price,
+                        cache_write_price, cache_read_price, updated_at
+                 FROM model_prices
+                 ORDER BY model_pattern",
+            )?;
+
+            let prices = stmt.query_map([], |row| {
+                Ok(ModelPrice {
+                    id: Some(row.get(0)?),
+                    model_pattern: row.get(1)?,
+                    input_price: row.get(2)?,
+                    output_price: row.get(3)?,
+                    cache_write_price: row.get(4)?,
+                    cache_read_price: row.get(5)?,
+                    updated_at: row.get(6)?,
+                })
+            })?;
+
+            prices.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// 保存模型价格
+    ///
+    /// 以 model_pattern 为唯一键，已存在则更新
+    pub fn upsert_price(&self, price: &ModelPrice) -> Result<i64> {
+        if price.model_pattern.trim().is_empty() {
+            anyhow::bail!("模型名称模式不能为空");
+        }
+
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "INSERT INTO model_prices
+                 (model_pattern, input_price, output_price, cache_write_price, cache_read_price)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(model_pattern) DO UPDATE SET
+                 input_price = ?2, output_price = ?3, cache_write_price = ?4,
+                 cache_read_price = ?5, updated_at = datetime('now', 'localtime')",
+                params![
+                    price.model_pattern.trim(),
+                    price.input_price,
+                    price.output_price,
+                    price.cache_write_price,
+                    price.cache_read_price,
+                ],
+            )?;
+
+            let id = conn.query_row(
+                "SELECT id FROM model_prices WHERE model_pattern = ?1",
+                params![price.model_pattern.trim()],
+                |row| row.get(0),
+            )?;
+            Ok(id)
+        })
+    }
+
+    /// 删除模型价格
+    pub fn delete_price(&self, id: i64) -> Result<bool> {
+        self.with_conn_inner(|conn| {
+            let rows_affected =
+                conn.execute("DELETE FROM model_prices WHERE id = ?1", params![id])?;
+            Ok(rows_affected > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+    use crate::parser::usage::PriceTable;
+
+    fn create_repo() -> ModelPriceRepository {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v23(&mut conn).unwrap();
+        ModelPriceRepository::with_conn(Arc::new(Mutex::new(conn)))
+    }
+
+    fn price(pattern: &str, input: f64) -> ModelPrice {
+        ModelPrice {
+            id: None,
+            model_pattern: pattern.to_string(),
+            input_price: input,
+            output_price: input * 5.0,
+            cache_write_price: 0.0,
+            cache_read_price: 0.0,
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_model_price_crud() {
+        let repo = create_repo();
+
+        // 迁移预置的默认价格按模式排序
+        let defaults = repo.get_all_prices().unwrap();
+        assert_eq!(defaults.len(), 10);
+        let patterns: Vec<&str> = defaults.iter().map(|p| p.model_pattern.as_str()).collect();
+        let mut sorted = patterns.clone();
+        sorted.sort();
+        assert_eq!(patterns, sorted);
+
+        // 新增
+        let id = repo.upsert_price(&price("  gpt-4o ", 2.5)).unwrap();
+        let saved = repo.get_all_prices().unwrap();
+        let gpt = saved.iter().find(|p| p.id == Some(id)).unwrap();
+        assert_eq!(gpt.model_pattern, "gpt-4o");
+        assert_eq!(gpt.output_price, 12.5);
+        assert!(!gpt.updated_at.is_empty());
+
+        // 相同模式更新已有记录
+        let updated_id = repo.upsert_price(&price("gpt-4o", 3.0)).unwrap();
+        assert_eq!(updated_id, id);
+        let saved = repo.get_all_prices().unwrap();
+        assert_eq!(saved.len(), 11);
+        assert_eq!(
+            saved.iter().find(|p| p.id == Some(id)).unwrap().input_price,
+            3.0
+        );
+
+        // 空模式被拒绝
+        assert!(repo.upsert_price(&price("  ", 1.0)).is_err());
+
+        // 删除
+        assert!(repo.delete_price(id).unwrap());
+        assert!(!repo.delete_price(id).unwrap());
+        assert_eq!(repo.get_all_prices().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_default_prices_prefer_longest_pattern() {
+        let repo = create_repo();
+        let table = PriceTable::new(repo.get_all_prices().unwrap());
+
+        let pattern = |model: &str| table.find(model).map(|p| p.model_pattern.clone());
+        assert_eq!(
+            pattern("claude-opus-4-5-20251101").as_deref(),
+            Some("claude-opus-4-5")
+        );
+        assert_eq!(
+            pattern("claude-opus-4-1-20250805").as_deref(),
+            Some("claude-opus-4")
+        );
+        assert_eq!(
+            pattern("claude-3-5-haiku-20241022").as_deref(),
+            Some("claude-3-5-haiku")
+        );
+        assert_eq!(pattern("gpt-4o"), None);
+
+        // 自定义的更长模式优先于默认模式
+        repo.upsert_price(&price("claude-opus-4-5-2025", 1.0))
+            .unwrap();
+        let table = PriceTable::new(repo.get_all_prices().unwrap());
+        assert_eq!(
+            table.find("claude-opus-4-5-20251101").unwrap().input_price,
+            1.0
+        );
+    }
+}
@@ -718,6 +718,10 @@ pub struct MonitoredDirectory {
     #[serde(rename = "is_active")]
     pub is_active: bool,
 
+    /// 会话来源（决定扫描时使用的适配器）
+    #[serde(default)]
+    pub source: crate::parser::source::SessionSourceKind,
+
     /// 创建时间
     #[serde(rename = "created_at")]
     pub created_at: String,
@@ -736,6 +740,7 @@ impl MonitoredDirectory {
             path,
             name,
             is_active: true,
+            source: Default::default(),
             created_at: now.clone(),
             updated_at: now,
         }
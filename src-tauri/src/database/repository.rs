@@ -1447,12 +1447,13 @@ impl MonitoredDirectoryRepository {
 
         let id = self.with_conn_inner(|conn| {
             conn.execute(
-                "INSERT INTO monitored_directories (path, name, is_active, created_at, updated_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                "INSERT INTO monitored_directories (path, name, is_active, source, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
                 params![
                     directory.path,
                     directory.name,
                     if directory.is_active { 1 } else { 0 },
+                    directory.source.as_str(),
                     now,
                     now,
                 ],
@@ -1468,7 +1469,7 @@ impl MonitoredDirectoryRepository {
     pub fn get_all_directories(&self) -> Result<Vec<crate::database::models::MonitoredDirectory>> {
         self.with_conn_inner(|conn| {
             let mut stmt = conn.prepare(
-                "SELECT id, path, name, is_active, created_at, updated_at
+                "SELECT id, path, name, is_active, created_at, updated_at, source
                  FROM monitored_directories
                  ORDER BY created_at DESC",
             )?;
@@ -1479,6 +1480,10 @@ impl MonitoredDirectoryRepository {
                     path: row.get(1)?,
                     name: row.get(2)?,
                     is_active: row.get::<_, i32>(3)? == 1,
+                    source: crate::parser::source::SessionSourceKind::parse(
+                        &row.get::<_, String>(6)?,
+                    )
+                    .unwrap_or_default(),
                     created_at: row.get(4)?,
                     updated_at: row.get(5)?,
                 })
@@ -1496,7 +1501,7 @@ impl MonitoredDirectoryRepository {
     ) -> Result<Vec<crate::database::models::MonitoredDirectory>> {
         self.with_conn_inner(|conn| {
             let mut stmt = conn.prepare(
-                "SELECT id, path, name, is_active, created_at, updated_at
+                "SELECT id, path, name, is_active, created_at, updated_at, source
                  FROM monitored_directories
                  WHERE is_active = 1
                  ORDER BY created_at DESC",
@@ -1508,6 +1513,10 @@ impl MonitoredDirectoryRepository {
                     path: row.get(1)?,
                     name: row.get(2)?,
                     is_active: row.get::<_, i32>(3)? == 1,
+                    source: crate::parser::source::SessionSourceKind::parse(
+                        &row.get::<_, String>(6)?,
+                    )
+                    .unwrap_or_default(),
                     created_at: row.get(4)?,
                     updated_at: row.get(5)?,
                 })
@@ -1526,7 +1535,7 @@ impl MonitoredDirectoryRepository {
     ) -> Result<Option<crate::database::models::MonitoredDirectory>> {
         self.with_conn_inner(|conn| {
             let mut stmt = conn.prepare(
-                "SELECT id, path, name, is_active, created_at, updated_at
+                "SELECT id, path, name, is_active, created_at, updated_at, source
                  FROM monitored_directories
                  WHERE id = ?1",
             )?;
@@ -1537,6 +1546,10 @@ impl MonitoredDirectoryRepository {
                     path: row.get(1)?,
                     name: row.get(2)?,
                     is_active: row.get::<_, i32>(3)? == 1,
+                    source: crate::parser::source::SessionSourceKind::parse(
+                        &row.get::<_, String>(6)?,
+                    )
+                    .unwrap_or_default(),
                     created_at: row.get(4)?,
                     updated_at: row.get(5)?,
                 })
@@ -1586,12 +1599,13 @@ impl MonitoredDirectoryRepository {
         self.with_conn_inner(|conn| {
             let rows = conn.execute(
                 "UPDATE monitored_directories
-                 SET path = ?1, name = ?2, is_active = ?3, updated_at = ?4
-                 WHERE id = ?5",
+                 SET path = ?1, name = ?2, is_active = ?3, source = ?4, updated_at = ?5
+                 WHERE id = ?6",
                 params![
                     directory.path,
                     directory.name,
                     if directory.is_active { 1 } else { 0 },
+                    directory.source.as_str(),
                     now,
                     id,
                 ],
@@ -1819,12 +1833,13 @@ impl ViewLevelPreferenceRepository {
                 )?;
             }
 
-            // 保存或更新视图等级偏好
+            // 保存或更新视图等级偏好（选择预设等级时清除自定义等级）
             conn.execute(
                 "INSERT INTO view_level_preferences (session_id, view_level, created_at, updated_at)
                  VALUES (?1, ?2, ?3, ?4)
                  ON CONFLICT(session_id) DO UPDATE SET
                     view_level = excluded.view_level,
+                    custom_level = NULL,
                     updated_at = excluded.updated_at",
                 params![session_id, view_level_str, now, now],
             )?;
@@ -1832,6 +1847,140 @@ impl ViewLevelPreferenceRepository {
         })
     }
 
+    /// 保存会话的自定义等级偏好
+    ///
+    /// view_level 记为 full（自定义等级基于完整消息求值），custom_level 记为等级名称
+    ///
+    /// # 参数
+    /// - `session_id`: 会话唯一标识
+    /// - `custom_level`: 自定义等级名称（必须已存在）
+    pub fn save_custom_preference(&mut self, session_id: &str, custom_level: &str) -> Result<()> {
+        if self.get_custom_level(custom_level)?.is_none() {
+            return Err(anyhow::anyhow!("自定义等级不存在: {}", custom_level));
+        }
+
+        // 复用预设等级的保存逻辑（包括占位会话的创建）
+        self.save_preference(session_id, crate::parser::view_level::ViewLevel::Full)?;
+
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "UPDATE view_level_preferences SET custom_level = ?1 WHERE session_id = ?2",
+                params![custom_level, session_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// 获取会话的自定义等级偏好
+    ///
+    /// # 返回
+    /// - `Some(name)`: 会话选择了自定义等级
+    /// - `None`: 会话使用预设等级或没有偏好记录
+    pub fn get_custom_preference(&self, session_id: &str) -> Result<Option<String>> {
+        self.with_conn_inner(|conn| {
+            let custom_level = conn.query_row(
+                "SELECT custom_level FROM view_level_preferences WHERE session_id = ?1",
+                params![session_id],
+                |row| row.get::<_, Option<String>>(0),
+            );
+
+            match custom_level {
+                Ok(custom_level) => Ok(custom_level),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    /// 保存自定义等级（同名等级会被覆盖）
+    pub fn save_custom_level(
+        &self,
+        level: &crate::parser::view_level::CustomViewLevel,
+    ) -> Result<()> {
+        level
+            .validate()
+            .map_err(|e| anyhow::anyhow!("无效的自定义等级: {}", e))?;
+
+        let now = Utc::now().to_rfc3339();
+        let predicate = serde_json::to_string(&level.predicate)?;
+
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "INSERT INTO custom_view_levels (name, description, predicate, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(name) DO UPDATE SET
+                    description = excluded.description,
+                    predicate = excluded.predicate,
+                    updated_at = excluded.updated_at",
+                params![level.name, level.description, predicate, now, now],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// 按名称获取自定义等级
+    pub fn get_custom_level(
+        &self,
+        name: &str,
+    ) -> Result<Option<crate::parser::view_level::CustomViewLevel>> {
+        self.with_conn_inner(|conn| {
+            let row = conn.query_row(
+                "SELECT name, description, predicate FROM custom_view_levels WHERE name = ?1",
+                params![name],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            );
+
+            match row {
+                Ok(row) => Ok(Some(custom_level_from_row(row)?)),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    /// 获取所有自定义等级
+    pub fn list_custom_levels(&self) -> Result<Vec<crate::parser::view_level::CustomViewLevel>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT name, description, predicate FROM custom_view_levels ORDER BY name",
+            )?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            rows.into_iter().map(custom_level_from_row).collect()
+        })
+    }
+
+    /// 删除自定义等级
+    ///
+    /// 使用该等级的会话偏好会回退到完整模式
+    pub fn delete_custom_level(&self, name: &str) -> Result<bool> {
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "UPDATE view_level_preferences SET custom_level = NULL WHERE custom_level = ?1",
+                params![name],
+            )?;
+            let rows = conn.execute(
+                "DELETE FROM custom_view_levels WHERE name = ?1",
+                params![name],
+            )?;
+            Ok(rows > 0)
+        })
+    }
+
     /// 获取会话的视图等级偏好
     ///
     /// # 参数
@@ -1939,12 +2088,25 @@ impl ViewLevelPreferenceRepository {
     }
 }
 
+/// 从数据库行构造自定义等级
+fn custom_level_from_row(
+    (name, description, predicate): (String, Option<String>, String),
+) -> Result<crate::parser::view_level::CustomViewLevel> {
+    let predicate = serde_json::from_str(&predicate)
+        .map_err(|e| anyhow::anyhow!("自定义等级 {} 的谓词无效: {}", name, e))?;
+    Ok(crate::parser::view_level::CustomViewLevel {
+        name,
+        description,
+        predicate,
+    })
+}
+
 #[cfg(test)]
 mod view_level_preference_tests {
     use super::*;
 
-    #[test]
-    fn test_view_level_preference_crud() {
+    /// 创建带有测试会话的内存数据库仓库
+    fn create_repo() -> ViewLevelPreferenceRepository {
         // 使用内存数据库进行测试
         let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
         {
@@ -1969,6 +2131,7 @@ mod view_level_preference_tests {
 
             // 执行迁移
             crate::database::migrations::migrate_v13_impl(&mut conn_guard).unwrap();
+            crate::database::migrations::migrate_v26(&mut conn_guard).unwrap();
 
             // 插入测试会话
             conn_guard.execute(
@@ -1986,7 +2149,12 @@ mod view_level_preference_tests {
             ).unwrap();
         }
 
-        let mut repo = ViewLevelPreferenceRepository::with_conn(conn);
+        ViewLevelPreferenceRepository::with_conn(conn)
+    }
+
+    #[test]
+    fn test_view_level_preference_crud() {
+        let mut repo = create_repo();
         let session_id = "test-session-123";
         let view_level = crate::parser::view_level::ViewLevel::Conversation;
 
@@ -2024,6 +2192,54 @@ mod view_level_preference_tests {
         let after_delete = repo.get_preference(session_id).unwrap();
         assert_eq!(after_delete, None);
     }
+
+    #[test]
+    fn test_custom_view_level_crud() {
+        use crate::parser::view_level::{CustomViewLevel, MessagePredicate, ViewLevel};
+
+        let mut repo = create_repo();
+        let session_id = "test-session-123";
+        let level = CustomViewLevel {
+            name: "review".to_string(),
+            description: Some("用户提问 + Edit 调用".to_string()),
+            predicate: MessagePredicate::Any {
+                predicates: vec![
+                    MessagePredicate::Role {
+                        values: vec!["user".to_string()],
+                    },
+                    MessagePredicate::ToolName {
+                        values: vec!["Edit".to_string()],
+                    },
+                ],
+            },
+        };
+        assert!(repo.save_custom_preference(session_id, "review").is_err());
+        repo.save_custom_level(&level).unwrap();
+        assert_eq!(
+            repo.get_custom_level("review").unwrap(),
+            Some(level.clone())
+        );
+        assert_eq!(repo.list_custom_levels().unwrap(), vec![level]);
+
+        repo.save_custom_preference(session_id, "review").unwrap();
+        assert_eq!(
+            repo.get_custom_preference(session_id).unwrap().as_deref(),
+            Some("review")
+        );
+        assert_eq!(
+            repo.get_preference(session_id).unwrap(),
+            Some(ViewLevel::Full)
+        );
+
+        // 选择预设等级时清除自定义等级
+        repo.save_preference(session_id, ViewLevel::UserOnly)
+            .unwrap();
+        assert_eq!(repo.get_custom_preference(session_id).unwrap(), None);
+
+        repo.save_custom_preference(session_id, "review").unwrap();
+        assert!(repo.delete_custom_level("review").unwrap());
+        assert_eq!(repo.get_custom_preference(session_id).unwrap(), None);
+    }
 }
 
 // ============================================================================
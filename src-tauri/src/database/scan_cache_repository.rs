@@ -0,0 +1,245 @@
+//! 会话扫描缓存仓库
+//!
+//! 按文件路径缓存扫描器提取的会话元数据（session_scan_cache），
+//! 文件大小和修改时间未变化时扫描器直接复用缓存
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashSet;
+use std::path::MAIN_SEPARATOR;
+use std::sync::{Arc, Mutex};
+
+/// 扫描缓存条目
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanCacheEntry {
+    /// 会话文件路径（主键）
+    pub file_path: String,
+    /// 文件大小（字节）
+    pub file_size: u64,
+    /// 文件修改时间（RFC3339）
+    pub file_mtime: String,
+    /// 会话 ID
+    pub session_id: String,
+    /// 项目路径
+    pub project_path: String,
+    /// 项目名称
+    pub project_name: String,
+    /// 消息数量（JSONL 行数）
+    pub message_count: usize,
+    /// 显示名称（summary 或第一条用户消息）
+    pub display_name: Option<String>,
+    /// 第一条消息的时间戳
+    pub first_timestamp: Option<String>,
+    /// 最后一条消息的时间戳
+    pub last_timestamp: Option<String>,
+    /// 会话文件类型（main / agent / unknown）
+    pub file_type: String,
+    /// 文件创建时间（RFC3339）
+    pub created_at: String,
+}
+
+/// 会话扫描缓存仓库
+pub struct ScanCacheRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl ScanCacheRepository {
+    /// 使用共享连接创建仓库实例
+    pub fn with_conn(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// 从默认数据库路径创建仓库
+    pub fn from_default_db() -> Result<Self> {
+        let conn = crate::database::init::get_connection_shared()?;
+        Ok(Self::with_conn(conn))
+    }
+
+    /// 辅助方法：获取连接锁
+    fn with_conn_inner<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Connection) -> Result<R>,
+    {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("获取数据库连接锁失败（Mutex 已被毒化）: {}", e))?;
+        f(&mut conn)
+    }
+
+    /// 获取文件的缓存条目
+    pub fn get(&self, file_path: &str) -> Result<Option<ScanCacheEntry>> {
+        self.with_conn_inner(|conn| {
+            conn.query_row(
+                "SELECT file_path, file_size, file_mtime, session_id, project_path, project_name,
+                        message_count, display_name, first_timestamp, last_timestamp,
+                        file_type, created_at
+                 FROM session_scan_cache
+                 WHERE file_path = ?1",
+                params![file_path],
+                |row| {
+                    Ok(ScanCacheEntry {
+                        file_path: row.get(0)?,
+                        file_size: row.get::<_, i64>(1)? as u64,
+                        file_mtime: row.get(2)?,
+                        session_id: row.get(3)?,
+                        project_path: row.get(4)?,
+                        project_name: row.get(5)?,
+                        message_count: row.get::<_, i64>(6)? as usize,
+                        display_name: row.get(7)?,
+                        first_timestamp: row.get(8)?,
+                        last_timestamp: row.get(9)?,
+                        file_type: row.get(10)?,
+                        created_at: row.get(11)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    /// 插入或更新缓存条目
+    pub fn upsert(&self, entry: &ScanCacheEntry) -> Result<()> {
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "INSERT INTO session_scan_cache
+                    (file_path, file_size, file_mtime, session_id, project_path, project_name,
+                     message_count, display_name, first_timestamp, last_timestamp,
+                     file_type, created_at, scanned_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12,
+                         datetime('now', 'localtime'))
+                 ON CONFLICT(file_path) DO UPDATE SET
+                    file_size = excluded.file_size,
+                    file_mtime = excluded.file_mtime,
+                    session_id = excluded.session_id,
+                    project_path = excluded.project_path,
+                    project_name = excluded.project_name,
+                    message_count = excluded.message_count,
+                    display_name = excluded.display_name,
+                    first_timestamp = excluded.first_timestamp,
+                    last_timestamp = excluded.last_timestamp,
+                    file_type = excluded.file_type,
+                    created_at = excluded.created_at,
+                    scanned_at = excluded.scanned_at",
+                params![
+                    entry.file_path,
+                    entry.file_size as i64,
+                    entry.file_mtime,
+                    entry.session_id,
+                    entry.project_path,
+                    entry.project_name,
+                    entry.message_count as i64,
+                    entry.display_name,
+                    entry.first_timestamp,
+                    entry.last_timestamp,
+                    entry.file_type,
+                    entry.created_at,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// 删除目录下已不存在的文件的缓存
+    ///
+    /// # 参数
+    /// - `directory`: 扫描的目录（匹配该目录下的路径，不包括同名前缀的兄弟目录）
+    /// - `existing`: 本次扫描看到的文件路径
+    ///
+    /// # 返回
+    /// 返回删除的条目数
+    pub fn prune(&self, directory: &str, existing: &HashSet<String>) -> Result<usize> {
+        let prefix = if directory.ends_with(MAIN_SEPARATOR) {
+            directory.to_string()
+        } else {
+            format!("{}{}", directory, MAIN_SEPARATOR)
+        };
+
+        self.with_conn_inner(|conn| {
+            let tx = conn.transaction()?;
+            let stale: Vec<String> = {
+                let mut stmt = tx.prepare(
+                    "SELECT file_path FROM session_scan_cache WHERE substr(file_path, 1, length(?1)) = ?1",
+                )?;
+                let paths = stmt.query_map(params![prefix], |row| row.get::<_, String>(0))?;
+                paths
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .filter(|path| !existing.contains(path))
+                    .collect()
+            };
+            for path in &stale {
+                tx.execute(
+                    "DELETE FROM session_scan_cache WHERE file_path = ?1",
+                    params![path],
+                )?;
+            }
+            tx.commit()?;
+            Ok(stale.len())
+        })
+    }
+
+    /// 清空全部缓存（用于完整重建）
+    pub fn clear(&self) -> Result<usize> {
+        self.with_conn_inner(|conn| {
+            conn.execute("DELETE FROM session_scan_cache", [])
+                .map_err(Into::into)
+        })
+    }
+}
+
+unsafe impl Send for ScanCacheRepository {}
+unsafe impl Sync for ScanCacheRepository {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+
+    fn create_repo() -> ScanCacheRepository {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v28(&mut conn).unwrap();
+        ScanCacheRepository::with_conn(Arc::new(Mutex::new(conn)))
+    }
+
+    fn entry(file_path: &str, message_count: usize) -> ScanCacheEntry {
+        ScanCacheEntry {
+            file_path: file_path.to_string(),
+            file_size: 128,
+            file_mtime: "2025-03-10T10:00:00+00:00".to_string(),
+            session_id: "s1".to_string(),
+            project_path: "/home/dev/app".to_string(),
+            project_name: "app".to_string(),
+            message_count,
+            display_name: Some("Fix watcher".to_string()),
+            first_timestamp: Some("2025-03-10T09:00:00Z".to_string()),
+            last_timestamp: Some("2025-03-10T10:00:00Z".to_string()),
+            file_type: "main".to_string(),
+            created_at: "2025-03-10T09:00:00+00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_scan_cache_roundtrip_and_prune() {
+        let repo = create_repo();
+        repo.upsert(&entry("/projects/a/s1.jsonl", 3)).unwrap();
+        repo.upsert(&entry("/projects/a/s1.jsonl", 5)).unwrap();
+        repo.upsert(&entry("/projects/a/s2.jsonl", 1)).unwrap();
+        repo.upsert(&entry("/other/s3.jsonl", 1)).unwrap();
+        repo.upsert(&entry("/projects-old/s4.jsonl", 1)).unwrap();
+
+        assert_eq!(
+            repo.get("/projects/a/s1.jsonl").unwrap(),
+            Some(entry("/projects/a/s1.jsonl", 5))
+        );
+
+        let existing: HashSet<String> = ["/projects/a/s1.jsonl".to_string()].into();
+        assert_eq!(repo.prune("/projects", &existing).unwrap(), 1);
+        assert!(repo.get("/projects/a/s2.jsonl").unwrap().is_none());
+        assert!(repo.get("/other/s3.jsonl").unwrap().is_some());
+        assert!(repo.get("/projects-old/s4.jsonl").unwrap().is_some());
+
+        assert_eq!(repo.clear().unwrap(), 3);
+    }
+}
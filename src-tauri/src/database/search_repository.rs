@@ -0,0 +1,906 @@
+//! 消息全文搜索仓库
+//!
+//! 基于 SQLite FTS5 的 `message_fts` 虚拟表，索引所有会话中的用户消息、
+//! 助手回复和工具结果文本，支持短语查询、项目/日期/角色过滤以及高亮片段。
+
+use anyhow::{Context, Result};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::parser::jsonl::{JsonlEntry, JsonlParser};
+use crate::parser::source::{adapter_for_path, SessionSourceKind};
+
+/// 高亮片段的开始标记
+pub const HIGHLIGHT_START: &str = "<mark>";
+
+/// 高亮片段的结束标记
+pub const HIGHLIGHT_END: &str = "</mark>";
+
+/// 默认返回结果数量
+const DEFAULT_LIMIT: usize = 50;
+
+/// 待索引的消息
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchDocument {
+    /// 消息 UUID（用于跳转）
+    pub uuid: String,
+    /// 角色：user / assistant / tool
+    pub role: String,
+    /// 消息时间戳（RFC3339）
+    pub timestamp: String,
+    /// 可搜索的文本
+    pub content: String,
+}
+
+/// 搜索条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    /// 查询文本：双引号包裹的部分作为短语匹配，`词*` 为前缀匹配，
+    /// 支持大写的 AND / OR / NOT 运算符，其余词之间为 AND
+    pub query: String,
+
+    /// 只搜索指定项目
+    pub project_path: Option<String>,
+
+    /// 只搜索指定会话
+    pub session_id: Option<String>,
+
+    /// 只搜索指定角色（user / assistant / tool）
+    pub roles: Option<Vec<String>>,
+
+    /// 起始时间（包含，RFC3339 或日期前缀，如 "2025-01-01"）
+    pub from: Option<String>,
+
+    /// 结束时间（包含，RFC3339 或日期前缀，如 "2025-01-31"）
+    pub to: Option<String>,
+
+    /// 返回数量（默认 50）
+    pub limit: Option<usize>,
+
+    /// 跳过数量（分页）
+    pub offset: Option<usize>,
+}
+
+/// 搜索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    /// 消息 UUID
+    pub uuid: String,
+    /// 会话 ID
+    pub session_id: String,
+    /// 项目路径
+    pub project_path: String,
+    /// 会话文件路径
+    pub file_path: String,
+    /// 角色
+    pub role: String,
+    /// 消息时间戳
+    pub timestamp: String,
+    /// 带 `<mark>` 高亮的匹配片段
+    pub snippet: String,
+    /// BM25 相关度（越小越相关）
+    pub rank: f64,
+}
+
+/// 会话文件的索引状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedSource {
+    /// 索引时的文件大小
+    pub file_size: u64,
+    /// 索引时的文件修改时间（RFC3339）
+    pub file_mtime: String,
+    /// 已索引到的字节偏移（位于行首，增量同步从这里继续）
+    pub end_offset: u64,
+    /// 偏移之前若干字节的指纹（其他来源的会话和旧版本写入的记录为 None）
+    pub end_fingerprint: Option<u64>,
+}
+
+/// 消息全文搜索仓库
+pub struct SearchRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SearchRepository {
+    /// 使用共享连接创建仓库实例
+    pub fn with_conn(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// 从默认数据库路径创建仓库
+    pub fn from_default_db() -> Result<Self> {
+        let conn = crate::database::init::get_connection_shared()?;
+        Ok(Self::with_conn(conn))
+    }
+
+    /// 辅助方法：获取连接锁
+    fn with_conn_inner<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Connection) -> Result<R>,
+    {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("获取数据库连接锁失败（Mutex 已被毒化）: {}", e))?;
+        f(&mut conn)
+    }
+
+    /// 获取会话文件的索引状态
+    pub fn get_source(&self, session_id: &str) -> Result<Option<IndexedSource>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT file_size, file_mtime, end_offset, end_fingerprint
+                 FROM message_fts_sources WHERE session_id = ?1",
+            )?;
+            let mut rows = stmt.query(params![session_id])?;
+            match rows.next()? {
+                Some(row) => Ok(Some(IndexedSource {
+                    file_size: row.get::<_, i64>(0)? as u64,
+                    file_mtime: row.get(1)?,
+                    end_offset: row.get::<_, i64>(2)? as u64,
+                    end_fingerprint: row.get::<_, Option<i64>>(3)?.map(|fp| fp as u64),
+                })),
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// 重建单个会话的索引
+    ///
+    /// 先删除该会话已有的索引行，再写入新的消息，整个过程在一个事务内完成
+    pub fn index_session(
+        &self,
+        session_id: &str,
+        project_path: &str,
+        file_path: &str,
+        source: &IndexedSource,
+        documents: &[SearchDocument],
+    ) -> Result<()> {
+        self.with_conn_inner(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM message_fts WHERE session_id = ?1",
+                params![session_id],
+            )?;
+
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO message_fts
+                     (content, uuid, session_id, project_path, role, timestamp, file_path)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )?;
+                for doc in documents {
+                    stmt.execute(params![
+                        doc.content,
+                        doc.uuid,
+                        session_id,
+                        project_path,
+                        doc.role,
+                        doc.timestamp,
+                        file_path,
+                    ])?;
+                }
+            }
+
+            tx.execute(
+                "INSERT OR REPLACE INTO message_fts_sources
+                 (session_id, project_path, file_path, file_size, file_mtime, end_offset,
+                  end_fingerprint, message_count, indexed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, datetime('now', 'localtime'))",
+                params![
+                    session_id,
+                    project_path,
+                    file_path,
+                    source.file_size as i64,
+                    source.file_mtime,
+                    source.end_offset as i64,
+                    source.end_fingerprint.map(|fp| fp as i64),
+                    documents.len() as i64,
+                ],
+            )?;
+
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// 向已索引的会话追加消息
+    ///
+    /// 写入新消息并更新索引状态，整个过程在一个事务内完成。`start_offset` 是解析
+    /// 新消息时的起始偏移；事务内记录的索引偏移已不等于它时（另一次同步已写入
+    /// 这段内容，或索引已被删除）不写入任何内容
+    ///
+    /// # 返回
+    /// 返回是否写入了消息
+    pub fn append_documents(
+        &self,
+        session_id: &str,
+        project_path: &str,
+        file_path: &str,
+        start_offset: u64,
+        source: &IndexedSource,
+        documents: &[SearchDocument],
+    ) -> Result<bool> {
+        self.with_conn_inner(|conn| {
+            let tx = conn.transaction()?;
+
+            let indexed_offset: Option<i64> = tx
+                .query_row(
+                    "SELECT end_offset FROM message_fts_sources WHERE session_id = ?1",
+                    params![session_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if indexed_offset != Some(start_offset as i64) {
+                return Ok(false);
+            }
+
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO message_fts
+                     (content, uuid, session_id, project_path, role, timestamp, file_path)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )?;
+                for doc in documents {
+                    stmt.execute(params![
+                        doc.content,
+                        doc.uuid,
+                        session_id,
+                        project_path,
+                        doc.role,
+                        doc.timestamp,
+                        file_path,
+                    ])?;
+                }
+            }
+
+            tx.execute(
+                "INSERT INTO message_fts_sources
+                 (session_id, project_path, file_path, file_size, file_mtime, end_offset,
+                  end_fingerprint, message_count, indexed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, datetime('now', 'localtime'))
+                 ON CONFLICT(session_id) DO UPDATE SET
+                    project_path = excluded.project_path,
+                    file_path = excluded.file_path,
+                    file_size = excluded.file_size,
+                    file_mtime = excluded.file_mtime,
+                    end_offset = excluded.end_offset,
+                    end_fingerprint = excluded.end_fingerprint,
+                    message_count = message_count + excluded.message_count,
+                    indexed_at = excluded.indexed_at",
+                params![
+                    session_id,
+                    project_path,
+                    file_path,
+                    source.file_size as i64,
+                    source.file_mtime,
+                    source.end_offset as i64,
+                    source.end_fingerprint.map(|fp| fp as i64),
+                    documents.len() as i64,
+                ],
+            )?;
+
+            tx.commit()?;
+            Ok(true)
+        })
+    }
+
+    /// 获取会话文件对应的会话 ID
+    pub fn session_id_for_file(&self, file_path: &str) -> Result<Option<String>> {
+        self.with_conn_inner(|conn| {
+            conn.query_row(
+                "SELECT session_id FROM message_fts_sources WHERE file_path = ?1",
+                params![file_path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    /// 删除会话文件对应的索引
+    ///
+    /// # 返回
+    /// 返回删除的消息数量
+    pub fn remove_file(&self, file_path: &str) -> Result<usize> {
+        self.with_conn_inner(|conn| {
+            let tx = conn.transaction()?;
+            let removed = tx.execute(
+                "DELETE FROM message_fts WHERE file_path = ?1",
+                params![file_path],
+            )?;
+            tx.execute(
+                "DELETE FROM message_fts_sources WHERE file_path = ?1",
+                params![file_path],
+            )?;
+            tx.commit()?;
+            Ok(removed)
+        })
+    }
+
+    /// 同步单个会话文件到索引
+    ///
+    /// 文件大小和修改时间与上次索引一致时跳过。Claude Code 会话从上次索引到的
+    /// 偏移继续解析，只写入新追加的消息；文件被截断或重写（按文件长度和偏移之前的
+    /// 内容指纹判断）、`force` 为 true 或其他来源的会话（没有稳定的行偏移）时
+    /// 重建整个会话的索引。
+    /// 已压缩归档的会话读取解压后的缓存文件（偏移与原文件一致）。
+    /// 并发同步同一文件时，同一段新内容只会被追加一次，见 `append_documents`。
+    ///
+    /// # 返回
+    /// 返回是否写入了索引（跳过时为 false）
+    pub fn sync_session_file(
+        &self,
+        session_id: &str,
+        project_path: &str,
+        file_path: &Path,
+        force: bool,
+    ) -> Result<bool> {
+        let metadata = std::fs::metadata(file_path)
+            .with_context(|| format!("无法读取文件元数据: {:?}", file_path))?;
+        let modified: chrono::DateTime<chrono::Utc> = metadata.modified()?.into();
+        let mut source = IndexedSource {
+            file_size: metadata.len(),
+            file_mtime: modified.to_rfc3339(),
+            end_offset: metadata.len(),
+            end_fingerprint: None,
+        };
+
+        let indexed = self.get_source(session_id)?;
+        if !force
+            && indexed.as_ref().is_some_and(|indexed| {
+                indexed.file_size == source.file_size && indexed.file_mtime == source.file_mtime
+            })
+        {
+            return Ok(false);
+        }
+
+        let file_path_str = file_path.to_string_lossy().to_string();
+        if adapter_for_path(file_path).kind() != SessionSourceKind::ClaudeCode {
+            let entries = crate::parser::source::read_entries(file_path)?;
+            let documents = documents_from_entries(&entries);
+            self.index_session(
+                session_id,
+                project_path,
+                &file_path_str,
+                &source,
+                &documents,
+            )?;
+            return Ok(true);
+        }
+
+        let checkpoint = match indexed {
+            Some(ref indexed) if !force => Some((indexed.end_offset, indexed.end_fingerprint)),
+            _ => None,
+        };
+        let start_offset = checkpoint.map_or(0, |(offset, _)| offset);
+        let readable_path = crate::session_archive::resolve_session_path(file_path)?;
+        let mut parser = match checkpoint {
+            Some((offset, Some(fingerprint))) => {
+                JsonlParser::with_checkpoint(readable_path, offset, fingerprint)?
+            }
+            _ => JsonlParser::with_offset(readable_path, start_offset)?,
+        };
+        let result = parser.parse_incremental()?;
+        source.end_offset = result.end_offset;
+        source.end_fingerprint = Some(result.end_fingerprint);
+
+        let documents = documents_from_entries(&result.entries);
+        if start_offset == 0 || result.reset {
+            self.index_session(
+                session_id,
+                project_path,
+                &file_path_str,
+                &source,
+                &documents,
+            )?;
+            Ok(true)
+        } else {
+            self.append_documents(
+                session_id,
+                project_path,
+                &file_path_str,
+                start_offset,
+                &source,
+                &documents,
+            )
+        }
+    }
+
+    /// 搜索消息
+    ///
+    /// 结果按 BM25 相关度排序；查询为空时返回空列表
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let expression = match fts_expression(&query.query) {
+            Some(expression) => expression,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut sql = format!(
+            "SELECT uuid, session_id, project_path, file_path, role, timestamp,
+                    snippet(message_fts, 0, '{}', '{}', '…', 16),
+                    bm25(message_fts)
+             FROM message_fts
+             WHERE message_fts MATCH ?",
+            HIGHLIGHT_START, HIGHLIGHT_END
+        );
+        let mut values: Vec<String> = vec![expression];
+
+        if let Some(ref project_path) = query.project_path {
+            sql.push_str(" AND project_path = ?");
+            values.push(project_path.clone());
+        }
+        if let Some(ref session_id) = query.session_id {
+            sql.push_str(" AND session_id = ?");
+            values.push(session_id.clone());
+        }
+        if let Some(ref roles) = query.roles {
+            if !roles.is_empty() {
+                let placeholders = vec!["?"; roles.len()].join(", ");
+                sql.push_str(&format!(" AND role IN ({})", placeholders));
+                values.extend(roles.iter().cloned());
+            }
+        }
+        // 按前缀比较，使 "2025-01-31" 这样的日期包含当天的所有消息
+        if let Some(ref from) = query.from {
+            sql.push_str(" AND substr(timestamp, 1, length(?)) >= ?");
+            values.push(from.clone());
+            values.push(from.clone());
+        }
+        if let Some(ref to) = query.to {
+            sql.push_str(" AND substr(timestamp, 1, length(?)) <= ?");
+            values.push(to.clone());
+            values.push(to.clone());
+        }
+
+        sql.push_str(&format!(
+            " ORDER BY bm25(message_fts) LIMIT {} OFFSET {}",
+            query.limit.unwrap_or(DEFAULT_LIMIT),
+            query.offset.unwrap_or(0)
+        ));
+
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let hits = stmt
+                .query_map(params_from_iter(values.iter()), |row| {
+                    Ok(SearchHit {
+                        uuid: row.get(0)?,
+                        session_id: row.get(1)?,
+                        project_path: row.get(2)?,
+                        file_path: row.get(3)?,
+                        role: row.get(4)?,
+                        timestamp: row.get(5)?,
+                        snippet: row.get(6)?,
+                        rank: row.get(7)?,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .context("全文搜索失败")?;
+            Ok(hits)
+        })
+    }
+
+    /// 统计已索引的会话数和消息数
+    pub fn stats(&self) -> Result<(usize, usize)> {
+        self.with_conn_inner(|conn| {
+            let sessions: i64 =
+                conn.query_row("SELECT COUNT(*) FROM message_fts_sources", [], |row| {
+                    row.get(0)
+                })?;
+            let messages: i64 = conn.query_row(
+                "SELECT COALESCE(SUM(message_count), 0) FROM message_fts_sources",
+                [],
+                |row| row.get(0),
+            )?;
+            Ok((sessions as usize, messages as usize))
+        })
+    }
+}
+
+unsafe impl Send for SearchRepository {}
+unsafe impl Sync for SearchRepository {}
+
+/// 从会话条目中提取待索引的消息
+///
+/// - user 条目：字符串内容或 text 块（跳过 isMeta 注入的消息）
+/// - assistant 条目：text 块（不索引 thinking）
+/// - 只包含 tool_result 的 user 条目：角色记为 tool
+pub fn documents_from_entries(entries: &[JsonlEntry]) -> Vec<SearchDocument> {
+    let mut documents = Vec::new();
+
+    for entry in entries {
+        let data = &entry.data;
+        let entry_type = match data.get("type").and_then(|v| v.as_str()) {
+            Some(t @ ("user" | "assistant")) => t,
+            _ => continue,
+        };
+        if data.get("isMeta").and_then(|v| v.as_bool()) == Some(true) {
+            continue;
+        }
+        let uuid = match data.get("uuid").and_then(|v| v.as_str()) {
+            Some(uuid) => uuid.to_string(),
+            None => continue,
+        };
+
+        let content = match data.get("message").and_then(|m| m.get("content")) {
+            Some(content) => content,
+            None => continue,
+        };
+
+        let mut texts: Vec<String> = Vec::new();
+        let mut only_tool_results = false;
+        match content {
+            Value::String(text) => texts.push(text.clone()),
+            Value::Array(blocks) => {
+                only_tool_results = !blocks.is_empty();
+                for block in blocks {
+                    match block.get("type").and_then(|v| v.as_str()) {
+                        Some("text") => {
+                            only_tool_results = false;
+                            if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
+                                texts.push(text.to_string());
+                            }
+                        }
+                        Some("tool_result") => texts.extend(tool_result_text(block)),
+                        _ => only_tool_results = false,
+                    }
+                }
+            }
+            _ => continue,
+        }
+
+        let text = texts.join("\n");
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let role = if only_tool_results {
+            "tool"
+        } else {
+            entry_type
+        };
+        documents.push(SearchDocument {
+            uuid,
+            role: role.to_string(),
+            timestamp: data
+                .get("timestamp")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+            content: text,
+        });
+    }
+
+    documents
+}
+
+/// tool_result 块的文本内容
+fn tool_result_text(block: &Value) -> Option<String> {
+    match block.get("content")? {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(parts) => {
+            let text = parts
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|v| v.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Some(text)
+        }
+        _ => None,
+    }
+}
+
+/// 将用户输入转换为 FTS5 查询表达式
+///
+/// 每个词都用双引号包裹，避免 `-`、`:` 等字符被解释为 FTS5 语法；
+/// 用户写的短语、`词*` 前缀和 AND / OR / NOT 运算符保持原有语义。
+/// 连续的运算符只保留第一个，首尾悬空的运算符被丢弃；未闭合的引号视为闭合。
+pub fn fts_expression(input: &str) -> Option<String> {
+    let mut terms: Vec<String> = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '"' {
+            chars.next();
+            let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+            if !phrase.trim().is_empty() {
+                terms.push(quote(phrase.trim()));
+            }
+            continue;
+        }
+
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '"' {
+                break;
+            }
+            word.push(c);
+            chars.next();
+        }
+
+        match word.as_str() {
+            // 紧跟在另一个运算符之后的运算符会导致 FTS5 语法错误
+            "AND" | "OR" | "NOT" => {
+                if !terms.last().is_some_and(|last| is_operator(last)) {
+                    terms.push(word);
+                }
+            }
+            _ => {
+                let (body, prefix) = match word.strip_suffix('*') {
+                    Some(body) => (body, true),
+                    None => (word.as_str(), false),
+                };
+                if !body.is_empty() {
+                    let mut term = quote(body);
+                    if prefix {
+                        term.push('*');
+                    }
+                    terms.push(term);
+                }
+            }
+        }
+    }
+
+    // 去掉首尾悬空的运算符（连续运算符已合并，首尾各至多一个）
+    if terms.first().is_some_and(|first| is_operator(first)) {
+        terms.remove(0);
+    }
+    if terms.last().is_some_and(|last| is_operator(last)) {
+        terms.pop();
+    }
+
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// 是否为 FTS5 运算符（引用后的词不会与之相等）
+fn is_operator(term: &str) -> bool {
+    matches!(term, "AND" | "OR" | "NOT")
+}
+
+/// 以 FTS5 字符串形式引用
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+    use serde_json::json;
+
+    fn create_repo() -> SearchRepository {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v24(&mut conn).unwrap();
+        migrations::migrate_v33(&mut conn).unwrap();
+        SearchRepository::with_conn(Arc::new(Mutex::new(conn)))
+    }
+
+    fn source() -> IndexedSource {
+        IndexedSource {
+            file_size: 1,
+            file_mtime: "2025-01-01T00:00:00+00:00".to_string(),
+            end_offset: 1,
+            end_fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn test_fts_expression() {
+        assert_eq!(
+            fts_expression("\"race condition\" watcher-thread").as_deref(),
+            Some("\"race condition\" \"watcher-thread\"")
+        );
+        assert_eq!(
+            fts_expression("pars* OR lexer NOT").as_deref(),
+            Some("\"pars\"* OR \"lexer\"")
+        );
+        assert_eq!(fts_expression("  \"\" "), None);
+    }
+
+    #[test]
+    fn test_fts_expression_collapses_adjacent_operators() {
+        assert_eq!(
+            fts_expression("foo AND OR bar").as_deref(),
+            Some("\"foo\" AND \"bar\"")
+        );
+        assert_eq!(fts_expression("NOT NOT x").as_deref(), Some("\"x\""));
+        assert_eq!(
+            fts_expression("a OR NOT AND b NOT OR").as_deref(),
+            Some("\"a\" OR \"b\"")
+        );
+        assert_eq!(fts_expression("AND OR NOT"), None);
+    }
+
+    #[test]
+    fn test_index_and_search_with_filters() {
+        let entries: Vec<JsonlEntry> = [
+            json!({"uuid": "u1", "type": "user", "timestamp": "2025-01-01T10:00:00Z",
+                   "message": {"role": "user", "content": "Why does the watcher miss events?"}}),
+            json!({"uuid": "a1", "type": "assistant", "timestamp": "2025-01-02T10:00:00Z",
+                   "message": {"role": "assistant", "content": [
+                       {"type": "thinking", "thinking": "secret reasoning"},
+                       {"type": "text", "text": "The watcher debounces events for two seconds."}
+                   ]}}),
+            json!({"uuid": "t1", "type": "user", "timestamp": "2025-01-02T10:01:00Z",
+                   "message": {"role": "user", "content": [
+                       {"type": "tool_result", "tool_use_id": "x", "content": "watcher.rs:98 run_event_loop"}
+                   ]}}),
+            json!({"uuid": "m1", "type": "user", "isMeta": true,
+                   "message": {"role": "user", "content": "watcher caveat"}}),
+        ]
+        .into_iter()
+        .map(|data| JsonlEntry::new(0, 0, data))
+        .collect();
+
+        let documents = documents_from_entries(&entries);
+        let roles: Vec<&str> = documents.iter().map(|d| d.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool"]);
+
+        let repo = create_repo();
+        repo.index_session("s1", "proj", "s1.jsonl", &source(), &documents)
+            .unwrap();
+        // 重建索引不应产生重复
+        repo.index_session("s1", "proj", "s1.jsonl", &source(), &documents)
+            .unwrap();
+        assert_eq!(repo.stats().unwrap(), (1, 3));
+        assert_eq!(repo.get_source("s1").unwrap(), Some(source()));
+
+        let hits = repo
+            .search(&SearchQuery {
+                query: "watcher".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(hits.len(), 3);
+
+        let hits = repo
+            .search(&SearchQuery {
+                query: "\"debounces events\"".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].uuid, "a1");
+        assert!(hits[0].snippet.contains("<mark>debounces"));
+
+        let hits = repo
+            .search(&SearchQuery {
+                query: "watcher".to_string(),
+                roles: Some(vec!["user".to_string(), "tool".to_string()]),
+                from: Some("2025-01-02".to_string()),
+                to: Some("2025-01-02".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].uuid, "t1");
+
+        assert!(repo
+            .search(&SearchQuery {
+                query: "reasoning".to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(repo.remove_file("s1.jsonl").unwrap(), 3);
+        assert_eq!(repo.stats().unwrap(), (0, 0));
+    }
+    #[test]
+    fn test_sync_session_file_incrementally() {
+        use std::io::Write;
+
+        let user = |uuid: &str, text: &str| {
+            format!(
+                "{}\n",
+                json!({"uuid": uuid, "type": "user", "timestamp": "2025-01-01T10:00:00Z",
+                       "message": {"role": "user", "content": text}})
+            )
+        };
+        let append = |path: &Path, text: &str| {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+        let count = |repo: &SearchRepository, query: &str| {
+            repo.search(&SearchQuery {
+                query: query.to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+            .len()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("s1.jsonl");
+        let repo = create_repo();
+
+        let first = format!("{}{}", user("u1", "alpha"), user("u2", "beta"));
+        append(&path, &first);
+        assert!(repo.sync_session_file("s1", "proj", &path, false).unwrap());
+        assert_eq!(repo.stats().unwrap(), (1, 2));
+        assert_eq!(
+            repo.get_source("s1").unwrap().unwrap().end_offset,
+            first.len() as u64
+        );
+
+        // 未变化的文件被跳过
+        assert!(!repo.sync_session_file("s1", "proj", &path, false).unwrap());
+
+        // 只索引新追加的完整行，未写完的行留到下次
+        let second = user("u3", "gamma");
+        append(&path, &second);
+        append(&path, "{\"uuid\": \"u4\"");
+        assert!(repo.sync_session_file("s1", "proj", &path, false).unwrap());
+        assert_eq!(repo.stats().unwrap(), (1, 3));
+        assert_eq!(
+            repo.get_source("s1").unwrap().unwrap().end_offset,
+            (first.len() + second.len()) as u64
+        );
+        assert_eq!(count(&repo, "alpha"), 1);
+        assert_eq!(count(&repo, "gamma"), 1);
+
+        // 另一次同步已写入同一段内容时不重复追加
+        let stale = IndexedSource {
+            end_offset: (first.len() + second.len()) as u64,
+            ..source()
+        };
+        let documents = documents_from_entries(&[JsonlEntry::new(
+            0,
+            0,
+            json!({"uuid": "u3", "type": "user", "message": {"role": "user", "content": "gamma"}}),
+        )]);
+        assert!(!repo
+            .append_documents(
+                "s1",
+                "proj",
+                "s1.jsonl",
+                first.len() as u64,
+                &stale,
+                &documents
+            )
+            .unwrap());
+        assert_eq!(repo.stats().unwrap(), (1, 3));
+        assert_eq!(count(&repo, "gamma"), 1);
+
+        // 文件被重写时重建索引
+        std::fs::write(&path, user("u9", "delta")).unwrap();
+        assert!(repo.sync_session_file("s1", "proj", &path, false).unwrap());
+        assert_eq!(repo.stats().unwrap(), (1, 1));
+        assert_eq!(count(&repo, "alpha"), 0);
+        assert_eq!(count(&repo, "delta"), 1);
+
+        // 文件被重写为相同长度的内容时同样重建索引
+        std::fs::write(&path, user("u8", "omega")).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))
+            .unwrap();
+        assert!(repo.sync_session_file("s1", "proj", &path, false).unwrap());
+        assert_eq!(repo.stats().unwrap(), (1, 1));
+        assert_eq!(count(&repo, "delta"), 0);
+        assert_eq!(count(&repo, "omega"), 1);
+
+        // 强制重建不会产生重复
+        assert!(repo.sync_session_file("s1", "proj", &path, true).unwrap());
+        assert_eq!(count(&repo, "delta"), 1);
+        assert_eq!(
+            repo.session_id_for_file(&path.to_string_lossy()).unwrap(),
+            Some("s1".to_string())
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! 会话归档清单仓库
+//!
+//! 记录已压缩归档的会话文件（session_archives），用于列出归档和恢复原文件。
+//! 条目以原文件路径为键：子代理文件的会话 ID 取自文件名，在不同会话下可能重复
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// 归档清单条目
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionArchiveEntry {
+    /// 会话 ID
+    pub session_id: String,
+    /// 原会话文件路径
+    pub original_path: String,
+    /// 压缩文件路径
+    pub archive_path: String,
+    /// 原文件大小（字节）
+    pub original_size: u64,
+    /// 压缩后大小（字节）
+    pub compressed_size: u64,
+    /// 归档时间（RFC3339）
+    pub archived_at: String,
+}
+
+impl SessionArchiveEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            session_id: row.get(0)?,
+            original_path: row.get(1)?,
+            archive_path: row.get(2)?,
+            original_size: row.get::<_, i64>(3)? as u64,
+            compressed_size: row.get::<_, i64>(4)? as u64,
+            archived_at: row.get(5)?,
+        })
+    }
+}
+
+/// 会话归档清单仓库
+pub struct SessionArchiveRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SessionArchiveRepository {
+    /// 使用共享连接创建仓库实例
+    pub fn with_conn(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// 从默认数据库路径创建仓库
+    pub fn from_default_db() -> Result<Self> {
+        let conn = crate::database::init::get_connection_shared()?;
+        Ok(Self::with_conn(conn))
+    }
+
+    /// 辅助方法：获取连接锁
+    fn with_conn_inner<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Connection) -> Result<R>,
+    {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("获取数据库连接锁失败（Mutex 已被毒化）: {}", e))?;
+        f(&mut conn)
+    }
+
+    /// 写入归档条目（同一文件重复归档时覆盖）
+    pub fn upsert(&self, entry: &SessionArchiveEntry) -> Result<()> {
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "INSERT INTO session_archives
+                    (session_id, original_path, archive_path, original_size, compressed_size, archived_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(original_path) DO UPDATE SET
+                    session_id = excluded.session_id,
+                    archive_path = excluded.archive_path,
+                    original_size = excluded.original_size,
+                    compressed_size = excluded.compressed_size,
+                    archived_at = excluded.archived_at",
+                params![
+                    entry.session_id,
+                    entry.original_path,
+                    entry.archive_path,
+                    entry.original_size as i64,
+                    entry.compressed_size as i64,
+                    entry.archived_at,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// 获取原文件的归档条目
+    pub fn get(&self, original_path: &str) -> Result<Option<SessionArchiveEntry>> {
+        self.with_conn_inner(|conn| {
+            conn.query_row(
+                "SELECT session_id, original_path, archive_path, original_size, compressed_size, archived_at
+                 FROM session_archives
+                 WHERE original_path = ?1",
+                params![original_path],
+                SessionArchiveEntry::from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    /// 获取全部归档条目（按归档时间倒序）
+    pub fn list(&self) -> Result<Vec<SessionArchiveEntry>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT session_id, original_path, archive_path, original_size, compressed_size, archived_at
+                 FROM session_archives
+                 ORDER BY archived_at DESC",
+            )?;
+            let entries = stmt.query_map([], SessionArchiveEntry::from_row)?;
+            entries.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// 删除原文件的归档条目
+    pub fn remove(&self, original_path: &str) -> Result<usize> {
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "DELETE FROM session_archives WHERE original_path = ?1",
+                params![original_path],
+            )
+            .map_err(Into::into)
+        })
+    }
+}
+
+unsafe impl Send for SessionArchiveRepository {}
+unsafe impl Sync for SessionArchiveRepository {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+
+    fn create_repo() -> SessionArchiveRepository {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v30(&mut conn).unwrap();
+        SessionArchiveRepository::with_conn(Arc::new(Mutex::new(conn)))
+    }
+
+    fn entry(session_id: &str, archived_at: &str) -> SessionArchiveEntry {
+        SessionArchiveEntry {
+            session_id: session_id.to_string(),
+            original_path: path(session_id),
+            archive_path: format!("/archive/app/{}.jsonl.gz", session_id),
+            original_size: 4_096,
+            compressed_size: 512,
+            archived_at: archived_at.to_string(),
+        }
+    }
+
+    fn path(session_id: &str) -> String {
+        format!("/projects/app/{}.jsonl", session_id)
+    }
+
+    #[test]
+    fn test_archive_manifest_crud() {
+        let repo = create_repo();
+        assert!(repo.get(&path("s1")).unwrap().is_none());
+        assert!(repo.list().unwrap().is_empty());
+
+        repo.upsert(&entry("s1", "2025-01-01T10:00:00Z")).unwrap();
+        repo.upsert(&entry("s2", "2025-01-02T10:00:00Z")).unwrap();
+        assert_eq!(
+            repo.get(&path("s1")).unwrap(),
+            Some(entry("s1", "2025-01-01T10:00:00Z"))
+        );
+
+        // 按归档时间倒序
+        let ids: Vec<String> = repo
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.session_id)
+            .collect();
+        assert_eq!(ids, vec!["s2", "s1"]);
+
+        // 重复归档覆盖原条目
+        let mut rearchived = entry("s1", "2025-01-03T10:00:00Z");
+        rearchived.compressed_size = 256;
+        repo.upsert(&rearchived).unwrap();
+        assert_eq!(repo.get(&path("s1")).unwrap(), Some(rearchived));
+        let ids: Vec<String> = repo
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.session_id)
+            .collect();
+        assert_eq!(ids, vec!["s1", "s2"]);
+
+        // 删除
+        assert_eq!(repo.remove(&path("s1")).unwrap(), 1);
+        assert_eq!(repo.remove(&path("s1")).unwrap(), 0);
+        assert!(repo.get(&path("s1")).unwrap().is_none());
+        assert_eq!(repo.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_archive_manifest_keeps_same_session_id_under_different_paths() {
+        let repo = create_repo();
+        let agent_a = SessionArchiveEntry {
+            original_path: "/projects/app/s1/subagents/agent-1.jsonl".to_string(),
+            archive_path: "/archive/app/s1/subagents/agent-1.jsonl.gz".to_string(),
+            ..entry("agent-1", "2025-01-01T10:00:00Z")
+        };
+        let agent_b = SessionArchiveEntry {
+            original_path: "/projects/app/s2/subagents/agent-1.jsonl".to_string(),
+            archive_path: "/archive/app/s2/subagents/agent-1.jsonl.gz".to_string(),
+            ..entry("agent-1", "2025-01-02T10:00:00Z")
+        };
+        repo.upsert(&agent_a).unwrap();
+        repo.upsert(&agent_b).unwrap();
+
+        assert_eq!(repo.list().unwrap().len(), 2);
+        assert_eq!(repo.get(&agent_a.original_path).unwrap(), Some(agent_a));
+        assert_eq!(repo.get(&agent_b.original_path).unwrap(), Some(agent_b));
+    }
+}
@@ -0,0 +1,327 @@
+//! 会话查询仓库
+//!
+//! 负责会话事实（session_facts）的存储、编译后查询的执行以及保存的查询
+
+use anyhow::Result;
+use rusqlite::{params, params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::database::models::Session;
+use crate::session_query::{CompiledQuery, SessionFact};
+
+/// 保存的查询
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedQuery {
+    /// 主键 ID
+    pub id: i64,
+    /// 查询名称（唯一）
+    pub name: String,
+    /// 查询文本
+    pub query: String,
+    /// 创建时间
+    pub created_at: String,
+    /// 更新时间
+    pub updated_at: String,
+}
+
+/// 会话查询仓库
+pub struct SessionQueryRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SessionQueryRepository {
+    /// 使用共享连接创建仓库实例
+    pub fn with_conn(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// 从默认数据库路径创建仓库
+    pub fn from_default_db() -> Result<Self> {
+        let conn = crate::database::init::get_connection_shared()?;
+        Ok(Self::with_conn(conn))
+    }
+
+    /// 辅助方法：获取连接锁
+    fn with_conn_inner<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Connection) -> Result<R>,
+    {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("获取数据库连接锁失败（Mutex 已被毒化）: {}", e))?;
+        f(&mut conn)
+    }
+
+    /// 替换会话的全部事实
+    pub fn replace_facts(&self, session_id: &str, facts: &[SessionFact]) -> Result<()> {
+        self.with_conn_inner(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM session_facts WHERE session_id = ?1",
+                params![session_id],
+            )?;
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT OR IGNORE INTO session_facts (session_id, kind, value)
+                     VALUES (?1, ?2, ?3)",
+                )?;
+                for (kind, value) in facts {
+                    stmt.execute(params![session_id, kind, value])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// 删除会话的全部事实
+    pub fn remove_facts(&self, session_id: &str) -> Result<usize> {
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "DELETE FROM session_facts WHERE session_id = ?1",
+                params![session_id],
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    /// 执行编译后的查询
+    ///
+    /// # 参数
+    /// - `query`: 编译后的查询
+    /// - `limit`: 最多返回的会话数
+    pub fn query_sessions(&self, query: &CompiledQuery, limit: usize) -> Result<Vec<Session>> {
+        let sql = format!(
+            "SELECT s.id, s.session_id, s.project_path, s.project_name, s.file_path,
+                    s.rating, s.tags, s.is_archived, s.is_active, s.created_at, s.updated_at
+             FROM sessions s
+             WHERE {}
+             ORDER BY s.updated_at DESC
+             LIMIT {}",
+            query.where_clause, limit
+        );
+
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let sessions = stmt.query_map(params_from_iter(query.params.iter()), |row| {
+                Ok(Session {
+                    id: Some(row.get(0)?),
+                    session_id: row.get(1)?,
+                    project_path: row.get(2)?,
+                    project_name: row.get(3)?,
+                    file_path: row.get(4)?,
+                    rating: row.get(5)?,
+                    tags: row.get(6)?,
+                    is_archived: row.get::<_, i32>(7)? == 1,
+                    is_active: row.get::<_, i32>(8)? == 1,
+                    created_at: row.get(9)?,
+                    updated_at: row.get(10)?,
+                })
+            })?;
+
+            sessions.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// 保存查询（同名查询会被覆盖）
+    pub fn save_query(&self, name: &str, query: &str) -> Result<SavedQuery> {
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "INSERT INTO saved_queries (name, query) VALUES (?1, ?2)
+                 ON CONFLICT(name) DO UPDATE SET
+                    query = excluded.query,
+                    updated_at = datetime('now', 'localtime')",
+                params![name, query],
+            )?;
+            conn.query_row(
+                "SELECT id, name, query, created_at, updated_at FROM saved_queries WHERE name = ?1",
+                params![name],
+                row_to_saved_query,
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    /// 获取所有保存的查询
+    pub fn list_queries(&self) -> Result<Vec<SavedQuery>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, name, query, created_at, updated_at FROM saved_queries ORDER BY name",
+            )?;
+            let queries = stmt.query_map([], row_to_saved_query)?;
+            queries.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// 按名称获取保存的查询
+    pub fn get_query(&self, name: &str) -> Result<Option<SavedQuery>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, name, query, created_at, updated_at FROM saved_queries WHERE name = ?1",
+            )?;
+            let mut rows = stmt.query_map(params![name], row_to_saved_query)?;
+            rows.next().transpose().map_err(Into::into)
+        })
+    }
+
+    /// 删除保存的查询
+    pub fn delete_query(&self, id: i64) -> Result<bool> {
+        self.with_conn_inner(|conn| {
+            let rows_affected =
+                conn.execute("DELETE FROM saved_queries WHERE id = ?1", params![id])?;
+            Ok(rows_affected > 0)
+        })
+    }
+}
+
+unsafe impl Send for SessionQueryRepository {}
+unsafe impl Sync for SessionQueryRepository {}
+
+fn row_to_saved_query(row: &rusqlite::Row) -> rusqlite::Result<SavedQuery> {
+    Ok(SavedQuery {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        query: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+    use crate::session_query::{
+        compile_query, parse_query, FACT_DAY, FACT_ERROR, FACT_FILE, FACT_TOOL,
+    };
+    use chrono::NaiveDate;
+
+    fn create_repo() -> SessionQueryRepository {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v4(&mut conn).unwrap();
+        migrations::migrate_v24(&mut conn).unwrap();
+        migrations::migrate_v25(&mut conn).unwrap();
+
+        for (session_id, project, rating, tags) in [
+            ("s1", "prism-forge", Some(5), r#"["bugfix"]"#),
+            ("s2", "prism-forge", None, "[]"),
+            ("s3", "other-app", Some(3), r#"["refactor"]"#),
+        ] {
+            conn.execute(
+                "INSERT INTO sessions (session_id, project_path, project_name, file_path,
+                                       rating, tags, created_at, updated_at)
+                 VALUES (?1, ?2, ?2, ?3, ?4, ?5, '2025-03-10', '2025-03-10')",
+                params![
+                    session_id,
+                    project,
+                    format!("{}.jsonl", session_id),
+                    rating,
+                    tags
+                ],
+            )
+            .unwrap();
+        }
+
+        SessionQueryRepository::with_conn(Arc::new(Mutex::new(conn)))
+    }
+
+    fn fact(kind: &str, value: &str) -> SessionFact {
+        (kind.to_string(), value.to_string())
+    }
+
+    fn run(repo: &SessionQueryRepository, query: &str) -> Vec<String> {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let expr = parse_query(query).unwrap();
+        let compiled = compile_query(expr.as_ref(), today).unwrap();
+        let mut ids: Vec<String> = repo
+            .query_sessions(&compiled, 100)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.session_id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_query_sessions() {
+        let repo = create_repo();
+        repo.replace_facts(
+            "s1",
+            &[
+                fact(FACT_TOOL, "Bash"),
+                fact(FACT_ERROR, "Bash"),
+                fact(FACT_FILE, "/repo/src/main.rs"),
+                fact(FACT_DAY, "2025-03-08"),
+            ],
+        )
+        .unwrap();
+        repo.replace_facts(
+            "s2",
+            &[
+                fact(FACT_TOOL, "Bash"),
+                fact(FACT_FILE, "/repo/README.md"),
+                fact(FACT_DAY, "2025-01-20"),
+            ],
+        )
+        .unwrap();
+        repo.replace_facts(
+            "s3",
+            &[fact(FACT_TOOL, "Read"), fact(FACT_DAY, "2025-03-09")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            run(
+                &repo,
+                "project:prism date:last-week tool:bash error:true file:*.rs"
+            ),
+            vec!["s1"]
+        );
+        assert_eq!(run(&repo, "tool:Bash -error:true"), vec!["s2"]);
+        assert_eq!(run(&repo, "tag:bugfix OR tag:refactor"), vec!["s1", "s3"]);
+        assert_eq!(run(&repo, "rating:>=4"), vec!["s1"]);
+        assert_eq!(run(&repo, "rating:none"), vec!["s2"]);
+        assert_eq!(run(&repo, "date:2025-01"), vec!["s2"]);
+        assert_eq!(run(&repo, "date:>2025-01"), vec!["s1", "s3"]);
+        assert_eq!(run(&repo, ""), vec!["s1", "s2", "s3"]);
+    }
+
+    #[test]
+    fn test_negated_facts_skip_unsynced_sessions() {
+        let repo = create_repo();
+        repo.replace_facts("s1", &[fact(FACT_TOOL, "Bash"), fact(FACT_ERROR, "Bash")])
+            .unwrap();
+        repo.replace_facts("s3", &[fact(FACT_TOOL, "Read")])
+            .unwrap();
+
+        // s2 尚未同步事实，不应被否定条件匹配
+        assert_eq!(run(&repo, "-error:true"), vec!["s3"]);
+        assert_eq!(run(&repo, "error:false"), vec!["s3"]);
+        assert_eq!(run(&repo, "-error:false"), vec!["s1"]);
+        assert_eq!(run(&repo, "-tag:bugfix"), vec!["s2", "s3"]);
+    }
+
+    #[test]
+    fn test_saved_queries() {
+        let repo = create_repo();
+        let saved = repo
+            .save_query("rust errors", "error:true file:*.rs")
+            .unwrap();
+        let updated = repo
+            .save_query("rust errors", "error:Bash file:*.rs")
+            .unwrap();
+        assert_eq!(saved.id, updated.id);
+
+        let queries = repo.list_queries().unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].query, "error:Bash file:*.rs");
+        assert!(repo.get_query("rust errors").unwrap().is_some());
+
+        assert!(repo.delete_query(saved.id).unwrap());
+        assert!(repo.get_query("rust errors").unwrap().is_none());
+    }
+}
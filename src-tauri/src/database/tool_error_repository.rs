@@ -0,0 +1,277 @@
+//! 工具错误仓库
+//!
+//! 负责已分类工具错误（tool_errors）的存储，以及按会话、按项目的类别统计
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::parser::tool_error::{ToolErrorCategory, ToolErrorRecord};
+
+/// 会话内某一类错误的数量
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolErrorCount {
+    /// 错误类别
+    pub category: ToolErrorCategory,
+    /// 产生错误的工具链（无法识别时为 None）
+    pub origin: Option<String>,
+    /// 错误次数
+    pub count: i64,
+}
+
+/// 项目内某一类错误的统计
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectToolErrorStats {
+    /// 项目路径
+    pub project_path: String,
+    /// 错误类别
+    pub category: ToolErrorCategory,
+    /// 错误次数
+    pub error_count: i64,
+    /// 出现该类错误的会话数
+    pub session_count: i64,
+}
+
+/// 工具错误仓库
+pub struct ToolErrorRepository {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl ToolErrorRepository {
+    /// 使用共享连接创建仓库实例
+    pub fn with_conn(conn: Arc<Mutex<Connection>>) -> Self {
+        Self { conn }
+    }
+
+    /// 从默认数据库路径创建仓库
+    pub fn from_default_db() -> Result<Self> {
+        let conn = crate::database::init::get_connection_shared()?;
+        Ok(Self::with_conn(conn))
+    }
+
+    /// 辅助方法：获取连接锁
+    fn with_conn_inner<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Connection) -> Result<R>,
+    {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("获取数据库连接锁失败（Mutex 已被毒化）: {}", e))?;
+        f(&mut conn)
+    }
+
+    /// 替换会话的全部工具错误
+    pub fn replace_errors(
+        &self,
+        session_id: &str,
+        project_path: &str,
+        errors: &[ToolErrorRecord],
+    ) -> Result<()> {
+        self.with_conn_inner(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM tool_errors WHERE session_id = ?1",
+                params![session_id],
+            )?;
+            {
+                let mut stmt = tx.prepare(
+                    "INSERT INTO tool_errors
+                        (session_id, project_path, uuid, tool_name, category, origin, message, timestamp)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )?;
+                for error in errors {
+                    stmt.execute(params![
+                        session_id,
+                        project_path,
+                        error.uuid,
+                        error.tool_name,
+                        error.category.as_str(),
+                        error.origin,
+                        error.message,
+                        error.timestamp,
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// 删除会话的全部工具错误
+    pub fn remove_errors(&self, session_id: &str) -> Result<usize> {
+        self.with_conn_inner(|conn| {
+            conn.execute(
+                "DELETE FROM tool_errors WHERE session_id = ?1",
+                params![session_id],
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    /// 获取会话的全部工具错误（按时间顺序）
+    pub fn list_errors(&self, session_id: &str) -> Result<Vec<ToolErrorRecord>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT uuid, tool_name, category, origin, message, timestamp
+                 FROM tool_errors
+                 WHERE session_id = ?1
+                 ORDER BY timestamp, id",
+            )?;
+            let errors = stmt.query_map(params![session_id], |row| {
+                Ok(ToolErrorRecord {
+                    uuid: row.get(0)?,
+                    tool_name: row.get(1)?,
+                    category: parse_category(&row.get::<_, String>(2)?),
+                    origin: row.get(3)?,
+                    message: row.get(4)?,
+                    timestamp: row.get(5)?,
+                })
+            })?;
+            errors.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// 按类别统计会话的工具错误（按次数降序）
+    pub fn session_stats(&self, session_id: &str) -> Result<Vec<ToolErrorCount>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT category, origin, COUNT(*) AS count
+                 FROM tool_errors
+                 WHERE session_id = ?1
+                 GROUP BY category, origin
+                 ORDER BY count DESC, category",
+            )?;
+            let counts = stmt.query_map(params![session_id], |row| {
+                Ok(ToolErrorCount {
+                    category: parse_category(&row.get::<_, String>(0)?),
+                    origin: row.get(1)?,
+                    count: row.get(2)?,
+                })
+            })?;
+            counts.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// 按项目和类别统计工具错误（按次数降序）
+    ///
+    /// # 参数
+    /// - `project_path`: 仅统计指定项目，为 None 时统计所有项目
+    pub fn project_stats(&self, project_path: Option<&str>) -> Result<Vec<ProjectToolErrorStats>> {
+        self.with_conn_inner(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT project_path, category, COUNT(*) AS error_count,
+                        COUNT(DISTINCT session_id) AS session_count
+                 FROM tool_errors
+                 WHERE ?1 IS NULL OR project_path = ?1
+                 GROUP BY project_path, category
+                 ORDER BY error_count DESC, project_path, category",
+            )?;
+            let stats = stmt.query_map(params![project_path], |row| {
+                Ok(ProjectToolErrorStats {
+                    project_path: row.get(0)?,
+                    category: parse_category(&row.get::<_, String>(1)?),
+                    error_count: row.get(2)?,
+                    session_count: row.get(3)?,
+                })
+            })?;
+            stats.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+}
+
+unsafe impl Send for ToolErrorRepository {}
+unsafe impl Sync for ToolErrorRepository {}
+
+/// 解析数据库中的类别标识，未知值归入 `Other`
+fn parse_category(value: &str) -> ToolErrorCategory {
+    ToolErrorCategory::parse(value).unwrap_or(ToolErrorCategory::Other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+
+    fn create_repo() -> ToolErrorRepository {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v24(&mut conn).unwrap();
+        migrations::migrate_v27(&mut conn).unwrap();
+        ToolErrorRepository::with_conn(Arc::new(Mutex::new(conn)))
+    }
+
+    fn record(uuid: &str, category: ToolErrorCategory, origin: Option<&str>) -> ToolErrorRecord {
+        ToolErrorRecord {
+            uuid: uuid.to_string(),
+            tool_name: Some("Bash".to_string()),
+            category,
+            origin: origin.map(|s| s.to_string()),
+            message: "error".to_string(),
+            timestamp: Some(format!("2025-01-01T10:00:0{}Z", uuid.len())),
+        }
+    }
+
+    #[test]
+    fn test_tool_error_stats() {
+        use ToolErrorCategory::*;
+
+        let repo = create_repo();
+        repo.replace_errors(
+            "s1",
+            "/repo/app",
+            &[
+                record("a1", CompileError, Some("rustc")),
+                record("a2", CompileError, Some("rustc")),
+                record("a3", TestFailure, Some("cargo test")),
+            ],
+        )
+        .unwrap();
+        repo.replace_errors(
+            "s2",
+            "/repo/app",
+            &[record("b1", CompileError, Some("tsc"))],
+        )
+        .unwrap();
+        repo.replace_errors("s3", "/repo/other", &[record("c1", Timeout, None)])
+            .unwrap();
+
+        let session = repo.session_stats("s1").unwrap();
+        assert_eq!(
+            session,
+            vec![
+                ToolErrorCount {
+                    category: CompileError,
+                    origin: Some("rustc".to_string()),
+                    count: 2
+                },
+                ToolErrorCount {
+                    category: TestFailure,
+                    origin: Some("cargo test".to_string()),
+                    count: 1
+                },
+            ]
+        );
+
+        let project = repo.project_stats(Some("/repo/app")).unwrap();
+        assert_eq!(project.len(), 2);
+        assert_eq!(project[0].category, CompileError);
+        assert_eq!(project[0].error_count, 3);
+        assert_eq!(project[0].session_count, 2);
+        assert_eq!(repo.project_stats(None).unwrap().len(), 3);
+
+        // 重新同步时替换旧记录
+        repo.replace_errors("s1", "/repo/app", &[]).unwrap();
+        assert!(repo.session_stats("s1").unwrap().is_empty());
+        assert_eq!(
+            repo.list_errors("s2").unwrap()[0].origin.as_deref(),
+            Some("tsc")
+        );
+
+        // 删除会话的错误
+        assert_eq!(repo.remove_errors("s2").unwrap(), 1);
+        assert!(repo.list_errors("s2").unwrap().is_empty());
+    }
+}
@@ -24,6 +24,7 @@
 //! - **v1.0.3 逻辑**: 排除开场白，配对 `(assistant1, user2), (assistant2, user3), ...`
 
 use crate::database::models::Message;
+use crate::parser::view_level::QAPair;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
@@ -148,6 +149,58 @@ impl QAPairDetector {
         pairs
     }
 
+    /// 基于问答轮次检测决策问答对
+    ///
+    /// 问答轮次由 `MessageFilter::extract_qa_turns` 沿 parentUuid 链路提取，
+    /// 答案是每轮的最终 assistant 文本，不受工具往返穿插的影响。
+    ///
+    /// # 逻辑说明
+    ///
+    /// - 跳过被回退或编辑取代的轮次，只分析当前分支
+    /// - 配对模式: `(第 k 轮的答案, 第 k+1 轮的问题)`，第一轮问题即开场白
+    /// - 跳过没有答案或 content 为 None 的轮次
+    /// - 前序上下文取此前的决策问答对（最多 5 对）
+    pub fn detect_from_qa_turns(&self, turns: &[QAPair]) -> Vec<DecisionQAPair> {
+        let active: Vec<&QAPair> = turns.iter().filter(|turn| !turn.superseded).collect();
+        let mut pairs: Vec<DecisionQAPair> = Vec::new();
+
+        for window in active.windows(2) {
+            let (previous, current) = (window[0], window[1]);
+            let Some(answer) = previous.answer.as_ref() else {
+                continue;
+            };
+            let (Some(assistant_content), Some(user_content)) =
+                (&answer.content, &current.question.content)
+            else {
+                continue;
+            };
+
+            let context_qa_pairs: Vec<QAPairContext> = pairs
+                .iter()
+                .take(5)
+                .map(|pair| QAPairContext {
+                    user_question: pair.user_decision.clone(),
+                    assistant_answer: pair.assistant_answer.clone(),
+                })
+                .collect();
+
+            pairs.push(DecisionQAPair {
+                qa_index: pairs.len(),
+                assistant_answer_uuid: answer.uuid.clone(),
+                user_decision_uuid: current.question.uuid.clone(),
+                assistant_answer: assistant_content.clone(),
+                user_decision: user_content.clone(),
+                context_qa_pairs: if context_qa_pairs.is_empty() {
+                    None
+                } else {
+                    Some(context_qa_pairs)
+                },
+            });
+        }
+
+        pairs
+    }
+
     /// 构建前序问答对上下文
     ///
     /// 提取指定位置之前的所有问答对（最多 5 对）
@@ -409,6 +462,36 @@ mod tests {
         assert_eq!(pairs[3].context_qa_pairs.as_ref().unwrap().len(), 3);
     }
 
+    #[test]
+    fn test_detect_from_qa_turns_skips_superseded() {
+        // 验证基于问答轮次的检测会跳过被取代的分支
+        let detector = QAPairDetector::new();
+        let turn = |question: &str, answer: Option<&str>, superseded: bool| QAPair {
+            question: create_test_message(question, "user", Some(question)),
+            answer: answer.map(|a| create_test_message(a, "assistant", Some(a))),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            superseded,
+        };
+        let turns = vec![
+            turn("u1", Some("a1"), false),
+            turn("u2", Some("a2"), true),
+            turn("u2-edit", Some("a3"), false),
+            turn("u3", None, false),
+        ];
+
+        let pairs = detector.detect_from_qa_turns(&turns);
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].assistant_answer_uuid, "a1");
+        assert_eq!(pairs[0].user_decision_uuid, "u2-edit");
+        assert!(pairs[0].context_qa_pairs.is_none());
+        assert_eq!(pairs[1].assistant_answer_uuid, "a3");
+        assert_eq!(pairs[1].user_decision_uuid, "u3");
+        let context = pairs[1].context_qa_pairs.as_ref().unwrap();
+        assert_eq!(context[0].user_question, "u2-edit");
+        assert_eq!(context[0].assistant_answer, "a1");
+    }
+
     #[test]
     fn test_qa_pair_detector_default() {
         // 验证 Default trait 实现
@@ -13,9 +13,12 @@ pub use embedding::EmbeddingGenerator;
 pub mod command_registry;
 pub mod command_wrapper;
 mod filter_config;
+mod redaction;
+mod session_query;
 pub mod logging;
 pub mod optimizer;
 pub mod path_resolver;
+pub mod session_archive;
 pub mod session_parser;
 pub mod session_reader;
 pub mod session_type_detector;
@@ -159,6 +162,7 @@ pub fn run() {
             sync_embeddings_now,
             // 多级日志读取命令
             cmd_get_messages_by_level,
+            cmd_get_appended_messages_by_level,
             cmd_get_qa_pairs_by_level,
             cmd_save_view_level_preference,
             cmd_get_view_level_preference,
@@ -224,6 +228,58 @@ pub fn run() {
             cmd_upsert_decision_keyword,
             cmd_delete_decision_keyword,
             cmd_import_decision_keywords,
+            // Token 用量与成本命令
+            cmd_get_session_cost,
+            cmd_get_project_cost,
+            cmd_get_daily_cost,
+            cmd_get_model_prices,
+            cmd_save_model_price,
+            cmd_delete_model_price,
+            // 文件变更时间线命令
+            cmd_get_file_change_timeline,
+            cmd_replay_files_at_message,
+            // 脱敏命令
+            cmd_get_redaction_config,
+            cmd_update_redaction_config,
+            cmd_preview_redaction,
+            // 会话分段命令
+            cmd_get_session_segments,
+            // 会话格式清单命令
+            cmd_get_schema_report,
+            // 损坏行诊断命令
+            cmd_get_parse_diagnostics,
+            cmd_repair_session_file,
+            // 全文搜索命令
+            cmd_search_messages,
+            cmd_rebuild_search_index,
+            // 会话查询命令
+            cmd_query_sessions,
+            cmd_save_session_query,
+            cmd_list_session_queries,
+            cmd_delete_session_query,
+            // 自定义视图等级命令
+            cmd_get_custom_view_level_preference,
+            cmd_list_custom_view_levels,
+            cmd_save_custom_view_level,
+            cmd_delete_custom_view_level,
+            // 会话分支命令
+            cmd_get_conversation_branches,
+            // 工具错误统计命令
+            cmd_get_session_tool_error_stats,
+            cmd_get_project_tool_error_stats,
+            // 扫描缓存命令
+            cmd_rebuild_scan_cache,
+            // 消息索引命令
+            cmd_list_session_messages,
+            cmd_get_message_at_offset,
+            cmd_get_session_outline,
+            cmd_get_message_window,
+            // 会话压缩归档命令
+            cmd_compress_old_sessions,
+            cmd_restore_compressed_session,
+            cmd_list_compressed_sessions,
+            // 续接会话命令
+            cmd_write_resume_session,
         ])
         .run(tauri::generate_context!())
         .map_err(|e| {
@@ -3,4 +3,5 @@
 //! 本模块提供会话文件扫描、实时监控、活跃状态检测等功能
 
 pub mod scanner;
+pub mod tail;
 pub mod watcher;
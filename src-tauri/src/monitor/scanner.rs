@@ -5,9 +5,18 @@
 use anyhow::Result;
 use dirs::home_dir;
 use glob::glob;
+use serde_json::Value;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
+use crate::database::scan_cache_repository::{ScanCacheEntry, ScanCacheRepository};
+use crate::path_resolver::{find_project_cwd, platform_converter};
+use crate::session_type_detector::{detect_session_type_by_filename, SessionFileType};
+
+/// 从第一条用户消息生成显示名称时保留的最大字符数
+const DISPLAY_NAME_CHARS: usize = 50;
+
 /// 会话元数据结构
 #[derive(Debug, Clone)]
 pub struct SessionMetadata {
@@ -27,6 +36,14 @@ pub struct SessionMetadata {
     pub message_count: usize,
     /// 是否活跃
     pub is_active: bool,
+    /// 显示名称（summary 或第一条用户消息）
+    pub display_name: Option<String>,
+    /// 第一条消息的时间戳
+    pub first_timestamp: Option<String>,
+    /// 最后一条消息的时间戳
+    pub last_timestamp: Option<String>,
+    /// 会话文件类型
+    pub file_type: SessionFileType,
 }
 
 /// 获取 Claude Code 项目目录
@@ -79,6 +96,7 @@ pub fn extract_session_metadata(path: &Path) -> Result<SessionMetadata> {
     } else {
         String::new()
     };
+    let project_path = resolve_project_path(path, project_path);
 
     // 提取项目名称（路径的最后一段）
     let project_name = Path::new(&project_path)
@@ -96,8 +114,8 @@ pub fn extract_session_metadata(path: &Path) -> Result<SessionMetadata> {
     let created_at = system_time_to_rfc3339(created)?;
     let updated_at = system_time_to_rfc3339(modified)?;
 
-    // 4. 计算消息数量（简单方法：计算 JSONL 文件的行数）
-    let message_count = count_jsonl_lines(path)?;
+    // 4. 一次遍历统计消息数量（JSONL 行数）、时间范围和显示名称
+    let stats = read_content_stats(path)?;
 
     // 5. 判断是否活跃
     let is_active = is_session_active(path);
@@ -109,8 +127,12 @@ pub fn extract_session_metadata(path: &Path) -> Result<SessionMetadata> {
         file_path: path.to_path_buf(),
         created_at,
         updated_at,
-        message_count,
+        message_count: stats.message_count,
         is_active,
+        display_name: stats.display_name,
+        first_timestamp: stats.first_timestamp,
+        last_timestamp: stats.last_timestamp,
+        file_type: detect_session_type_by_filename(path),
     })
 }
 
@@ -132,12 +154,50 @@ pub fn scan_session_files() -> Result<Vec<SessionMetadata>> {
 
 /// 扫描指定目录的会话文件
 ///
+/// 使用默认数据库中的扫描缓存；缓存不可用时退回完整扫描
+///
 /// # 参数
 /// - `directory`: 要扫描的目录路径
 ///
 /// # 返回
 /// 返回扫描到的所有会话元数据列表
 pub fn scan_directory(directory: &Path) -> Result<Vec<SessionMetadata>> {
+    let cache = match ScanCacheRepository::from_default_db() {
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            eprintln!("警告: 无法打开扫描缓存，将完整扫描: {}", e);
+            None
+        }
+    };
+
+    scan_directory_with_cache(directory, cache.as_ref())
+}
+
+/// 清空目录的扫描缓存后重新扫描
+///
+/// # 参数
+/// - `directory`: 要扫描的目录路径
+/// - `cache`: 扫描缓存
+pub fn rebuild_directory_cache(
+    directory: &Path,
+    cache: &ScanCacheRepository,
+) -> Result<Vec<SessionMetadata>> {
+    cache.prune(&directory.to_string_lossy(), &HashSet::new())?;
+    scan_directory_with_cache(directory, Some(cache))
+}
+
+/// 使用扫描缓存扫描指定目录的会话文件
+///
+/// 文件大小和修改时间与缓存一致时直接复用缓存的元数据（活跃状态总是重新判断），
+/// 否则重新提取并写回缓存；目录下已删除文件的缓存会被清理。
+///
+/// # 参数
+/// - `directory`: 要扫描的目录路径
+/// - `cache`: 扫描缓存，为 None 时每个文件都重新提取
+pub fn scan_directory_with_cache(
+    directory: &Path,
+    cache: Option<&ScanCacheRepository>,
+) -> Result<Vec<SessionMetadata>> {
     // 如果目录不存在，返回空列表
     if !directory.exists() {
         eprintln!("警告: 目录不存在: {:?}", directory);
@@ -151,12 +211,46 @@ pub fn scan_directory(directory: &Path) -> Result<Vec<SessionMetadata>> {
         .ok_or_else(|| anyhow::anyhow!("无效的路径模式"))?;
 
     let mut sessions = Vec::new();
+    let mut seen = HashSet::new();
 
     for entry in glob(pattern_str)? {
         match entry {
             Ok(path) => {
+                let file_path = path.to_string_lossy().to_string();
+                seen.insert(file_path.clone());
+
+                // 提取前记录文件状态，避免提取期间的追加被误认为已缓存
+                let (file_size, file_mtime) = match file_stat(&path) {
+                    Ok(stat) => stat,
+                    Err(e) => {
+                        eprintln!("跳过无法读取的文件 {:?}: {}", path, e);
+                        continue;
+                    }
+                };
+
+                if let Some(cache) = cache {
+                    match cache.get(&file_path) {
+                        Ok(Some(entry))
+                            if entry.file_size == file_size && entry.file_mtime == file_mtime =>
+                        {
+                            sessions.push(metadata_from_cache(&path, entry));
+                            continue;
+                        }
+                        Ok(_) => {}
+                        Err(e) => eprintln!("读取扫描缓存失败 {:?}: {}", path, e),
+                    }
+                }
+
                 match extract_session_metadata_from_dir(&path, directory) {
-                    Ok(metadata) => sessions.push(metadata),
+                    Ok(metadata) => {
+                        if let Some(cache) = cache {
+                            let entry = cache_entry(&metadata, file_size, file_mtime);
+                            if let Err(e) = cache.upsert(&entry) {
+                                eprintln!("写入扫描缓存失败 {:?}: {}", path, e);
+                            }
+                        }
+                        sessions.push(metadata);
+                    }
                     Err(e) => {
                         // 跳过损坏的文件，记录到日志
                         eprintln!("跳过损坏的文件 {:?}: {}", path, e);
@@ -169,6 +263,13 @@ pub fn scan_directory(directory: &Path) -> Result<Vec<SessionMetadata>> {
         }
     }
 
+    // 清理已删除文件的缓存
+    if let Some(cache) = cache {
+        if let Err(e) = cache.prune(&directory.to_string_lossy(), &seen) {
+            eprintln!("清理扫描缓存失败: {}", e);
+        }
+    }
+
     Ok(sessions)
 }
 
@@ -210,6 +311,7 @@ fn extract_session_metadata_from_dir(path: &Path, base_dir: &Path) -> Result<Ses
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_default()
     };
+    let project_path = resolve_project_path(path, project_path);
 
     // 提取项目名称（路径的最后一段）
     let project_name = if project_path.is_empty() {
@@ -235,8 +337,8 @@ fn extract_session_metadata_from_dir(path: &Path, base_dir: &Path) -> Result<Ses
     let created_at = system_time_to_rfc3339(created)?;
     let updated_at = system_time_to_rfc3339(modified)?;
 
-    // 4. 计算消息数量（简单方法：计算 JSONL 文件的行数）
-    let message_count = count_jsonl_lines(path)?;
+    // 4. 一次遍历统计消息数量（JSONL 行数）、时间范围和显示名称
+    let stats = read_content_stats(path)?;
 
     // 5. 判断是否活跃
     let is_active = is_session_active(path);
@@ -248,11 +350,29 @@ fn extract_session_metadata_from_dir(path: &Path, base_dir: &Path) -> Result<Ses
         file_path: path.to_path_buf(),
         created_at,
         updated_at,
-        message_count,
+        message_count: stats.message_count,
         is_active,
+        display_name: stats.display_name,
+        first_timestamp: stats.first_timestamp,
+        last_timestamp: stats.last_timestamp,
+        file_type: detect_session_type_by_filename(path),
     })
 }
 
+/// 将会话目录名称替换为真实的项目路径
+///
+/// 目录名称由项目路径有损编码而来，会话条目中记录的 `cwd` 编码后与目录名称一致时
+/// 使用 `cwd`，否则保留目录名称。
+fn resolve_project_path(session_file: &Path, folder_name: String) -> String {
+    if folder_name.is_empty() {
+        return folder_name;
+    }
+
+    find_project_cwd(session_file, &folder_name, platform_converter().as_ref())
+        .map(|cwd| cwd.to_string_lossy().to_string())
+        .unwrap_or(folder_name)
+}
+
 /// 检查是否为有效的 UUID 格式
 fn is_valid_uuid(s: &str) -> bool {
     // 简单的 UUID 格式验证 (xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx)
@@ -276,14 +396,120 @@ fn system_time_to_rfc3339(time: SystemTime) -> Result<String> {
     Ok(dt.to_rfc3339())
 }
 
-/// 计算 JSONL 文件的行数（消息数量）
-fn count_jsonl_lines(path: &Path) -> Result<usize> {
+/// 会话文件内容统计
+#[derive(Debug, Default)]
+struct ContentStats {
+    /// JSONL 行数（消息数量）
+    message_count: usize,
+    /// 显示名称
+    display_name: Option<String>,
+    /// 第一条消息的时间戳
+    first_timestamp: Option<String>,
+    /// 最后一条消息的时间戳
+    last_timestamp: Option<String>,
+}
+
+/// 一次遍历统计会话文件内容
+///
+/// 显示名称优先使用第一个 summary，其次是第一条纯文本用户消息（跳过命令标签）。
+/// 含无效 UTF-8 或无法解析的行只计数，不影响其余行的统计。
+fn read_content_stats(path: &Path) -> Result<ContentStats> {
     use std::io::BufRead;
     let file = std::fs::File::open(path)?;
-    let reader = std::io::BufReader::new(file);
+    let mut reader = std::io::BufReader::new(file);
 
-    let count = reader.lines().count();
-    Ok(count)
+    let mut stats = ContentStats::default();
+    let mut summary = None;
+    let mut first_user_text = None;
+
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        stats.message_count += 1;
+
+        let line = String::from_utf8_lossy(&buf);
+        let value: Value = match serde_json::from_str(&line) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+
+        if let Some(timestamp) = value.get("timestamp").and_then(|v| v.as_str()) {
+            if stats.first_timestamp.is_none() {
+                stats.first_timestamp = Some(timestamp.to_string());
+            }
+            stats.last_timestamp = Some(timestamp.to_string());
+        }
+
+        match value.get("type").and_then(|v| v.as_str()) {
+            Some("summary") if summary.is_none() => {
+                summary = value
+                    .get("summary")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+            }
+            Some("user") if first_user_text.is_none() => {
+                first_user_text = value
+                    .get("message")
+                    .and_then(|m| m.get("content"))
+                    .and_then(|c| c.as_str())
+                    .map(str::trim)
+                    .filter(|text| !text.is_empty() && !text.starts_with('<'))
+                    .map(|text| text.chars().take(DISPLAY_NAME_CHARS).collect());
+            }
+            _ => {}
+        }
+    }
+
+    stats.display_name = summary.or(first_user_text);
+    Ok(stats)
+}
+
+/// 读取文件大小和修改时间（RFC3339）
+fn file_stat(path: &Path) -> Result<(u64, String)> {
+    let metadata = std::fs::metadata(path)?;
+    Ok((
+        metadata.len(),
+        system_time_to_rfc3339(metadata.modified()?)?,
+    ))
+}
+
+/// 由扫描结果生成缓存条目
+fn cache_entry(metadata: &SessionMetadata, file_size: u64, file_mtime: String) -> ScanCacheEntry {
+    ScanCacheEntry {
+        file_path: metadata.file_path.to_string_lossy().to_string(),
+        file_size,
+        file_mtime,
+        session_id: metadata.session_id.clone(),
+        project_path: metadata.project_path.clone(),
+        project_name: metadata.project_name.clone(),
+        message_count: metadata.message_count,
+        display_name: metadata.display_name.clone(),
+        first_timestamp: metadata.first_timestamp.clone(),
+        last_timestamp: metadata.last_timestamp.clone(),
+        file_type: metadata.file_type.as_str().to_string(),
+        created_at: metadata.created_at.clone(),
+    }
+}
+
+/// 由缓存条目还原会话元数据（活跃状态重新判断）
+fn metadata_from_cache(path: &Path, entry: ScanCacheEntry) -> SessionMetadata {
+    SessionMetadata {
+        session_id: entry.session_id,
+        project_path: entry.project_path,
+        project_name: entry.project_name,
+        file_path: path.to_path_buf(),
+        created_at: entry.created_at,
+        updated_at: entry.file_mtime,
+        message_count: entry.message_count,
+        is_active: is_session_active(path),
+        display_name: entry.display_name,
+        first_timestamp: entry.first_timestamp,
+        last_timestamp: entry.last_timestamp,
+        file_type: SessionFileType::parse(&entry.file_type),
+    }
 }
 
 /// 降级方法：基于时间判断（所有平台通用）
@@ -309,7 +535,7 @@ fn is_active_by_time(path: &Path, threshold_secs: u64) -> bool {
 
 /// 判断会话是否活跃（Windows 平台）
 #[cfg(target_os = "windows")]
-fn is_session_active(path: &Path) -> bool {
+pub(crate) fn is_session_active(path: &Path) -> bool {
     // Windows: 优先使用文件锁定检测
     if is_file_locked(path) {
         return true;
@@ -322,7 +548,7 @@ fn is_session_active(path: &Path) -> bool {
 
 /// 判断会话是否活跃（非 Windows 平台）
 #[cfg(not(target_os = "windows"))]
-fn is_session_active(path: &Path) -> bool {
+pub(crate) fn is_session_active(path: &Path) -> bool {
     // macOS/Linux: 直接使用时间判断
     let threshold = get_default_active_threshold();
     is_active_by_time(path, threshold)
@@ -416,6 +642,99 @@ mod tests {
         assert!(!is_valid_uuid("12345"));
     }
 
+    #[test]
+    fn test_scan_directory_with_cache() {
+        use crate::database::migrations;
+        use rusqlite::Connection;
+        use std::io::Write;
+        use std::sync::{Arc, Mutex};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let project_dir = temp_dir.path().join("-home-dev-app");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let session_file = project_dir.join("01234567-89ab-cdef-0123-456789abcdef.jsonl");
+        std::fs::write(
+            &session_file,
+            concat!(
+                r#"{"type":"user","uuid":"u1","timestamp":"2025-03-10T09:00:00Z","message":{"role":"user","content":"Fix the watcher"}}"#,
+                "\n",
+                r#"{"type":"assistant","uuid":"a1","timestamp":"2025-03-10T09:01:00Z"}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v28(&mut conn).unwrap();
+        let cache = ScanCacheRepository::with_conn(Arc::new(Mutex::new(conn)));
+
+        let sessions = scan_directory_with_cache(temp_dir.path(), Some(&cache)).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].message_count, 2);
+        assert_eq!(sessions[0].display_name.as_deref(), Some("Fix the watcher"));
+        assert_eq!(
+            sessions[0].last_timestamp.as_deref(),
+            Some("2025-03-10T09:01:00Z")
+        );
+
+        // 文件未变化时直接使用缓存
+        let key = session_file.to_string_lossy().to_string();
+        let mut entry = cache.get(&key).unwrap().unwrap();
+        entry.message_count = 99;
+        cache.upsert(&entry).unwrap();
+        let sessions = scan_directory_with_cache(temp_dir.path(), Some(&cache)).unwrap();
+        assert_eq!(sessions[0].message_count, 99);
+
+        // 文件追加后重新提取
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&session_file)
+            .unwrap();
+        writeln!(
+            file,
+            r#"{{"type":"summary","summary":"Watcher fix","leafUuid":"a1"}}"#
+        )
+        .unwrap();
+        drop(file);
+        let sessions = scan_directory_with_cache(temp_dir.path(), Some(&cache)).unwrap();
+        assert_eq!(sessions[0].message_count, 3);
+        assert_eq!(sessions[0].display_name.as_deref(), Some("Watcher fix"));
+
+        // 删除文件后清理缓存
+        std::fs::remove_file(&session_file).unwrap();
+        assert!(scan_directory_with_cache(temp_dir.path(), Some(&cache))
+            .unwrap()
+            .is_empty());
+        assert!(cache.get(&key).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_content_stats_tolerates_invalid_utf8() {
+        use std::io::Write;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"type":"user","timestamp":"2025-01-01T00:00:00Z","message":{{"content":"hello"}}}}"#
+        )
+        .unwrap();
+        file.write_all(b"{\"type\":\"user\",\"x\":\"\xff\xfe\"}\n")
+            .unwrap();
+        writeln!(
+            file,
+            r#"{{"type":"assistant","timestamp":"2025-01-01T00:01:00Z"}}"#
+        )
+        .unwrap();
+
+        let stats = read_content_stats(file.path()).unwrap();
+        assert_eq!(stats.message_count, 3);
+        assert_eq!(stats.display_name.as_deref(), Some("hello"));
+        assert_eq!(
+            stats.last_timestamp.as_deref(),
+            Some("2025-01-01T00:01:00Z")
+        );
+    }
+
     #[test]
     fn test_system_time_to_rfc3339() {
         let time = SystemTime::UNIX_EPOCH;
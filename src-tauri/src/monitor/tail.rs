@@ -0,0 +1,368 @@
+//! 会话文件增量读取
+//!
+//! 记录每个会话文件已读取的字节偏移，文件变更后只解析新增的完整行，
+//! 生成结构化的会话事件（新会话、消息追加、摘要更新、会话删除），
+//! 前端据此直接追加消息而无需重新扫描和解析整个文件。
+
+use anyhow::{Context, Result};
+use glob::glob;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::parser::jsonl::JsonlParser;
+
+/// 单个事件直接携带的最大消息数，超过时只发送数量和最后一条消息的 UUID
+pub const MAX_INLINE_MESSAGES: usize = 200;
+
+/// 结构化会话事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// 出现新的会话文件
+    #[serde(rename_all = "camelCase")]
+    SessionCreated { session_id: String, path: String },
+
+    /// 会话文件追加了消息
+    #[serde(rename_all = "camelCase")]
+    MessagesAppended {
+        session_id: String,
+        path: String,
+        /// 新增消息数量
+        count: usize,
+        /// 最后一条新增消息的 UUID
+        last_uuid: Option<String>,
+        /// 新增消息（数量超过 `MAX_INLINE_MESSAGES` 时为空）
+        messages: Vec<Value>,
+        /// 文件被截断或重写，已从头重新读取
+        reset: bool,
+        /// 无法解析而跳过的行数（无效的 UTF-8 或 JSON）
+        skipped: usize,
+    },
+
+    /// 会话追加了新的 `summary` 行
+    #[serde(rename_all = "camelCase")]
+    SummaryUpdated {
+        session_id: String,
+        path: String,
+        summary: String,
+    },
+
+    /// 会话文件被删除
+    #[serde(rename_all = "camelCase")]
+    SessionDeleted { session_id: String, path: String },
+}
+
+/// 向前查找末尾时每次读取的字节数
+const SEEK_CHUNK_BYTES: u64 = 64 * 1024;
+
+/// 会话文件增量读取器
+#[derive(Debug, Default)]
+pub struct SessionTailer {
+    /// 每个文件的增量解析器（记录已完整解析的偏移量和指纹，缓冲末尾未写完的行）
+    parsers: HashMap<PathBuf, JsonlParser>,
+}
+
+impl SessionTailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录目录下已有会话文件的当前位置
+    ///
+    /// 监控启动前已存在的内容不会作为新增消息推送；
+    /// 末尾尚未写完的行从行首开始记录，写完后作为新增消息推送
+    pub fn seed(&mut self, directory: &Path) -> Result<()> {
+        let pattern = directory.join("**").join("*.jsonl");
+        let pattern_str = pattern
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("无效的路径模式"))?;
+
+        for path in glob(pattern_str)?.flatten() {
+            let seeded = last_line_end(&path).and_then(|offset| {
+                let mut parser = JsonlParser::with_offset(path.clone(), offset)?;
+                // 记录指纹并缓冲末尾未写完的行
+                parser.parse_incremental()?;
+                Ok(parser)
+            });
+            match seeded {
+                Ok(parser) => {
+                    self.parsers.insert(path, parser);
+                }
+                Err(e) => eprintln!("⚠️  无法记录会话文件位置 {:?}: {}", path, e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 处理一个文件的变更，返回对应的会话事件
+    ///
+    /// 文件不存在时视为删除（被压缩归档的文件除外）；未记录过的文件视为新会话并从头读取；
+    /// 文件被截断或重写时从头读取并标记 `reset`。
+    /// 末尾尚未写完的行留到下次读取；无法解析的完整行被跳过，数量记录在事件的 `skipped` 中。
+    pub fn process(&mut self, path: &Path) -> Result<Vec<SessionEvent>> {
+        let session_id = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_string();
+        let path_str = path.to_string_lossy().to_string();
+        let mut events = Vec::new();
+
+        if !path.exists() {
+            if self.parsers.remove(path).is_some() && !crate::session_archive::is_archived(path) {
+                events.push(SessionEvent::SessionDeleted {
+                    session_id,
+                    path: path_str,
+                });
+            }
+            return Ok(events);
+        }
+
+        if !self.parsers.contains_key(path) {
+            let parser = JsonlParser::new(path.to_path_buf())?;
+            self.parsers.insert(path.to_path_buf(), parser);
+            events.push(SessionEvent::SessionCreated {
+                session_id: session_id.clone(),
+                path: path_str.clone(),
+            });
+        }
+        let parser = self
+            .parsers
+            .get_mut(path)
+            .expect("parser was inserted above");
+        let result = parser.parse_incremental()?;
+
+        let mut messages = Vec::new();
+        let mut summary = None;
+        for entry in result.entries {
+            if entry.message_type().as_deref() == Some("summary") {
+                if let Some(text) = entry.data.get("summary").and_then(|v| v.as_str()) {
+                    summary = Some(text.to_string());
+                }
+            } else if entry.data.get("uuid").is_some() {
+                messages.push(entry.data);
+            }
+        }
+
+        let skipped = result.diagnostics.len();
+        if !messages.is_empty() || result.reset || skipped > 0 {
+            let last_uuid = messages
+                .last()
+                .and_then(|m| m.get("uuid"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            let count = messages.len();
+            if count > MAX_INLINE_MESSAGES {
+                messages.clear();
+            }
+            events.push(SessionEvent::MessagesAppended {
+                session_id: session_id.clone(),
+                path: path_str.clone(),
+                count,
+                last_uuid,
+                messages,
+                reset: result.reset,
+                skipped,
+            });
+        }
+
+        if let Some(summary) = summary {
+            events.push(SessionEvent::SummaryUpdated {
+                session_id,
+                path: path_str,
+                summary,
+            });
+        }
+
+        Ok(events)
+    }
+}
+
+/// 返回文件最后一个换行符之后的偏移量（没有换行符时为 0）
+fn last_line_end(path: &Path) -> Result<u64> {
+    let mut file = File::open(path).with_context(|| format!("无法打开文件: {:?}", path))?;
+    let mut end = file
+        .metadata()
+        .with_context(|| format!("无法读取文件元数据: {:?}", path))?
+        .len();
+    let mut chunk = Vec::new();
+
+    while end > 0 {
+        let start = end.saturating_sub(SEEK_CHUNK_BYTES);
+        chunk.resize((end - start) as usize, 0);
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        if let Some(pos) = chunk.iter().rposition(|&b| b == b'\n') {
+            return Ok(start + pos as u64 + 1);
+        }
+        end = start;
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_tail_session_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_dir = temp_dir.path().join("-home-dev-app");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let existing = project_dir.join("old.jsonl");
+        append(&existing, "{\"uuid\":\"o1\",\"type\":\"user\"}\n");
+
+        let mut tailer = SessionTailer::new();
+        tailer.seed(temp_dir.path()).unwrap();
+
+        // 已有内容不会被推送
+        assert!(tailer.process(&existing).unwrap().is_empty());
+
+        // 新会话：末尾未写完的行留到下次
+        let path = project_dir.join("s1.jsonl");
+        append(
+            &path,
+            "{\"uuid\":\"u1\",\"type\":\"user\"}\n{\"uuid\":\"a1\",\"type\":\"assis",
+        );
+        let events = tailer.process(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], SessionEvent::SessionCreated { .. }));
+        match &events[1] {
+            SessionEvent::MessagesAppended {
+                count, last_uuid, ..
+            } => {
+                assert_eq!(*count, 1);
+                assert_eq!(last_uuid.as_deref(), Some("u1"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        // 无法解析的行被跳过并计数
+        let broken = project_dir.join("broken.jsonl");
+        let mut content = b"{\"uuid\":\"b1\",\"type\":\"user\"}\n".to_vec();
+        content.extend_from_slice(b"{\"text\": \"\xe4\xbd\"}\n");
+        content.extend_from_slice(b"not json\n\n");
+        std::fs::write(&broken, content).unwrap();
+        let events = tailer.process(&broken).unwrap();
+        match &events[1] {
+            SessionEvent::MessagesAppended { count, skipped, .. } => {
+                assert_eq!(*count, 1);
+                assert_eq!(*skipped, 2);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        append(&broken, "garbage\n");
+        match &tailer.process(&broken).unwrap()[..] {
+            [SessionEvent::MessagesAppended { count, skipped, .. }] => {
+                assert_eq!(*count, 0);
+                assert_eq!(*skipped, 1);
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        // 补全上一行并追加摘要
+        append(
+            &path,
+            "tant\"}\n{\"type\":\"summary\",\"summary\":\"Fix watcher\",\"leafUuid\":\"a1\"}\n",
+        );
+        let events = tailer.process(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        match &events[0] {
+            SessionEvent::MessagesAppended {
+                count,
+                last_uuid,
+                messages,
+                reset,
+                skipped,
+                ..
+            } => {
+                assert_eq!(*count, 1);
+                assert_eq!(last_uuid.as_deref(), Some("a1"));
+                assert_eq!(messages[0]["type"], "assistant");
+                assert!(!reset);
+                assert_eq!(*skipped, 0);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert_eq!(
+            events[1],
+            SessionEvent::SummaryUpdated {
+                session_id: "s1".to_string(),
+                path: path.to_string_lossy().to_string(),
+                summary: "Fix watcher".to_string(),
+            }
+        );
+
+        // 文件被删除
+        std::fs::remove_file(&path).unwrap();
+        let events = tailer.process(&path).unwrap();
+        assert!(matches!(events[..], [SessionEvent::SessionDeleted { .. }]));
+        assert!(tailer.process(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tail_seeded_partial_line_and_rewrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("s2.jsonl");
+        append(
+            &path,
+            "{\"uuid\":\"u1\",\"type\":\"user\"}\n{\"uuid\":\"a1\",\"type\":\"assis",
+        );
+
+        let mut tailer = SessionTailer::new();
+        tailer.seed(temp_dir.path()).unwrap();
+
+        // 启动时未写完的行写完后作为新增消息推送
+        append(&path, "tant\"}\n");
+        match &tailer.process(&path).unwrap()[..] {
+            [SessionEvent::MessagesAppended {
+                count,
+                last_uuid,
+                reset,
+                ..
+            }] => {
+                assert_eq!(*count, 1);
+                assert_eq!(last_uuid.as_deref(), Some("a1"));
+                assert!(!reset);
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        // 重写为相同长度的内容时从头读取
+        std::fs::write(
+            &path,
+            "{\"uuid\":\"x1\",\"type\":\"user\"}\n{\"uuid\":\"x2\",\"type\":\"assistant\"}\n",
+        )
+        .unwrap();
+        match &tailer.process(&path).unwrap()[..] {
+            [SessionEvent::MessagesAppended {
+                count,
+                last_uuid,
+                reset,
+                ..
+            }] => {
+                assert_eq!(*count, 2);
+                assert_eq!(last_uuid.as_deref(), Some("x2"));
+                assert!(reset);
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+}
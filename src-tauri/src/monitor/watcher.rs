@@ -1,16 +1,42 @@
 //! 文件监控器
 //!
 //! 使用 notify crate 监控 Claude 会话文件变更，并通过 Tauri Events 推送到前端
+//!
+//! - `sessions-changed`: 文件级变更（created / modified / deleted）
+//! - `session-event`: 结构化会话事件（新会话、消息追加、摘要更新、会话删除），见 `SessionEvent`
 
 use anyhow::{Context, Result};
 use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::OnceLock;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 
+use super::scanner::SessionMetadata;
+use super::tail::{SessionEvent, SessionTailer};
+
+/// 会话文件停止变化多久后重新提取会话事实和工具错误分类
+///
+/// 提取需要完整解析会话文件，等会话停止写入后再做，避免每次追加消息都重新解析
+const FACTS_IDLE_DELAY: Duration = Duration::from_secs(30);
+
+/// 索引同步线程的任务通道
+///
+/// 文件监控器和会话扫描共用同一个同步线程，同一文件不会被并发同步
+static INDEX_SYNC_TX: OnceLock<Sender<IndexTask>> = OnceLock::new();
+
+/// 索引同步任务
+enum IndexTask {
+    /// 文件变更事件
+    Changed(WatchEvent),
+    /// 扫描得到的会话（元数据已提取）
+    Scanned(SessionMetadata),
+}
+
 /// 监控事件
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WatchEvent {
@@ -35,6 +61,8 @@ pub struct SessionWatcher {
     /// Tauri App Handle（用于发送事件到前端）
     #[allow(dead_code)]
     app_handle: AppHandle,
+    /// 会话文件增量读取器
+    tailer: SessionTailer,
 }
 
 impl SessionWatcher {
@@ -53,6 +81,7 @@ impl SessionWatcher {
             watch_path,
             tx,
             app_handle,
+            tailer: SessionTailer::new(),
         })
     }
 
@@ -62,7 +91,7 @@ impl SessionWatcher {
     ///
     /// # 返回
     /// 返回线程句柄或错误
-    pub fn start(self) -> Result<thread::JoinHandle<()>> {
+    pub fn start(mut self) -> Result<thread::JoinHandle<()>> {
         // 创建事件接收通道
         let (event_tx, event_rx): (Sender<notify::Event>, Receiver<notify::Event>) =
             mpsc::channel();
@@ -83,6 +112,11 @@ impl SessionWatcher {
             .watch(&self.watch_path, RecursiveMode::Recursive)
             .context("设置监控目录失败")?;
 
+        // 记录已有会话文件的大小，只推送之后追加的消息
+        if let Err(e) = self.tailer.seed(&self.watch_path) {
+            eprintln!("记录会话文件偏移失败: {}", e);
+        }
+
         // 启动处理线程
         let handle = thread::spawn(move || {
             eprintln!("文件监控器已启动，监控目录: {:?}", self.watch_path);
@@ -95,7 +129,7 @@ impl SessionWatcher {
     /// 运行事件处理循环
     ///
     /// 处理文件变更事件，去重、防抖后推送到前端
-    fn run_event_loop(self, event_rx: Receiver<notify::Event>) {
+    fn run_event_loop(mut self, event_rx: Receiver<notify::Event>) {
         let mut debounce_buffer: Vec<WatchEvent> = Vec::new();
         let mut last_event_time = std::time::Instant::now();
         let debounce_duration = Duration::from_secs(2);
@@ -153,28 +187,282 @@ impl SessionWatcher {
 
     /// 批量处理事件
     ///
-    /// 去重后推送到前端
-    fn flush_events(&self, events: &mut Vec<WatchEvent>) {
+    /// 去重后提交索引同步任务并推送到前端，再为每个文件推送增量读取得到的会话事件
+    fn flush_events(&mut self, events: &mut Vec<WatchEvent>) {
         if events.is_empty() {
             return;
         }
 
         eprintln!("文件监控器: 处理 {} 个事件", events.len());
 
-        // 去重：同一个文件的多个事件只保留最新的
-        let mut unique_events: std::collections::HashMap<String, WatchEvent> =
-            std::collections::HashMap::new();
-        for event in events.drain(..) {
-            unique_events.insert(event.path.clone(), event);
+        let unique_events = dedup_events(events);
+
+        // 提交全文索引和消息索引的同步任务
+        for event in unique_events.values() {
+            submit_index_task(IndexTask::Changed(event.clone()));
         }
 
-        // 推送到前端
+        // 推送到前端（压缩归档删除原文件不视为会话删除）
         for event in unique_events.values() {
+            if is_archived_removal(event) {
+                continue;
+            }
             if let Err(e) = self.app_handle.emit("sessions-changed", &event) {
                 eprintln!("推送事件到前端失败: {}", e);
             }
         }
+
+        // 推送结构化会话事件
+        for session_event in session_events(&mut self.tailer, unique_events.values()) {
+            if let Err(e) = self.app_handle.emit("session-event", &session_event) {
+                eprintln!("推送会话事件到前端失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 去重：同一个文件的多个事件只保留最新的
+fn dedup_events(events: &mut Vec<WatchEvent>) -> HashMap<String, WatchEvent> {
+    let mut unique_events = HashMap::new();
+    for event in events.drain(..) {
+        unique_events.insert(event.path.clone(), event);
+    }
+    unique_events
+}
+
+/// 增量读取变更的文件，生成推送到前端的 `session-event` 事件
+///
+/// 读取失败的文件只记录日志
+fn session_events<'a>(
+    tailer: &mut SessionTailer,
+    events: impl IntoIterator<Item = &'a WatchEvent>,
+) -> Vec<SessionEvent> {
+    let mut session_events = Vec::new();
+    for event in events {
+        match tailer.process(&PathBuf::from(&event.path)) {
+            Ok(events) => session_events.extend(events),
+            Err(e) => eprintln!("增量读取会话文件失败 {}: {}", event.path, e),
+        }
+    }
+    session_events
+}
+
+/// 变更事件是否为压缩归档删除原文件
+fn is_archived_removal(event: &WatchEvent) -> bool {
+    let path = PathBuf::from(&event.path);
+    (event.kind == "deleted" || !path.exists()) && crate::session_archive::is_archived(&path)
+}
+
+/// 提交扫描得到的会话，由索引同步线程增量同步全文索引和消息索引
+///
+/// 未变化的文件在同步时被跳过
+pub fn submit_scanned_sessions(sessions: &[SessionMetadata]) {
+    for metadata in sessions {
+        submit_index_task(IndexTask::Scanned(metadata.clone()));
+    }
+}
+
+/// 提交索引同步任务，首次提交时启动同步线程
+///
+/// 索引同步在独立线程中进行，避免阻塞事件处理和扫描命令
+fn submit_index_task(task: IndexTask) {
+    let tx = INDEX_SYNC_TX.get_or_init(|| {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || run_index_sync(rx));
+        tx
+    });
+    if tx.send(task).is_err() {
+        eprintln!("索引同步线程已退出");
+    }
+}
+
+/// 运行索引同步循环
+///
+/// 每次取出通道中排队的全部任务，同一文件的多次变更合并为一次同步，
+/// 因此同步耗时超过防抖间隔时不会堆积重复的任务。
+/// 全文索引和消息索引在每次变更时增量同步；会话事实和工具错误分类
+/// 在文件停止变化 `FACTS_IDLE_DELAY` 后才重新提取
+fn run_index_sync(rx: Receiver<IndexTask>) {
+    // 会话 ID 和项目信息在文件追加时不会变化，只需提取一次
+    let mut metadata_cache: HashMap<PathBuf, SessionMetadata> = HashMap::new();
+    // 等待重新提取事实的会话文件 → 最后一次变更时间
+    let mut stale_facts: HashMap<PathBuf, Instant> = HashMap::new();
+
+    loop {
+        let task = match stale_facts.values().min() {
+            Some(oldest) => {
+                match rx.recv_timeout(FACTS_IDLE_DELAY.saturating_sub(oldest.elapsed())) {
+                    Ok(task) => Some(task),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            None => match rx.recv() {
+                Ok(task) => Some(task),
+                Err(_) => break,
+            },
+        };
+
+        if let Some(task) = task {
+            let mut pending: HashMap<String, WatchEvent> = HashMap::new();
+            for task in std::iter::once(task).chain(std::iter::from_fn(|| rx.try_recv().ok())) {
+                let event = task_event(task, &mut metadata_cache);
+                pending.insert(event.path.clone(), event);
+            }
+
+            for event in pending.values() {
+                let path = PathBuf::from(&event.path);
+                if sync_indexes(event, &mut metadata_cache) {
+                    stale_facts.insert(path, Instant::now());
+                } else if !path.exists() {
+                    stale_facts.remove(&path);
+                }
+            }
+        }
+
+        let idle: Vec<PathBuf> = stale_facts
+            .iter()
+            .filter(|(_, changed_at)| changed_at.elapsed() >= FACTS_IDLE_DELAY)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in idle {
+            stale_facts.remove(&path);
+            if let Some(metadata) = metadata_cache.get(&path) {
+                sync_facts(metadata);
+            }
+        }
+    }
+}
+
+/// 将索引同步任务转换为文件变更事件
+///
+/// 扫描得到的会话视为一次修改，并用扫描提取的元数据刷新缓存
+fn task_event(
+    task: IndexTask,
+    metadata_cache: &mut HashMap<PathBuf, SessionMetadata>,
+) -> WatchEvent {
+    match task {
+        IndexTask::Changed(event) => event,
+        IndexTask::Scanned(metadata) => {
+            let event = WatchEvent {
+                kind: "modified".to_string(),
+                path: metadata.file_path.to_string_lossy().to_string(),
+                is_jsonl: metadata
+                    .file_path
+                    .extension()
+                    .is_some_and(|ext| ext == "jsonl"),
+                timestamp: chrono::Utc::now().to_rfc3339(),
+            };
+            metadata_cache.insert(metadata.file_path.clone(), metadata);
+            event
+        }
+    }
+}
+
+/// 重新提取会话事实和工具错误分类，失败只记录日志
+fn sync_facts(metadata: &SessionMetadata) {
+    let result = crate::database::init::get_connection_shared().and_then(|conn| {
+        crate::session_query::sync_session_facts(
+            conn,
+            &metadata.session_id,
+            &metadata.project_path,
+            &metadata.file_path,
+        )
+    });
+    if let Err(e) = result {
+        eprintln!("提取会话事实失败 {:?}: {}", metadata.file_path, e);
+    }
+}
+
+/// 按文件变更事件同步全文索引和消息索引
+///
+/// 删除事件移除该文件的索引、会话事实和工具错误（已压缩归档的文件除外）；
+/// 其余事件增量索引新追加的消息。会话元数据只在首次遇到文件时提取并缓存。
+/// 失败只记录日志。
+///
+/// # 返回
+/// 全文索引写入了新内容时返回 true
+fn sync_indexes(
+    event: &WatchEvent,
+    metadata_cache: &mut HashMap<PathBuf, SessionMetadata>,
+) -> bool {
+    use crate::database::{MessageIndexRepository, SearchRepository};
+
+    let conn = match crate::database::init::get_connection_shared() {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("获取数据库连接失败: {}", e);
+            return false;
+        }
+    };
+    let search_repo = SearchRepository::with_conn(conn.clone());
+    let message_repo = MessageIndexRepository::with_conn(conn.clone());
+
+    let path = PathBuf::from(&event.path);
+    if event.kind == "deleted" || !path.exists() {
+        let metadata = metadata_cache.remove(&path);
+        // 压缩归档会删除原文件，已归档会话的索引需要保留
+        if is_archived_removal(event) {
+            return false;
+        }
+
+        let session_id = match metadata {
+            Some(metadata) => Some(metadata.session_id),
+            None => search_repo
+                .session_id_for_file(&event.path)
+                .unwrap_or_else(|e| {
+                    eprintln!("查询会话 ID 失败 {}: {}", event.path, e);
+                    None
+                }),
+        };
+        if let Some(session_id) = session_id {
+            if let Err(e) = crate::session_query::remove_session_facts(conn, &session_id) {
+                eprintln!("删除会话事实失败 {}: {}", event.path, e);
+            }
+        }
+        if let Err(e) = search_repo.remove_file(&event.path) {
+            eprintln!("删除全文索引失败 {}: {}", event.path, e);
+        }
+        if let Err(e) = message_repo.remove_file(&event.path) {
+            eprintln!("删除消息索引失败 {}: {}", event.path, e);
+        }
+        return false;
+    }
+
+    if !metadata_cache.contains_key(&path) {
+        match crate::parser::source::adapter_for_path(&path).read_metadata(&path) {
+            Ok(metadata) => {
+                metadata_cache.insert(path.clone(), metadata);
+            }
+            Err(e) => {
+                eprintln!("提取会话元数据失败 {}: {}", event.path, e);
+                return false;
+            }
+        }
+    }
+    let metadata = &metadata_cache[&path];
+
+    let indexed = match search_repo.sync_session_file(
+        &metadata.session_id,
+        &metadata.project_path,
+        &metadata.file_path,
+        false,
+    ) {
+        Ok(indexed) => indexed,
+        Err(e) => {
+            eprintln!("同步全文索引失败 {}: {}", event.path, e);
+            false
+        }
+    };
+    if let Err(e) = message_repo.sync_session_file(
+        &metadata.session_id,
+        &metadata.project_path,
+        &metadata.project_name,
+        &metadata.file_path,
+    ) {
+        eprintln!("同步消息索引失败 {}: {}", event.path, e);
     }
+    indexed
 }
 
 /// 获取 Claude 项目目录
@@ -189,10 +477,90 @@ pub fn get_claude_projects_dir() -> Result<PathBuf> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Write;
+    use std::path::Path;
 
     #[test]
     fn test_get_claude_projects_dir() {
         let dir = get_claude_projects_dir().unwrap();
         assert!(dir.ends_with(".claude/projects"));
     }
+
+    fn watch_event(kind: &str, path: &Path) -> WatchEvent {
+        WatchEvent {
+            kind: kind.to_string(),
+            path: path.to_string_lossy().to_string(),
+            is_jsonl: true,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    fn append(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    /// 模拟一次防抖批量处理，返回推送的 `session-event` 事件（JSON）
+    fn flush(tailer: &mut SessionTailer, mut events: Vec<WatchEvent>) -> Vec<serde_json::Value> {
+        let unique_events = dedup_events(&mut events);
+        assert!(events.is_empty());
+        session_events(tailer, unique_events.values())
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_session_event_output() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut tailer = SessionTailer::new();
+        tailer.seed(temp_dir.path()).unwrap();
+        let path = temp_dir.path().join("s1.jsonl");
+
+        // 新会话：同一文件的多个变更合并为一次读取
+        append(&path, "{\"uuid\":\"u1\",\"type\":\"user\"}\n");
+        let events = flush(
+            &mut tailer,
+            vec![
+                watch_event("created", &path),
+                watch_event("modified", &path),
+            ],
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["kind"], "session_created");
+        assert_eq!(events[0]["sessionId"], "s1");
+        assert_eq!(events[1]["kind"], "messages_appended");
+        assert_eq!(events[1]["count"], 1);
+        assert_eq!(events[1]["lastUuid"], "u1");
+        assert_eq!(events[1]["messages"][0]["uuid"], "u1");
+        assert_eq!(events[1]["reset"], false);
+        assert_eq!(events[1]["skipped"], 0);
+
+        // 追加消息和摘要
+        append(
+            &path,
+            "{\"uuid\":\"a1\",\"type\":\"assistant\"}\n\
+             {\"type\":\"summary\",\"summary\":\"Fix watcher\",\"leafUuid\":\"a1\"}\n",
+        );
+        let events = flush(&mut tailer, vec![watch_event("modified", &path)]);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["kind"], "messages_appended");
+        assert_eq!(events[0]["lastUuid"], "a1");
+        assert_eq!(events[1]["kind"], "summary_updated");
+        assert_eq!(events[1]["summary"], "Fix watcher");
+
+        // 没有新内容时不推送
+        assert!(flush(&mut tailer, vec![watch_event("modified", &path)]).is_empty());
+
+        // 删除
+        std::fs::remove_file(&path).unwrap();
+        let events = flush(&mut tailer, vec![watch_event("deleted", &path)]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["kind"], "session_deleted");
+        assert_eq!(events[0]["path"], path.to_string_lossy().as_ref());
+    }
 }
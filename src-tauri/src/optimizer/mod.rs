@@ -151,6 +151,12 @@ impl PromptOptimizer {
             .collect::<Vec<String>>()
             .join("\n--------------------\n");
 
+        // 提交给第三方 LLM 前脱敏
+        let context_str = match crate::redaction::Redactor::for_llm() {
+            Some(mut redactor) => redactor.redact_text(&context_str),
+            None => context_str,
+        };
+
         // 3. 构建系统提示词（从数据库加载）
         let system_prompt = Self::load_system_prompt(&request.language)?;
 
@@ -16,7 +16,9 @@ use crate::llm::{
     interface::{Message, ModelParams},
     LLMClientManager,
 };
+use crate::parser::segment::SegmentScope;
 use crate::parser::view_level::{MessageFilter, QAPair, ViewLevel};
+use crate::redaction::Redactor;
 use crate::session_parser::{SessionParserConfig, SessionParserService};
 use crate::tokenizer::TokenCounter;
 
@@ -258,10 +260,12 @@ impl PromptGenerator {
                 .unwrap_or("unknown");
 
             // 2. 解析会话并提取问答对
+            // 只取最后一次压缩之后的内容，压缩前的对话已由摘要概括
             let config = SessionParserConfig {
                 enable_content_filter: false,
                 view_level: ViewLevel::Full,
                 debug: cfg!(debug_assertions),
+                segment_scope: SegmentScope::Current,
             };
 
             let parser = SessionParserService::new(config);
@@ -271,7 +275,19 @@ impl PromptGenerator {
 
             // 3. 提取问答对
             let filter = MessageFilter::new(ViewLevel::QAPairs);
-            let qa_pairs = filter.extract_qa_pairs(parse_result.messages);
+            let mut qa_pairs = filter.extract_qa_pairs(parse_result.messages);
+            // 被回退或编辑取代的分支不属于当前对话
+            qa_pairs.retain(|pair| !pair.superseded);
+
+            // 提交给第三方 LLM 前脱敏
+            if let Some(mut redactor) = Redactor::for_llm() {
+                for pair in qa_pairs.iter_mut() {
+                    redactor.redact_message(&mut pair.question);
+                    if let Some(answer) = pair.answer.as_mut() {
+                        redactor.redact_message(answer);
+                    }
+                }
+            }
 
             #[cfg(debug_assertions)]
             eprintln!("[PromptGenerator] 提取到 {} 个问答对", qa_pairs.len());
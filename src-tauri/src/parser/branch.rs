@@ -0,0 +1,805 @@
+//! 会话分支检测模块
+//!
+//! 用户编辑提示词或回退（rewind）后，Claude Code 会在同一个父节点下写入新的
+//! user 子节点，原来的子树仍然保留在文件中；重试助手回复则会在同一父节点下
+//! 产生多个 assistant 子节点。消息树把这些兄弟节点当作普通子节点展示。
+//!
+//! 本模块识别这些兄弟分支（备选尝试），统计每个分支的规模与结果，并以包含
+//! 最新活动的分支作为"存活"分支，便于比较哪次提示词改写真正奏效。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use super::tree::{ConversationTree, MessageNode, NodeIndex};
+
+/// 分支预览的最大字符数
+const PREVIEW_CHARS: usize = 200;
+
+/// 分支类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BranchKind {
+    /// 用户编辑提示词或回退后重新提问（兄弟节点为 user 提示词）
+    Edit,
+    /// 重试助手回复（兄弟节点为 assistant 消息）
+    Retry,
+}
+
+/// 分支标记（附加在每个分支的首个节点上）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchLabel {
+    /// 分叉点（共同父节点）ID，首条提示词被编辑时为 None
+    pub branch_point_id: Option<String>,
+
+    /// 分支类型
+    pub kind: BranchKind,
+
+    /// 分支序号（从 0 开始，按时间顺序）
+    pub index: usize,
+
+    /// 分叉点的分支总数
+    pub count: usize,
+
+    /// 是否为存活分支
+    pub surviving: bool,
+}
+
+/// 分支统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchStats {
+    /// 消息数量
+    pub message_count: usize,
+
+    /// 用户提示词数量（不含 tool_result）
+    pub prompt_count: usize,
+
+    /// 助手消息数量
+    pub assistant_count: usize,
+
+    /// 工具调用次数
+    pub tool_call_count: usize,
+
+    /// 出错的工具结果数量
+    pub tool_error_count: usize,
+
+    /// 输出 token 数（同一 API 响应只计一次）
+    pub output_tokens: u64,
+
+    /// 最长对话链长度
+    pub depth: usize,
+
+    /// 分支开始时间
+    pub started_at: Option<String>,
+
+    /// 分支内最后一条消息的时间
+    pub ended_at: Option<String>,
+}
+
+/// 单个分支
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationBranch {
+    /// 分支序号（从 0 开始，按时间顺序）
+    pub index: usize,
+
+    /// 分支首个节点 ID
+    pub root_id: String,
+
+    /// 分支首个节点的内容预览（提示词或回复）
+    pub preview: Option<String>,
+
+    /// 分支内最后一条助手回复的预览
+    pub final_reply: Option<String>,
+
+    /// 是否为存活分支
+    pub surviving: bool,
+
+    /// 分支统计
+    pub stats: BranchStats,
+}
+
+/// 分叉点：同一父节点下的一组备选尝试
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchPoint {
+    /// 共同父节点 ID，首条提示词被编辑时为 None
+    pub parent_id: Option<String>,
+
+    /// 分支类型
+    pub kind: BranchKind,
+
+    /// 分支列表（按时间顺序，便于并排比较）
+    pub branches: Vec<ConversationBranch>,
+
+    /// 存活分支的序号
+    pub surviving_index: usize,
+}
+
+/// 分支检测器
+pub struct BranchDetector;
+
+impl BranchDetector {
+    /// 检测消息树中的所有分叉点
+    ///
+    /// 按深度优先顺序返回，嵌套在其他分支内的分叉点也会被列出
+    pub fn detect(tree: &ConversationTree) -> Vec<BranchPoint> {
+        let mut points = Vec::new();
+
+        // 首条提示词被编辑时，各版本都是没有父节点的根
+        let mut root_prompts: Vec<&MessageNode> =
+            tree.roots.iter().filter(|root| is_prompt(root)).collect();
+        if root_prompts.len() > 1 {
+            sort_by_time(&mut root_prompts);
+            points.push(Self::branch_point(None, BranchKind::Edit, &root_prompts));
+        }
+
+        let mut stack: Vec<&MessageNode> = tree.roots.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            if let Some((kind, siblings)) = sibling_attempts(node) {
+                points.push(Self::branch_point(Some(node.id.clone()), kind, &siblings));
+            }
+            stack.extend(node.children.iter().rev());
+        }
+
+        points
+    }
+
+    /// 检测分叉点并在每个分支的首个节点上写入分支标记
+    ///
+    /// 返回检测到的分叉点
+    pub fn annotate(tree: &mut ConversationTree) -> Vec<BranchPoint> {
+        let points = Self::detect(tree);
+
+        let mut labels: HashMap<String, BranchLabel> = HashMap::new();
+        for point in &points {
+            for branch in &point.branches {
+                labels.insert(
+                    branch.root_id.clone(),
+                    BranchLabel {
+                        branch_point_id: point.parent_id.clone(),
+                        kind: point.kind,
+                        index: branch.index,
+                        count: point.branches.len(),
+                        surviving: branch.surviving,
+                    },
+                );
+            }
+        }
+
+        if !labels.is_empty() {
+            let mut stack: Vec<&mut MessageNode> = tree.roots.iter_mut().collect();
+            while let Some(node) = stack.pop() {
+                node.branch = labels.remove(&node.id);
+                stack.extend(node.children.iter_mut());
+            }
+        }
+
+        points
+    }
+
+    /// 新子树挂载后重新标记受影响的分叉点
+    ///
+    /// 增量解析时代替 `annotate`：只重新计算新子树的祖先、新子树内部节点以及
+    /// 根节点层级的兄弟分支（新子树会改变祖先的最后活动时间，从而改变存活分支），
+    /// 存活分支按 `NodeIndex::last_activity` 判断，不重新统计整棵子树。
+    /// 标记发生变化的已有节点通过 `NodeIndex::mark_updated` 记录。
+    pub fn relabel(tree: &mut ConversationTree, index: &mut NodeIndex, attached: &[String]) {
+        if attached.is_empty() {
+            return;
+        }
+
+        // 新子树中的节点（随子树整体返回，不记录为已更新）
+        let mut appended: HashSet<String> = HashSet::new();
+        for root_id in attached {
+            let mut stack: Vec<&MessageNode> = index.get(tree, root_id).into_iter().collect();
+            while let Some(node) = stack.pop() {
+                appended.insert(node.id.clone());
+                stack.extend(node.children.iter());
+            }
+        }
+
+        // 受影响的分叉点：新子树的祖先和新子树内部的节点
+        let mut parents: Vec<&str> = appended.iter().map(|id| id.as_str()).collect();
+        let mut seen: HashSet<&str> = parents.iter().copied().collect();
+        for root_id in attached {
+            for ancestor in index.ancestors(root_id) {
+                // 已记录节点的祖先也都已记录
+                if !seen.insert(ancestor) {
+                    break;
+                }
+                parents.push(ancestor);
+            }
+        }
+
+        let activity = |node: &MessageNode| index.last_activity(&node.id);
+        let mut labels: Vec<(String, Option<BranchLabel>)> = Vec::new();
+
+        // 根节点层级：首条提示词的各个版本
+        let mut root_prompts: Vec<&MessageNode> =
+            tree.roots.iter().filter(|root| is_prompt(root)).collect();
+        sort_by_time(&mut root_prompts);
+        let siblings = (root_prompts.len() > 1).then_some((BranchKind::Edit, root_prompts));
+        labels.extend(sibling_labels(None, &tree.roots, siblings, activity));
+
+        for parent_id in parents {
+            if let Some(parent) = index.get(tree, parent_id) {
+                labels.extend(sibling_labels(
+                    Some(parent_id),
+                    &parent.children,
+                    sibling_attempts(parent),
+                    activity,
+                ));
+            }
+        }
+
+        for (node_id, label) in labels {
+            let changed = match index.get_mut(tree, &node_id) {
+                Some(node) if node.branch != label => {
+                    node.branch = label;
+                    true
+                }
+                _ => false,
+            };
+            if changed && !appended.contains(&node_id) {
+                index.mark_updated(&node_id);
+            }
+        }
+    }
+
+    /// 存活的根节点
+    ///
+    /// 首条提示词被编辑时只保留存活的版本，压缩边界等其他根节点原样保留
+    pub fn surviving_roots(tree: &ConversationTree) -> Vec<&MessageNode> {
+        let mut root_prompts: Vec<&MessageNode> =
+            tree.roots.iter().filter(|root| is_prompt(root)).collect();
+        if root_prompts.len() <= 1 {
+            return tree.roots.iter().collect();
+        }
+
+        sort_by_time(&mut root_prompts);
+        let ended: Vec<Option<&str>> = root_prompts.iter().map(|root| ended_at(root)).collect();
+        let surviving = root_prompts[surviving_position(&ended)].id.as_str();
+        tree.roots
+            .iter()
+            .filter(|root| !is_prompt(root) || root.id == surviving)
+            .collect()
+    }
+
+    /// 从 `root` 沿存活分支走到叶子，返回经过的节点（含 `root`）
+    ///
+    /// 分叉点处选择存活分支；不构成分支的多个子节点取时间最晚的一个。
+    /// 侧链子节点不会进入路径
+    pub fn surviving_path(root: &MessageNode) -> Vec<&MessageNode> {
+        // 各节点子树的最后活动时间只统计一次，供路径上的每个分叉点使用
+        let activity = last_activity(root);
+        let mut path = vec![root];
+        let mut node = root;
+
+        loop {
+            let next = match sibling_attempts(node) {
+                Some((_, siblings)) => {
+                    let ended: Vec<Option<&str>> = siblings
+                        .iter()
+                        .map(|sibling| activity.get(sibling.id.as_str()).copied().flatten())
+                        .collect();
+                    Some(siblings[surviving_position(&ended)])
+                }
+                None => {
+                    let mut children: Vec<&MessageNode> = node
+                        .children
+                        .iter()
+                        .filter(|child| !child.is_sidechain)
+                        .collect();
+                    sort_by_time(&mut children);
+                    children.pop()
+                }
+            };
+
+            match next {
+                Some(child) => {
+                    path.push(child);
+                    node = child;
+                }
+                None => break,
+            }
+        }
+
+        path
+    }
+
+    /// 汇总一组兄弟节点为分叉点
+    fn branch_point(
+        parent_id: Option<String>,
+        kind: BranchKind,
+        siblings: &[&MessageNode],
+    ) -> BranchPoint {
+        let mut branches: Vec<ConversationBranch> = siblings
+            .iter()
+            .enumerate()
+            .map(|(index, node)| ConversationBranch {
+                index,
+                root_id: node.id.clone(),
+                preview: text_preview(node),
+                final_reply: final_reply(node),
+                surviving: false,
+                stats: branch_stats(node),
+            })
+            .collect();
+
+        let ended: Vec<Option<&str>> = branches
+            .iter()
+            .map(|branch| branch.stats.ended_at.as_deref())
+            .collect();
+        let surviving_index = surviving_position(&ended);
+        branches[surviving_index].surviving = true;
+
+        BranchPoint {
+            parent_id,
+            kind,
+            branches,
+            surviving_index,
+        }
+    }
+}
+
+/// 找出节点下构成备选尝试的兄弟子节点
+///
+/// - 两个以上的 user 提示词子节点：编辑或回退
+/// - 两个以上属于不同 API 响应的 assistant 子节点：重试
+///
+/// tool_result、侧链等子节点不构成分支
+fn sibling_attempts(node: &MessageNode) -> Option<(BranchKind, Vec<&MessageNode>)> {
+    let children = node.children.iter().filter(|child| !child.is_sidechain);
+
+    let mut prompts: Vec<&MessageNode> =
+        children.clone().filter(|child| is_prompt(child)).collect();
+    if prompts.len() > 1 {
+        sort_by_time(&mut prompts);
+        return Some((BranchKind::Edit, prompts));
+    }
+
+    // 同一 API 响应拆分出的多个条目共享 message.id，不算重试
+    let mut response_ids = HashSet::new();
+    let mut replies: Vec<&MessageNode> = children
+        .filter(|child| child.is_assistant_message())
+        .filter(|child| match response_id(child) {
+            Some(id) => response_ids.insert(id),
+            None => true,
+        })
+        .collect();
+    if replies.len() > 1 {
+        sort_by_time(&mut replies);
+        return Some((BranchKind::Retry, replies));
+    }
+
+    None
+}
+
+/// 存活分支的下标（分支已按时间排序）
+///
+/// 最后活动时间最晚的分支，时间相同时取靠后的
+fn surviving_position(ended_at: &[Option<&str>]) -> usize {
+    ended_at
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.cmp(b))
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+/// 一组子节点的分支标记，不属于分支的子节点标记为 None
+fn sibling_labels<'a>(
+    parent_id: Option<&str>,
+    children: &[MessageNode],
+    siblings: Option<(BranchKind, Vec<&'a MessageNode>)>,
+    ended_at: impl Fn(&MessageNode) -> Option<&'a str>,
+) -> Vec<(String, Option<BranchLabel>)> {
+    let mut labels: HashMap<&str, BranchLabel> = HashMap::new();
+    if let Some((kind, siblings)) = siblings {
+        let ended: Vec<Option<&str>> = siblings.iter().map(|node| ended_at(node)).collect();
+        let surviving = surviving_position(&ended);
+        for (index, node) in siblings.iter().enumerate() {
+            labels.insert(
+                node.id.as_str(),
+                BranchLabel {
+                    branch_point_id: parent_id.map(|s| s.to_string()),
+                    kind,
+                    index,
+                    count: siblings.len(),
+                    surviving: index == surviving,
+                },
+            );
+        }
+    }
+
+    children
+        .iter()
+        .map(|child| (child.id.clone(), labels.remove(child.id.as_str())))
+        .collect()
+}
+
+/// 子树内最后一条消息的时间
+fn ended_at(root: &MessageNode) -> Option<&str> {
+    let mut ended = None;
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        ended = ended.max(timestamp(node));
+        stack.extend(node.children.iter());
+    }
+    ended
+}
+
+/// 子树中每个节点的最后活动时间（节点 ID → 子树内最后一条消息的时间）
+fn last_activity(root: &MessageNode) -> HashMap<&str, Option<&str>> {
+    // 广度优先展开，父节点总在子节点之前，逆序即可把子节点的时间汇总到父节点
+    let mut order: Vec<(&MessageNode, Option<usize>)> = vec![(root, None)];
+    let mut next = 0;
+    while next < order.len() {
+        let node = order[next].0;
+        order.extend(node.children.iter().map(|child| (child, Some(next))));
+        next += 1;
+    }
+
+    let mut ended: Vec<Option<&str>> = order.iter().map(|(node, _)| timestamp(node)).collect();
+    for position in (1..order.len()).rev() {
+        if let Some(parent) = order[position].1 {
+            ended[parent] = ended[parent].max(ended[position]);
+        }
+    }
+
+    order
+        .iter()
+        .zip(ended)
+        .map(|((node, _), ended)| (node.id.as_str(), ended))
+        .collect()
+}
+
+/// 是否为用户提示词（排除 tool_result、元消息和压缩摘要）
+fn is_prompt(node: &MessageNode) -> bool {
+    node.is_user_message()
+        && !node.is_compact_summary()
+        && node.message_data.get("isMeta").and_then(|v| v.as_bool()) != Some(true)
+        && !node.content_blocks().is_some_and(|blocks| {
+            blocks
+                .iter()
+                .any(|block| block_type(block) == Some("tool_result"))
+        })
+}
+
+/// API 响应 ID（`message.id`）
+fn response_id(node: &MessageNode) -> Option<&str> {
+    node.message_data
+        .get("message")
+        .and_then(|m| m.get("id"))
+        .and_then(|v| v.as_str())
+}
+
+/// 内容块类型
+fn block_type(block: &Value) -> Option<&str> {
+    block.get("type").and_then(|v| v.as_str())
+}
+
+/// 节点时间戳
+fn timestamp(node: &MessageNode) -> Option<&str> {
+    node.message_data.get("timestamp").and_then(|v| v.as_str())
+}
+
+/// 按时间戳排序兄弟节点（消息树中的子节点顺序不保证与文件一致）
+fn sort_by_time(nodes: &mut [&MessageNode]) {
+    nodes.sort_by(|a, b| (timestamp(a), &a.id).cmp(&(timestamp(b), &b.id)));
+}
+
+/// 节点文本预览（字符串 content 或 text 块拼接，最多 200 个字符）
+fn text_preview(node: &MessageNode) -> Option<String> {
+    let content = node
+        .message_data
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .or_else(|| node.message_data.get("content"))?;
+
+    let text = match content {
+        Value::String(text) => text.trim().to_string(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|block| block_type(block) == Some("text"))
+            .filter_map(|block| block.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string(),
+        _ => return None,
+    };
+
+    if text.is_empty() {
+        return None;
+    }
+    Some(match text.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    })
+}
+
+/// 统计分支子树
+fn branch_stats(root: &MessageNode) -> BranchStats {
+    let mut stats = BranchStats {
+        started_at: timestamp(root).map(|s| s.to_string()),
+        ..Default::default()
+    };
+    let mut responses = HashSet::new();
+
+    let mut stack = vec![(root, 1)];
+    while let Some((node, depth)) = stack.pop() {
+        stats.message_count += 1;
+        stats.depth = stats.depth.max(depth);
+
+        if let Some(ts) = timestamp(node) {
+            if stats.ended_at.as_deref().is_none_or(|ended| ts > ended) {
+                stats.ended_at = Some(ts.to_string());
+            }
+        }
+
+        if is_prompt(node) {
+            stats.prompt_count += 1;
+        } else if node.is_assistant_message() {
+            stats.assistant_count += 1;
+            let counted = response_id(node).is_none_or(|id| responses.insert(id));
+            if counted {
+                stats.output_tokens += node
+                    .message_data
+                    .get("message")
+                    .and_then(|m| m.get("usage"))
+                    .and_then(|u| u.get("output_tokens"))
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0);
+            }
+        }
+
+        for block in node.content_blocks().into_iter().flatten() {
+            match block_type(block) {
+                Some("tool_use") => stats.tool_call_count += 1,
+                Some("tool_result")
+                    if block.get("is_error").and_then(|v| v.as_bool()) == Some(true) =>
+                {
+                    stats.tool_error_count += 1
+                }
+                _ => {}
+            }
+        }
+
+        stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+    }
+
+    stats
+}
+
+/// 分支内沿最新子节点走到底时遇到的最后一条助手文本回复
+fn final_reply(root: &MessageNode) -> Option<String> {
+    let mut reply = None;
+    let mut node = root;
+    loop {
+        if node.is_assistant_message() {
+            if let Some(text) = text_preview(node) {
+                reply = Some(text);
+            }
+        }
+        match node
+            .children
+            .iter()
+            .filter(|child| !child.is_sidechain)
+            .max_by_key(|child| timestamp(child))
+        {
+            Some(child) => node = child,
+            None => return reply,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::jsonl::JsonlEntry;
+    use crate::parser::tree::MessageTreeBuilder;
+    use serde_json::json;
+
+    fn entry(data: Value) -> JsonlEntry {
+        JsonlEntry::new(0, 0, data)
+    }
+
+    fn prompt(uuid: &str, parent: Option<&str>, text: &str, ts: &str) -> JsonlEntry {
+        entry(
+            json!({"uuid": uuid, "parentUuid": parent, "type": "user", "timestamp": ts,
+                     "message": {"role": "user", "content": text}}),
+        )
+    }
+
+    fn reply(uuid: &str, parent: &str, id: &str, text: &str, ts: &str) -> JsonlEntry {
+        entry(
+            json!({"uuid": uuid, "parentUuid": parent, "type": "assistant", "timestamp": ts,
+                     "message": {"id": id, "role": "assistant", "usage": {"output_tokens": 10},
+                                 "content": [{"type": "text", "text": text}]}}),
+        )
+    }
+
+    /// u1 → a1 → {u2 → a2 → r2（工具出错）, u2b → {a3, a4}}
+    fn edit_and_retry_entries() -> Vec<JsonlEntry> {
+        vec![
+            prompt("u1", None, "fix the bug", "2025-01-01T10:00:00Z"),
+            reply("a1", "u1", "msg_1", "done", "2025-01-01T10:01:00Z"),
+            // 第一次提问：带工具调用，失败
+            prompt("u2", Some("a1"), "add tests", "2025-01-01T10:02:00Z"),
+            entry(
+                json!({"uuid": "a2", "parentUuid": "u2", "type": "assistant",
+                     "timestamp": "2025-01-01T10:03:00Z",
+                     "message": {"id": "msg_2", "role": "assistant", "content": [
+                         {"type": "tool_use", "id": "t1", "name": "Bash", "input": {}}]}}),
+            ),
+            entry(json!({"uuid": "r2", "parentUuid": "a2", "type": "user",
+                     "timestamp": "2025-01-01T10:04:00Z",
+                     "message": {"role": "user", "content": [
+                         {"type": "tool_result", "tool_use_id": "t1", "is_error": true}]}})),
+            // 编辑后的提问：两次重试回复
+            prompt(
+                "u2b",
+                Some("a1"),
+                "add unit tests for parser",
+                "2025-01-01T10:05:00Z",
+            ),
+            reply("a3", "u2b", "msg_3", "first try", "2025-01-01T10:06:00Z"),
+            reply("a4", "u2b", "msg_4", "second try", "2025-01-01T10:07:00Z"),
+        ]
+    }
+
+    /// 所有节点的分支标记
+    fn labels(tree: &ConversationTree) -> HashMap<String, Option<BranchLabel>> {
+        let mut labels = HashMap::new();
+        let mut stack: Vec<&MessageNode> = tree.roots.iter().collect();
+        while let Some(node) = stack.pop() {
+            labels.insert(node.id.clone(), node.branch.clone());
+            stack.extend(node.children.iter());
+        }
+        labels
+    }
+
+    #[test]
+    fn test_detect_edit_and_retry_branches() {
+        let entries = edit_and_retry_entries();
+        let mut tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+
+        let points = BranchDetector::annotate(&mut tree);
+        assert_eq!(points.len(), 2);
+
+        let edit = &points[0];
+        assert_eq!(edit.parent_id.as_deref(), Some("a1"));
+        assert_eq!(edit.kind, BranchKind::Edit);
+        assert_eq!(edit.surviving_index, 1);
+        assert_eq!(edit.branches[0].stats.tool_call_count, 1);
+        assert_eq!(edit.branches[0].stats.tool_error_count, 1);
+        assert_eq!(edit.branches[0].stats.prompt_count, 1);
+        assert_eq!(edit.branches[1].stats.message_count, 3);
+        assert_eq!(edit.branches[1].stats.output_tokens, 20);
+        assert_eq!(edit.branches[1].final_reply.as_deref(), Some("second try"));
+
+        let retry = &points[1];
+        assert_eq!(retry.parent_id.as_deref(), Some("u2b"));
+        assert_eq!(retry.kind, BranchKind::Retry);
+        assert_eq!(retry.branches[1].root_id, "a4");
+        assert!(retry.branches[1].surviving);
+
+        let label = tree
+            .find_node_where(|node| node.id == "u2")
+            .and_then(|node| node.branch.clone())
+            .unwrap();
+        assert_eq!(label.index, 0);
+        assert_eq!(label.count, 2);
+        assert!(!label.surviving);
+        assert!(tree
+            .find_node_where(|node| node.id == "r2")
+            .unwrap()
+            .branch
+            .is_none());
+    }
+
+    #[test]
+    fn test_shared_response_id_is_not_retry() {
+        // 同一 API 响应拆分出的多个条目挂在同一父节点下
+        let entries = vec![
+            prompt("u1", None, "hello", "2025-01-01T10:00:00Z"),
+            reply("a1", "u1", "msg_1", "part one", "2025-01-01T10:01:00Z"),
+            reply("a1b", "u1", "msg_1", "part two", "2025-01-01T10:01:01Z"),
+        ];
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        assert!(BranchDetector::detect(&tree).is_empty());
+
+        // 不同的 API 响应才算重试
+        let mut entries = entries;
+        entries.push(reply("a2", "u1", "msg_2", "retry", "2025-01-01T10:02:00Z"));
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        let points = BranchDetector::detect(&tree);
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].kind, BranchKind::Retry);
+        let roots: Vec<&str> = points[0]
+            .branches
+            .iter()
+            .map(|branch| branch.root_id.as_str())
+            .collect();
+        assert_eq!(roots, vec!["a1", "a2"]);
+    }
+
+    #[test]
+    fn test_surviving_roots_after_root_prompt_edit() {
+        // 首条提示词被编辑：较早的版本之后仍有活动，因此存活
+        let entries = vec![
+            prompt("u1", None, "fix the bug", "2025-01-01T10:00:00Z"),
+            prompt("u1b", None, "fix the parser bug", "2025-01-01T10:05:00Z"),
+            reply("a1b", "u1b", "msg_2", "which one?", "2025-01-01T10:06:00Z"),
+            reply("a1", "u1", "msg_1", "done", "2025-01-01T10:10:00Z"),
+        ];
+        let mut tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+
+        let roots: Vec<&str> = BranchDetector::surviving_roots(&tree)
+            .iter()
+            .map(|root| root.id.as_str())
+            .collect();
+        assert_eq!(roots, vec!["u1"]);
+
+        let points = BranchDetector::annotate(&mut tree);
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].parent_id, None);
+        assert_eq!(points[0].kind, BranchKind::Edit);
+        assert_eq!(points[0].surviving_index, 0);
+        let label = tree.roots[1].branch.clone().unwrap();
+        assert_eq!(label.branch_point_id, None);
+        assert_eq!(label.index, 1);
+        assert!(!label.surviving);
+    }
+
+    #[test]
+    fn test_surviving_path() {
+        let tree = MessageTreeBuilder::build_from_entries(&edit_and_retry_entries()).unwrap();
+
+        let path: Vec<&str> = BranchDetector::surviving_path(&tree.roots[0])
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect();
+        assert_eq!(path, vec!["u1", "a1", "u2b", "a4"]);
+    }
+
+    #[test]
+    fn test_relabel_matches_annotate() {
+        let mut entries = edit_and_retry_entries();
+        let mut tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        BranchDetector::annotate(&mut tree);
+        let mut index = NodeIndex::build(&tree);
+
+        // 第一次追加：被编辑掉的分支有了更晚的活动，存活分支翻转
+        // 第二次追加：新的重试让编辑后的分支重新存活，并改变重试分支数
+        let batches = vec![
+            (
+                reply("a5", "r2", "msg_5", "fixed", "2025-01-01T10:08:00Z"),
+                vec!["u2", "u2b"],
+            ),
+            (
+                reply("a6", "u2b", "msg_6", "third try", "2025-01-01T10:09:00Z"),
+                vec!["a3", "a4", "u2", "u2b"],
+            ),
+        ];
+
+        for (appended, expected) in batches {
+            entries.push(appended.clone());
+            MessageTreeBuilder::append_entries(&mut tree, &mut index, &[appended]).unwrap();
+            let attached = index.take_attached();
+            BranchDetector::relabel(&mut tree, &mut index, &attached);
+
+            let mut updated = index.take_updated();
+            updated.sort();
+            assert_eq!(updated, expected);
+
+            let mut rebuilt = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+            BranchDetector::annotate(&mut rebuilt);
+            assert_eq!(labels(&tree), labels(&rebuilt));
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! 微调数据集导出模块
+//!
+//! 将会话整理为结构化的多轮对话，并导出为常见的训练/评测数据格式：
+//! - OpenAI chat `messages` JSONL（工具调用使用 `tool_calls` / `role: tool`）
+//! - ShareGPT 对话 JSON（工具调用使用 `function_call` / `observation`）
+//! - Anthropic Messages JSONL（保留 `tool_use` / `tool_result` 内容块）
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+use super::branch::BranchDetector;
+use super::tree::{ConversationTree, MessageNode};
+use super::view_level::QAPair;
+use crate::database::models::Message;
+
+/// 对话角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetRole {
+    User,
+    Assistant,
+}
+
+/// 对话内容块
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatasetBlock {
+    /// 文本
+    Text(String),
+    /// 工具调用
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    /// 工具结果
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+        is_error: bool,
+    },
+}
+
+/// 单轮对话（连续的同角色消息会合并为一轮）
+#[derive(Debug, Clone)]
+pub struct DatasetTurn {
+    pub role: DatasetRole,
+    pub blocks: Vec<DatasetBlock>,
+}
+
+/// 一条完整的对话样本
+#[derive(Debug, Clone, Default)]
+pub struct DatasetConversation {
+    pub turns: Vec<DatasetTurn>,
+}
+
+impl DatasetConversation {
+    /// 追加内容块，与上一轮角色相同时合并
+    fn push(&mut self, role: DatasetRole, blocks: Vec<DatasetBlock>) {
+        if blocks.is_empty() {
+            return;
+        }
+        match self.turns.last_mut() {
+            Some(last) if last.role == role => last.blocks.extend(blocks),
+            _ => self.turns.push(DatasetTurn { role, blocks }),
+        }
+    }
+
+    /// 是否为可用样本（至少包含一轮用户输入和一轮助手回复）
+    fn is_usable(&self) -> bool {
+        self.turns.iter().any(|t| t.role == DatasetRole::User)
+            && self.turns.iter().any(|t| t.role == DatasetRole::Assistant)
+    }
+}
+
+/// 数据集构建器
+pub struct DatasetBuilder;
+
+impl DatasetBuilder {
+    /// 从消息树构建对话（每个根节点一条样本）
+    ///
+    /// # 参数
+    /// * `tree` - 消息树
+    /// * `include_tools` - 是否保留工具调用和工具结果
+    ///
+    /// 每条样本只包含从根节点到存活叶子的一条路径（见 `BranchDetector::surviving_path`），
+    /// 被编辑、回退或重试掉的分支不会混入。侧链（子代理）消息、meta 消息和 thinking 块不会导出
+    pub fn from_tree(tree: &ConversationTree, include_tools: bool) -> Vec<DatasetConversation> {
+        Self::from_tree_filtered(tree, include_tools, |_| true)
+    }
+
+    /// 从消息树构建对话，只导出满足 `keep` 的节点
+    ///
+    /// 路径的选择不受 `keep` 影响，用于在视图等级过滤后的消息上保留树结构
+    pub fn from_tree_filtered<F>(
+        tree: &ConversationTree,
+        include_tools: bool,
+        keep: F,
+    ) -> Vec<DatasetConversation>
+    where
+        F: Fn(&MessageNode) -> bool,
+    {
+        BranchDetector::surviving_roots(tree)
+            .into_iter()
+            .map(|root| {
+                let mut conversation = DatasetConversation::default();
+                for node in BranchDetector::surviving_path(root) {
+                    if !keep(node) {
+                        continue;
+                    }
+                    if let Some(role) = Self::node_role(node) {
+                        conversation.push(role, Self::node_blocks(node, include_tools));
+                    }
+                }
+                conversation
+            })
+            .filter(|c| c.is_usable())
+            .collect()
+    }
+
+    /// 从问答对构建对话（每个问答对一条样本）
+    pub fn from_qa_pairs(pairs: &[QAPair]) -> Vec<DatasetConversation> {
+        pairs
+            .iter()
+            .filter_map(|pair| {
+                let answer = pair.answer.as_ref()?;
+                let mut conversation = DatasetConversation::default();
+                conversation.push(DatasetRole::User, Self::message_blocks(&pair.question));
+                conversation.push(DatasetRole::Assistant, Self::message_blocks(answer));
+                Some(conversation)
+            })
+            .filter(|c| c.is_usable())
+            .collect()
+    }
+
+    /// 扁平消息的文本内容块
+    fn message_blocks(message: &Message) -> Vec<DatasetBlock> {
+        message
+            .content
+            .as_ref()
+            .or(message.summary.as_ref())
+            .map(|text| text.trim())
+            .filter(|text| !text.is_empty())
+            .map(|text| vec![DatasetBlock::Text(text.to_string())])
+            .unwrap_or_default()
+    }
+
+    /// 节点角色（只导出用户和助手消息）
+    fn node_role(node: &MessageNode) -> Option<DatasetRole> {
+        if node.is_sidechain
+            || node.message_data.get("isMeta").and_then(|v| v.as_bool()) == Some(true)
+        {
+            return None;
+        }
+
+        match node.role().or_else(|| node.message_type())?.as_str() {
+            "user" => Some(DatasetRole::User),
+            "assistant" => Some(DatasetRole::Assistant),
+            _ => None,
+        }
+    }
+
+    /// 节点内容块
+    fn node_blocks(node: &MessageNode, include_tools: bool) -> Vec<DatasetBlock> {
+        // 字符串内容：顶层 content、message.content 或 message 本身
+        let text = node
+            .message_data
+            .get("content")
+            .and_then(|v| v.as_str())
+            .or_else(|| {
+                let message = node.message_data.get("message")?;
+                message
+                    .get("content")
+                    .and_then(|v| v.as_str())
+                    .or_else(|| message.as_str())
+            });
+        if let Some(text) = text {
+            let text = text.trim();
+            return if text.is_empty() {
+                Vec::new()
+            } else {
+                vec![DatasetBlock::Text(text.to_string())]
+            };
+        }
+
+        let blocks = match node.content_blocks() {
+            Some(blocks) => blocks,
+            None => return Vec::new(),
+        };
+
+        blocks
+            .iter()
+            .filter_map(|block| match block.get("type").and_then(|v| v.as_str())? {
+                "text" => {
+                    let text = block.get("text")?.as_str()?.trim();
+                    (!text.is_empty()).then(|| DatasetBlock::Text(text.to_string()))
+                }
+                "tool_use" if include_tools => Some(DatasetBlock::ToolUse {
+                    id: block.get("id")?.as_str()?.to_string(),
+                    name: block.get("name")?.as_str()?.to_string(),
+                    input: block.get("input").cloned().unwrap_or_else(|| json!({})),
+                }),
+                "tool_result" if include_tools => Some(DatasetBlock::ToolResult {
+                    tool_use_id: block.get("tool_use_id")?.as_str()?.to_string(),
+                    content: Self::tool_result_text(block.get("content")),
+                    is_error: block
+                        .get("is_error")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 工具结果的文本内容（字符串或 text 块数组）
+    fn tool_result_text(content: Option<&Value>) -> String {
+        match content {
+            Some(Value::String(text)) => text.clone(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|item| item.get("text").and_then(|v| v.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => String::new(),
+        }
+    }
+}
+
+/// 数据集格式写出器
+pub struct DatasetWriter;
+
+impl DatasetWriter {
+    /// OpenAI chat `messages` JSONL（每行一条样本）
+    pub fn to_openai_jsonl(conversations: &[DatasetConversation]) -> Result<String> {
+        let mut output = String::new();
+
+        for conversation in conversations {
+            let mut messages = Vec::new();
+
+            for turn in &conversation.turns {
+                let mut texts = Vec::new();
+                let mut tool_calls = Vec::new();
+
+                for block in &turn.blocks {
+                    match block {
+                        DatasetBlock::Text(text) => texts.push(text.as_str()),
+                        DatasetBlock::ToolUse { id, name, input } => tool_calls.push(json!({
+                            "id": id,
+                            "type": "function",
+                            "function": {
+                                "name": name,
+                                "arguments": serde_json::to_string(input)?,
+                            }
+                        })),
+                        // 工具结果在 OpenAI 格式中是独立的 tool 消息
+                        DatasetBlock::ToolResult {
+                            tool_use_id,
+                            content,
+                            ..
+                        } => messages.push(json!({
+                            "role": "tool",
+                            "tool_call_id": tool_use_id,
+                            "content": content,
+                        })),
+                    }
+                }
+
+                let content = texts.join("\n\n");
+                match turn.role {
+                    DatasetRole::User => {
+                        if !content.is_empty() {
+                            messages.push(json!({"role": "user", "content": content}));
+                        }
+                    }
+                    DatasetRole::Assistant => {
+                        if content.is_empty() && tool_calls.is_empty() {
+                            continue;
+                        }
+                        let mut message = json!({
+                            "role": "assistant",
+                            "content": if content.is_empty() { Value::Null } else { json!(content) },
+                        });
+                        if !tool_calls.is_empty() {
+                            message["tool_calls"] = Value::Array(tool_calls);
+                        }
+                        messages.push(message);
+                    }
+                }
+            }
+
+            output.push_str(&serde_json::to_string(&json!({ "messages": messages }))?);
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+
+    /// ShareGPT 对话 JSON（样本数组）
+    pub fn to_sharegpt(conversations: &[DatasetConversation]) -> Result<String> {
+        let samples: Vec<Value> = conversations
+            .iter()
+            .map(|conversation| {
+                let mut turns = Vec::new();
+                for turn in &conversation.turns {
+                    for block in &turn.blocks {
+                        let (from, value) = match (turn.role, block) {
+                            (DatasetRole::User, DatasetBlock::Text(text)) => {
+                                ("human", text.clone())
+                            }
+                            (DatasetRole::Assistant, DatasetBlock::Text(text)) => {
+                                ("gpt", text.clone())
+                            }
+                            (_, DatasetBlock::ToolUse { name, input, .. }) => (
+                                "function_call",
+                                json!({"name": name, "arguments": input}).to_string(),
+                            ),
+                            (_, DatasetBlock::ToolResult { content, .. }) => {
+                                ("observation", content.clone())
+                            }
+                        };
+                        turns.push(json!({"from": from, "value": value}));
+                    }
+                }
+                json!({ "conversations": turns })
+            })
+            .collect();
+
+        serde_json::to_string_pretty(&samples).context("序列化 ShareGPT 数据失败")
+    }
+
+    /// Anthropic Messages JSONL（每行一条样本，保留工具内容块）
+    pub fn to_anthropic_jsonl(conversations: &[DatasetConversation]) -> Result<String> {
+        let mut output = String::new();
+
+        for conversation in conversations {
+            let messages: Vec<Value> = conversation
+                .turns
+                .iter()
+                .map(|turn| {
+                    let content: Vec<Value> = turn
+                        .blocks
+                        .iter()
+                        .map(|block| match block {
+                            DatasetBlock::Text(text) => json!({"type": "text", "text": text}),
+                            DatasetBlock::ToolUse { id, name, input } => json!({
+                                "type": "tool_use",
+                                "id": id,
+                                "name": name,
+                                "input": input,
+                            }),
+                            DatasetBlock::ToolResult {
+                                tool_use_id,
+                                content,
+                                is_error,
+                            } => json!({
+                                "type": "tool_result",
+                                "tool_use_id": tool_use_id,
+                                "content": content,
+                                "is_error": is_error,
+                            }),
+                        })
+                        .collect();
+                    let role = match turn.role {
+                        DatasetRole::User => "user",
+                        DatasetRole::Assistant => "assistant",
+                    };
+                    json!({"role": role, "content": content})
+                })
+                .collect();
+
+            output.push_str(&serde_json::to_string(&json!({ "messages": messages }))?);
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::jsonl::JsonlEntry;
+    use crate::parser::tree::MessageTreeBuilder;
+
+    fn build_tree() -> ConversationTree {
+        let entries = vec![
+            JsonlEntry::new(
+                0,
+                0,
+                json!({"uuid": "u1", "type": "user", "message": {"role": "user", "content": "List files"}}),
+            ),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({"uuid": "a1", "parentUuid": "u1", "type": "assistant", "message": {"role": "assistant", "content": [
+                    {"type": "thinking", "thinking": "hmm"},
+                    {"type": "text", "text": "Running ls."},
+                    {"type": "tool_use", "id": "toolu_1", "name": "Bash", "input": {"command": "ls"}}
+                ]}}),
+            ),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({"uuid": "r1", "parentUuid": "a1", "type": "user", "message": {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": "main.rs"}
+                ]}}),
+            ),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({"uuid": "a2", "parentUuid": "r1", "type": "assistant", "message": {"role": "assistant", "content": [
+                    {"type": "text", "text": "There is one file."}
+                ]}}),
+            ),
+        ];
+        MessageTreeBuilder::build_from_entries(&entries).unwrap()
+    }
+
+    #[test]
+    fn test_anthropic_keeps_tool_blocks() {
+        let conversations = DatasetBuilder::from_tree(&build_tree(), true);
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].turns.len(), 4);
+
+        let output = DatasetWriter::to_anthropic_jsonl(&conversations).unwrap();
+        let sample: Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        let messages = sample["messages"].as_array().unwrap();
+        assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+        assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_1");
+    }
+
+    #[test]
+    fn test_openai_and_sharegpt_tool_mapping() {
+        let conversations = DatasetBuilder::from_tree(&build_tree(), true);
+
+        let output = DatasetWriter::to_openai_jsonl(&conversations).unwrap();
+        let sample: Value = serde_json::from_str(output.trim()).unwrap();
+        let roles: Vec<&str> = sample["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool", "assistant"]);
+        assert_eq!(
+            sample["messages"][1]["tool_calls"][0]["function"]["arguments"],
+            "{\"command\":\"ls\"}"
+        );
+
+        let sharegpt: Value =
+            serde_json::from_str(&DatasetWriter::to_sharegpt(&conversations).unwrap()).unwrap();
+        let froms: Vec<&str> = sharegpt[0]["conversations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["from"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            froms,
+            vec!["human", "gpt", "function_call", "observation", "gpt"]
+        );
+
+        // 不保留工具时只剩文本
+        let text_only = DatasetBuilder::from_tree(&build_tree(), false);
+        assert_eq!(text_only[0].turns.len(), 2);
+    }
+
+    #[test]
+    fn test_samples_follow_surviving_branch() {
+        let entries = vec![
+            JsonlEntry::new(
+                0,
+                0,
+                json!({"uuid": "u1", "type": "user", "timestamp": "2025-01-01T10:00:00Z",
+                       "message": {"role": "user", "content": "Say hi"}}),
+            ),
+            // 被重试掉的回复
+            JsonlEntry::new(
+                0,
+                0,
+                json!({"uuid": "a1", "parentUuid": "u1", "type": "assistant",
+                       "timestamp": "2025-01-01T10:01:00Z",
+                       "message": {"id": "msg_1", "role": "assistant", "content": [
+                           {"type": "text", "text": "rejected"}]}}),
+            ),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({"uuid": "a2", "parentUuid": "u1", "type": "assistant",
+                       "timestamp": "2025-01-01T10:02:00Z",
+                       "message": {"id": "msg_2", "role": "assistant", "content": [
+                           {"type": "text", "text": "hi"}]}}),
+            ),
+        ];
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+
+        let conversations = DatasetBuilder::from_tree(&tree, true);
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].turns.len(), 2);
+        assert_eq!(
+            conversations[0].turns[1].blocks,
+            vec![DatasetBlock::Text("hi".to_string())]
+        );
+    }
+}
@@ -3,12 +3,38 @@
 //! 从消息树中提取工具调用、错误消息、代码变更等关键信息，生成摘要。
 
 use anyhow::{Context, Result};
+use chrono::DateTime;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 
+use super::dataset::{DatasetBuilder, DatasetConversation, DatasetWriter};
+use super::tool_error::{ToolErrorClass, ToolErrorClassifier};
 use super::tree::{
-    CodeChange, ConversationTree, ErrorMessage, MessageMetadata, MessageNode, ToolCall,
+    CodeChange, ConversationTree, ErrorMessage, MessageMetadata, MessageNode, NodeIndex, ToolCall,
 };
+use crate::redaction::Redactor;
+
+/// tool_result 内容保留的最大字符数
+const TOOL_RESULT_PREVIEW_CHARS: usize = 500;
+
+/// 工具结果（用于与 tool_use 配对）
+struct ToolResultInfo {
+    /// 结果内容（截断后）
+    content: String,
+
+    /// 是否为错误结果
+    is_error: bool,
+
+    /// 错误分类（基于完整内容，仅错误结果有值）
+    class: Option<ToolErrorClass>,
+
+    /// 错误摘要（仅错误结果有值）
+    error_summary: Option<String>,
+
+    /// 结果消息的时间戳
+    timestamp: Option<String>,
+}
 
 /// 关键信息提取器
 ///
@@ -27,9 +53,210 @@ impl MetadataExtractor {
         for root in &mut tree.roots {
             Self::extract_node_metadata_recursive(root)?;
         }
+
+        // 按 tool_use_id 配对工具调用与结果
+        Self::pair_tool_results(tree);
+
         Ok(())
     }
 
+    /// 按 tool_use_id 将 tool_result 配对到对应的工具调用
+    ///
+    /// 填充 `ToolCall` 的结果内容、错误标记、耗时，并以结果的 `is_error`
+    /// 覆盖根据文本猜测的状态。带有 ID 但尚无结果的调用标记为 `pending`，
+    /// 结果追加后再次调用会更新为 `success` 或 `error`。
+    ///
+    /// 出错的结果会被分类（见 `ToolErrorClassifier`），作为带类别的
+    /// `ErrorMessage` 记录到发起调用的节点上；重复调用不会产生重复记录。
+    pub fn pair_tool_results(tree: &mut ConversationTree) {
+        // 第一遍：收集所有 tool_result
+        let mut results: HashMap<String, ToolResultInfo> = HashMap::new();
+        let mut stack: Vec<&MessageNode> = tree.roots.iter().collect();
+        while let Some(node) = stack.pop() {
+            Self::collect_tool_results(node, &mut results);
+            stack.extend(node.children.iter());
+        }
+
+        // 第二遍：回填到工具调用
+        let mut stack: Vec<&mut MessageNode> = tree.roots.iter_mut().collect();
+        while let Some(node) = stack.pop() {
+            Self::apply_tool_results(node, &results, true);
+            stack.extend(node.children.iter_mut());
+        }
+    }
+
+    /// 增量配对新挂载子树中的 tool_result
+    ///
+    /// 只遍历 `roots` 对应的新子树：新子树中的调用按 `pair_tool_results`
+    /// 的规则回填；结果对应的调用在已有节点上时通过 `index` 直接定位，
+    /// 只回填尚未配对的调用，并通过 `NodeIndex::mark_updated` 记录该节点。
+    pub fn pair_appended_tool_results(
+        tree: &mut ConversationTree,
+        index: &mut NodeIndex,
+        roots: &[String],
+    ) {
+        let mut results: HashMap<String, ToolResultInfo> = HashMap::new();
+        let mut appended: HashSet<String> = HashSet::new();
+        for root_id in roots {
+            let mut stack: Vec<&MessageNode> = index.get(tree, root_id).into_iter().collect();
+            while let Some(node) = stack.pop() {
+                Self::collect_tool_results(node, &mut results);
+                appended.insert(node.id.clone());
+                stack.extend(node.children.iter());
+            }
+        }
+
+        for root_id in roots {
+            let mut stack: Vec<&mut MessageNode> =
+                index.get_mut(tree, root_id).into_iter().collect();
+            while let Some(node) = stack.pop() {
+                Self::apply_tool_results(node, &results, true);
+                stack.extend(node.children.iter_mut());
+            }
+        }
+
+        // 已有节点上的调用：按 tool_use_id 定位发起调用的节点
+        let mut callers: Vec<String> = results
+            .keys()
+            .filter_map(|tool_use_id| index.tool_call_node(tool_use_id))
+            .filter(|node_id| !appended.contains(*node_id))
+            .map(|node_id| node_id.to_string())
+            .collect();
+        callers.sort();
+        callers.dedup();
+
+        for node_id in callers {
+            let updated = index
+                .get_mut(tree, &node_id)
+                .map(|node| Self::apply_tool_results(node, &results, false))
+                .unwrap_or(false);
+            if updated {
+                index.mark_updated(&node_id);
+            }
+        }
+    }
+
+    /// 将 tool_result 回填到单个节点的工具调用，返回是否有调用被回填
+    ///
+    /// `rebuild` 为 true 时先清除节点上已分类的错误，并把没有结果的调用标记为
+    /// `pending`；为 false 时只回填尚未配对的调用，已有的结果和错误保持不变。
+    fn apply_tool_results(
+        node: &mut MessageNode,
+        results: &HashMap<String, ToolResultInfo>,
+        rebuild: bool,
+    ) -> bool {
+        let call_timestamp = node
+            .message_data
+            .get("timestamp")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let metadata = match node.metadata {
+            Some(ref mut metadata) => metadata,
+            None => return false,
+        };
+        if rebuild {
+            metadata.errors.retain(|error| error.category.is_none());
+        }
+
+        let mut paired = false;
+        for call in &mut metadata.tool_calls {
+            let result = match call.id.as_ref().map(|id| results.get(id)) {
+                Some(Some(result)) => result,
+                Some(None) => {
+                    if rebuild {
+                        call.status = "pending".to_string();
+                    }
+                    continue;
+                }
+                None => continue,
+            };
+            if !rebuild && call.is_error.is_some() {
+                continue;
+            }
+
+            call.result = Some(result.content.clone());
+            call.is_error = Some(result.is_error);
+            call.status = if result.is_error { "error" } else { "success" }.to_string();
+            call.duration_ms =
+                Self::elapsed_ms(call_timestamp.as_deref(), result.timestamp.as_deref());
+            paired = true;
+
+            if let Some(class) = result.class {
+                metadata.errors.push(ErrorMessage {
+                    error_type: class.category.error_type().to_string(),
+                    message: result.error_summary.clone().unwrap_or_default(),
+                    related_tool: Some(call.name.clone()),
+                    category: Some(class.category),
+                    origin: class.origin.map(|s| s.to_string()),
+                });
+            }
+        }
+        paired
+    }
+
+    /// 收集单个节点中的 tool_result
+    fn collect_tool_results(node: &MessageNode, results: &mut HashMap<String, ToolResultInfo>) {
+        let timestamp = node
+            .message_data
+            .get("timestamp")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let mut insert = |block: &Value| {
+            if let Some(tool_use_id) = block.get("tool_use_id").and_then(|v| v.as_str()) {
+                let content = block
+                    .get("content")
+                    .map(Self::extract_text_content)
+                    .unwrap_or_default();
+                let is_error = block
+                    .get("is_error")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                results.insert(
+                    tool_use_id.to_string(),
+                    ToolResultInfo {
+                        content: Self::truncate_chars(&content, TOOL_RESULT_PREVIEW_CHARS),
+                        is_error,
+                        class: is_error.then(|| ToolErrorClassifier::classify(&content)),
+                        error_summary: is_error
+                            .then(|| ToolErrorClassifier::summary_line(&content)),
+                        timestamp: timestamp.clone(),
+                    },
+                );
+            }
+        };
+
+        // 方法1: 直接是 tool_result 类型的消息
+        if node.message_type().as_deref() == Some("tool_result") {
+            insert(&node.message_data);
+        }
+
+        // 方法2: content 数组中的 tool_result 块
+        if let Some(blocks) = node.content_blocks() {
+            for block in blocks {
+                if block.get("type").and_then(|v| v.as_str()) == Some("tool_result") {
+                    insert(block);
+                }
+            }
+        }
+    }
+
+    /// 计算两个 RFC3339 时间戳之间的毫秒数
+    fn elapsed_ms(start: Option<&str>, end: Option<&str>) -> Option<i64> {
+        let start = DateTime::parse_from_rfc3339(start?).ok()?;
+        let end = DateTime::parse_from_rfc3339(end?).ok()?;
+        Some((end - start).num_milliseconds())
+    }
+
+    /// 按字符数截断文本（UTF-8 安全）
+    fn truncate_chars(text: &str, max_chars: usize) -> String {
+        match text.char_indices().nth(max_chars) {
+            Some((idx, _)) => format!("{}...", &text[..idx]),
+            None => text.to_string(),
+        }
+    }
+
     /// 递归提取节点元数据
     ///
     /// 深度优先遍历树结构，为每个节点提取元数据
@@ -52,7 +279,7 @@ impl MetadataExtractor {
     ///
     /// # 返回
     /// 返回提取的元数据或错误
-    fn extract_metadata_from_node(node: &MessageNode) -> Result<MessageMetadata> {
+    pub(crate) fn extract_metadata_from_node(node: &MessageNode) -> Result<MessageMetadata> {
         // 提取工具调用
         let tool_calls = Self::extract_tool_calls(node);
 
@@ -98,37 +325,50 @@ impl MetadataExtractor {
                     name: tool_name.to_string(),
                     input,
                     status,
+                    id: node
+                        .message_data
+                        .get("id")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string()),
+                    result: None,
+                    is_error: None,
+                    duration_ms: None,
                 });
             }
         }
 
         // 方法2: 从 content 数组中提取 tool_use 块
-        if let Some(content) = node.message_data.get("content") {
-            if let Some(content_array) = content.as_array() {
-                for item in content_array {
-                    if let Some(item_type) = item.get("type").and_then(|v| v.as_str()) {
-                        if item_type == "tool_use" {
-                            if let Some(tool_name) = item.get("name").and_then(|v| v.as_str()) {
-                                let input = item
-                                    .get("input")
-                                    .cloned()
-                                    .unwrap_or(Value::Object(serde_json::Map::new()));
-
-                                // 从 tool_use 内容中提取状态
-                                let status = if let Some(content_text) =
-                                    item.get("content").and_then(|v| v.as_str())
-                                {
-                                    Self::parse_tool_status(content_text)
-                                } else {
-                                    "success".to_string()
-                                };
-
-                                tool_calls.push(ToolCall {
-                                    name: tool_name.to_string(),
-                                    input,
-                                    status,
-                                });
-                            }
+        if let Some(content_array) = node.content_blocks() {
+            for item in content_array {
+                if let Some(item_type) = item.get("type").and_then(|v| v.as_str()) {
+                    if item_type == "tool_use" {
+                        if let Some(tool_name) = item.get("name").and_then(|v| v.as_str()) {
+                            let input = item
+                                .get("input")
+                                .cloned()
+                                .unwrap_or(Value::Object(serde_json::Map::new()));
+
+                            // 从 tool_use 内容中提取状态
+                            let status = if let Some(content_text) =
+                                item.get("content").and_then(|v| v.as_str())
+                            {
+                                Self::parse_tool_status(content_text)
+                            } else {
+                                "success".to_string()
+                            };
+
+                            tool_calls.push(ToolCall {
+                                name: tool_name.to_string(),
+                                input,
+                                status,
+                                id: item
+                                    .get("id")
+                                    .and_then(|v| v.as_str())
+                                    .map(|s| s.to_string()),
+                                result: None,
+                                is_error: None,
+                                duration_ms: None,
+                            });
                         }
                     }
                 }
@@ -242,12 +482,16 @@ impl MetadataExtractor {
                                 error_type,
                                 message,
                                 related_tool: related_tool.clone(),
+                                category: None,
+                                origin: None,
                             });
                         } else if line.contains("失败") || line.contains("错误") {
                             errors.push(ErrorMessage {
                                 error_type: "RuntimeError".to_string(),
                                 message: line.trim().to_string(),
                                 related_tool: related_tool.clone(),
+                                category: None,
+                                origin: None,
                             });
                         }
                     }
@@ -265,6 +509,8 @@ impl MetadataExtractor {
                         error_type: "ToolError".to_string(),
                         message: error_msg.to_string(),
                         related_tool: related_tool.clone(),
+                        category: None,
+                        origin: None,
                     });
                 }
 
@@ -527,6 +773,31 @@ impl ExtractionLevel {
 pub enum ExportFormat {
     Markdown,
     Json,
+    /// OpenAI chat `messages` JSONL
+    OpenAi,
+    /// ShareGPT 对话 JSON
+    ShareGpt,
+    /// Anthropic Messages JSONL
+    Anthropic,
+}
+
+impl ExportFormat {
+    /// 导出文件扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json | ExportFormat::ShareGpt => "json",
+            ExportFormat::OpenAi | ExportFormat::Anthropic => "jsonl",
+        }
+    }
+
+    /// 是否为微调数据集格式
+    pub fn is_dataset(&self) -> bool {
+        matches!(
+            self,
+            ExportFormat::OpenAi | ExportFormat::ShareGpt | ExportFormat::Anthropic
+        )
+    }
 }
 
 /// 日志提取引擎
@@ -535,6 +806,24 @@ pub enum ExportFormat {
 pub struct ExtractionEngine;
 
 impl ExtractionEngine {
+    /// 准备导出用的消息树
+    ///
+    /// 如果提供了脱敏引擎，先脱敏原始数据再提取元数据，
+    /// 保证所有导出格式（包括工具调用、代码变更等元数据）都不含敏感信息。
+    ///
+    /// # 参数
+    /// - `tree`: 对话树
+    /// - `redactor`: 可选的脱敏引擎
+    pub fn prepare_tree(
+        tree: &mut ConversationTree,
+        redactor: Option<&mut Redactor>,
+    ) -> Result<()> {
+        if let Some(redactor) = redactor {
+            redactor.redact_tree(tree);
+        }
+        MetadataExtractor::extract_tree_metadata(tree)
+    }
+
     /// 提取会话内容
     ///
     /// # 参数
@@ -627,7 +916,18 @@ impl ExtractionEngine {
             if !metadata.tool_calls.is_empty() {
                 output.push_str(&format!("{}  工具调用:\n", indent));
                 for tool_call in &metadata.tool_calls {
-                    output.push_str(&format!("{}    - {}\n", indent, tool_call.name));
+                    output.push_str(&format!("{}    - {}", indent, tool_call.name));
+                    if tool_call.is_error.is_some() {
+                        output.push_str(&format!(" [{}]", tool_call.status));
+                    }
+                    if let Some(duration_ms) = tool_call.duration_ms {
+                        output.push_str(&format!(" ({} ms)", duration_ms));
+                    }
+                    output.push('\n');
+                    if let Some(ref result) = tool_call.result {
+                        let first_line = result.lines().next().unwrap_or("");
+                        output.push_str(&format!("{}      → {}\n", indent, first_line));
+                    }
                 }
             }
 
@@ -787,6 +1087,45 @@ impl ExtractionEngine {
 
         std::fs::write(path, json).context(format!("写入 JSON 文件失败: {:?}", path))
     }
+
+    /// 导出为微调数据集文件
+    ///
+    /// # 参数
+    /// - `tree`: 对话树
+    /// - `level`: 提取等级（L3 仅保留文本，其余等级保留工具调用）
+    /// - `format`: 数据集格式
+    /// - `path`: 目标文件路径
+    ///
+    /// # 返回
+    /// 返回导出的样本数或错误
+    pub fn export_dataset(
+        tree: &ConversationTree,
+        level: ExtractionLevel,
+        format: ExportFormat,
+        path: &std::path::Path,
+    ) -> Result<usize> {
+        let include_tools = level != ExtractionLevel::L3PromptOnly;
+        let conversations = DatasetBuilder::from_tree(tree, include_tools);
+        let content = Self::render_dataset(&conversations, format)?;
+
+        std::fs::write(path, content).context(format!("写入数据集文件失败: {:?}", path))?;
+        Ok(conversations.len())
+    }
+
+    /// 将对话渲染为指定的数据集格式
+    pub fn render_dataset(
+        conversations: &[DatasetConversation],
+        format: ExportFormat,
+    ) -> Result<String> {
+        match format {
+            ExportFormat::OpenAi => DatasetWriter::to_openai_jsonl(conversations),
+            ExportFormat::ShareGpt => DatasetWriter::to_sharegpt(conversations),
+            ExportFormat::Anthropic => DatasetWriter::to_anthropic_jsonl(conversations),
+            ExportFormat::Markdown | ExportFormat::Json => {
+                anyhow::bail!("{:?} 不是数据集格式", format)
+            }
+        }
+    }
 }
 
 // ========== 单元测试 ==========
@@ -813,6 +1152,115 @@ mod tests {
         assert_eq!(tool_calls[0].name, "read_file");
     }
 
+    #[test]
+    fn test_pair_tool_results() {
+        use crate::parser::jsonl::JsonlEntry;
+        use crate::parser::tree::MessageTreeBuilder;
+
+        let entries = vec![
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "user1",
+                    "role": "user",
+                    "type": "message",
+                    "content": "Run the tests",
+                    "timestamp": "2025-01-01T10:00:00.000Z"
+                }),
+            ),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "assistant1",
+                    "parentUuid": "user1",
+                    "role": "assistant",
+                    "type": "message",
+                    "timestamp": "2025-01-01T10:00:01.000Z",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": "Bash",
+                        "input": {"command": "cargo test"}
+                    }]
+                }),
+            ),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "result1",
+                    "parentUuid": "assistant1",
+                    "role": "user",
+                    "type": "message",
+                    "timestamp": "2025-01-01T10:00:03.500Z",
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": "toolu_1",
+                        "is_error": true,
+                        "content": [{"type": "text", "text": "test failed"}]
+                    }]
+                }),
+            ),
+        ];
+
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        let assistant = &tree.roots[0].children[0];
+        let call = &assistant.metadata.as_ref().unwrap().tool_calls[0];
+
+        assert_eq!(call.id.as_deref(), Some("toolu_1"));
+        assert_eq!(call.result.as_deref(), Some("test failed"));
+        assert_eq!(call.is_error, Some(true));
+        assert_eq!(call.status, "error");
+        assert_eq!(call.duration_ms, Some(2500));
+    }
+
+    #[test]
+    fn test_unpaired_tool_call_is_pending() {
+        use crate::parser::jsonl::JsonlEntry;
+        use crate::parser::tree::MessageTreeBuilder;
+
+        let entries = vec![
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "user1",
+                    "role": "user",
+                    "type": "message",
+                    "content": "Run the tests"
+                }),
+            ),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "assistant1",
+                    "parentUuid": "user1",
+                    "role": "assistant",
+                    "type": "message",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": "Bash",
+                        "input": {"command": "cargo test"}
+                    }]
+                }),
+            ),
+        ];
+
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        let call = &tree.roots[0].children[0]
+            .metadata
+            .as_ref()
+            .unwrap()
+            .tool_calls[0];
+
+        assert_eq!(call.status, "pending");
+        assert_eq!(call.is_error, None);
+    }
+
     #[test]
     fn test_extract_errors() {
         let message_data = json!({
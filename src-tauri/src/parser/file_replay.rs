@@ -0,0 +1,694 @@
+//! 文件内容回放模块
+//!
+//! 给定会话中的某条消息，重建该时刻所有被触及文件的已知内容。
+//! 以完整的 Read 结果、Write 输入或 Edit 结果中的 `originalFile` 作为快照，
+//! 再依次应用之后的 Edit/MultiEdit。无法重建的情况（在从未见过的内容上编辑、
+//! `old_string` 在已知内容中找不到）会报告为缺口，而不是猜测。
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use super::file_timeline::{FileOperation, FileTimelineBuilder};
+use super::tree::{ConversationTree, MessageNode};
+
+/// Read 工具输出的行号前缀（`     1→内容` 或 `     1\t内容`）
+static NUMBERED_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(\d+)(?:→|\t)(.*)$").expect("行号正则应当有效"));
+
+/// Read 工具未指定 limit 时最多返回的行数
+const READ_DEFAULT_LINE_LIMIT: usize = 2000;
+
+/// Read 工具输出中单行的最大字符数，更长的行会被截断
+const READ_MAX_LINE_CHARS: usize = 2000;
+
+/// 快照来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnapshotSource {
+    /// 完整读取文件的 Read 结果
+    Read,
+    /// Write 写入的内容
+    Write,
+    /// Edit 结果中记录的编辑前内容
+    OriginalFile,
+}
+
+/// 重建状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayStatus {
+    /// 内容已完整重建
+    Reconstructed,
+    /// 存在缺口，内容未知
+    Gap,
+}
+
+/// 重建缺口
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayGap {
+    /// 导致缺口的消息 UUID
+    pub message_uuid: String,
+
+    /// 缺口原因
+    pub reason: String,
+}
+
+/// 某一时刻的文件内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconstructedFile {
+    /// 文件路径
+    pub file_path: String,
+
+    /// 重建状态
+    pub status: ReplayStatus,
+
+    /// 重建出的内容（存在缺口时为 None）
+    pub content: Option<String>,
+
+    /// 快照来源
+    pub base_source: Option<SnapshotSource>,
+
+    /// 快照所在的消息 UUID
+    pub base_message_uuid: Option<String>,
+
+    /// 快照之后已应用的编辑次数
+    pub edits_applied: usize,
+
+    /// 最后一次触及该文件的消息 UUID
+    pub last_message_uuid: String,
+
+    /// 最后一次触及该文件的时间戳
+    pub last_timestamp: Option<String>,
+
+    /// 缺口信息（仅 status 为 Gap 时有值）
+    pub gap: Option<ReplayGap>,
+}
+
+impl ReconstructedFile {
+    fn new(file_path: &str, node: &MessageNode) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            status: ReplayStatus::Gap,
+            content: None,
+            base_source: None,
+            base_message_uuid: None,
+            edits_applied: 0,
+            last_message_uuid: node.id.clone(),
+            last_timestamp: Self::node_timestamp(node),
+            gap: None,
+        }
+    }
+
+    /// 以完整快照重置文件状态
+    fn set_snapshot(&mut self, content: String, source: SnapshotSource, message_uuid: &str) {
+        self.status = ReplayStatus::Reconstructed;
+        self.content = Some(content);
+        self.base_source = Some(source);
+        self.base_message_uuid = Some(message_uuid.to_string());
+        self.edits_applied = 0;
+        self.gap = None;
+    }
+
+    /// 标记缺口（保留最早的缺口原因）
+    fn set_gap(&mut self, message_uuid: &str, reason: &str) {
+        if self.status == ReplayStatus::Gap && self.gap.is_some() {
+            return;
+        }
+        self.status = ReplayStatus::Gap;
+        self.content = None;
+        self.gap = Some(ReplayGap {
+            message_uuid: message_uuid.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    fn touch(&mut self, node: &MessageNode) {
+        self.last_message_uuid = node.id.clone();
+        self.last_timestamp = Self::node_timestamp(node);
+    }
+
+    fn node_timestamp(node: &MessageNode) -> Option<String> {
+        node.message_data
+            .get("timestamp")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    }
+}
+
+/// 文件内容回放器
+pub struct FileReplayer;
+
+impl FileReplayer {
+    /// 重建指定消息时刻所有被触及文件的内容
+    ///
+    /// # 参数
+    /// * `tree` - 会话消息树
+    /// * `message_uuid` - 目标消息 UUID（包含该消息本身的操作）
+    ///
+    /// # 返回
+    /// 按文件路径排序的文件内容列表；消息不存在时返回错误
+    ///
+    /// # 说明
+    /// 只应用目标消息祖先链上的操作，其他分支上的编辑即使时间更早也会被忽略
+    pub fn replay_at(
+        tree: &ConversationTree,
+        message_uuid: &str,
+    ) -> Result<Vec<ReconstructedFile>> {
+        let chain = Self::ancestor_chain(tree, message_uuid)
+            .ok_or_else(|| anyhow::anyhow!("消息不存在: {}", message_uuid))?;
+
+        let nodes = FileTimelineBuilder::ordered_nodes(tree);
+        let cutoff = nodes
+            .iter()
+            .position(|node| node.id == message_uuid)
+            .ok_or_else(|| anyhow::anyhow!("消息不存在: {}", message_uuid))?;
+
+        // Edit 结果中的编辑前内容（按 tool_use_id 索引）
+        let original_files = Self::collect_original_files(&nodes);
+
+        // Read 调用：tool_use_id → (文件路径, 是否完整读取)
+        let mut read_calls: HashMap<String, (String, bool)> = HashMap::new();
+        let mut files: HashMap<String, ReconstructedFile> = HashMap::new();
+
+        for node in nodes[..=cutoff]
+            .iter()
+            .filter(|node| chain.contains(node.id.as_str()))
+        {
+            // 1. 记录 Read 调用
+            if let Some(ref metadata) = node.metadata {
+                for call in &metadata.tool_calls {
+                    if !matches!(call.name.as_str(), "Read" | "read_file") {
+                        continue;
+                    }
+                    let (id, path) = match (
+                        call.id.as_ref(),
+                        call.input
+                            .get("file_path")
+                            .or_else(|| call.input.get("path"))
+                            .and_then(|v| v.as_str()),
+                    ) {
+                        (Some(id), Some(path)) => (id, path),
+                        _ => continue,
+                    };
+                    let complete =
+                        call.input.get("offset").is_none() && call.input.get("limit").is_none();
+                    read_calls.insert(id.clone(), (path.to_string(), complete));
+                }
+            }
+
+            // 2. Read 结果作为快照
+            for (file_path, content) in Self::read_snapshots(node, &read_calls) {
+                let file = files
+                    .entry(file_path.clone())
+                    .or_insert_with(|| ReconstructedFile::new(&file_path, node));
+                match content {
+                    Some(content) => file.set_snapshot(content, SnapshotSource::Read, &node.id),
+                    // 无法确认输出覆盖整个文件：已知内容保持不变，否则报告缺口
+                    None if file.content.is_none() => {
+                        file.set_gap(&node.id, "Read 输出可能被截断，无法确认文件的完整内容")
+                    }
+                    None => {}
+                }
+                file.touch(node);
+            }
+
+            // 3. 依次应用 Write/Edit/MultiEdit
+            for op in FileTimelineBuilder::node_operations(node) {
+                // 出错的调用不会修改文件
+                if op.is_error == Some(true) {
+                    continue;
+                }
+
+                let file = files
+                    .entry(op.file_path.clone())
+                    .or_insert_with(|| ReconstructedFile::new(&op.file_path, node));
+                file.touch(node);
+
+                if op.operation == FileOperation::Write {
+                    if let Some(content) = op.apply(None) {
+                        file.set_snapshot(content, SnapshotSource::Write, &node.id);
+                    }
+                    continue;
+                }
+
+                // 内容未知时，尝试使用 Edit 结果中的编辑前内容
+                if file.content.is_none() {
+                    if let Some(original) = op
+                        .tool_use_id
+                        .as_ref()
+                        .and_then(|id| original_files.get(id))
+                    {
+                        file.set_snapshot(original.clone(), SnapshotSource::OriginalFile, &node.id);
+                    }
+                }
+
+                let applied = file.content.as_deref().map(|before| op.apply(Some(before)));
+                match applied {
+                    None => file.set_gap(&node.id, "编辑作用于从未见过的文件内容"),
+                    Some(None) => file.set_gap(&node.id, "old_string 在已知内容中不存在"),
+                    Some(Some(after)) => {
+                        file.content = Some(after);
+                        file.edits_applied += 1;
+                    }
+                }
+            }
+        }
+
+        let mut result: Vec<ReconstructedFile> = files.into_values().collect();
+        result.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        Ok(result)
+    }
+
+    /// 收集目标消息的祖先链（含目标本身）及挂在祖先节点上的子代理侧链
+    ///
+    /// 消息不存在时返回 None
+    fn ancestor_chain<'a>(
+        tree: &'a ConversationTree,
+        message_uuid: &str,
+    ) -> Option<HashSet<&'a str>> {
+        // 深度优先查找目标，记录从根到当前节点的路径
+        let mut path: Vec<&MessageNode> = Vec::new();
+        let mut stack: Vec<(usize, &MessageNode)> =
+            tree.roots.iter().rev().map(|root| (0, root)).collect();
+        let mut found = false;
+        while let Some((depth, node)) = stack.pop() {
+            path.truncate(depth);
+            path.push(node);
+            if node.id == message_uuid {
+                found = true;
+                break;
+            }
+            stack.extend(node.children.iter().rev().map(|child| (depth + 1, child)));
+        }
+        if !found {
+            return None;
+        }
+
+        let mut chain: HashSet<&str> = path.iter().map(|node| node.id.as_str()).collect();
+
+        // 子代理在侧链中的操作同样作用于主会话的文件
+        for node in &path {
+            let mut sidechains: Vec<&MessageNode> = node
+                .children
+                .iter()
+                .filter(|child| child.is_sidechain && !node.is_sidechain)
+                .collect();
+            while let Some(side) = sidechains.pop() {
+                chain.insert(side.id.as_str());
+                sidechains.extend(side.children.iter());
+            }
+        }
+
+        Some(chain)
+    }
+
+    /// 收集 Edit 结果中记录的编辑前内容（`toolUseResult.originalFile`）
+    fn collect_original_files(nodes: &[&MessageNode]) -> HashMap<String, String> {
+        let mut original_files = HashMap::new();
+
+        for node in nodes {
+            let original = match node
+                .message_data
+                .get("toolUseResult")
+                .and_then(|r| r.get("originalFile"))
+                .and_then(|v| v.as_str())
+            {
+                Some(original) => original,
+                None => continue,
+            };
+
+            // toolUseResult 对应整条消息，只有一个 tool_result 时才能确定归属
+            let ids = Self::tool_result_blocks(node)
+                .filter_map(|block| block.get("tool_use_id").and_then(|v| v.as_str()))
+                .collect::<Vec<_>>();
+            if let [id] = ids.as_slice() {
+                original_files.insert(id.to_string(), original.to_string());
+            }
+        }
+
+        original_files
+    }
+
+    /// 从节点中提取完整 Read 结果的文件内容
+    ///
+    /// 无法确认输出覆盖整个文件时内容为 None
+    fn read_snapshots(
+        node: &MessageNode,
+        read_calls: &HashMap<String, (String, bool)>,
+    ) -> Vec<(String, Option<String>)> {
+        let blocks: Vec<&Value> = Self::tool_result_blocks(node).collect();
+        let mut snapshots = Vec::new();
+
+        for block in &blocks {
+            if block.get("is_error").and_then(|v| v.as_bool()) == Some(true) {
+                continue;
+            }
+            let (file_path, complete) = match block
+                .get("tool_use_id")
+                .and_then(|v| v.as_str())
+                .and_then(|id| read_calls.get(id))
+            {
+                Some(call) => call,
+                None => continue,
+            };
+            if !complete {
+                continue;
+            }
+
+            // 优先使用结构化结果（toolUseResult.file），只有一个 tool_result 时才能确定归属
+            let (structured, total_lines) = if blocks.len() == 1 {
+                (
+                    Self::structured_read_content(node),
+                    Self::structured_total_lines(node),
+                )
+            } else {
+                (None, None)
+            };
+
+            let content = structured.or_else(|| {
+                let text = match block.get("content") {
+                    Some(Value::String(text)) => text.clone(),
+                    Some(Value::Array(items)) => items
+                        .iter()
+                        .filter_map(|item| item.get("text").and_then(|v| v.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    _ => return None,
+                };
+                let content = Self::strip_line_numbers(&text)?;
+                Self::is_complete_text_read(&content, total_lines).then_some(content)
+            });
+
+            snapshots.push((file_path.clone(), content));
+        }
+
+        snapshots
+    }
+
+    /// 判断去掉行号后的 Read 文本输出是否为完整文件
+    ///
+    /// 有 `toolUseResult.file.totalLines` 时行数必须一致；否则要求行数明显低于
+    /// Read 的默认行数上限，且没有可能被截断的超长行。
+    fn is_complete_text_read(content: &str, total_lines: Option<u64>) -> bool {
+        let line_count = content.lines().count();
+        match total_lines {
+            Some(total) => line_count as u64 == total,
+            None => {
+                line_count < READ_DEFAULT_LINE_LIMIT
+                    && content
+                        .lines()
+                        .all(|line| line.chars().count() < READ_MAX_LINE_CHARS)
+            }
+        }
+    }
+
+    /// 读取 `toolUseResult.file.totalLines`
+    fn structured_total_lines(node: &MessageNode) -> Option<u64> {
+        node.message_data
+            .get("toolUseResult")?
+            .get("file")?
+            .get("totalLines")?
+            .as_u64()
+    }
+
+    /// 读取 `toolUseResult.file.content`（仅当结果覆盖整个文件时）
+    fn structured_read_content(node: &MessageNode) -> Option<String> {
+        let file = node.message_data.get("toolUseResult")?.get("file")?;
+        let content = file.get("content")?.as_str()?;
+
+        let start_line = file.get("startLine").and_then(|v| v.as_u64()).unwrap_or(1);
+        let num_lines = file.get("numLines").and_then(|v| v.as_u64());
+        let total_lines = file.get("totalLines").and_then(|v| v.as_u64());
+        if start_line != 1 || matches!((num_lines, total_lines), (Some(n), Some(t)) if n < t) {
+            return None;
+        }
+
+        Some(content.to_string())
+    }
+
+    /// 去掉 Read 输出的行号前缀，还原文件内容
+    ///
+    /// 行号必须从 1 开始连续递增，否则视为无法还原。
+    /// Read 输出不保留末尾换行信息，这里按以换行结尾处理。
+    fn strip_line_numbers(text: &str) -> Option<String> {
+        let mut lines = Vec::new();
+
+        for line in text.lines() {
+            if line.starts_with("<system-reminder>") {
+                break;
+            }
+            match NUMBERED_LINE.captures(line) {
+                Some(caps) if caps[1].parse::<usize>().ok() == Some(lines.len() + 1) => {
+                    lines.push(caps[2].to_string());
+                }
+                _ if line.trim().is_empty() => continue,
+                _ => return None,
+            }
+        }
+
+        if lines.is_empty() {
+            return None;
+        }
+
+        let mut content = lines.join("\n");
+        content.push('\n');
+        Some(content)
+    }
+
+    /// 遍历节点中的 tool_result 块
+    fn tool_result_blocks(node: &MessageNode) -> impl Iterator<Item = &Value> {
+        node.content_blocks()
+            .into_iter()
+            .flatten()
+            .filter(|block| block.get("type").and_then(|v| v.as_str()) == Some("tool_result"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::jsonl::JsonlEntry;
+    use crate::parser::tree::MessageTreeBuilder;
+    use serde_json::json;
+
+    fn entry(uuid: &str, parent: Option<&str>, role: &str, content: Value) -> JsonlEntry {
+        let mut data = json!({"uuid": uuid, "role": role, "type": "message", "content": content});
+        if let Some(parent) = parent {
+            data["parentUuid"] = json!(parent);
+        }
+        JsonlEntry::new(0, 0, data)
+    }
+
+    fn tool_use(id: &str, name: &str, input: Value) -> Value {
+        json!([{"type": "tool_use", "id": id, "name": name, "input": input}])
+    }
+
+    fn tool_result(id: &str, text: &str) -> Value {
+        json!([{"type": "tool_result", "tool_use_id": id, "content": text}])
+    }
+
+    fn build_session() -> ConversationTree {
+        let entries = vec![
+            entry("u1", None, "user", json!("refactor")),
+            entry(
+                "a1",
+                Some("u1"),
+                "assistant",
+                tool_use("t1", "Read", json!({"file_path": "lib.rs"})),
+            ),
+            entry(
+                "r1",
+                Some("a1"),
+                "user",
+                tool_result("t1", "     1→fn a() {}\n     2→fn b() {}\n"),
+            ),
+            entry(
+                "a2",
+                Some("r1"),
+                "assistant",
+                tool_use(
+                    "t2",
+                    "Edit",
+                    json!({"file_path": "lib.rs", "old_string": "fn b() {}", "new_string": "fn c() {}"}),
+                ),
+            ),
+            entry(
+                "a3",
+                Some("a2"),
+                "assistant",
+                tool_use(
+                    "t3",
+                    "Edit",
+                    json!({"file_path": "main.rs", "old_string": "x", "new_string": "y"}),
+                ),
+            ),
+            entry(
+                "a4",
+                Some("a3"),
+                "assistant",
+                tool_use(
+                    "t4",
+                    "Write",
+                    json!({"file_path": "lib.rs", "content": "overwritten\n"}),
+                ),
+            ),
+        ];
+        MessageTreeBuilder::build_from_entries(&entries).unwrap()
+    }
+
+    #[test]
+    fn test_replay_intermediate_version() {
+        let tree = build_session();
+
+        let files = FileReplayer::replay_at(&tree, "a3").unwrap();
+        assert_eq!(files.len(), 2);
+
+        let lib = &files[0];
+        assert_eq!(lib.file_path, "lib.rs");
+        assert_eq!(lib.status, ReplayStatus::Reconstructed);
+        assert_eq!(lib.content.as_deref(), Some("fn a() {}\nfn c() {}\n"));
+        assert_eq!(lib.base_source, Some(SnapshotSource::Read));
+        assert_eq!(lib.edits_applied, 1);
+
+        // 未见过 main.rs 的内容，应报告缺口而不是猜测
+        let main = &files[1];
+        assert_eq!(main.status, ReplayStatus::Gap);
+        assert!(main.content.is_none());
+        assert_eq!(main.gap.as_ref().unwrap().message_uuid, "a3");
+
+        // 之后被覆盖写入
+        let files = FileReplayer::replay_at(&tree, "a4").unwrap();
+        assert_eq!(files[0].content.as_deref(), Some("overwritten\n"));
+        assert_eq!(files[0].base_source, Some(SnapshotSource::Write));
+
+        assert!(FileReplayer::replay_at(&tree, "missing").is_err());
+    }
+
+    #[test]
+    fn test_replay_ignores_sibling_branches() {
+        let entries = vec![
+            entry("u1", None, "user", json!("refactor")),
+            entry(
+                "a1",
+                Some("u1"),
+                "assistant",
+                tool_use(
+                    "t1",
+                    "Write",
+                    json!({"file_path": "lib.rs", "content": "v1\n"}),
+                ),
+            ),
+            // 被回退的分支：把 v1 改成 rewound
+            entry(
+                "a2",
+                Some("a1"),
+                "assistant",
+                tool_use(
+                    "t2",
+                    "Edit",
+                    json!({"file_path": "lib.rs", "old_string": "v1", "new_string": "rewound"}),
+                ),
+            ),
+            // 保留的分支：把 v1 改成 kept
+            entry(
+                "a3",
+                Some("a1"),
+                "assistant",
+                tool_use(
+                    "t3",
+                    "Edit",
+                    json!({"file_path": "lib.rs", "old_string": "v1", "new_string": "kept"}),
+                ),
+            ),
+        ];
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+
+        let files = FileReplayer::replay_at(&tree, "a3").unwrap();
+        assert_eq!(files[0].status, ReplayStatus::Reconstructed);
+        assert_eq!(files[0].content.as_deref(), Some("kept\n"));
+        assert_eq!(files[0].edits_applied, 1);
+
+        let files = FileReplayer::replay_at(&tree, "a2").unwrap();
+        assert_eq!(files[0].content.as_deref(), Some("rewound\n"));
+    }
+
+    #[test]
+    fn test_replay_rejects_truncated_read_output() {
+        let numbered = |count: usize| {
+            (1..=count)
+                .map(|n| format!("{:>6}→line {}", n, n))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        // 未指定 limit 的 Read 只返回前 2000 行，不能当作完整快照
+        let mut entries = vec![
+            entry("u1", None, "user", json!("review")),
+            entry(
+                "a1",
+                Some("u1"),
+                "assistant",
+                tool_use("t1", "Read", json!({"file_path": "big.rs"})),
+            ),
+            entry(
+                "r1",
+                Some("a1"),
+                "user",
+                tool_result("t1", &numbered(READ_DEFAULT_LINE_LIMIT)),
+            ),
+            entry(
+                "a2",
+                Some("r1"),
+                "assistant",
+                tool_use("t2", "Read", json!({"file_path": "small.rs"})),
+            ),
+            entry("r2", Some("a2"), "user", tool_result("t2", &numbered(3))),
+        ];
+        // totalLines 表明文件比输出更长
+        entries[4].data["toolUseResult"] =
+            json!({"file": {"filePath": "small.rs", "totalLines": 5}});
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+
+        let files = FileReplayer::replay_at(&tree, "r2").unwrap();
+        assert_eq!(files.len(), 2);
+        for file in &files {
+            assert_eq!(file.status, ReplayStatus::Gap);
+            assert!(file.content.is_none());
+        }
+        assert_eq!(files[0].gap.as_ref().unwrap().message_uuid, "r1");
+        assert_eq!(files[1].gap.as_ref().unwrap().message_uuid, "r2");
+
+        // totalLines 与输出行数一致时接受
+        entries[4].data["toolUseResult"]["file"]["totalLines"] = json!(3);
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        let files = FileReplayer::replay_at(&tree, "r2").unwrap();
+        assert_eq!(files[1].status, ReplayStatus::Reconstructed);
+        assert_eq!(
+            files[1].content.as_deref(),
+            Some("line 1\nline 2\nline 3\n")
+        );
+
+        // 超长行可能被截断
+        assert!(!FileReplayer::is_complete_text_read(
+            &format!("{}\n", "x".repeat(READ_MAX_LINE_CHARS)),
+            None
+        ));
+        assert!(FileReplayer::is_complete_text_read("a\nb\n", None));
+    }
+
+    #[test]
+    fn test_strip_line_numbers() {
+        let text = "     1→a\n     2→\n     3→c\n\n<system-reminder>note</system-reminder>";
+        assert_eq!(
+            FileReplayer::strip_line_numbers(text).as_deref(),
+            Some("a\n\nc\n")
+        );
+        assert!(FileReplayer::strip_line_numbers("     2→b").is_none());
+        assert!(FileReplayer::strip_line_numbers("plain text").is_none());
+    }
+}
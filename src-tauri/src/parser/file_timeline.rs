@@ -0,0 +1,468 @@
+//! 文件变更时间线模块
+//!
+//! 遍历消息树，收集 Write/Edit/MultiEdit 工具调用的输入，按文件生成有序的
+//! unified diff，并记录引起每次变更的消息 UUID 和时间戳。
+//!
+//! 如果某个文件此前被完整写入过（Write），后续的 Edit 会在已知内容上重放，
+//! 生成带真实行号的整文件 diff；否则只能生成被替换片段的 diff。
+
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+use std::collections::HashMap;
+
+use super::tree::{ConversationTree, MessageNode};
+
+/// unified diff 的上下文行数
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// 文件操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileOperation {
+    /// 整文件写入
+    Write,
+    /// 单处替换
+    Edit,
+    /// 多处替换
+    MultiEdit,
+}
+
+/// 单处字符串替换
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StringEdit {
+    /// 被替换的原始字符串
+    pub old_string: String,
+
+    /// 替换后的字符串
+    pub new_string: String,
+
+    /// 是否替换所有匹配
+    pub replace_all: bool,
+}
+
+/// 从工具调用中解析出的文件操作
+#[derive(Debug, Clone)]
+pub struct ToolFileOperation {
+    /// 目标文件路径
+    pub file_path: String,
+
+    /// 操作类型
+    pub operation: FileOperation,
+
+    /// 引起变更的消息 UUID
+    pub message_uuid: String,
+
+    /// 消息时间戳（RFC3339）
+    pub timestamp: Option<String>,
+
+    /// tool_use 块的 ID
+    pub tool_use_id: Option<String>,
+
+    /// 工具调用是否返回错误（None 表示没有找到对应的结果）
+    pub is_error: Option<bool>,
+
+    /// Write 操作写入的完整内容
+    pub content: Option<String>,
+
+    /// Edit/MultiEdit 操作的替换列表
+    pub edits: Vec<StringEdit>,
+}
+
+impl ToolFileOperation {
+    /// 在已知的文件内容上应用此操作
+    ///
+    /// # 返回
+    /// 返回操作后的完整内容；Edit 的 `old_string` 找不到时返回 None
+    pub fn apply(&self, before: Option<&str>) -> Option<String> {
+        if let Some(ref content) = self.content {
+            return Some(content.clone());
+        }
+
+        let mut text = before?.to_string();
+        for edit in &self.edits {
+            if !text.contains(&edit.old_string) {
+                return None;
+            }
+            text = if edit.replace_all {
+                text.replace(&edit.old_string, &edit.new_string)
+            } else {
+                text.replacen(&edit.old_string, &edit.new_string, 1)
+            };
+        }
+        Some(text)
+    }
+}
+
+/// 单次文件变更
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChange {
+    /// 操作类型
+    pub operation: FileOperation,
+
+    /// 引起变更的消息 UUID
+    pub message_uuid: String,
+
+    /// 消息时间戳（RFC3339）
+    pub timestamp: Option<String>,
+
+    /// tool_use 块的 ID
+    pub tool_use_id: Option<String>,
+
+    /// 工具调用是否返回错误（出错的调用不会改变文件）
+    pub is_error: Option<bool>,
+
+    /// unified diff 文本
+    pub diff: String,
+
+    /// 新增行数
+    pub lines_added: usize,
+
+    /// 删除行数
+    pub lines_removed: usize,
+
+    /// 变更前的文件内容是否已知
+    ///
+    /// 为 true 时 diff 基于完整文件，行号可信；
+    /// 为 false 时 diff 只反映被替换的片段（Write 则视为从空文件写入）
+    pub base_known: bool,
+}
+
+/// 单个文件的变更时间线
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTimeline {
+    /// 文件路径
+    pub file_path: String,
+
+    /// 按时间排序的变更列表
+    pub changes: Vec<FileChange>,
+}
+
+/// 文件时间线构建器
+pub struct FileTimelineBuilder;
+
+impl FileTimelineBuilder {
+    /// 构建会话中所有文件的变更时间线
+    ///
+    /// # 返回
+    /// 按文件路径排序的时间线列表
+    pub fn build(tree: &ConversationTree) -> Vec<FileTimeline> {
+        let mut known: HashMap<String, String> = HashMap::new();
+        let mut timelines: HashMap<String, Vec<FileChange>> = HashMap::new();
+
+        for op in Self::collect_operations(tree) {
+            let before = known.get(&op.file_path).cloned();
+            let change = Self::diff_operation(&op, before.as_deref());
+
+            // 出错的调用不会修改文件，只记录不更新状态
+            if op.is_error != Some(true) {
+                match op.apply(before.as_deref()) {
+                    Some(after) => {
+                        known.insert(op.file_path.clone(), after);
+                    }
+                    None => {
+                        known.remove(&op.file_path);
+                    }
+                }
+            }
+
+            timelines.entry(op.file_path).or_default().push(change);
+        }
+
+        let mut result: Vec<FileTimeline> = timelines
+            .into_iter()
+            .map(|(file_path, changes)| FileTimeline { file_path, changes })
+            .collect();
+        result.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        result
+    }
+
+    /// 按时间顺序收集消息树中的所有文件操作
+    pub fn collect_operations(tree: &ConversationTree) -> Vec<ToolFileOperation> {
+        Self::ordered_nodes(tree)
+            .into_iter()
+            .flat_map(Self::node_operations)
+            .collect()
+    }
+
+    /// 按时间顺序排列消息树中的所有节点
+    ///
+    /// 先按树的先序遍历排序，再按时间戳稳定排序；
+    /// 缺少时间戳的消息沿用前一条消息的时间
+    pub fn ordered_nodes(tree: &ConversationTree) -> Vec<&MessageNode> {
+        let mut nodes: Vec<(Option<DateTime<FixedOffset>>, &MessageNode)> = Vec::new();
+        let mut last_time = None;
+
+        let mut stack: Vec<&MessageNode> = tree.roots.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            if let Some(time) = node
+                .message_data
+                .get("timestamp")
+                .and_then(|v| v.as_str())
+                .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+            {
+                last_time = Some(time);
+            }
+            nodes.push((last_time, node));
+            stack.extend(node.children.iter().rev());
+        }
+
+        nodes.sort_by_key(|(time, _)| *time);
+        nodes.into_iter().map(|(_, node)| node).collect()
+    }
+
+    /// 解析单个节点中的文件操作
+    pub fn node_operations(node: &MessageNode) -> Vec<ToolFileOperation> {
+        let timestamp = node
+            .message_data
+            .get("timestamp")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        node.metadata
+            .iter()
+            .flat_map(|metadata| metadata.tool_calls.iter())
+            .filter_map(|call| {
+                Self::parse_tool_call(
+                    &call.name,
+                    &call.input,
+                    &node.id,
+                    timestamp.clone(),
+                    call.id.clone(),
+                    call.is_error,
+                )
+            })
+            .collect()
+    }
+
+    /// 将工具调用解析为文件操作
+    fn parse_tool_call(
+        name: &str,
+        input: &Value,
+        message_uuid: &str,
+        timestamp: Option<String>,
+        tool_use_id: Option<String>,
+        is_error: Option<bool>,
+    ) -> Option<ToolFileOperation> {
+        let file_path = input
+            .get("file_path")
+            .or_else(|| input.get("path"))
+            .and_then(|v| v.as_str())?
+            .to_string();
+
+        let (operation, content, edits) = match name {
+            "Write" | "write_file" => {
+                let content = input.get("content").and_then(|v| v.as_str())?;
+                (FileOperation::Write, Some(content.to_string()), Vec::new())
+            }
+            "Edit" | "edit_file" => (
+                FileOperation::Edit,
+                None,
+                vec![Self::parse_string_edit(input)?],
+            ),
+            "MultiEdit" => {
+                let edits = input
+                    .get("edits")
+                    .and_then(|v| v.as_array())?
+                    .iter()
+                    .filter_map(Self::parse_string_edit)
+                    .collect::<Vec<_>>();
+                if edits.is_empty() {
+                    return None;
+                }
+                (FileOperation::MultiEdit, None, edits)
+            }
+            _ => return None,
+        };
+
+        Some(ToolFileOperation {
+            file_path,
+            operation,
+            message_uuid: message_uuid.to_string(),
+            timestamp,
+            tool_use_id,
+            is_error,
+            content,
+            edits,
+        })
+    }
+
+    /// 解析单处替换（old_string/new_string/replace_all）
+    fn parse_string_edit(value: &Value) -> Option<StringEdit> {
+        Some(StringEdit {
+            old_string: value.get("old_string")?.as_str()?.to_string(),
+            new_string: value.get("new_string")?.as_str()?.to_string(),
+            replace_all: value
+                .get("replace_all")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        })
+    }
+
+    /// 生成单次操作的 diff
+    fn diff_operation(op: &ToolFileOperation, before: Option<&str>) -> FileChange {
+        let full_diff = before.and_then(|before| {
+            op.apply(Some(before))
+                .map(|after| Self::unified_diff(&op.file_path, before, &after))
+        });
+
+        let (base_known, (diff, lines_added, lines_removed)) = match full_diff {
+            Some(diff) => (true, diff),
+            None => match op.content {
+                // 变更前内容未知的 Write：视为从空文件写入
+                Some(ref content) => (false, Self::unified_diff(&op.file_path, "", content)),
+                // 变更前内容未知的 Edit：只能展示被替换的片段
+                None => {
+                    let mut diff = String::new();
+                    let mut lines_added = 0;
+                    let mut lines_removed = 0;
+                    for edit in &op.edits {
+                        let (hunk, added, removed) =
+                            Self::unified_diff(&op.file_path, &edit.old_string, &edit.new_string);
+                        if diff.is_empty() {
+                            diff = hunk;
+                        } else {
+                            // 多处替换只保留第一个文件头
+                            diff.push_str(
+                                &hunk
+                                    .lines()
+                                    .skip_while(|line| !line.starts_with("@@"))
+                                    .map(|line| format!("{}\n", line))
+                                    .collect::<String>(),
+                            );
+                        }
+                        lines_added += added;
+                        lines_removed += removed;
+                    }
+                    (false, (diff, lines_added, lines_removed))
+                }
+            },
+        };
+
+        FileChange {
+            operation: op.operation,
+            message_uuid: op.message_uuid.clone(),
+            timestamp: op.timestamp.clone(),
+            tool_use_id: op.tool_use_id.clone(),
+            is_error: op.is_error,
+            diff,
+            lines_added,
+            lines_removed,
+            base_known,
+        }
+    }
+
+    /// 生成 unified diff 文本并统计增删行数
+    fn unified_diff(file_path: &str, before: &str, after: &str) -> (String, usize, usize) {
+        let diff = TextDiff::from_lines(before, after);
+
+        let mut lines_added = 0;
+        let mut lines_removed = 0;
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => lines_added += 1,
+                ChangeTag::Delete => lines_removed += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+
+        let text = diff
+            .unified_diff()
+            .context_radius(DIFF_CONTEXT_LINES)
+            .header(&format!("a/{}", file_path), &format!("b/{}", file_path))
+            .to_string();
+
+        (text, lines_added, lines_removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::jsonl::JsonlEntry;
+    use crate::parser::tree::MessageTreeBuilder;
+    use serde_json::json;
+
+    fn tool_entry(
+        uuid: &str,
+        parent: &str,
+        timestamp: &str,
+        name: &str,
+        input: Value,
+    ) -> JsonlEntry {
+        JsonlEntry::new(
+            0,
+            0,
+            json!({
+                "uuid": uuid,
+                "parentUuid": parent,
+                "role": "assistant",
+                "type": "message",
+                "timestamp": timestamp,
+                "content": [{"type": "tool_use", "id": format!("toolu_{}", uuid), "name": name, "input": input}]
+            }),
+        )
+    }
+
+    #[test]
+    fn test_write_then_edit_produces_full_file_diffs() {
+        let entries = vec![
+            JsonlEntry::new(
+                0,
+                0,
+                json!({"uuid": "u1", "role": "user", "type": "message", "content": "go"}),
+            ),
+            tool_entry(
+                "a1",
+                "u1",
+                "2025-01-01T10:00:00Z",
+                "Write",
+                json!({"file_path": "src/foo.rs", "content": "fn a() {}\nfn b() {}\n"}),
+            ),
+            tool_entry(
+                "a2",
+                "a1",
+                "2025-01-01T10:01:00Z",
+                "Edit",
+                json!({"file_path": "src/foo.rs", "old_string": "fn b() {}", "new_string": "fn c() {}"}),
+            ),
+            tool_entry(
+                "a3",
+                "a2",
+                "2025-01-01T10:02:00Z",
+                "MultiEdit",
+                json!({"file_path": "src/bar.rs", "edits": [
+                    {"old_string": "x", "new_string": "y"},
+                    {"old_string": "1", "new_string": "2"}
+                ]}),
+            ),
+        ];
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+
+        let timelines = FileTimelineBuilder::build(&tree);
+        assert_eq!(timelines.len(), 2);
+
+        let bar = &timelines[0];
+        assert_eq!(bar.file_path, "src/bar.rs");
+        assert_eq!(bar.changes[0].operation, FileOperation::MultiEdit);
+        assert!(!bar.changes[0].base_known);
+        assert_eq!(bar.changes[0].lines_added, 2);
+
+        let foo = &timelines[1];
+        assert_eq!(foo.changes.len(), 2);
+        assert_eq!(foo.changes[0].message_uuid, "a1");
+        assert_eq!(foo.changes[0].lines_added, 2);
+        assert_eq!(foo.changes[1].message_uuid, "a2");
+        assert!(foo.changes[1].base_known);
+        assert!(foo.changes[1].diff.contains("-fn b() {}"));
+        assert!(foo.changes[1].diff.contains("+fn c() {}"));
+        assert!(foo.changes[1].diff.contains(" fn a() {}"));
+        assert_eq!(
+            foo.changes[1].timestamp.as_deref(),
+            Some("2025-01-01T10:01:00Z")
+        );
+    }
+}
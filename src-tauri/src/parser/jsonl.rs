@@ -4,10 +4,17 @@
 //! 支持 Windows FileShare 模式，允许在文件被占用时读取。
 
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// 损坏行预览保留的最大字符数
+const DIAGNOSTIC_PREVIEW_CHARS: usize = 100;
+
+/// 偏移量指纹覆盖的字节数（偏移量之前的最后若干字节）
+const FINGERPRINT_BYTES: u64 = 64;
 
 /// JSONL 条目
 ///
@@ -89,6 +96,67 @@ impl JsonlEntry {
     }
 }
 
+/// 损坏行诊断信息
+///
+/// 记录无法解析为 JSON 的行，用于发现被截断或损坏的会话文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineDiagnostic {
+    /// 行在文件中的字节偏移量
+    pub offset: u64,
+    /// 行字节长度（含换行符）
+    pub length: usize,
+    /// 行号（从 1 开始；增量解析时无法确定，为 None）
+    pub line_number: Option<usize>,
+    /// serde_json 错误信息
+    pub error: String,
+    /// 行内容预览（前 100 个字符）
+    pub preview: String,
+    /// 是否为文件末尾未以换行结束的行（通常是写入中断导致的截断）
+    pub truncated: bool,
+}
+
+/// 带诊断信息的解析结果
+#[derive(Debug, Clone)]
+pub struct JsonlParseResult {
+    /// 成功解析的条目
+    pub entries: Vec<JsonlEntry>,
+    /// 无法解析的行
+    pub diagnostics: Vec<LineDiagnostic>,
+}
+
+/// 修复结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairReport {
+    /// 修复后文件路径
+    pub output_path: String,
+    /// 保留的行数
+    pub kept_lines: usize,
+    /// 丢弃的损坏行
+    pub dropped: Vec<LineDiagnostic>,
+}
+
+/// 增量解析结果
+///
+/// 由 [`JsonlParser::parse_incremental`] 返回，仅包含上次解析之后追加的条目
+#[derive(Debug, Clone)]
+pub struct IncrementalParseResult {
+    /// 新追加的条目
+    pub entries: Vec<JsonlEntry>,
+    /// 可恢复解析的字节偏移量（不含缓冲区中未完成的行）
+    ///
+    /// 无状态调用方可保存该值，下次通过 [`JsonlParser::with_checkpoint`] 继续解析
+    pub end_offset: u64,
+    /// `end_offset` 之前若干字节的指纹，与 `end_offset` 一起保存，
+    /// 用于发现被重写为相同或更大长度的文件
+    pub end_fingerprint: u64,
+    /// 文件是否被截断或替换（此时解析从头开始，调用方应重建状态）
+    pub reset: bool,
+    /// 本次解析中无法解析的完整行
+    pub diagnostics: Vec<LineDiagnostic>,
+}
+
 /// JSONL 解析器
 ///
 /// 支持流式读取和增量解析 JSONL 文件
@@ -96,19 +164,28 @@ impl JsonlEntry {
 pub struct JsonlParser {
     /// 文件路径
     file_path: PathBuf,
-    /// 未完成行的缓冲区
+    /// 增量解析中未完成行的缓冲区（全量解析不使用）
     buffer: String,
+    /// 已消费的字节偏移量（增量解析使用，包含缓冲区中的字节）
+    offset: u64,
+    /// 已完整解析的偏移量之前若干字节的指纹（未知时只按文件长度检测重写）
+    fingerprint: Option<u64>,
+    /// 最近一次解析收集到的损坏行
+    diagnostics: Vec<LineDiagnostic>,
 }
 
 impl JsonlParser {
     /// 创建新的 JSONL 解析器
     ///
+    /// 原文件已被压缩归档时透明读取解压后的缓存文件
+    ///
     /// # 参数
     /// - `path`: JSONL 文件路径
     ///
     /// # 返回
     /// 返回解析器实例或错误
     pub fn new(path: PathBuf) -> Result<Self> {
+        let path = crate::session_archive::resolve_session_path(&path)?;
         if !path.exists() {
             return Err(anyhow::anyhow!("文件不存在: {:?}", path));
         }
@@ -116,9 +193,172 @@ impl JsonlParser {
         Ok(Self {
             file_path: path,
             buffer: String::new(),
+            offset: 0,
+            fingerprint: None,
+            diagnostics: Vec::new(),
+        })
+    }
+
+    /// 从指定偏移量创建解析器
+    ///
+    /// 用于恢复增量解析：调用方保存上次的 `end_offset`，下次从该位置继续读取
+    ///
+    /// # 参数
+    /// - `path`: JSONL 文件路径
+    /// - `offset`: 已消费的字节偏移量（必须位于行首）
+    pub fn with_offset(path: PathBuf, offset: u64) -> Result<Self> {
+        let mut parser = Self::new(path)?;
+        parser.offset = offset;
+        Ok(parser)
+    }
+
+    /// 从保存的偏移量和指纹创建解析器
+    ///
+    /// 与 [`JsonlParser::with_offset`] 相同，但偏移量之前的内容与保存时不一致
+    /// （文件被重写为相同或更大的长度）时也会从头解析并设置 `reset`
+    ///
+    /// # 参数
+    /// - `path`: JSONL 文件路径
+    /// - `offset`: 上次解析返回的 `end_offset`
+    /// - `fingerprint`: 上次解析返回的 `end_fingerprint`
+    pub fn with_checkpoint(path: PathBuf, offset: u64, fingerprint: u64) -> Result<Self> {
+        let mut parser = Self::with_offset(path, offset)?;
+        parser.fingerprint = Some(fingerprint);
+        Ok(parser)
+    }
+
+    /// 获取已完整解析的字节偏移量（不含缓冲区中未完成的行）
+    pub fn consumed_offset(&self) -> u64 {
+        self.offset - self.buffer.len() as u64
+    }
+
+    /// 解析所有条目并返回损坏行诊断
+    pub fn parse_with_diagnostics(&mut self) -> Result<JsonlParseResult> {
+        let entries = self.parse_all()?;
+        Ok(JsonlParseResult {
+            entries,
+            diagnostics: self.diagnostics.clone(),
         })
     }
 
+    /// 写出去除损坏行后的副本
+    ///
+    /// 原文件保持不变；有效行按原样（去除首尾空白）写入 `output`，
+    /// 空行和无法解析的行被丢弃。内容先写入临时文件，完成后再重命名为 `output`。
+    ///
+    /// # 参数
+    /// - `output`: 修复后文件路径（不能已存在，也不能指向原文件）
+    pub fn write_repaired_copy(&mut self, output: &Path) -> Result<RepairReport> {
+        if std::fs::symlink_metadata(output).is_ok() {
+            anyhow::bail!("修复输出文件已存在: {:?}", output);
+        }
+        let source = self
+            .file_path
+            .canonicalize()
+            .context(format!("无法解析文件路径: {:?}", self.file_path))?;
+        let file_name = output
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("无效的修复输出路径: {:?}", output))?;
+        let parent = match output.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let parent = parent
+            .canonicalize()
+            .context(format!("输出目录不存在: {:?}", parent))?;
+        if parent.join(file_name) == source {
+            anyhow::bail!("修复输出路径不能与原文件相同: {:?}", output);
+        }
+
+        let mut temp_name = file_name.to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = parent.join(temp_name);
+
+        let result = self
+            .write_repaired_lines(&temp_path)
+            .and_then(|(kept_lines, dropped)| {
+                std::fs::rename(&temp_path, output)
+                    .context(format!("无法写入文件: {:?}", output))?;
+                Ok(RepairReport {
+                    output_path: output.to_string_lossy().to_string(),
+                    kept_lines,
+                    dropped,
+                })
+            });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    /// 将有效行写入新建的临时文件，返回保留的行数和丢弃的损坏行
+    fn write_repaired_lines(&self, temp_path: &Path) -> Result<(usize, Vec<LineDiagnostic>)> {
+        let file = self.open_file_shared()?;
+        let mut reader = BufReader::new(file);
+        let temp_file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(temp_path)
+            .context(format!("无法创建文件: {:?}", temp_path))?;
+        let mut writer = BufWriter::new(temp_file);
+
+        let mut dropped = Vec::new();
+        let mut kept_lines = 0;
+        let mut line_bytes: Vec<u8> = Vec::new();
+        let mut offset = 0u64;
+        let mut line_number = 0;
+
+        loop {
+            line_bytes.clear();
+            let bytes_read = reader
+                .read_until(b'\n', &mut line_bytes)
+                .context("读取文件失败")?;
+            if bytes_read == 0 {
+                break;
+            }
+            line_number += 1;
+
+            let truncated = line_bytes.last() != Some(&b'\n');
+            match std::str::from_utf8(&line_bytes) {
+                Ok(text) => {
+                    let line = text.trim();
+                    if !line.is_empty() {
+                        match serde_json::from_str::<Value>(line) {
+                            Ok(_) => {
+                                writer.write_all(line.as_bytes())?;
+                                writer.write_all(b"\n")?;
+                                kept_lines += 1;
+                            }
+                            Err(e) => dropped.push(Self::diagnose(
+                                line,
+                                offset,
+                                bytes_read,
+                                Some(line_number),
+                                &e,
+                                truncated,
+                            )),
+                        }
+                    }
+                }
+                Err(e) => dropped.push(Self::diagnose_invalid_utf8(
+                    &line_bytes,
+                    offset,
+                    Some(line_number),
+                    &e,
+                    truncated,
+                )),
+            }
+
+            offset += bytes_read as u64;
+        }
+
+        writer
+            .flush()
+            .context(format!("写入文件失败: {:?}", temp_path))?;
+
+        Ok((kept_lines, dropped))
+    }
+
     /// 解析所有条目
     ///
     /// 读取整个文件并解析所有 JSONL 条目
@@ -130,14 +370,10 @@ impl JsonlParser {
         let mut reader = BufReader::new(file);
         let mut entries = Vec::new();
 
-        let result = self.parse_reader(&mut reader, |entry| {
+        self.parse_reader(&mut reader, |entry| {
             entries.push(entry.clone());
-        });
-
-        // 清空缓冲区，准备下次解析
-        self.buffer.clear();
+        })?;
 
-        result?;
         Ok(entries)
     }
 
@@ -157,12 +393,139 @@ impl JsonlParser {
         let file = self.open_file_shared()?;
         let mut reader = BufReader::new(file);
 
-        let result = self.parse_reader(&mut reader, callback);
+        self.parse_reader(&mut reader, callback)
+    }
+
+    /// 增量解析（只返回新追加的条目）
+    ///
+    /// 从上次消费的偏移量继续读取文件，适用于正在被 Claude Code 写入的会话文件：
+    /// - 完整的行被解析并返回，偏移量随之前移
+    /// - 末尾未以换行结束且无法解析的行（写入中）保存在缓冲区，下次调用时与新数据拼接
+    /// - 如果文件长度小于已消费的偏移量，或偏移量之前的内容指纹与上次不同
+    ///   （被截断、替换或重写），从头重新解析并设置 `reset`
+    ///
+    /// # 返回
+    /// 返回增量解析结果或错误
+    pub fn parse_incremental(&mut self) -> Result<IncrementalParseResult> {
+        let mut file = self.open_file_shared()?;
+        let file_len = file
+            .metadata()
+            .context(format!("无法读取文件元数据: {:?}", self.file_path))?
+            .len();
+
+        let consumed = self.consumed_offset();
+        let rewritten = file_len < self.offset
+            || match self.fingerprint {
+                Some(expected) => {
+                    consumed > 0 && Self::fingerprint_at(&mut file, consumed)? != expected
+                }
+                None => false,
+            };
 
-        // 清空缓冲区
-        self.buffer.clear();
+        let mut reset = false;
+        if rewritten {
+            eprintln!(
+                "警告: 文件已被截断或重写（长度 {}，已消费偏移量 {}），从头重新解析: {:?}",
+                file_len, self.offset, self.file_path
+            );
+            self.offset = 0;
+            self.buffer.clear();
+            reset = true;
+        }
 
-        result
+        self.diagnostics.clear();
+        file.seek(SeekFrom::Start(self.offset))
+            .context(format!("无法跳转到偏移量 {}", self.offset))?;
+
+        let mut reader = BufReader::new(file);
+        let mut entries = Vec::new();
+        let mut line_bytes: Vec<u8> = Vec::new();
+
+        loop {
+            line_bytes.clear();
+            let bytes_read = reader
+                .read_until(b'\n', &mut line_bytes)
+                .context("读取文件失败")?;
+
+            if bytes_read == 0 {
+                break;
+            }
+
+            let terminated = line_bytes.last() == Some(&b'\n');
+
+            // 写入中的行可能在多字节字符中间被截断，此时不消费这些字节
+            let chunk = match std::str::from_utf8(&line_bytes) {
+                Ok(text) => text,
+                Err(_) if !terminated => break,
+                Err(e) => {
+                    // 与缓冲区中的前半行一起记为损坏行
+                    let mut bytes = std::mem::take(&mut self.buffer).into_bytes();
+                    let entry_offset = self.offset - bytes.len() as u64;
+                    bytes.extend_from_slice(&line_bytes);
+                    self.diagnostics.push(Self::diagnose_invalid_utf8(
+                        &bytes,
+                        entry_offset,
+                        None,
+                        &e,
+                        false,
+                    ));
+                    self.offset += bytes_read as u64;
+                    continue;
+                }
+            };
+
+            // 缓冲区中的字节已计入 offset，条目起点需回退
+            let entry_offset = self.offset - self.buffer.len() as u64;
+            self.buffer.push_str(chunk);
+            self.offset += bytes_read as u64;
+
+            let line = self.buffer.trim().to_string();
+            if line.is_empty() {
+                self.buffer.clear();
+                continue;
+            }
+
+            if !terminated && serde_json::from_str::<Value>(&line).is_err() {
+                // 末尾的未完成行，保留在缓冲区等待后续写入
+                break;
+            }
+
+            let line_length = self.buffer.len();
+            self.buffer.clear();
+
+            match Self::parse_line(&line, entry_offset, line_length, None, !terminated) {
+                Ok(entry) => entries.push(entry),
+                Err(diagnostic) => self.diagnostics.push(diagnostic),
+            }
+        }
+
+        let end_offset = self.consumed_offset();
+        let end_fingerprint = Self::fingerprint_at(reader.get_mut(), end_offset)?;
+        self.fingerprint = Some(end_fingerprint);
+
+        Ok(IncrementalParseResult {
+            entries,
+            end_offset,
+            end_fingerprint,
+            reset,
+            diagnostics: self.diagnostics.clone(),
+        })
+    }
+
+    /// 计算偏移量之前最后 `FINGERPRINT_BYTES` 个字节的指纹（FNV-1a）
+    ///
+    /// 指纹会被持久化，因此使用固定的哈希算法而不是 `DefaultHasher`
+    fn fingerprint_at(file: &mut File, offset: u64) -> Result<u64> {
+        let start = offset.saturating_sub(FINGERPRINT_BYTES);
+        file.seek(SeekFrom::Start(start))
+            .context(format!("无法跳转到偏移量 {}", start))?;
+        let mut bytes = vec![0u8; (offset - start) as usize];
+        file.read_exact(&mut bytes)
+            .context(format!("无法读取偏移量 {} 之前的内容", offset))?;
+
+        Ok(bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        }))
     }
 
     /// 按偏移量读取单条消息
@@ -196,7 +559,7 @@ impl JsonlParser {
 
     /// 从文件中提取消息内容
     ///
-    /// 返回文件中原始消息的数量（不包括损坏的条目）
+    /// 返回文件中原始消息的数量（不包括损坏的条目，损坏行可通过 `diagnostics` 获取）
     pub fn count_entries(&mut self) -> Result<usize> {
         let file = self.open_file_shared()?;
         let mut reader = BufReader::new(file);
@@ -206,9 +569,6 @@ impl JsonlParser {
             count += 1;
         })?;
 
-        // 清空缓冲区
-        self.buffer.clear();
-
         Ok(count)
     }
 
@@ -237,70 +597,68 @@ impl JsonlParser {
 
     /// 解析 Reader 内容
     ///
-    /// 核心解析逻辑，处理行缓冲和 JSON 解析
+    /// 核心解析逻辑，逐行解析 JSON。
+    /// 全量解析总是从文件开头读取，不使用也不修改增量解析的偏移量和未完成行缓冲区
     fn parse_reader<F>(&mut self, reader: &mut BufReader<File>, mut callback: F) -> Result<()>
     where
         F: FnMut(&JsonlEntry),
     {
-        let mut line_buffer = String::new();
+        let mut line_bytes: Vec<u8> = Vec::new();
         let mut current_offset = 0u64;
+        let mut line_number = 0;
+        self.diagnostics.clear();
 
         loop {
-            line_buffer.clear();
+            line_bytes.clear();
 
-            // 读取一行
-            let bytes_read = reader.read_line(&mut line_buffer).context("读取文件失败")?;
+            // 按原始字节读取一行，无效的 UTF-8 不会中断整个解析
+            let bytes_read = reader
+                .read_until(b'\n', &mut line_bytes)
+                .context("读取文件失败")?;
+            line_number += 1;
 
             // 文件结束
             if bytes_read == 0 {
-                // 处理缓冲区中剩余的数据
-                if !self.buffer.trim().is_empty() {
-                    eprintln!(
-                        "警告: 文件末尾有未完成的数据，已丢弃: {} 字节",
-                        self.buffer.len()
-                    );
-                }
                 break;
             }
 
             let line_length = bytes_read;
-            let line = if self.buffer.is_empty() {
-                // 无缓冲区数据，直接使用当前行
-                line_buffer.trim().to_string()
-            } else {
-                // 有缓冲区数据，拼接后处理
-                self.buffer.push_str(&line_buffer);
-                let combined = self.buffer.trim().to_string();
-                self.buffer.clear();
-                combined
+            let truncated = line_bytes.last() != Some(&b'\n');
+
+            // 写入中断可能截断多字节字符，记录为损坏行后继续解析
+            let line_buffer = match std::str::from_utf8(&line_bytes) {
+                Ok(text) => text,
+                Err(e) => {
+                    self.diagnostics.push(Self::diagnose_invalid_utf8(
+                        &line_bytes,
+                        current_offset,
+                        Some(line_number),
+                        &e,
+                        truncated,
+                    ));
+                    current_offset += line_length as u64;
+                    continue;
+                }
             };
 
+            let line = line_buffer.trim().to_string();
+
             // 跳过空行
             if line.is_empty() {
                 current_offset += line_length as u64;
                 continue;
             }
 
-            // 尝试解析 JSON
-            match serde_json::from_str::<Value>(&line) {
-                Ok(json_data) => {
-                    // 解析成功，创建条目
-                    let entry = JsonlEntry::new(current_offset, line_length, json_data);
-                    callback(&entry);
-                }
-                Err(e) => {
-                    // 解析失败，记录日志并跳过
-                    eprintln!(
-                        "警告: 偏移量 {} 处的 JSON 解析失败: {}，内容: {}",
-                        current_offset,
-                        e,
-                        if line.len() > 100 {
-                            &line[..100]
-                        } else {
-                            &line
-                        }
-                    );
-                }
+            // 尝试解析 JSON，失败时记录诊断信息并跳过该行
+            match Self::parse_line(
+                &line,
+                current_offset,
+                line_length,
+                Some(line_number),
+                truncated,
+            ) {
+                Ok(entry) => callback(&entry),
+                Err(diagnostic) => self.diagnostics.push(diagnostic),
             }
 
             current_offset += line_length as u64;
@@ -308,6 +666,78 @@ impl JsonlParser {
 
         Ok(())
     }
+
+    /// 解析单行 JSON
+    ///
+    /// 解析失败时记录日志并返回诊断信息（调用方跳过该行）
+    fn parse_line(
+        line: &str,
+        offset: u64,
+        length: usize,
+        line_number: Option<usize>,
+        truncated: bool,
+    ) -> std::result::Result<JsonlEntry, LineDiagnostic> {
+        match serde_json::from_str::<Value>(line) {
+            Ok(json_data) => Ok(JsonlEntry::new(offset, length, json_data)),
+            Err(e) => {
+                let diagnostic = Self::diagnose(line, offset, length, line_number, &e, truncated);
+                eprintln!(
+                    "警告: 偏移量 {} 处的 JSON 解析失败: {}，内容: {}",
+                    offset, e, diagnostic.preview
+                );
+                Err(diagnostic)
+            }
+        }
+    }
+
+    /// 构建无效 UTF-8 行的诊断信息（预览中无效字节替换为 U+FFFD）
+    fn diagnose_invalid_utf8(
+        bytes: &[u8],
+        offset: u64,
+        line_number: Option<usize>,
+        error: &std::str::Utf8Error,
+        truncated: bool,
+    ) -> LineDiagnostic {
+        let text = String::from_utf8_lossy(bytes);
+        let diagnostic = Self::diagnose(
+            text.trim(),
+            offset,
+            bytes.len(),
+            line_number,
+            format!("无效的 UTF-8: {}", error),
+            truncated,
+        );
+        eprintln!(
+            "警告: 偏移量 {} 处的数据不是有效的 UTF-8: {}，内容: {}",
+            offset, error, diagnostic.preview
+        );
+        diagnostic
+    }
+
+    /// 构建损坏行诊断信息
+    fn diagnose(
+        line: &str,
+        offset: u64,
+        length: usize,
+        line_number: Option<usize>,
+        error: impl std::fmt::Display,
+        truncated: bool,
+    ) -> LineDiagnostic {
+        let preview_end = line
+            .char_indices()
+            .nth(DIAGNOSTIC_PREVIEW_CHARS)
+            .map(|(idx, _)| idx)
+            .unwrap_or(line.len());
+
+        LineDiagnostic {
+            offset,
+            length,
+            line_number,
+            error: error.to_string(),
+            preview: line[..preview_end].to_string(),
+            truncated,
+        }
+    }
 }
 
 // ========== 单元测试 ==========
@@ -389,6 +819,106 @@ not a json at all
         std::fs::remove_file(file_path).ok();
     }
 
+    #[test]
+    fn test_diagnostics_and_repair() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("damaged.jsonl");
+        std::fs::write(
+            &file_path,
+            "{\"type\": \"user\"}\n{broken}\n{\"type\": \"assistant\"}\n{\"type\": \"assis",
+        )
+        .unwrap();
+
+        let mut parser = JsonlParser::new(file_path.clone()).unwrap();
+        let result = parser.parse_with_diagnostics().unwrap();
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.diagnostics.len(), 2);
+
+        let broken = &result.diagnostics[0];
+        assert_eq!(broken.line_number, Some(2));
+        assert_eq!(broken.offset, 17);
+        assert_eq!(broken.preview, "{broken}");
+        assert!(!broken.truncated);
+        assert!(result.diagnostics[1].truncated);
+
+        assert_eq!(parser.count_entries().unwrap(), 2);
+
+        let repaired_path = dir.path().join("damaged.repaired.jsonl");
+        let report = parser.write_repaired_copy(&repaired_path).unwrap();
+        assert_eq!(report.kept_lines, 2);
+        assert_eq!(report.dropped.len(), 2);
+
+        let mut repaired = JsonlParser::new(repaired_path).unwrap();
+        assert_eq!(
+            repaired.parse_with_diagnostics().unwrap().diagnostics.len(),
+            0
+        );
+
+        // 不允许覆盖原文件或已存在的文件，包括经由 `..` 或符号链接指向原文件的路径
+        assert!(parser.write_repaired_copy(&file_path).is_err());
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let dotted = dir.path().join("sub").join("..").join("damaged.jsonl");
+        assert!(parser.write_repaired_copy(&dotted).is_err());
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link.jsonl");
+            std::os::unix::fs::symlink(&file_path, &link).unwrap();
+            assert!(parser.write_repaired_copy(&link).is_err());
+        }
+        assert!(parser
+            .write_repaired_copy(&dir.path().join("damaged.repaired.jsonl"))
+            .is_err());
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap().lines().count(),
+            4
+        );
+        assert!(!dir.path().join("damaged.repaired.jsonl.tmp").exists());
+    }
+
+    #[test]
+    fn test_invalid_utf8_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("utf8.jsonl");
+
+        // 第二行在“你”的三个字节中间被截断，末尾的行同样被截断
+        let mut content = b"{\"type\": \"user\"}\n".to_vec();
+        content.extend_from_slice(b"{\"text\": \"\xe4\xbd\n");
+        content.extend_from_slice(b"{\"type\": \"assistant\"}\n");
+        content.extend_from_slice(b"{\"text\": \"\xe4");
+        std::fs::write(&file_path, &content).unwrap();
+
+        let mut parser = JsonlParser::new(file_path.clone()).unwrap();
+        let result = parser.parse_with_diagnostics().unwrap();
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.diagnostics.len(), 2);
+
+        let broken = &result.diagnostics[0];
+        assert_eq!(broken.line_number, Some(2));
+        assert_eq!(broken.offset, 17);
+        assert_eq!(broken.length, 13);
+        assert!(broken.error.contains("UTF-8"));
+        assert_eq!(broken.preview, "{\"text\": \"\u{FFFD}");
+        assert!(!broken.truncated);
+        assert_eq!(result.diagnostics[1].line_number, Some(4));
+        assert!(result.diagnostics[1].truncated);
+
+        // 增量解析记录完整的损坏行，末尾写入中的行等待后续数据
+        let mut incremental = JsonlParser::new(file_path.clone()).unwrap();
+        let result = incremental.parse_incremental().unwrap();
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.diagnostics[0].offset, 17);
+        assert_eq!(result.diagnostics[0].line_number, None);
+        assert_eq!(result.end_offset, 52);
+
+        // 修复副本丢弃两条损坏行
+        let report = parser
+            .write_repaired_copy(&dir.path().join("utf8.repaired.jsonl"))
+            .unwrap();
+        assert_eq!(report.kept_lines, 2);
+        assert_eq!(report.dropped.len(), 2);
+    }
+
     #[test]
     fn test_parse_entry_at_offset() {
         let content = r#"{"type": "message", "role": "user"}
@@ -443,4 +973,171 @@ not a json at all
         // 清理
         std::fs::remove_file(file_path).ok();
     }
+
+    #[test]
+    fn test_parse_incremental_appended_lines() {
+        let file_path = std::env::temp_dir().join("test_session_incremental.jsonl");
+        std::fs::write(&file_path, "{\"uuid\": \"a\"}\n{\"uuid\": \"b\"}\n").unwrap();
+
+        let mut parser = JsonlParser::new(file_path.clone()).unwrap();
+        let first = parser.parse_incremental().unwrap();
+        assert_eq!(first.entries.len(), 2);
+        assert_eq!(first.entries[0].offset, 0);
+        assert!(!first.reset);
+
+        // 没有新数据时返回空列表
+        let empty = parser.parse_incremental().unwrap();
+        assert!(empty.entries.is_empty());
+        assert_eq!(empty.end_offset, first.end_offset);
+
+        // 追加一条完整行和一条写入中的行
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&file_path)
+                .unwrap();
+            file.write_all(b"{\"uuid\": \"c\"}\n{\"uuid\": ").unwrap();
+        }
+
+        let second = parser.parse_incremental().unwrap();
+        assert_eq!(second.entries.len(), 1);
+        assert_eq!(second.entries[0].data["uuid"], "c");
+        assert_eq!(second.entries[0].offset, first.end_offset);
+        let pending_offset = second.end_offset;
+
+        // 补全写入中的行
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&file_path)
+                .unwrap();
+            file.write_all(b"\"d\"}\n").unwrap();
+        }
+
+        let third = parser.parse_incremental().unwrap();
+        assert_eq!(third.entries.len(), 1);
+        assert_eq!(third.entries[0].data["uuid"], "d");
+        assert_eq!(third.entries[0].offset, pending_offset);
+
+        // 条目的偏移量和长度可用于随机读取
+        let value = parser
+            .parse_entry_at_offset(third.entries[0].offset, third.entries[0].length)
+            .unwrap();
+        assert_eq!(value["uuid"], "d");
+
+        std::fs::remove_file(file_path).ok();
+    }
+
+    #[test]
+    fn test_parse_incremental_resume_and_truncate() {
+        let file_path = std::env::temp_dir().join("test_session_incremental_resume.jsonl");
+        std::fs::write(&file_path, "{\"uuid\": \"a\"}\n{\"uuid\": \"b\"}\n").unwrap();
+
+        let mut parser = JsonlParser::new(file_path.clone()).unwrap();
+        let first = parser.parse_incremental().unwrap();
+
+        // 无状态恢复：从保存的偏移量继续
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&file_path)
+                .unwrap();
+            file.write_all(b"{\"uuid\": \"c\"}\n").unwrap();
+        }
+        let mut resumed = JsonlParser::with_offset(file_path.clone(), first.end_offset).unwrap();
+        let result = resumed.parse_incremental().unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].data["uuid"], "c");
+
+        // 文件被截断后从头解析
+        std::fs::write(&file_path, "{\"uuid\": \"x\"}\n").unwrap();
+        let result = resumed.parse_incremental().unwrap();
+        assert!(result.reset);
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].data["uuid"], "x");
+
+        std::fs::remove_file(file_path).ok();
+    }
+
+    #[test]
+    fn test_full_parse_keeps_incremental_state() {
+        let file_path = std::env::temp_dir().join("test_session_incremental_full_parse.jsonl");
+        std::fs::write(&file_path, "{\"uuid\": \"a\"}\n{\"uuid\": \"b").unwrap();
+
+        let mut parser = JsonlParser::new(file_path.clone()).unwrap();
+        let first = parser.parse_incremental().unwrap();
+        assert_eq!(first.entries.len(), 1);
+
+        // 全量解析不会拼接增量解析缓冲的未完成行
+        let entries = parser.parse_all().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data["uuid"], "a");
+        assert_eq!(parser.count_entries().unwrap(), 1);
+
+        // 增量解析仍从缓冲的未完成行继续
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&file_path)
+                .unwrap();
+            file.write_all(b"\"}\n").unwrap();
+        }
+        let result = parser.parse_incremental().unwrap();
+        assert!(!result.reset);
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].data["uuid"], "b");
+
+        std::fs::remove_file(file_path).ok();
+    }
+
+    #[test]
+    fn test_parse_incremental_detects_rewrite_without_shrinking() {
+        let file_path = std::env::temp_dir().join("test_session_incremental_rewrite.jsonl");
+        std::fs::write(&file_path, "{\"uuid\": \"a\"}\n").unwrap();
+
+        let mut parser = JsonlParser::new(file_path.clone()).unwrap();
+        let first = parser.parse_incremental().unwrap();
+        assert_eq!(first.entries.len(), 1);
+
+        // 同一实例：文件被重写为更长的内容，旧偏移落在行中间
+        std::fs::write(&file_path, "{\"uuid\": \"xyz\"}\n{\"uuid\": \"y\"}\n").unwrap();
+        let result = parser.parse_incremental().unwrap();
+        assert!(result.reset);
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.entries[0].data["uuid"], "xyz");
+
+        // 无状态恢复：文件被重写为相同长度的内容
+        let mut resumed = JsonlParser::with_checkpoint(
+            file_path.clone(),
+            result.end_offset,
+            result.end_fingerprint,
+        )
+        .unwrap();
+        std::fs::write(&file_path, "{\"uuid\": \"abc\"}\n{\"uuid\": \"z\"}\n").unwrap();
+        let result = resumed.parse_incremental().unwrap();
+        assert!(result.reset);
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.entries[1].data["uuid"], "z");
+
+        // 内容未变时继续增量解析
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&file_path)
+                .unwrap();
+            file.write_all(b"{\"uuid\": \"w\"}\n").unwrap();
+        }
+        let mut resumed = JsonlParser::with_checkpoint(
+            file_path.clone(),
+            result.end_offset,
+            result.end_fingerprint,
+        )
+        .unwrap();
+        let result = resumed.parse_incremental().unwrap();
+        assert!(!result.reset);
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].data["uuid"], "w");
+
+        std::fs::remove_file(file_path).ok();
+    }
 }
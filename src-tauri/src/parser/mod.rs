@@ -2,7 +2,17 @@
 //!
 //! 负责 JSONL 格式的 Claude Code 会话文件解析，支持流式读取和增量解析。
 
+pub mod branch;
+pub mod dataset;
 pub mod extractor;
+pub mod file_replay;
+pub mod file_timeline;
 pub mod jsonl;
+pub mod resume;
+pub mod schema;
+pub mod segment;
+pub mod source;
+pub mod tool_error;
 pub mod tree;
+pub mod usage;
 pub mod view_level;
@@ -0,0 +1,400 @@
+//! 续接会话写出
+//!
+//! 从已有会话中选取部分消息，写成新的 Claude Code 会话文件（`<uuid>.jsonl`），
+//! 放入 `~/.claude/projects/<项目目录>` 后即可用 `claude --resume <uuid>` 继续对话。
+//!
+//! 写出规则：
+//! - 按文件顺序保留选中的条目（没有 uuid 的摘要行不保留）
+//! - 每条消息分配新的 uuid，`sessionId` 改为新会话 ID
+//! - `parentUuid` 指向最近的被选中祖先；没有时指向上一条写出的消息，保证链路连续
+//! - 选中的 `tool_use` 和 `tool_result` 必须成对出现，否则恢复后 API 会拒绝请求
+//!
+//! 先写入临时文件并用 `MessageTreeBuilder` 重新解析校验，全部消息都挂到消息树上后才重命名为
+//! 正式文件，因此项目目录中不会出现无法恢复的会话。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::jsonl::{JsonlEntry, JsonlParser};
+use super::segment::SegmentScope;
+use super::tree::MessageTreeBuilder;
+
+/// 消息选择方式
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ResumeSelection {
+    /// 按消息 uuid 选择
+    Messages { uuids: Vec<String> },
+    /// 按问答对下标选择（含两端）
+    ///
+    /// 下标与使用相同 `segmentScope` 和 `customLevel` 的 QAPairs 等级返回的问答对一致
+    #[serde(rename_all = "camelCase")]
+    QaRange {
+        start: usize,
+        end: usize,
+        /// 压缩分段范围（默认全部分段）
+        #[serde(default)]
+        segment_scope: Option<SegmentScope>,
+        /// 自定义等级名称
+        #[serde(default)]
+        custom_level: Option<String>,
+    },
+}
+
+/// 写出的续接会话
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeSessionFile {
+    /// 新会话 ID
+    pub session_id: String,
+    /// 会话文件路径
+    pub file_path: String,
+    /// 写出的消息数
+    pub message_count: usize,
+    /// 继续对话的命令
+    pub resume_command: String,
+    /// 范围内被跳过的问答对下标（位于已被取代的分支上）
+    #[serde(default)]
+    pub skipped_qa_pairs: Vec<usize>,
+}
+
+/// 条目的 uuid
+fn entry_uuid(entry: &JsonlEntry) -> Option<&str> {
+    entry.data.get("uuid").and_then(|v| v.as_str())
+}
+
+/// 条目的父消息 uuid（压缩边界使用 `logicalParentUuid` 连接压缩前的消息）
+fn entry_parent(entry: &JsonlEntry) -> Option<&str> {
+    entry
+        .data
+        .get("parentUuid")
+        .and_then(|v| v.as_str())
+        .or_else(|| entry.data.get("logicalParentUuid").and_then(|v| v.as_str()))
+}
+
+/// 问答对在消息树上的路径：从答案沿父链回溯到问题（含两端，按文件顺序）
+///
+/// 路径包含中间的工具调用往返。链路在到达问题前断开时只返回问题和答案。
+pub fn qa_path(entries: &[JsonlEntry], question: &str, answer: &str) -> Vec<String> {
+    let parents: HashMap<&str, &str> = entries
+        .iter()
+        .filter_map(|entry| Some((entry_uuid(entry)?, entry_parent(entry)?)))
+        .collect();
+
+    let mut path = vec![answer.to_string()];
+    let mut current = answer;
+    while current != question {
+        match parents.get(current) {
+            Some(parent) if !path.iter().any(|uuid| uuid == parent) => {
+                path.push(parent.to_string());
+                current = parent;
+            }
+            _ => return vec![question.to_string(), answer.to_string()],
+        }
+    }
+
+    path.reverse();
+    path
+}
+
+/// 重写选中的条目，返回新会话的 JSONL 行
+///
+/// # 错误
+/// - 没有选中任何消息
+/// - 选中的 uuid 不在会话中
+/// - 第一条消息不是用户消息
+/// - `tool_use` 和 `tool_result` 没有成对选中
+pub fn rewrite_entries(
+    entries: &[JsonlEntry],
+    selected: &HashSet<String>,
+    session_id: &str,
+) -> Result<Vec<Value>> {
+    let parents: HashMap<&str, &str> = entries
+        .iter()
+        .filter_map(|entry| Some((entry_uuid(entry)?, entry_parent(entry)?)))
+        .collect();
+
+    let known: HashSet<&str> = entries.iter().filter_map(entry_uuid).collect();
+    let mut missing: Vec<&str> = selected
+        .iter()
+        .map(String::as_str)
+        .filter(|uuid| !known.contains(uuid))
+        .collect();
+    if !missing.is_empty() {
+        missing.sort_unstable();
+        anyhow::bail!("会话中不存在的消息: {}", missing.join(", "));
+    }
+
+    let mut new_ids: HashMap<&str, String> = HashMap::new();
+    let mut previous: Option<String> = None;
+    let mut lines = Vec::new();
+
+    for entry in entries {
+        let uuid = match entry_uuid(entry) {
+            Some(uuid) if selected.contains(uuid) && !new_ids.contains_key(uuid) => uuid,
+            _ => continue,
+        };
+
+        // 最近的被选中祖先（祖先都在前面，已分配新 uuid）
+        let mut ancestor = parents.get(uuid).copied();
+        let mut visited = HashSet::new();
+        while let Some(candidate) = ancestor {
+            if new_ids.contains_key(candidate) || !visited.insert(candidate) {
+                break;
+            }
+            ancestor = parents.get(candidate).copied();
+        }
+        let parent = ancestor
+            .and_then(|candidate| new_ids.get(candidate).cloned())
+            .or_else(|| previous.clone());
+
+        let new_uuid = uuid::Uuid::new_v4().to_string();
+        let mut data = entry.data.clone();
+        if let Some(object) = data.as_object_mut() {
+            object.insert("uuid".to_string(), Value::String(new_uuid.clone()));
+            object.insert(
+                "parentUuid".to_string(),
+                parent.map(Value::String).unwrap_or(Value::Null),
+            );
+            object.insert(
+                "sessionId".to_string(),
+                Value::String(session_id.to_string()),
+            );
+            object.remove("logicalParentUuid");
+        }
+
+        new_ids.insert(uuid, new_uuid.clone());
+        previous = Some(new_uuid);
+        lines.push(data);
+    }
+
+    let first = lines
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("没有选中任何消息"))?;
+    if first.get("type").and_then(|v| v.as_str()) != Some("user") {
+        anyhow::bail!("第一条消息必须是用户消息");
+    }
+    check_tool_pairs(&lines)?;
+
+    Ok(lines)
+}
+
+/// 检查 `tool_use` 和 `tool_result` 是否成对出现
+fn check_tool_pairs(lines: &[Value]) -> Result<()> {
+    let mut uses = HashSet::new();
+    let mut results = HashSet::new();
+
+    for line in lines {
+        let blocks = match line
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array())
+        {
+            Some(blocks) => blocks,
+            None => continue,
+        };
+        for block in blocks {
+            match block.get("type").and_then(|v| v.as_str()) {
+                Some("tool_use") => {
+                    if let Some(id) = block.get("id").and_then(|v| v.as_str()) {
+                        uses.insert(id);
+                    }
+                }
+                Some("tool_result") => {
+                    if let Some(id) = block.get("tool_use_id").and_then(|v| v.as_str()) {
+                        results.insert(id);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(id) = results.difference(&uses).next() {
+        anyhow::bail!("工具结果 {} 对应的工具调用未被选中", id);
+    }
+    if let Some(id) = uses.difference(&results).next() {
+        anyhow::bail!("工具调用 {} 的结果未被选中", id);
+    }
+    Ok(())
+}
+
+/// 将选中的消息写成新的续接会话
+///
+/// # 参数
+/// - `entries`: 原会话的全部条目（按文件顺序）
+/// - `selected`: 选中的消息 uuid
+/// - `project_dir`: 写入目录（`~/.claude/projects/<项目目录>`）
+pub fn write_resume_session(
+    entries: &[JsonlEntry],
+    selected: &HashSet<String>,
+    project_dir: &Path,
+) -> Result<ResumeSessionFile> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let lines = rewrite_entries(entries, selected, &session_id)?;
+
+    fs::create_dir_all(project_dir).with_context(|| format!("无法创建目录: {:?}", project_dir))?;
+    let target = project_dir.join(format!("{}.jsonl", session_id));
+    let temp_path = project_dir.join(format!("{}.jsonl.tmp", session_id));
+
+    let result = write_lines(&temp_path, &lines)
+        .and_then(|_| validate(&temp_path, lines.len()))
+        .and_then(|_| {
+            fs::rename(&temp_path, &target).with_context(|| format!("无法写入文件: {:?}", target))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    Ok(ResumeSessionFile {
+        resume_command: format!("claude --resume {}", session_id),
+        session_id,
+        file_path: target.to_string_lossy().to_string(),
+        message_count: lines.len(),
+        skipped_qa_pairs: Vec::new(),
+    })
+}
+
+/// 逐行写出 JSONL
+fn write_lines(path: &Path, lines: &[Value]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("无法创建文件: {:?}", path))?;
+    let mut writer = BufWriter::new(file);
+    for line in lines {
+        serde_json::to_writer(&mut writer, line)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// 重新解析写出的文件，确认每条消息都挂在消息树上
+fn validate(path: &Path, expected: usize) -> Result<()> {
+    let entries = JsonlParser::new(path.to_path_buf())?.parse_all()?;
+    let tree = MessageTreeBuilder::build_from_entries(&entries)?;
+    if entries.len() != expected || tree.total_count != expected {
+        anyhow::bail!(
+            "校验失败: 写出 {} 条消息，解析出 {} 条，消息树中 {} 条",
+            expected,
+            entries.len(),
+            tree.total_count
+        );
+    }
+    Ok(())
+}
+
+/// 续接会话的默认写入目录
+///
+/// 优先使用条目中的 `cwd` 对应的 `~/.claude/projects/<目录名>`（`claude --resume`
+/// 按当前工作目录查找会话），没有 `cwd` 时使用原会话文件所在目录。
+pub fn default_project_dir(file_path: &Path, entries: &[JsonlEntry]) -> Result<PathBuf> {
+    let cwd = entries
+        .iter()
+        .find_map(|entry| entry.data.get("cwd").and_then(|v| v.as_str()));
+    if let Some(cwd) = cwd {
+        return crate::path_resolver::resolve_session_directory(Path::new(cwd))
+            .map_err(|e| anyhow::anyhow!("无法解析项目目录: {}", e));
+    }
+
+    file_path
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| anyhow::anyhow!("无法确定项目目录"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn create_entries() -> Vec<JsonlEntry> {
+        [
+            json!({"type": "summary", "summary": "Fix tests", "leafUuid": "a3"}),
+            json!({"type": "user", "uuid": "u1", "parentUuid": null, "sessionId": "s1", "cwd": "/home/dev/app",
+                   "message": {"role": "user", "content": "run the tests"}}),
+            json!({"type": "assistant", "uuid": "a1", "parentUuid": "u1", "sessionId": "s1",
+                   "message": {"role": "assistant", "content": [{"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "cargo test"}}]}}),
+            json!({"type": "user", "uuid": "r1", "parentUuid": "a1", "sessionId": "s1",
+                   "message": {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "t1", "content": "ok"}]}}),
+            json!({"type": "assistant", "uuid": "a2", "parentUuid": "r1", "sessionId": "s1",
+                   "message": {"role": "assistant", "content": [{"type": "text", "text": "All tests pass."}]}}),
+            json!({"type": "user", "uuid": "u2", "parentUuid": "a2", "sessionId": "s1",
+                   "message": {"role": "user", "content": "now run clippy"}}),
+            json!({"type": "assistant", "uuid": "a3", "parentUuid": "u2", "sessionId": "s1",
+                   "message": {"role": "assistant", "content": [{"type": "text", "text": "Clippy is clean."}]}}),
+        ]
+        .into_iter()
+        .map(|data| JsonlEntry::new(0, 0, data))
+        .collect()
+    }
+
+    fn select(uuids: &[&str]) -> HashSet<String> {
+        uuids.iter().map(|uuid| uuid.to_string()).collect()
+    }
+
+    #[test]
+    fn test_write_resume_session() {
+        let entries = create_entries();
+        let temp_dir = TempDir::new().unwrap();
+        let project_dir = temp_dir.path().join("-home-dev-app");
+
+        let mut selected: HashSet<String> = qa_path(&entries, "u1", "a2").into_iter().collect();
+        assert_eq!(selected, select(&["u1", "a1", "r1", "a2"]));
+        selected.insert("a3".to_string());
+
+        let written = write_resume_session(&entries, &selected, &project_dir).unwrap();
+        assert_eq!(written.message_count, 5);
+        assert_eq!(
+            written.resume_command,
+            format!("claude --resume {}", written.session_id)
+        );
+        let file_path = PathBuf::from(&written.file_path);
+        assert_eq!(
+            file_path,
+            project_dir.join(format!("{}.jsonl", written.session_id))
+        );
+        assert_eq!(fs::read_dir(&project_dir).unwrap().count(), 1);
+
+        let lines = JsonlParser::new(file_path).unwrap().parse_all().unwrap();
+        assert!(lines
+            .iter()
+            .all(|line| line.data["sessionId"] == written.session_id));
+        assert!(lines[0].data["parentUuid"].is_null());
+        for pair in lines.windows(2) {
+            assert_eq!(pair[1].data["parentUuid"], pair[0].data["uuid"]);
+            assert_ne!(pair[1].data["uuid"], "a1");
+        }
+        assert_eq!(
+            lines[4].data["message"]["content"][0]["text"],
+            "Clippy is clean."
+        );
+    }
+
+    #[test]
+    fn test_rewrite_rejects_invalid_selection() {
+        let entries = create_entries();
+
+        // 工具结果缺少对应的工具调用
+        assert!(rewrite_entries(&entries, &select(&["u1", "r1", "a2"]), "s2").is_err());
+        // 工具调用缺少结果
+        assert!(rewrite_entries(&entries, &select(&["u1", "a1"]), "s2").is_err());
+        // 第一条消息不是用户消息
+        assert!(rewrite_entries(&entries, &select(&["a2", "u2"]), "s2").is_err());
+        assert!(rewrite_entries(&entries, &select(&[]), "s2").is_err());
+        // 不在会话中的 uuid
+        let err =
+            rewrite_entries(&entries, &select(&["u1", "a1", "r1", "a2", "x9"]), "s2").unwrap_err();
+        assert!(err.to_string().contains("x9"));
+
+        // 从后面的问题开始也能形成完整链路
+        let lines = rewrite_entries(&entries, &select(&["u2", "a3"]), "s2").unwrap();
+        assert!(lines[0]["parentUuid"].is_null());
+        assert_eq!(lines[1]["parentUuid"], lines[0]["uuid"]);
+    }
+}
@@ -0,0 +1,393 @@
+//! 会话格式清单模块
+//!
+//! Claude Code 的会话 JSONL 格式会随版本变化，而各解析器
+//! （`JsonlEntry::effective_message_type`、`session_reader`、`compressor` 等）
+//! 都对格式做了各自的假设。本模块扫描会话文件，统计出现过的条目 `type`、
+//! 内容块 `type`、顶层字段和 Claude Code `version`，并标记未知的结构，
+//! 便于在新版本破坏解析时第一时间发现。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// 已知的条目类型
+const KNOWN_ENTRY_TYPES: &[&str] = &[
+    "user",
+    "assistant",
+    "system",
+    "summary",
+    "file-history-snapshot",
+    "queue-operation",
+];
+
+/// 已知的内容块类型
+const KNOWN_CONTENT_BLOCK_TYPES: &[&str] = &[
+    "text",
+    "thinking",
+    "redacted_thinking",
+    "tool_use",
+    "tool_result",
+    "image",
+    "document",
+];
+
+/// 已知的顶层字段
+const KNOWN_TOP_LEVEL_KEYS: &[&str] = &[
+    "type",
+    "uuid",
+    "parentUuid",
+    "logicalParentUuid",
+    "sessionId",
+    "timestamp",
+    "message",
+    "content",
+    "role",
+    "subtype",
+    "level",
+    "isSidechain",
+    "isMeta",
+    "isCompactSummary",
+    "isVisibleInTranscriptOnly",
+    "isApiErrorMessage",
+    "compactMetadata",
+    "userType",
+    "cwd",
+    "version",
+    "gitBranch",
+    "slug",
+    "agentId",
+    "requestId",
+    "toolUseResult",
+    "toolUseID",
+    "sourceToolUseID",
+    "thinkingMetadata",
+    "todos",
+    "summary",
+    "leafUuid",
+    "messageId",
+    "snapshot",
+    "isSnapshotUpdate",
+    "operation",
+    "error",
+    "cause",
+    "retryInMs",
+    "retryAttempt",
+    "maxRetries",
+    "stopReason",
+    "preventedContinuation",
+    "hasOutput",
+    "hookCount",
+    "hookInfos",
+    "hookErrors",
+];
+
+/// 未知结构的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShapeKind {
+    /// 未知的条目类型
+    EntryType,
+    /// 未知的内容块类型
+    ContentBlockType,
+    /// 未知的顶层字段
+    TopLevelKey,
+    /// `message.content` 既不是字符串也不是数组
+    ContentShape,
+    /// user/assistant 条目缺少 `message` 字段
+    MissingMessage,
+}
+
+/// 未知结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnknownShape {
+    /// 类别
+    pub kind: ShapeKind,
+
+    /// 具体的值（类型名或字段名）
+    pub value: String,
+
+    /// 出现次数
+    pub count: usize,
+
+    /// 首次出现的文件
+    pub first_seen_file: String,
+
+    /// 首次出现时的 Claude Code 版本
+    pub first_seen_version: Option<String>,
+}
+
+/// 格式清单报告
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaReport {
+    /// 扫描的文件数
+    pub files_scanned: usize,
+
+    /// 扫描的条目数
+    pub entries_scanned: usize,
+
+    /// 无法解析为 JSON 的行数
+    pub invalid_lines: usize,
+
+    /// 条目类型 -> 次数
+    pub entry_types: BTreeMap<String, usize>,
+
+    /// system 条目 subtype -> 次数
+    pub system_subtypes: BTreeMap<String, usize>,
+
+    /// 内容块类型 -> 次数
+    pub content_block_types: BTreeMap<String, usize>,
+
+    /// 顶层字段 -> 次数
+    pub top_level_keys: BTreeMap<String, usize>,
+
+    /// Claude Code 版本 -> 条目数
+    pub versions: BTreeMap<String, usize>,
+
+    /// 未知结构（按类别和值排序）
+    pub unknown_shapes: Vec<UnknownShape>,
+
+    /// 是否发现格式漂移（存在未知结构）
+    pub drift: bool,
+}
+
+/// 格式清单扫描器
+#[derive(Default)]
+pub struct SchemaInventory {
+    report: SchemaReport,
+    unknown: BTreeMap<(ShapeKind, String), UnknownShape>,
+}
+
+impl SchemaInventory {
+    /// 创建扫描器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 扫描单个会话文件
+    ///
+    /// 含有非法 UTF-8 字节的行按替换字符解码，无法解析为 JSON 的行计入 `invalid_lines`
+    pub fn scan_file(&mut self, path: &Path) -> Result<()> {
+        let file = File::open(path).with_context(|| format!("无法打开文件: {:?}", path))?;
+        let file_name = path.to_string_lossy().to_string();
+
+        let mut reader = BufReader::new(file);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            let read = reader
+                .read_until(b'\n', &mut buf)
+                .with_context(|| format!("读取文件失败: {:?}", path))?;
+            if read == 0 {
+                break;
+            }
+
+            let line = String::from_utf8_lossy(&buf);
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Value>(&line) {
+                Ok(data) => self.record_entry(&data, &file_name),
+                Err(_) => self.report.invalid_lines += 1,
+            }
+        }
+
+        self.report.files_scanned += 1;
+        Ok(())
+    }
+
+    /// 记录单个条目
+    pub fn record_entry(&mut self, data: &Value, file: &str) {
+        self.report.entries_scanned += 1;
+
+        let object = match data.as_object() {
+            Some(object) => object,
+            None => {
+                self.flag(ShapeKind::ContentShape, "<non-object entry>", file, None);
+                return;
+            }
+        };
+
+        let version = object
+            .get("version")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        if let Some(ref version) = version {
+            *self.report.versions.entry(version.clone()).or_default() += 1;
+        }
+
+        for key in object.keys() {
+            *self.report.top_level_keys.entry(key.clone()).or_default() += 1;
+            if !KNOWN_TOP_LEVEL_KEYS.contains(&key.as_str()) {
+                self.flag(ShapeKind::TopLevelKey, key, file, version.as_deref());
+            }
+        }
+
+        let entry_type = object
+            .get("type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("<missing>");
+        *self
+            .report
+            .entry_types
+            .entry(entry_type.to_string())
+            .or_default() += 1;
+        if !KNOWN_ENTRY_TYPES.contains(&entry_type) {
+            self.flag(ShapeKind::EntryType, entry_type, file, version.as_deref());
+        }
+
+        if entry_type == "system" {
+            let subtype = object
+                .get("subtype")
+                .and_then(|v| v.as_str())
+                .unwrap_or("<none>");
+            *self
+                .report
+                .system_subtypes
+                .entry(subtype.to_string())
+                .or_default() += 1;
+        }
+
+        if matches!(entry_type, "user" | "assistant") {
+            match object.get("message").and_then(|m| m.get("content")) {
+                Some(Value::String(_)) => {}
+                Some(Value::Array(blocks)) => {
+                    for block in blocks {
+                        let block_type = block
+                            .get("type")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("<missing>");
+                        *self
+                            .report
+                            .content_block_types
+                            .entry(block_type.to_string())
+                            .or_default() += 1;
+                        if !KNOWN_CONTENT_BLOCK_TYPES.contains(&block_type) {
+                            self.flag(
+                                ShapeKind::ContentBlockType,
+                                block_type,
+                                file,
+                                version.as_deref(),
+                            );
+                        }
+                    }
+                }
+                Some(other) => {
+                    let shape = match other {
+                        Value::Null => "null",
+                        Value::Bool(_) => "bool",
+                        Value::Number(_) => "number",
+                        _ => "object",
+                    };
+                    self.flag(ShapeKind::ContentShape, shape, file, version.as_deref());
+                }
+                None => {
+                    if object.get("message").is_none() {
+                        self.flag(
+                            ShapeKind::MissingMessage,
+                            entry_type,
+                            file,
+                            version.as_deref(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// 生成报告
+    pub fn finish(mut self) -> SchemaReport {
+        self.report.unknown_shapes = self.unknown.into_values().collect();
+        self.report.drift = !self.report.unknown_shapes.is_empty();
+        self.report
+    }
+
+    /// 记录未知结构
+    fn flag(&mut self, kind: ShapeKind, value: &str, file: &str, version: Option<&str>) {
+        self.unknown
+            .entry((kind, value.to_string()))
+            .or_insert_with(|| UnknownShape {
+                kind,
+                value: value.to_string(),
+                count: 0,
+                first_seen_file: file.to_string(),
+                first_seen_version: version.map(|s| s.to_string()),
+            })
+            .count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_inventory_flags_unknown_shapes() {
+        let mut inventory = SchemaInventory::new();
+        let entries = [
+            json!({"type": "user", "uuid": "u1", "version": "1.0.80",
+                   "message": {"role": "user", "content": "hi"}}),
+            json!({"type": "assistant", "uuid": "a1", "version": "1.0.80",
+            "message": {"role": "assistant", "content": [
+                {"type": "text", "text": "ok"},
+                {"type": "server_tool_use", "id": "x"}
+            ]}}),
+            json!({"type": "system", "subtype": "compact_boundary", "uuid": "b1", "version": "2.0.1"}),
+            json!({"type": "progress", "uuid": "p1", "version": "2.0.1", "newField": 1}),
+        ];
+        for entry in &entries {
+            inventory.record_entry(entry, "session.jsonl");
+        }
+
+        let report = inventory.finish();
+        assert_eq!(report.entries_scanned, 4);
+        assert_eq!(report.versions.get("1.0.80"), Some(&2));
+        assert_eq!(report.system_subtypes.get("compact_boundary"), Some(&1));
+        assert_eq!(report.content_block_types.get("text"), Some(&1));
+        assert!(report.drift);
+
+        let flagged: Vec<(ShapeKind, &str)> = report
+            .unknown_shapes
+            .iter()
+            .map(|s| (s.kind, s.value.as_str()))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![
+                (ShapeKind::EntryType, "progress"),
+                (ShapeKind::ContentBlockType, "server_tool_use"),
+                (ShapeKind::TopLevelKey, "newField"),
+            ]
+        );
+        assert_eq!(
+            report.unknown_shapes[0].first_seen_version.as_deref(),
+            Some("2.0.1")
+        );
+    }
+
+    #[test]
+    fn test_scan_file_tolerates_invalid_utf8() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+
+        let mut content = Vec::new();
+        content.extend_from_slice(b"{\"type\": \"user\", \"uuid\": \"u1\"}\n");
+        content.extend_from_slice(b"\xff\xfe broken\n");
+        content.extend_from_slice(b"{\"type\": \"assistant\", \"uuid\": \"a1\"}\n");
+        std::fs::write(&path, content).unwrap();
+
+        let mut inventory = SchemaInventory::new();
+        inventory.scan_file(&path).unwrap();
+
+        let report = inventory.finish();
+        assert_eq!(report.files_scanned, 1);
+        assert_eq!(report.entries_scanned, 2);
+        assert_eq!(report.invalid_lines, 1);
+    }
+}
@@ -0,0 +1,286 @@
+//! 会话分段模块
+//!
+//! Claude Code 压缩（compact）对话时，会写入一条 `subtype: compact_boundary`
+//! 的 system 条目（没有父节点），随后是一条 `isCompactSummary: true` 的
+//! user 条目，内容为压缩前对话的摘要；旧版本则直接以摘要 user 条目作为新的根。
+//!
+//! 本模块以这些压缩边界把会话划分为有序的分段，并支持只取"当前分段"
+//! （最后一次压缩之后的内容）。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::jsonl::JsonlEntry;
+use super::tree::{ConversationTree, MessageNode};
+
+/// 分段范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentScope {
+    /// 所有分段
+    #[default]
+    All,
+    /// 仅当前分段（最后一次压缩之后）
+    Current,
+}
+
+/// 会话分段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSegment {
+    /// 分段序号（从 0 开始，按时间先后）
+    pub index: usize,
+
+    /// 分段包含的根节点 ID
+    pub root_ids: Vec<String>,
+
+    /// 开启该分段的压缩边界 ID（第一个分段为 None）
+    pub boundary_uuid: Option<String>,
+
+    /// 压缩摘要（压缩前对话的总结）
+    pub summary: Option<String>,
+
+    /// 压缩触发方式（auto / manual）
+    pub trigger: Option<String>,
+
+    /// 压缩前的 token 数
+    pub pre_tokens: Option<u64>,
+
+    /// 分段开始时间
+    pub started_at: Option<String>,
+
+    /// 分段内的消息数量
+    pub message_count: usize,
+}
+
+/// 检查条目是否为压缩边界
+pub fn is_compact_boundary(data: &Value) -> bool {
+    data.get("type").and_then(|v| v.as_str()) == Some("system")
+        && data.get("subtype").and_then(|v| v.as_str()) == Some("compact_boundary")
+}
+
+/// 检查条目是否为压缩摘要消息
+pub fn is_compact_summary(data: &Value) -> bool {
+    data.get("isCompactSummary").and_then(|v| v.as_bool()) == Some(true)
+}
+
+/// 检查条目是否开启一个新分段
+///
+/// 压缩边界总是开启新分段；摘要消息只有在没有父节点时（旧版本格式）才开启，
+/// 挂在边界下的摘要属于边界开启的分段。
+pub fn starts_segment(data: &Value) -> bool {
+    is_compact_boundary(data)
+        || (is_compact_summary(data) && data.get("parentUuid").and_then(|v| v.as_str()).is_none())
+}
+
+/// 会话分段检测器
+pub struct SegmentDetector;
+
+impl SegmentDetector {
+    /// 按范围截取条目
+    ///
+    /// `Current` 只保留最后一个分段起点及之后的条目
+    pub fn apply_scope(entries: Vec<JsonlEntry>, scope: SegmentScope) -> Vec<JsonlEntry> {
+        match scope {
+            SegmentScope::All => entries,
+            SegmentScope::Current => {
+                let start = entries
+                    .iter()
+                    .rposition(|entry| starts_segment(&entry.data))
+                    .unwrap_or(0);
+                entries.into_iter().skip(start).collect()
+            }
+        }
+    }
+
+    /// 将消息树划分为分段
+    ///
+    /// 根节点按文件顺序排列，每个压缩边界（或无父节点的摘要消息）开启新分段
+    pub fn segments(tree: &ConversationTree) -> Vec<SessionSegment> {
+        let mut segments: Vec<SessionSegment> = Vec::new();
+
+        for root in &tree.roots {
+            if segments.is_empty() || starts_segment(&root.message_data) {
+                segments.push(Self::open_segment(segments.len(), root));
+            }
+
+            let segment = segments.last_mut().expect("至少存在一个分段");
+            segment.root_ids.push(root.id.clone());
+            segment.message_count += count_nodes(root);
+        }
+
+        segments
+    }
+
+    /// 取出指定分段构成的消息树
+    pub fn segment_tree(tree: &ConversationTree, index: usize) -> Option<ConversationTree> {
+        let segment = Self::segments(tree).into_iter().nth(index)?;
+
+        let mut segment_tree = ConversationTree::new();
+        for root in &tree.roots {
+            if segment.root_ids.contains(&root.id) {
+                segment_tree.add_root(root.clone());
+            }
+        }
+
+        // 与 MessageTreeBuilder 一致：线程数为不同 thread_id 的数量
+        let mut threads = std::collections::HashSet::new();
+        let mut stack: Vec<&MessageNode> = segment_tree.roots.iter().collect();
+        while let Some(node) = stack.pop() {
+            if let Some(ref thread_id) = node.thread_id {
+                threads.insert(thread_id.clone());
+            }
+            stack.extend(node.children.iter());
+        }
+        segment_tree.thread_count = threads.len();
+
+        Some(segment_tree)
+    }
+
+    /// 按范围取出消息树
+    pub fn scope_tree(tree: ConversationTree, scope: SegmentScope) -> ConversationTree {
+        match scope {
+            SegmentScope::All => tree,
+            SegmentScope::Current => {
+                let last = Self::segments(&tree).len().saturating_sub(1);
+                Self::segment_tree(&tree, last).unwrap_or(tree)
+            }
+        }
+    }
+
+    /// 以根节点开启新分段
+    fn open_segment(index: usize, root: &MessageNode) -> SessionSegment {
+        let is_boundary = root.is_compact_boundary();
+        let compact_metadata = root.message_data.get("compactMetadata");
+
+        // 摘要：边界下的第一条摘要消息，或根节点本身
+        let summary_node = if is_boundary {
+            find_compact_summary(root)
+        } else if root.is_compact_summary() {
+            Some(root)
+        } else {
+            None
+        };
+
+        SessionSegment {
+            index,
+            root_ids: Vec::new(),
+            boundary_uuid: is_boundary.then(|| root.id.clone()),
+            summary: summary_node.and_then(node_text),
+            trigger: compact_metadata
+                .and_then(|m| m.get("trigger"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            pre_tokens: compact_metadata
+                .and_then(|m| m.get("preTokens"))
+                .and_then(|v| v.as_u64()),
+            started_at: root
+                .message_data
+                .get("timestamp")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            message_count: 0,
+        }
+    }
+}
+
+/// 在子树中查找第一条压缩摘要消息
+fn find_compact_summary(node: &MessageNode) -> Option<&MessageNode> {
+    let mut stack = vec![node];
+    while let Some(current) = stack.pop() {
+        if current.is_compact_summary() {
+            return Some(current);
+        }
+        stack.extend(current.children.iter().rev());
+    }
+    None
+}
+
+/// 节点的文本内容（字符串 content 或 text 块拼接）
+fn node_text(node: &MessageNode) -> Option<String> {
+    let content = node
+        .message_data
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .or_else(|| node.message_data.get("content"))?;
+
+    let text = match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// 统计子树节点数量
+fn count_nodes(node: &MessageNode) -> usize {
+    1 + node.children.iter().map(count_nodes).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tree::MessageTreeBuilder;
+    use serde_json::json;
+
+    fn compacted_entries() -> Vec<JsonlEntry> {
+        [
+            json!({"type": "summary", "summary": "Refactor parser", "leafUuid": "a1"}),
+            json!({"uuid": "u1", "type": "user", "timestamp": "2025-01-01T10:00:00Z",
+                   "message": {"role": "user", "content": "old question"}}),
+            json!({"uuid": "a1", "parentUuid": "u1", "type": "assistant",
+                   "message": {"role": "assistant", "content": [{"type": "text", "text": "old answer"}]}}),
+            json!({"uuid": "b1", "parentUuid": null, "logicalParentUuid": "a1", "type": "system",
+                   "subtype": "compact_boundary", "timestamp": "2025-01-01T11:00:00Z",
+                   "content": "Conversation compacted",
+                   "compactMetadata": {"trigger": "auto", "preTokens": 155000}}),
+            json!({"uuid": "s1", "parentUuid": "b1", "type": "user", "isCompactSummary": true,
+                   "message": {"role": "user", "content": "Summary of earlier work"}}),
+            json!({"uuid": "u2", "parentUuid": "s1", "type": "user",
+                   "message": {"role": "user", "content": "new question"}}),
+        ]
+        .into_iter()
+        .map(|data| JsonlEntry::new(0, 0, data))
+        .collect()
+    }
+
+    #[test]
+    fn test_segments_split_at_compact_boundary() {
+        let tree = MessageTreeBuilder::build_from_entries(&compacted_entries()).unwrap();
+        let segments = SegmentDetector::segments(&tree);
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].root_ids, vec!["u1".to_string()]);
+        assert_eq!(segments[0].message_count, 2);
+        assert_eq!(segments[1].boundary_uuid.as_deref(), Some("b1"));
+        assert_eq!(
+            segments[1].summary.as_deref(),
+            Some("Summary of earlier work")
+        );
+        assert_eq!(segments[1].trigger.as_deref(), Some("auto"));
+        assert_eq!(segments[1].pre_tokens, Some(155000));
+        assert_eq!(segments[1].message_count, 3);
+
+        let current = SegmentDetector::scope_tree(tree, SegmentScope::Current);
+        assert_eq!(current.roots.len(), 1);
+        assert_eq!(current.total_count, 3);
+    }
+
+    #[test]
+    fn test_apply_scope_keeps_last_segment() {
+        let entries = SegmentDetector::apply_scope(compacted_entries(), SegmentScope::Current);
+        let ids: Vec<&str> = entries
+            .iter()
+            .filter_map(|e| e.data.get("uuid").and_then(|v| v.as_str()))
+            .collect();
+        assert_eq!(ids, vec!["b1", "s1", "u2"]);
+
+        let all = SegmentDetector::apply_scope(compacted_entries(), SegmentScope::All);
+        assert_eq!(all.len(), 6);
+    }
+}
@@ -0,0 +1,291 @@
+//! Aider 会话来源
+//!
+//! Aider 在项目根目录追加写入 `.aider.chat.history.md`，一个文件包含该项目的全部对话：
+//! - `# aider chat started at YYYY-MM-DD HH:MM:SS`：一次运行的开始（本地时间），
+//!   每次运行作为消息树中的一个独立根节点
+//! - `#### `：用户输入（多行输入每行都带前缀）
+//! - `> `：工具输出（编辑结果、命令输出等）；运行开头的 `> ` 行是版本和模型信息，直接跳过
+//! - 其他非空行：模型回复
+//!
+//! 连续的同类行合并为一条消息，条目的字节范围覆盖原文件中的整段内容。
+
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde_json::{json, Value};
+use std::path::Path;
+
+use super::{EntryBuilder, SessionSourceAdapter, SessionSourceKind};
+use crate::parser::jsonl::JsonlEntry;
+
+/// 会话文件名
+const HISTORY_FILE_NAME: &str = ".aider.chat.history.md";
+
+/// 运行开始标记
+const RUN_HEADER_PREFIX: &str = "# aider chat started at ";
+
+/// 用户输入前缀
+const USER_PREFIX: &str = "#### ";
+
+/// 工具输出前缀
+const TOOL_PREFIX: &str = "> ";
+
+/// Aider 会话适配器
+pub struct AiderAdapter;
+
+impl SessionSourceAdapter for AiderAdapter {
+    fn kind(&self) -> SessionSourceKind {
+        SessionSourceKind::Aider
+    }
+
+    fn file_pattern(&self) -> &'static str {
+        HISTORY_FILE_NAME
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name().and_then(|name| name.to_str()) == Some(HISTORY_FILE_NAME)
+    }
+
+    fn read_entries(&self, path: &Path) -> Result<Vec<JsonlEntry>> {
+        let content =
+            std::fs::read_to_string(path).with_context(|| format!("无法读取文件: {:?}", path))?;
+
+        let project_dir = path
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut reader = HistoryReader {
+            builder: EntryBuilder::new(session_id_for_project(&project_dir), Some(project_dir)),
+            block: None,
+            timestamp: None,
+            in_banner: false,
+        };
+
+        let mut offset = 0u64;
+        for raw_line in content.split_inclusive('\n') {
+            let line = raw_line.trim_end_matches(['\n', '\r']);
+            reader.feed(line, offset);
+            offset += raw_line.len() as u64;
+        }
+        reader.flush();
+
+        Ok(reader.builder.finish())
+    }
+}
+
+/// 由项目目录生成稳定的会话 ID（非字母数字字符替换为 `-`）
+fn session_id_for_project(project_dir: &str) -> String {
+    let normalized: String = project_dir
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("aider-{}", normalized.trim_matches('-'))
+}
+
+/// 行的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    User,
+    Assistant,
+    Tool,
+}
+
+/// 正在累积的消息块
+struct Block {
+    kind: BlockKind,
+    lines: Vec<String>,
+    offset: u64,
+    /// 最后一个非空行的结束位置
+    end: u64,
+}
+
+/// 逐行读取历史文件并生成条目
+struct HistoryReader {
+    builder: EntryBuilder,
+    block: Option<Block>,
+    timestamp: Option<String>,
+    /// 是否处于运行开头的版本信息区
+    in_banner: bool,
+}
+
+impl HistoryReader {
+    fn feed(&mut self, line: &str, offset: u64) {
+        if let Some(started_at) = line.strip_prefix(RUN_HEADER_PREFIX) {
+            self.flush();
+            self.builder.start_root();
+            self.timestamp = parse_local_time(started_at.trim());
+            self.in_banner = true;
+            return;
+        }
+
+        let (kind, text) = if let Some(text) = line.strip_prefix(USER_PREFIX) {
+            (BlockKind::User, text)
+        } else if line == USER_PREFIX.trim_end() {
+            (BlockKind::User, "")
+        } else if let Some(text) = line.strip_prefix(TOOL_PREFIX) {
+            (BlockKind::Tool, text)
+        } else if line == TOOL_PREFIX.trim_end() {
+            (BlockKind::Tool, "")
+        } else if line.trim().is_empty() {
+            // 空行属于当前块（保留段落结构），不改变块的种类
+            if let Some(block) = self.block.as_mut() {
+                block.lines.push(String::new());
+            }
+            return;
+        } else {
+            (BlockKind::Assistant, line)
+        };
+
+        if self.in_banner {
+            if kind == BlockKind::Tool {
+                return;
+            }
+            self.in_banner = false;
+        }
+
+        if self.block.as_ref().map(|b| b.kind) != Some(kind) {
+            self.flush();
+            self.block = Some(Block {
+                kind,
+                lines: Vec::new(),
+                offset,
+                end: offset,
+            });
+        }
+        if let Some(block) = self.block.as_mut() {
+            block.lines.push(text.to_string());
+            block.end = offset + line.len() as u64;
+        }
+    }
+
+    fn flush(&mut self) {
+        let Some(block) = self.block.take() else {
+            return;
+        };
+        let text = block.lines.join("\n").trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+
+        let (msg_type, content) = match block.kind {
+            BlockKind::User => ("user", Value::String(text)),
+            BlockKind::Assistant => ("assistant", json!([{"type": "text", "text": text}])),
+            BlockKind::Tool => ("system", Value::String(text)),
+        };
+        self.builder.push(
+            msg_type,
+            content,
+            self.timestamp.as_deref(),
+            block.offset,
+            (block.end - block.offset) as usize,
+        );
+    }
+}
+
+/// 解析运行开始时间（本地时间）为 RFC3339
+fn parse_local_time(value: &str) -> Option<String> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tree::MessageTreeBuilder;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_aider_history() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_dir = temp_dir.path().join("app");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let path = project_dir.join(HISTORY_FILE_NAME);
+        let content = "\
+# aider chat started at 2025-03-10 09:00:00
+
+> Aider v0.75.0
+> Main model: claude-3-7-sonnet with diff edit format
+> Git repo: .git with 12 files
+
+#### add a greet function
+#### that takes a name
+
+Here is the change:
+
+hello.py
+def greet(name):
+    return f\"Hello, {name}\"
+
+> Applied edit to hello.py
+> Commit 1a2b3c4 feat: add greet function
+
+#### thanks
+
+You're welcome!
+
+# aider chat started at 2025-03-11 10:00:00
+
+> Aider v0.75.0
+
+#### rename greet to hello
+
+Done.
+";
+        std::fs::write(&path, content).unwrap();
+
+        let adapter = AiderAdapter;
+        assert!(adapter.matches(&path));
+        let entries = adapter.read_entries(&path).unwrap();
+        let types: Vec<_> = entries.iter().map(|e| e.message_type().unwrap()).collect();
+        assert_eq!(
+            types,
+            [
+                "user",
+                "assistant",
+                "system",
+                "user",
+                "assistant",
+                "user",
+                "assistant"
+            ]
+        );
+
+        let first = &entries[0];
+        assert_eq!(
+            first.data["message"]["content"],
+            "add a greet function\nthat takes a name"
+        );
+        assert!(first.data["timestamp"]
+            .as_str()
+            .unwrap()
+            .starts_with("2025-03-10T09:00:00"));
+        let raw = &content[first.offset as usize..first.offset as usize + first.length];
+        assert_eq!(raw, "#### add a greet function\n#### that takes a name");
+
+        let reply = entries[1].data["message"]["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        assert!(reply.starts_with("Here is the change:\n\nhello.py"));
+        assert_eq!(
+            entries[2].data["message"]["content"],
+            "Applied edit to hello.py\nCommit 1a2b3c4 feat: add greet function"
+        );
+
+        // 第二次运行从新的根节点开始
+        assert!(entries[5].data["parentUuid"].is_null());
+        assert!(entries[5].data["timestamp"]
+            .as_str()
+            .unwrap()
+            .starts_with("2025-03-11T10:00:00"));
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        assert_eq!(tree.roots.len(), 2);
+
+        let metadata = adapter.read_metadata(&path).unwrap();
+        assert!(metadata.session_id.starts_with("aider-"));
+        assert!(metadata.session_id.ends_with("-app"));
+        assert_eq!(metadata.project_name, "app");
+        assert_eq!(metadata.message_count, 7);
+    }
+}
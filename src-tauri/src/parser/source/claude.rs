@@ -0,0 +1,39 @@
+//! Claude Code 会话来源
+//!
+//! 原生格式即兼容格式，直接复用 `JsonlParser` 和会话扫描器（含扫描缓存）
+
+use anyhow::Result;
+use std::path::Path;
+
+use super::{SessionSourceAdapter, SessionSourceKind};
+use crate::monitor::scanner::{self, SessionMetadata};
+use crate::parser::jsonl::{JsonlEntry, JsonlParser};
+
+/// Claude Code 会话适配器
+pub struct ClaudeCodeAdapter;
+
+impl SessionSourceAdapter for ClaudeCodeAdapter {
+    fn kind(&self) -> SessionSourceKind {
+        SessionSourceKind::ClaudeCode
+    }
+
+    fn file_pattern(&self) -> &'static str {
+        "*.jsonl"
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.extension().and_then(|ext| ext.to_str()) == Some("jsonl")
+    }
+
+    fn read_entries(&self, path: &Path) -> Result<Vec<JsonlEntry>> {
+        JsonlParser::new(path.to_path_buf())?.parse_all()
+    }
+
+    fn read_metadata(&self, path: &Path) -> Result<SessionMetadata> {
+        scanner::extract_session_metadata(path)
+    }
+
+    fn scan(&self, directory: &Path) -> Result<Vec<SessionMetadata>> {
+        scanner::scan_directory(directory)
+    }
+}
@@ -0,0 +1,295 @@
+//! Codex CLI 会话来源
+//!
+//! Codex CLI 把每次会话写入 `~/.codex/sessions/YYYY/MM/DD/rollout-<时间>-<uuid>.jsonl`。
+//! 新版本每行为 `{"timestamp", "type", "payload"}`，其中 `session_meta` 记录会话 ID 和
+//! 工作目录，`response_item` 为模型输入输出项；旧版本第一行是会话头，之后每行直接是输入输出项。
+//!
+//! 输入输出项的转换规则：
+//! - `message`（user / assistant）→ 文本消息（跳过 Codex 注入的环境上下文和项目指令）
+//! - `function_call` / `custom_tool_call` / `local_shell_call` → assistant 的 `tool_use`
+//! - `function_call_output` / `custom_tool_call_output` → user 的 `tool_result`
+//! - `reasoning` → assistant 的 `thinking`
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::path::Path;
+
+use super::{EntryBuilder, SessionSourceAdapter, SessionSourceKind};
+use crate::parser::jsonl::{JsonlEntry, JsonlParser};
+
+/// 会话文件名前缀
+const ROLLOUT_PREFIX: &str = "rollout-";
+
+/// Codex 注入到用户消息中的上下文标签，这些消息不是用户输入
+const INJECTED_CONTEXT_TAGS: [&str; 2] = ["<environment_context>", "<user_instructions>"];
+
+/// Codex CLI 会话适配器
+pub struct CodexAdapter;
+
+impl SessionSourceAdapter for CodexAdapter {
+    fn kind(&self) -> SessionSourceKind {
+        SessionSourceKind::CodexCli
+    }
+
+    fn file_pattern(&self) -> &'static str {
+        "rollout-*.jsonl"
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with(ROLLOUT_PREFIX) && name.ends_with(".jsonl"))
+            .unwrap_or(false)
+    }
+
+    fn read_entries(&self, path: &Path) -> Result<Vec<JsonlEntry>> {
+        let lines = JsonlParser::new(path.to_path_buf())?.parse_all()?;
+        let mut builder = EntryBuilder::new(session_id_from_file_name(path), None);
+        let mut last_timestamp: Option<String> = None;
+
+        for line in &lines {
+            let data = &line.data;
+            if let Some(timestamp) = data.get("timestamp").and_then(|v| v.as_str()) {
+                last_timestamp = Some(timestamp.to_string());
+            }
+
+            let item = match data.get("type").and_then(|v| v.as_str()) {
+                Some("session_meta") => {
+                    if let Some(payload) = data.get("payload") {
+                        apply_session_meta(&mut builder, payload);
+                    }
+                    continue;
+                }
+                Some("response_item") => match data.get("payload") {
+                    Some(payload) => payload,
+                    None => continue,
+                },
+                // 旧版本：输入输出项直接写在行上
+                Some(_) => data,
+                // 旧版本会话头
+                None => {
+                    apply_session_meta(&mut builder, data);
+                    continue;
+                }
+            };
+
+            if let Some((msg_type, content)) = convert_item(item) {
+                builder.push(
+                    msg_type,
+                    content,
+                    last_timestamp.as_deref(),
+                    line.offset,
+                    line.length,
+                );
+            }
+        }
+
+        Ok(builder.finish())
+    }
+}
+
+/// 从文件名末尾提取会话 UUID（`rollout-<时间>-<uuid>.jsonl`）
+fn session_id_from_file_name(path: &Path) -> String {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    match stem.char_indices().rev().nth(35) {
+        Some((idx, _)) => stem[idx..].to_string(),
+        None => stem.to_string(),
+    }
+}
+
+/// 从会话头读取会话 ID 和工作目录
+fn apply_session_meta(builder: &mut EntryBuilder, meta: &Value) {
+    if let Some(id) = meta.get("id").and_then(|v| v.as_str()) {
+        builder.set_session_id(id.to_string());
+    }
+    if let Some(cwd) = meta.get("cwd").and_then(|v| v.as_str()) {
+        builder.set_cwd(cwd.to_string());
+    }
+}
+
+/// 把一个输入输出项转换为（条目类型，`message.content`），无需展示的项返回 None
+fn convert_item(item: &Value) -> Option<(&'static str, Value)> {
+    match item.get("type")?.as_str()? {
+        "message" => {
+            let text = message_text(item)?;
+            match item.get("role")?.as_str()? {
+                "user" => {
+                    let trimmed = text.trim_start();
+                    if INJECTED_CONTEXT_TAGS
+                        .iter()
+                        .any(|tag| trimmed.starts_with(tag))
+                    {
+                        return None;
+                    }
+                    Some(("user", Value::String(text)))
+                }
+                "assistant" => Some(("assistant", json!([{"type": "text", "text": text}]))),
+                _ => None,
+            }
+        }
+        "function_call" | "custom_tool_call" => {
+            let raw = item
+                .get("arguments")
+                .or_else(|| item.get("input"))
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let input = serde_json::from_str::<Value>(raw)
+                .ok()
+                .filter(|v| v.is_object())
+                .unwrap_or_else(|| json!({ "input": raw }));
+            Some((
+                "assistant",
+                json!([{
+                    "type": "tool_use",
+                    "id": item.get("call_id"),
+                    "name": item.get("name"),
+                    "input": input,
+                }]),
+            ))
+        }
+        "local_shell_call" => Some((
+            "assistant",
+            json!([{
+                "type": "tool_use",
+                "id": item.get("call_id"),
+                "name": "shell",
+                "input": item.get("action").cloned().unwrap_or(Value::Null),
+            }]),
+        )),
+        "function_call_output" | "custom_tool_call_output" => {
+            let (output, is_error) = tool_output(item.get("output")?);
+            Some((
+                "user",
+                json!([{
+                    "type": "tool_result",
+                    "tool_use_id": item.get("call_id"),
+                    "content": output,
+                    "is_error": is_error,
+                }]),
+            ))
+        }
+        "reasoning" => {
+            let summary: Vec<&str> = item
+                .get("summary")?
+                .as_array()?
+                .iter()
+                .filter_map(|part| part.get("text").and_then(|v| v.as_str()))
+                .collect();
+            if summary.is_empty() {
+                return None;
+            }
+            Some((
+                "assistant",
+                json!([{"type": "thinking", "thinking": summary.join("\n\n")}]),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// 拼接消息中的文本块（`input_text` / `output_text`）
+fn message_text(item: &Value) -> Option<String> {
+    let text: Vec<&str> = item
+        .get("content")?
+        .as_array()?
+        .iter()
+        .filter_map(|block| block.get("text").and_then(|v| v.as_str()))
+        .collect();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.join("\n"))
+    }
+}
+
+/// 解析工具输出，返回（输出文本，是否出错）
+///
+/// 输出可能是纯文本，也可能是 `{"output": "...", "metadata": {"exit_code": 1}}`
+/// 形式的对象或其 JSON 字符串，退出码非零时视为出错
+fn tool_output(output: &Value) -> (String, bool) {
+    let structured = match output {
+        Value::String(text) => serde_json::from_str::<Value>(text)
+            .ok()
+            .filter(|v| v.get("output").is_some()),
+        Value::Object(_) => Some(output.clone()),
+        _ => None,
+    };
+
+    match structured {
+        Some(value) => {
+            let text = value
+                .get("output")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            let exit_code = value
+                .get("metadata")
+                .and_then(|m| m.get("exit_code"))
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0);
+            (text, exit_code != 0)
+        }
+        None => (output.as_str().unwrap_or_default().to_string(), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tree::MessageTreeBuilder;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_codex_rollout() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir
+            .path()
+            .join("rollout-2025-03-10T09-00-00-0195f4a1-7c2e-7d31-9b8a-2f1e3c4d5e6f.jsonl");
+        let lines = [
+            r#"{"timestamp":"2025-03-10T09:00:00Z","type":"session_meta","payload":{"id":"0195f4a1-7c2e-7d31-9b8a-2f1e3c4d5e6f","cwd":"/home/dev/app"}}"#,
+            r#"{"timestamp":"2025-03-10T09:00:01Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"<environment_context>\n  <cwd>/home/dev/app</cwd>\n</environment_context>"}]}}"#,
+            r#"{"timestamp":"2025-03-10T09:00:02Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"run the tests"}]}}"#,
+            r#"{"timestamp":"2025-03-10T09:00:03Z","type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"cargo\",\"test\"]}","call_id":"call_1"}}"#,
+            r#"{"timestamp":"2025-03-10T09:00:09Z","type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"{\"output\":\"test result: FAILED. 1 passed; 1 failed\",\"metadata\":{\"exit_code\":101}}"}}"#,
+            r#"{"timestamp":"2025-03-10T09:00:10Z","type":"event_msg","payload":{"type":"token_count"}}"#,
+            r#"{"timestamp":"2025-03-10T09:00:11Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"One test fails."}]}}"#,
+        ];
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        let adapter = CodexAdapter;
+        let entries = adapter.read_entries(&path).unwrap();
+        assert_eq!(entries.len(), 4);
+
+        let first = &entries[0].data;
+        assert_eq!(first["type"], "user");
+        assert_eq!(first["message"]["content"], "run the tests");
+        assert_eq!(first["cwd"], "/home/dev/app");
+        assert_eq!(first["timestamp"], "2025-03-10T09:00:02Z");
+        assert!(first["parentUuid"].is_null());
+
+        assert_eq!(entries[1].data["message"]["content"][0]["type"], "tool_use");
+        assert_eq!(
+            entries[1].data["message"]["content"][0]["input"]["command"][1],
+            "test"
+        );
+        let result = &entries[2].data["message"]["content"][0];
+        assert_eq!(result["tool_use_id"], "call_1");
+        assert_eq!(result["is_error"], true);
+        assert_eq!(entries[3].data["parentUuid"], entries[2].data["uuid"]);
+
+        // 条目指向原文件中的行
+        let parser = JsonlParser::new(path.clone()).unwrap();
+        let raw = parser
+            .parse_entry_at_offset(entries[0].offset, entries[0].length)
+            .unwrap();
+        assert_eq!(raw["payload"]["content"][0]["text"], "run the tests");
+
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        assert_eq!(tree.total_count, 4);
+
+        let metadata = adapter.read_metadata(&path).unwrap();
+        assert_eq!(metadata.session_id, "0195f4a1-7c2e-7d31-9b8a-2f1e3c4d5e6f");
+        assert_eq!(metadata.project_name, "app");
+        assert_eq!(metadata.display_name.as_deref(), Some("run the tests"));
+    }
+}
@@ -0,0 +1,319 @@
+//! 会话来源适配器
+//!
+//! 解析栈以 Claude Code 的 JSONL 为准。其他 AI 编程工具（Codex CLI、Aider）的会话历史
+//! 由 [`SessionSourceAdapter`] 转换为 Claude Code 兼容的 [`JsonlEntry`]
+//! （`type`、`uuid`、`parentUuid`、`timestamp`、`message.content`），从而复用消息树构建、
+//! `database::models::Message` 转换、视图等级、全文搜索和优化器。
+//!
+//! 每个监控目录记录自己的来源类型（`monitored_directories.source`），扫描时使用对应的适配器；
+//! 读取单个会话文件时按文件名识别来源。
+
+pub mod aider;
+pub mod claude;
+pub mod codex;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use glob::glob;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::jsonl::JsonlEntry;
+use super::tree::{ConversationTree, MessageTreeBuilder};
+use crate::monitor::scanner::{self, SessionMetadata};
+use crate::session_type_detector::SessionFileType;
+
+pub use aider::AiderAdapter;
+pub use claude::ClaudeCodeAdapter;
+pub use codex::CodexAdapter;
+
+/// 从第一条用户消息生成显示名称时保留的最大字符数
+const DISPLAY_NAME_CHARS: usize = 50;
+
+/// 会话来源类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSourceKind {
+    /// Claude Code（`~/.claude/projects/**/*.jsonl`）
+    #[default]
+    ClaudeCode,
+    /// Codex CLI（`~/.codex/sessions/**/rollout-*.jsonl`）
+    CodexCli,
+    /// Aider（项目目录下的 `.aider.chat.history.md`）
+    Aider,
+}
+
+impl SessionSourceKind {
+    /// 所有来源类型
+    pub const ALL: [SessionSourceKind; 3] = [
+        SessionSourceKind::ClaudeCode,
+        SessionSourceKind::CodexCli,
+        SessionSourceKind::Aider,
+    ];
+
+    /// 来源标识（与序列化值一致，用于数据库存储）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionSourceKind::ClaudeCode => "claude_code",
+            SessionSourceKind::CodexCli => "codex_cli",
+            SessionSourceKind::Aider => "aider",
+        }
+    }
+
+    /// 从来源标识解析
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    /// 获取该来源的适配器
+    pub fn adapter(&self) -> &'static dyn SessionSourceAdapter {
+        match self {
+            SessionSourceKind::ClaudeCode => &ClaudeCodeAdapter,
+            SessionSourceKind::CodexCli => &CodexAdapter,
+            SessionSourceKind::Aider => &AiderAdapter,
+        }
+    }
+}
+
+/// 会话来源适配器
+///
+/// 实现者只需把原生格式转换为 Claude Code 兼容的条目，
+/// 消息树、会话元数据和目录扫描都有基于条目的默认实现。
+pub trait SessionSourceAdapter: Send + Sync {
+    /// 来源类型
+    fn kind(&self) -> SessionSourceKind;
+
+    /// 会话文件名的 glob 模式（相对于任意子目录）
+    fn file_pattern(&self) -> &'static str;
+
+    /// 文件是否属于该来源
+    fn matches(&self, path: &Path) -> bool;
+
+    /// 读取会话文件并转换为 Claude Code 兼容的条目
+    ///
+    /// 条目的 `offset` / `length` 指向原文件中对应的字节范围
+    fn read_entries(&self, path: &Path) -> Result<Vec<JsonlEntry>>;
+
+    /// 查找目录下的会话文件
+    fn discover(&self, directory: &Path) -> Result<Vec<PathBuf>> {
+        let pattern = directory.join("**").join(self.file_pattern());
+        let pattern_str = pattern
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("无效的路径模式"))?;
+
+        Ok(glob(pattern_str)?
+            .flatten()
+            .filter(|path| self.matches(path))
+            .collect())
+    }
+
+    /// 读取会话并构建消息树
+    fn build_tree(&self, path: &Path) -> Result<ConversationTree> {
+        MessageTreeBuilder::build_from_entries(&self.read_entries(path)?)
+    }
+
+    /// 提取会话元数据
+    fn read_metadata(&self, path: &Path) -> Result<SessionMetadata> {
+        let entries = self.read_entries(path)?;
+        metadata_from_entries(path, &entries)
+    }
+
+    /// 扫描目录下的所有会话，无法读取的文件会被跳过
+    fn scan(&self, directory: &Path) -> Result<Vec<SessionMetadata>> {
+        let mut sessions = Vec::new();
+        for path in self.discover(directory)? {
+            match self.read_metadata(&path) {
+                Ok(metadata) => sessions.push(metadata),
+                Err(e) => eprintln!("警告: 读取会话 {:?} 失败: {}", path, e),
+            }
+        }
+        Ok(sessions)
+    }
+}
+
+/// 按文件名识别会话文件的来源适配器（无法识别时视为 Claude Code）
+pub fn adapter_for_path(path: &Path) -> &'static dyn SessionSourceAdapter {
+    [SessionSourceKind::CodexCli, SessionSourceKind::Aider]
+        .into_iter()
+        .map(|kind| kind.adapter())
+        .find(|adapter| adapter.matches(path))
+        .unwrap_or(&ClaudeCodeAdapter)
+}
+
+/// 读取任意来源的会话文件，返回 Claude Code 兼容的条目
+pub fn read_entries(path: &Path) -> Result<Vec<JsonlEntry>> {
+    adapter_for_path(path).read_entries(path)
+}
+
+/// 会话 ID：优先取条目中的 `sessionId`，否则使用文件名
+pub fn session_id_from_entries(path: &Path, entries: &[JsonlEntry]) -> String {
+    entries
+        .iter()
+        .find_map(|entry| entry.data.get("sessionId").and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string()
+        })
+}
+
+/// 由转换后的条目生成会话元数据
+///
+/// 项目路径取条目中的 `cwd`，没有时使用会话文件所在目录
+fn metadata_from_entries(path: &Path, entries: &[JsonlEntry]) -> Result<SessionMetadata> {
+    let file_metadata = std::fs::metadata(path)?;
+    let modified: DateTime<Utc> = file_metadata.modified()?.into();
+    let created: DateTime<Utc> = file_metadata.created().map(Into::into).unwrap_or(modified);
+
+    let project_path = entries
+        .iter()
+        .find_map(|entry| entry.data.get("cwd").and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+        .or_else(|| path.parent().map(|p| p.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    let project_name = Path::new(&project_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_string();
+
+    let timestamp = |entry: &JsonlEntry| {
+        entry
+            .data
+            .get("timestamp")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+
+    Ok(SessionMetadata {
+        session_id: session_id_from_entries(path, entries),
+        project_path,
+        project_name,
+        file_path: path.to_path_buf(),
+        created_at: created.to_rfc3339(),
+        updated_at: modified.to_rfc3339(),
+        message_count: entries.len(),
+        is_active: scanner::is_session_active(path),
+        display_name: entries.iter().find_map(first_user_text),
+        first_timestamp: entries.iter().find_map(timestamp),
+        last_timestamp: entries.iter().rev().find_map(timestamp),
+        file_type: SessionFileType::Main,
+    })
+}
+
+/// 用户文本消息的前若干字符（用作显示名称）
+fn first_user_text(entry: &JsonlEntry) -> Option<String> {
+    if entry.message_type().as_deref() != Some("user") {
+        return None;
+    }
+    let text = match entry.data.get("message")?.get("content")? {
+        Value::String(text) => text.trim(),
+        _ => return None,
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.chars().take(DISPLAY_NAME_CHARS).collect())
+}
+
+/// 按顺序生成线性对话的兼容条目
+///
+/// 为每条消息分配 `{session_id}-{序号}` 形式的 UUID，并把上一条消息设为父节点
+/// （调用 `start_root` 后的第一条消息除外）
+pub(crate) struct EntryBuilder {
+    session_id: String,
+    cwd: Option<String>,
+    parent_uuid: Option<String>,
+    entries: Vec<JsonlEntry>,
+}
+
+impl EntryBuilder {
+    pub(crate) fn new(session_id: String, cwd: Option<String>) -> Self {
+        Self {
+            session_id,
+            cwd,
+            parent_uuid: None,
+            entries: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_session_id(&mut self, session_id: String) {
+        self.session_id = session_id;
+    }
+
+    pub(crate) fn set_cwd(&mut self, cwd: String) {
+        self.cwd = Some(cwd);
+    }
+
+    /// 开始新的对话根：下一条消息没有父节点
+    pub(crate) fn start_root(&mut self) {
+        self.parent_uuid = None;
+    }
+
+    /// 追加一条消息
+    ///
+    /// # 参数
+    /// - `msg_type`: 条目类型（user / assistant / system）
+    /// - `content`: `message.content`（字符串或内容块数组）
+    /// - `timestamp`: 消息时间戳
+    /// - `offset` / `length`: 原文件中的字节范围
+    pub(crate) fn push(
+        &mut self,
+        msg_type: &str,
+        content: Value,
+        timestamp: Option<&str>,
+        offset: u64,
+        length: usize,
+    ) {
+        let uuid = format!("{}-{}", self.session_id, self.entries.len());
+        let data = serde_json::json!({
+            "type": msg_type,
+            "uuid": uuid,
+            "parentUuid": self.parent_uuid,
+            "sessionId": self.session_id,
+            "cwd": self.cwd,
+            "timestamp": timestamp,
+            "message": {
+                "role": msg_type,
+                "content": content,
+            },
+        });
+        self.entries.push(JsonlEntry::new(offset, length, data));
+        self.parent_uuid = Some(uuid);
+    }
+
+    pub(crate) fn finish(self) -> Vec<JsonlEntry> {
+        self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adapter_for_path() {
+        let cases = [
+            (
+                "/home/dev/.codex/sessions/2025/03/10/rollout-2025-03-10T09-00-00-0195f4a1-7c2e-7d31-9b8a-2f1e3c4d5e6f.jsonl",
+                SessionSourceKind::CodexCli,
+            ),
+            (
+                "/home/dev/app/.aider.chat.history.md",
+                SessionSourceKind::Aider,
+            ),
+            (
+                "/home/dev/.claude/projects/-home-dev-app/0195f4a1-7c2e-7d31-9b8a-2f1e3c4d5e6f.jsonl",
+                SessionSourceKind::ClaudeCode,
+            ),
+        ];
+
+        for (path, kind) in cases {
+            assert_eq!(adapter_for_path(Path::new(path)).kind(), kind, "{}", path);
+            assert_eq!(SessionSourceKind::parse(kind.as_str()), Some(kind));
+        }
+    }
+}
@@ -0,0 +1,358 @@
+//! 工具错误分类模块
+//!
+//! `ErrorMessage.error_type` 只是从文本中截取的松散字符串。本模块根据
+//! tool_result 的内容识别错误类别：编译错误（rustc、tsc）、测试失败
+//! （cargo test、jest、pytest）、权限拒绝、文件不存在、超时以及用户中断，
+//! 供按会话和项目统计"在哪类错误上花了最多轮次"。
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::tree::{ConversationTree, MessageNode};
+
+/// 错误消息保留的最大字符数
+const ERROR_MESSAGE_CHARS: usize = 200;
+
+/// rustc 错误码，如 `error[E0308]`
+static RUSTC_ERROR: Lazy<Regex> = Lazy::new(|| Regex::new(r"error\[E\d{4}\]").unwrap());
+
+/// tsc 错误码，如 `error TS2322:`
+static TSC_ERROR: Lazy<Regex> = Lazy::new(|| Regex::new(r"error TS\d+:").unwrap());
+
+/// jest 汇总行，如 `Tests:       1 failed, 3 passed`
+static JEST_SUMMARY: Lazy<Regex> = Lazy::new(|| Regex::new(r"Tests:\s+\d+ failed").unwrap());
+
+/// pytest 汇总行，如 `==== 2 failed, 5 passed in 0.12s ====`
+static PYTEST_SUMMARY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"=+ .*\d+ failed.* in [\d.]+s").unwrap());
+
+/// 工具错误类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorCategory {
+    /// 编译错误（rustc、tsc）
+    CompileError,
+    /// 测试失败（cargo test、jest、pytest）
+    TestFailure,
+    /// 权限拒绝（文件系统权限或用户拒绝授权）
+    PermissionDenied,
+    /// 文件不存在
+    FileNotFound,
+    /// 超时
+    Timeout,
+    /// 用户中断
+    UserInterrupted,
+    /// 其他错误
+    Other,
+}
+
+impl ToolErrorCategory {
+    /// 所有类别
+    pub const ALL: [ToolErrorCategory; 7] = [
+        ToolErrorCategory::CompileError,
+        ToolErrorCategory::TestFailure,
+        ToolErrorCategory::PermissionDenied,
+        ToolErrorCategory::FileNotFound,
+        ToolErrorCategory::Timeout,
+        ToolErrorCategory::UserInterrupted,
+        ToolErrorCategory::Other,
+    ];
+
+    /// 类别标识（与序列化值一致，用于数据库存储）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolErrorCategory::CompileError => "compile_error",
+            ToolErrorCategory::TestFailure => "test_failure",
+            ToolErrorCategory::PermissionDenied => "permission_denied",
+            ToolErrorCategory::FileNotFound => "file_not_found",
+            ToolErrorCategory::Timeout => "timeout",
+            ToolErrorCategory::UserInterrupted => "user_interrupted",
+            ToolErrorCategory::Other => "other",
+        }
+    }
+
+    /// 从类别标识解析
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.as_str() == value)
+    }
+
+    /// 写入 `ErrorMessage.error_type` 的类型名
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            ToolErrorCategory::CompileError => "CompileError",
+            ToolErrorCategory::TestFailure => "TestFailure",
+            ToolErrorCategory::PermissionDenied => "PermissionDenied",
+            ToolErrorCategory::FileNotFound => "FileNotFound",
+            ToolErrorCategory::Timeout => "Timeout",
+            ToolErrorCategory::UserInterrupted => "UserInterrupted",
+            ToolErrorCategory::Other => "ToolError",
+        }
+    }
+}
+
+/// 分类结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolErrorClass {
+    /// 错误类别
+    pub category: ToolErrorCategory,
+
+    /// 产生错误的工具链（rustc、tsc、cargo test、jest、pytest），无法识别时为 None
+    pub origin: Option<&'static str>,
+}
+
+impl ToolErrorClass {
+    fn new(category: ToolErrorCategory, origin: Option<&'static str>) -> Self {
+        Self { category, origin }
+    }
+}
+
+/// 工具错误分类器
+pub struct ToolErrorClassifier;
+
+impl ToolErrorClassifier {
+    /// 对出错的 tool_result 内容分类
+    ///
+    /// 按以下顺序匹配，先命中者生效：用户拒绝授权、用户中断、测试失败、编译错误、
+    /// 权限拒绝、文件不存在、超时，都不匹配时为 `Other`。
+    /// 测试先于编译判断，因为 `cargo test` 的输出中也会出现编译信息。
+    /// 用户拒绝执行工具归为权限拒绝，只有中断正在进行的请求才算用户中断。
+    pub fn classify(text: &str) -> ToolErrorClass {
+        use ToolErrorCategory::*;
+
+        let lower = text.to_lowercase();
+
+        if text.contains("The user doesn't want to proceed with this tool use") {
+            return ToolErrorClass::new(PermissionDenied, None);
+        }
+
+        if text.contains("[Request interrupted by user") || lower.contains("interrupted by user") {
+            return ToolErrorClass::new(UserInterrupted, None);
+        }
+
+        if text.contains("test result: FAILED") || text.contains("error: test failed") {
+            return ToolErrorClass::new(TestFailure, Some("cargo test"));
+        }
+        if JEST_SUMMARY.is_match(text)
+            || (text.contains("Test Suites:") && lower.contains("failed"))
+        {
+            return ToolErrorClass::new(TestFailure, Some("jest"));
+        }
+        if PYTEST_SUMMARY.is_match(text) || text.contains("short test summary info") {
+            return ToolErrorClass::new(TestFailure, Some("pytest"));
+        }
+
+        if RUSTC_ERROR.is_match(text) || text.contains("error: could not compile") {
+            return ToolErrorClass::new(CompileError, Some("rustc"));
+        }
+        if TSC_ERROR.is_match(text) {
+            return ToolErrorClass::new(CompileError, Some("tsc"));
+        }
+
+        if lower.contains("permission denied")
+            || text.contains("EACCES")
+            || text.contains("EPERM")
+            || lower.contains("operation not permitted")
+            || lower.contains("requested permissions")
+        {
+            return ToolErrorClass::new(PermissionDenied, None);
+        }
+
+        if lower.contains("no such file or directory")
+            || text.contains("ENOENT")
+            || lower.contains("file does not exist")
+            || lower.contains("file not found")
+            || lower.contains("cannot find the path")
+        {
+            return ToolErrorClass::new(FileNotFound, None);
+        }
+
+        if lower.contains("timed out")
+            || text.contains("ETIMEDOUT")
+            || lower.contains("deadline exceeded")
+        {
+            return ToolErrorClass::new(Timeout, None);
+        }
+
+        ToolErrorClass::new(Other, None)
+    }
+
+    /// 错误摘要：第一个包含错误特征的行，找不到时取第一个非空行
+    pub fn summary_line(text: &str) -> String {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let line = lines
+            .clone()
+            .find(|line| {
+                let lower = line.to_lowercase();
+                lower.starts_with("error") || lower.contains("failed") || lower.contains("denied")
+            })
+            .or_else(|| lines.next())
+            .unwrap_or("");
+
+        match line.char_indices().nth(ERROR_MESSAGE_CHARS) {
+            Some((idx, _)) => format!("{}...", &line[..idx]),
+            None => line.to_string(),
+        }
+    }
+}
+
+/// 已分类的工具错误（用于持久化和统计）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolErrorRecord {
+    /// 发起工具调用的消息 UUID
+    pub uuid: String,
+
+    /// 工具名称
+    pub tool_name: Option<String>,
+
+    /// 错误类别
+    pub category: ToolErrorCategory,
+
+    /// 产生错误的工具链
+    pub origin: Option<String>,
+
+    /// 错误摘要
+    pub message: String,
+
+    /// 消息时间戳
+    pub timestamp: Option<String>,
+}
+
+/// 从已提取元数据的消息树中收集所有已分类的工具错误
+pub fn collect_tool_errors(tree: &ConversationTree) -> Vec<ToolErrorRecord> {
+    let mut records = Vec::new();
+    let mut stack: Vec<&MessageNode> = tree.roots.iter().rev().collect();
+
+    while let Some(node) = stack.pop() {
+        if let Some(ref metadata) = node.metadata {
+            for error in &metadata.errors {
+                let category = match error.category {
+                    Some(category) => category,
+                    None => continue,
+                };
+                records.push(ToolErrorRecord {
+                    uuid: node.id.clone(),
+                    tool_name: error.related_tool.clone(),
+                    category,
+                    origin: error.origin.clone(),
+                    message: error.message.clone(),
+                    timestamp: node
+                        .message_data
+                        .get("timestamp")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string()),
+                });
+            }
+        }
+        stack.extend(node.children.iter().rev());
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::jsonl::JsonlEntry;
+    use crate::parser::tree::MessageTreeBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn test_classify_tool_errors() {
+        use ToolErrorCategory::*;
+
+        let cases = [
+            (
+                "error[E0308]: mismatched types\n --> src/main.rs:4:5",
+                CompileError,
+                Some("rustc"),
+            ),
+            (
+                "src/app.ts(3,7): error TS2322: Type 'string' is not assignable",
+                CompileError,
+                Some("tsc"),
+            ),
+            (
+                "running 3 tests\ntest a ... FAILED\n\ntest result: FAILED. 2 passed; 1 failed",
+                TestFailure,
+                Some("cargo test"),
+            ),
+            (
+                "Tests:       1 failed, 4 passed, 5 total",
+                TestFailure,
+                Some("jest"),
+            ),
+            (
+                "========= 2 failed, 10 passed in 0.53s =========",
+                TestFailure,
+                Some("pytest"),
+            ),
+            (
+                "bash: ./deploy.sh: Permission denied",
+                PermissionDenied,
+                None,
+            ),
+            (
+                "cat: missing.txt: No such file or directory",
+                FileNotFound,
+                None,
+            ),
+            ("Command timed out after 2m 0.0s", Timeout, None),
+            (
+                "[Request interrupted by user for tool use]",
+                UserInterrupted,
+                None,
+            ),
+            (
+                "The user doesn't want to proceed with this tool use. The tool use was rejected \
+                 (eg. if it was a file edit, the new_string was NOT written to the file).",
+                PermissionDenied,
+                None,
+            ),
+            ("Exit code 1", Other, None),
+        ];
+
+        for (text, category, origin) in cases {
+            let class = ToolErrorClassifier::classify(text);
+            assert_eq!(class.category, category, "{}", text);
+            assert_eq!(class.origin, origin, "{}", text);
+            assert_eq!(ToolErrorCategory::parse(category.as_str()), Some(category));
+        }
+
+        assert_eq!(
+            ToolErrorClassifier::summary_line("Compiling app\nerror[E0425]: cannot find value"),
+            "error[E0425]: cannot find value"
+        );
+    }
+
+    #[test]
+    fn test_collect_tool_errors_from_tree() {
+        let entries: Vec<JsonlEntry> = [
+            json!({"uuid": "u1", "type": "user", "message": {"role": "user", "content": "build it"}}),
+            json!({"uuid": "a1", "parentUuid": "u1", "type": "assistant",
+                   "timestamp": "2025-01-01T10:00:00Z",
+                   "message": {"role": "assistant", "content": [
+                       {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "cargo build"}}]}}),
+            json!({"uuid": "r1", "parentUuid": "a1", "type": "user",
+                   "message": {"role": "user", "content": [
+                       {"type": "tool_result", "tool_use_id": "t1", "is_error": true,
+                        "content": "   Compiling app v0.1.0\nerror[E0308]: mismatched types"}]}}),
+        ]
+        .into_iter()
+        .map(|data| JsonlEntry::new(0, 0, data))
+        .collect();
+
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        let records = collect_tool_errors(&tree);
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].uuid, "a1");
+        assert_eq!(records[0].tool_name.as_deref(), Some("Bash"));
+        assert_eq!(records[0].category, ToolErrorCategory::CompileError);
+        assert_eq!(records[0].origin.as_deref(), Some("rustc"));
+        assert_eq!(records[0].message, "error[E0308]: mismatched types");
+    }
+}
@@ -7,9 +7,15 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 
+use super::branch::BranchLabel;
 use super::extractor::MetadataExtractor;
-use super::jsonl::JsonlEntry;
+use super::jsonl::{JsonlEntry, JsonlParser};
+use super::tool_error::ToolErrorCategory;
+use crate::session_type_detector::{detect_session_type_by_filename, SessionFileType};
 
 /// 消息元数据
 ///
@@ -40,8 +46,24 @@ pub struct ToolCall {
     /// 工具输入参数
     pub input: serde_json::Value,
 
-    /// 调用状态（success/error）
+    /// 调用状态（success/error/pending）
     pub status: String,
+
+    /// tool_use 块的 ID（用于与 tool_result 配对）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// 配对的 tool_result 内容（截断后）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+
+    /// tool_result 是否标记为错误（未配对时为 None）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_error: Option<bool>,
+
+    /// 从调用到返回结果的耗时（毫秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
 }
 
 /// 错误消息
@@ -56,6 +78,14 @@ pub struct ErrorMessage {
 
     /// 相关的工具名称（如果有）
     pub related_tool: Option<String>,
+
+    /// 错误类别（仅由 tool_result 分类得到的错误有值）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<ToolErrorCategory>,
+
+    /// 产生错误的工具链（rustc、tsc、cargo test、jest、pytest）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
 }
 
 /// 代码变更记录
@@ -116,14 +146,27 @@ pub struct MessageNode {
     /// 消息类型（缓存的 type 字段，方便前端使用）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub msg_type: Option<String>,
+
+    /// 是否为侧链消息（来自 agent-*.jsonl 的子代理会话）
+    #[serde(rename = "sidechain", default)]
+    pub is_sidechain: bool,
+
+    /// 侧链所属的子代理 ID（仅侧链消息有值）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sidechain_agent_id: Option<String>,
+
+    /// 分支标记（仅编辑、回退或重试产生的兄弟分支的首个节点有值）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<BranchLabel>,
 }
 
 impl MessageNode {
     /// 创建新的消息节点
     pub fn new(id: String, parent_id: Option<String>, message_data: Value) -> Self {
-        // 提取并缓存 role 和 type 字段
+        // 提取并缓存 role 和 type 字段（Claude Code 原始格式中 role 位于 message.role）
         let role = message_data
             .get("role")
+            .or_else(|| message_data.get("message").and_then(|m| m.get("role")))
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
         let msg_type = message_data
@@ -134,6 +177,12 @@ impl MessageNode {
         // 提取消息内容
         let (content, full_content) = Self::extract_message_content(&message_data, role.as_deref());
 
+        // 子代理会话中的条目带有 isSidechain 标记
+        let is_sidechain = message_data
+            .get("isSidechain")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
         Self {
             id,
             parent_id,
@@ -146,6 +195,9 @@ impl MessageNode {
             full_content,
             role,
             msg_type,
+            is_sidechain,
+            sidechain_agent_id: None,
+            branch: None,
         }
     }
 
@@ -284,6 +336,47 @@ impl MessageNode {
     pub fn is_assistant_message(&self) -> bool {
         self.role().as_deref() == Some("assistant")
     }
+
+    /// 检查是否为压缩边界（`type: system`, `subtype: compact_boundary`）
+    pub fn is_compact_boundary(&self) -> bool {
+        super::segment::is_compact_boundary(&self.message_data)
+    }
+
+    /// 检查是否为压缩摘要消息（`isCompactSummary: true`）
+    pub fn is_compact_summary(&self) -> bool {
+        super::segment::is_compact_summary(&self.message_data)
+    }
+
+    /// 获取内容块数组
+    ///
+    /// 兼容顶层 `content` 和 Claude Code 原始格式中的 `message.content`
+    pub fn content_blocks(&self) -> Option<&Vec<Value>> {
+        self.message_data
+            .get("content")
+            .and_then(|v| v.as_array())
+            .or_else(|| {
+                self.message_data
+                    .get("message")
+                    .and_then(|m| m.get("content"))
+                    .and_then(|v| v.as_array())
+            })
+    }
+
+    /// 检查节点是否包含指定 ID 的 tool_use 块
+    pub fn has_tool_use_id(&self, tool_use_id: &str) -> bool {
+        if self.is_tool_use()
+            && self.message_data.get("id").and_then(|v| v.as_str()) == Some(tool_use_id)
+        {
+            return true;
+        }
+
+        self.content_blocks().is_some_and(|blocks| {
+            blocks.iter().any(|block| {
+                block.get("type").and_then(|v| v.as_str()) == Some("tool_use")
+                    && block.get("id").and_then(|v| v.as_str()) == Some(tool_use_id)
+            })
+        })
+    }
 }
 
 /// 完整的对话树
@@ -323,6 +416,21 @@ impl ConversationTree {
         self.roots.push(root);
     }
 
+    /// 查找第一个满足条件的节点
+    pub fn find_node_where<F>(&self, predicate: F) -> Option<&MessageNode>
+    where
+        F: Fn(&MessageNode) -> bool,
+    {
+        let mut stack: Vec<&MessageNode> = self.roots.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            if predicate(node) {
+                return Some(node);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        None
+    }
+
     /// 计算节点深度
     fn calculate_depth(&self, node: &MessageNode, current_depth: usize) -> usize {
         let mut max_child_depth = current_depth;
@@ -343,6 +451,355 @@ impl ConversationTree {
     }
 }
 
+/// 消息树的节点索引
+///
+/// 记录每个节点的父节点和它在父节点 `children`（根节点为 `roots`）中的下标，
+/// 增量更新时按 ID 定位节点，无需遍历整棵树。树中的节点只会被追加，
+/// 已有节点的下标保持不变。
+#[derive(Debug, Default)]
+pub struct NodeIndex {
+    /// 节点 ID → 位置
+    nodes: HashMap<String, NodePosition>,
+
+    /// 最近一次 `take_attached` 之后挂载的子树根节点 ID（按挂载顺序）
+    attached: Vec<String>,
+
+    /// tool_use ID → 发起调用的节点 ID
+    tool_calls: HashMap<String, String>,
+
+    /// 最近一次 `take_updated` 之后内容发生变化的已有节点 ID
+    updated: Vec<String>,
+}
+
+/// 节点在树中的位置
+#[derive(Debug)]
+struct NodePosition {
+    /// 父节点 ID（根节点为 None）
+    parent_id: Option<String>,
+
+    /// 在父节点 `children` 或 `roots` 中的下标
+    index: usize,
+
+    /// 子树内最后一条消息的时间
+    last_activity: Option<String>,
+}
+
+impl NodeIndex {
+    /// 为整棵树建立索引
+    pub fn build(tree: &ConversationTree) -> Self {
+        let mut index = Self::default();
+        for (position, root) in tree.roots.iter().enumerate() {
+            index.insert_subtree(None, position, root);
+        }
+        index
+    }
+
+    /// 是否包含指定 ID 的节点
+    pub fn contains(&self, id: &str) -> bool {
+        self.nodes.contains_key(id)
+    }
+
+    /// 节点的父节点 ID（根节点或节点不存在时为 None）
+    pub fn parent_id(&self, id: &str) -> Option<&str> {
+        self.nodes.get(id)?.parent_id.as_deref()
+    }
+
+    /// 节点的祖先 ID（从父节点到根节点）
+    pub fn ancestors<'a>(&'a self, id: &str) -> impl Iterator<Item = &'a str> + 'a {
+        let mut current = self.parent_id(id);
+        std::iter::from_fn(move || {
+            let id = current?;
+            current = self.parent_id(id);
+            Some(id)
+        })
+    }
+
+    /// 节点子树内最后一条消息的时间（含侧链）
+    pub fn last_activity(&self, id: &str) -> Option<&str> {
+        self.nodes.get(id)?.last_activity.as_deref()
+    }
+
+    /// 按 ID 查找节点
+    pub fn get<'a>(&self, tree: &'a ConversationTree, id: &str) -> Option<&'a MessageNode> {
+        let path = self.path(id)?;
+        let (first, rest) = path.split_first()?;
+        let mut node = tree.roots.get(*first)?;
+        for &position in rest {
+            node = node.children.get(position)?;
+        }
+        Some(node)
+    }
+
+    /// 按 ID 查找节点（可变引用）
+    pub fn get_mut<'a>(
+        &self,
+        tree: &'a mut ConversationTree,
+        id: &str,
+    ) -> Option<&'a mut MessageNode> {
+        let path = self.path(id)?;
+        let (first, rest) = path.split_first()?;
+        let mut node = tree.roots.get_mut(*first)?;
+        for &position in rest {
+            node = node.children.get_mut(position)?;
+        }
+        Some(node)
+    }
+
+    /// 将子树挂载到 `parent_id` 下（None 表示作为新的根节点），同时更新索引、
+    /// 子树中各节点的深度以及树的总数和最大深度
+    ///
+    /// 返回挂载的节点数量；父节点不存在时不挂载，返回 0
+    pub fn attach(
+        &mut self,
+        tree: &mut ConversationTree,
+        parent_id: Option<&str>,
+        mut node: MessageNode,
+    ) -> usize {
+        let (depth, siblings) = match parent_id {
+            Some(parent_id) => match self.get_mut(tree, parent_id) {
+                Some(parent) => (parent.depth + 1, &mut parent.children),
+                None => return 0,
+            },
+            None => (0, &mut tree.roots),
+        };
+
+        // 子树内的深度相对于挂载点重新计算
+        let mut stack: Vec<(&mut MessageNode, usize)> = vec![(&mut node, depth)];
+        while let Some((current, current_depth)) = stack.pop() {
+            current.depth = current_depth;
+            stack.extend(
+                current
+                    .children
+                    .iter_mut()
+                    .map(|child| (child, current_depth + 1)),
+            );
+        }
+
+        let id = node.id.clone();
+        let position = siblings.len();
+        siblings.push(node);
+        let (count, max_depth) = self.insert_subtree(
+            parent_id.map(|s| s.to_string()),
+            position,
+            &siblings[position],
+        );
+
+        tree.total_count += count;
+        tree.max_depth = tree.max_depth.max(max_depth);
+
+        // 新子树的最后活动时间向上汇总到祖先
+        let activity = self.last_activity(&id).map(|s| s.to_string());
+        let mut current = parent_id.map(|s| s.to_string());
+        while let Some(ancestor) = current {
+            if !self.touch(&ancestor, activity.as_deref()) {
+                break;
+            }
+            current = self.parent_id(&ancestor).map(|s| s.to_string());
+        }
+
+        self.attached.push(id);
+        count
+    }
+
+    /// 取出最近挂载的子树根节点 ID（按挂载顺序）
+    pub fn take_attached(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.attached)
+    }
+
+    /// 发起指定工具调用的节点 ID
+    pub fn tool_call_node(&self, tool_use_id: &str) -> Option<&str> {
+        self.tool_calls.get(tool_use_id).map(|s| s.as_str())
+    }
+
+    /// 记录内容发生变化的已有节点（如工具调用被回填、分支标记改变）
+    pub fn mark_updated(&mut self, id: &str) {
+        self.updated.push(id.to_string());
+    }
+
+    /// 取出最近更新的已有节点 ID（按首次更新顺序，已去重）
+    pub fn take_updated(&mut self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut updated = std::mem::take(&mut self.updated);
+        updated.retain(|id| seen.insert(id.clone()));
+        updated
+    }
+
+    /// 从根到节点的下标路径
+    fn path(&self, id: &str) -> Option<Vec<usize>> {
+        let mut path = Vec::new();
+        let mut current = self.nodes.get(id)?;
+        loop {
+            path.push(current.index);
+            match current.parent_id {
+                Some(ref parent_id) => current = self.nodes.get(parent_id)?,
+                None => break,
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// 索引整棵子树，返回节点数量和子树中的最大深度
+    fn insert_subtree(
+        &mut self,
+        parent_id: Option<String>,
+        position: usize,
+        root: &MessageNode,
+    ) -> (usize, usize) {
+        let mut count = 0;
+        let mut max_depth = 0;
+        let mut order = Vec::new();
+        let mut stack = vec![(parent_id, position, root)];
+        while let Some((parent_id, index, node)) = stack.pop() {
+            count += 1;
+            max_depth = max_depth.max(node.depth);
+            let last_activity = node
+                .message_data
+                .get("timestamp")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            self.nodes.insert(
+                node.id.clone(),
+                NodePosition {
+                    parent_id,
+                    index,
+                    last_activity,
+                },
+            );
+            order.push(node);
+            if let Some(ref metadata) = node.metadata {
+                for call in &metadata.tool_calls {
+                    if let Some(ref tool_use_id) = call.id {
+                        self.tool_calls.insert(tool_use_id.clone(), node.id.clone());
+                    }
+                }
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .enumerate()
+                    .map(|(index, child)| (Some(node.id.clone()), index, child)),
+            );
+        }
+
+        // 父节点总是先于子节点出栈，逆序即可把子节点的活动时间汇总到父节点
+        for node in order.iter().skip(1).rev() {
+            let position = &self.nodes[&node.id];
+            if let Some(parent_id) = position.parent_id.clone() {
+                let activity = position.last_activity.clone();
+                self.touch(&parent_id, activity.as_deref());
+            }
+        }
+        (count, max_depth)
+    }
+
+    /// 用更晚的活动时间更新节点，返回是否发生了更新
+    fn touch(&mut self, id: &str, activity: Option<&str>) -> bool {
+        match self.nodes.get_mut(id) {
+            Some(position) if activity > position.last_activity.as_deref() => {
+                position.last_activity = activity.map(|s| s.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 增量合并中尚未挂载的新节点
+///
+/// 父节点也是新节点的条目先在这里组装成子树，每棵子树只需定位一次挂载点
+#[derive(Default)]
+struct PendingNodes {
+    /// 节点 ID → 节点
+    nodes: HashMap<String, MessageNode>,
+
+    /// 父节点 ID → 子节点 ID（按文件顺序）
+    children: HashMap<String, Vec<String>>,
+
+    /// 子树根节点及其挂载点（按文件顺序，挂载点为 None 表示新的根节点）
+    roots: Vec<(Option<String>, String)>,
+}
+
+impl PendingNodes {
+    fn contains(&self, id: &str) -> bool {
+        self.nodes.contains_key(id)
+    }
+
+    /// 加入新节点，`attach_to` 为父节点 ID（None 表示新的根节点）
+    ///
+    /// 父节点既不在新节点中也不在树中时丢弃该节点
+    fn push(&mut self, node: MessageNode, attach_to: Option<String>, index: &NodeIndex) {
+        match attach_to {
+            Some(parent) if self.nodes.contains_key(&parent) => {
+                self.children
+                    .entry(parent)
+                    .or_default()
+                    .push(node.id.clone());
+            }
+            Some(parent) if index.contains(&parent) => {
+                self.roots.push((Some(parent), node.id.clone()));
+            }
+            Some(_) => return,
+            None => self.roots.push((None, node.id.clone())),
+        }
+        self.nodes.insert(node.id.clone(), node);
+    }
+
+    /// 把组装好的子树挂载到树上，返回挂载的节点数量和各子树的根节点 ID
+    fn attach(
+        mut self,
+        tree: &mut ConversationTree,
+        index: &mut NodeIndex,
+    ) -> (usize, Vec<String>) {
+        let mut count = 0;
+        let mut attached = Vec::new();
+        for (parent_id, root_id) in std::mem::take(&mut self.roots) {
+            if let Some(subtree) = self.take_subtree(&root_id) {
+                let added = index.attach(tree, parent_id.as_deref(), subtree);
+                if added > 0 {
+                    count += added;
+                    attached.push(root_id);
+                }
+            }
+        }
+        (count, attached)
+    }
+
+    /// 取出以 `root_id` 为根的子树（先序收集，再逆序把子节点放回父节点）
+    fn take_subtree(&mut self, root_id: &str) -> Option<MessageNode> {
+        let mut order = Vec::new();
+        let mut stack = vec![root_id.to_string()];
+        while let Some(id) = stack.pop() {
+            if let Some(children) = self.children.get(&id) {
+                stack.extend(children.iter().cloned());
+            }
+            order.push(id);
+        }
+
+        let mut built: HashMap<String, MessageNode> = HashMap::new();
+        for id in order.into_iter().rev() {
+            let mut node = self.nodes.remove(&id)?;
+            for child_id in self.children.remove(&id).unwrap_or_default() {
+                if let Some(child) = built.remove(&child_id) {
+                    node.children.push(child);
+                }
+            }
+            built.insert(id, node);
+        }
+        built.remove(root_id)
+    }
+}
+
+/// 子代理会话文件的增量读取状态
+///
+/// 记录每个已挂载 agent 文件的解析器（含偏移量）和子代理 ID，
+/// 以及已确认不属于当前会话的 agent 文件
+#[derive(Debug, Default)]
+pub struct SidechainCursor {
+    parsers: HashMap<PathBuf, (JsonlParser, String)>,
+    foreign: HashSet<PathBuf>,
+}
+
 /// 消息树构建器
 ///
 /// 从 JSONL 条目列表构建消息树，使用迭代算法避免栈溢出
@@ -355,6 +812,9 @@ pub struct MessageTreeBuilder {
 
     /// 根节点 ID 集合（没有父节点的节点）
     root_ids: HashSet<String>,
+
+    /// 根节点 ID（按文件顺序，用于保证会话分段的先后顺序）
+    root_order: Vec<String>,
 }
 
 impl MessageTreeBuilder {
@@ -364,6 +824,7 @@ impl MessageTreeBuilder {
             node_map: HashMap::new(),
             child_to_parent: HashMap::new(),
             root_ids: HashSet::new(),
+            root_order: Vec::new(),
         }
     }
 
@@ -392,12 +853,11 @@ impl MessageTreeBuilder {
         // 第一遍扫描：创建所有节点
         for entry in entries {
             // 提取消息 ID（uuid 字段）
-            let id = entry
-                .data
-                .get("uuid")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| anyhow::anyhow!("消息缺少 uuid 字段"))?
-                .to_string();
+            // 没有 uuid 的条目（如 `type: summary` 标题行）不参与构建
+            let id = match entry.data.get("uuid").and_then(|v| v.as_str()) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
 
             // 提取父消息 ID（parentUuid 字段，可能不存在）
             let parent_id = entry
@@ -417,7 +877,9 @@ impl MessageTreeBuilder {
                 builder.child_to_parent.insert(id.clone(), parent.clone());
             } else {
                 // 没有父节点，是根节点
-                builder.root_ids.insert(id.clone());
+                if builder.root_ids.insert(id.clone()) {
+                    builder.root_order.push(id.clone());
+                }
             }
         }
 
@@ -425,13 +887,13 @@ impl MessageTreeBuilder {
         let mut tree = ConversationTree::new();
 
         // 处理每个根节点
-        for root_id in &builder.root_ids {
+        for root_id in &builder.root_order {
             if let Some(root_node) = builder.node_map.get(root_id) {
                 // 克隆根节点（因为我们需要修改它）
                 let built_root = builder.build_tree_iterative(root_node)?;
 
-                // 只保留 User 消息作为根节点（过滤掉其他类型的根节点）
-                if built_root.is_user_message() {
+                // 只保留 User 消息和压缩边界作为根节点（过滤掉其他类型的根节点）
+                if built_root.is_user_message() || built_root.is_compact_boundary() {
                     tree.add_root(built_root);
                 }
             }
@@ -446,6 +908,373 @@ impl MessageTreeBuilder {
         Ok(tree)
     }
 
+    /// 将新追加的条目合并到已有的对话树中
+    ///
+    /// 用于增量解析：只处理 `JsonlParser::parse_incremental` 返回的新条目，
+    /// 无需重新构建整棵树。
+    ///
+    /// # 参数
+    /// * `tree` - 已有的对话树
+    /// * `index` - `tree` 的节点索引，新节点挂载后同步更新
+    /// * `entries` - 新追加的 JSONL 条目（按文件顺序）
+    ///
+    /// # 返回
+    /// 返回实际挂载到树上的节点数量；新子树的根节点可通过 `NodeIndex::take_attached` 获取
+    ///
+    /// # 规则
+    /// 与 `build_from_entries` 保持一致：
+    /// - 父节点已在树中（或是本批新节点） → 作为子节点挂载
+    /// - 没有父节点的 User 消息或压缩边界 → 作为新的根节点
+    /// - 其他情况（父节点不存在或被过滤） → 丢弃
+    pub fn append_entries(
+        tree: &mut ConversationTree,
+        index: &mut NodeIndex,
+        entries: &[JsonlEntry],
+    ) -> Result<usize> {
+        let mut pending = PendingNodes::default();
+
+        for entry in entries {
+            // 没有 uuid 的条目（如 summary 行）不参与构建
+            let id = match entry.data.get("uuid").and_then(|v| v.as_str()) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+
+            // 重复的条目（如文件被重写后重新读取）直接跳过
+            if index.contains(&id) || pending.contains(&id) {
+                continue;
+            }
+
+            let parent_id = entry
+                .data
+                .get("parentUuid")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+
+            let mut node = MessageNode::new(id, parent_id.clone(), entry.data.clone());
+            node.metadata = Some(
+                MetadataExtractor::extract_metadata_from_node(&node)
+                    .context("提取消息元数据失败")?,
+            );
+
+            if parent_id.is_none() && !node.is_user_message() && !node.is_compact_boundary() {
+                continue;
+            }
+            pending.push(node, parent_id, index);
+        }
+
+        let (appended, roots) = pending.attach(tree, index);
+
+        // 新条目可能包含已有调用的结果，只需配对新子树
+        if appended > 0 {
+            MetadataExtractor::pair_appended_tool_results(tree, index, &roots);
+        }
+
+        Ok(appended)
+    }
+
+    /// 将同目录下的子代理会话（agent-*.jsonl）拼接到主会话树中
+    ///
+    /// 通过 Task 工具启动的子代理会写入独立的 `agent-{id}.jsonl` 文件。
+    /// 此方法扫描主会话所在的项目目录，找到属于该会话的 agent 文件，
+    /// 将其条目挂载到发起调用的 tool_use 节点下，所有节点标记为侧链。
+    /// `cursor` 记住每个 agent 文件的解析偏移量，重复调用时只读取新追加的条目，
+    /// 并按节点 ID 合并到已挂载的侧链中。
+    ///
+    /// # 参数
+    /// * `tree` - 主会话的对话树
+    /// * `index` - `tree` 的节点索引，新节点挂载后同步更新
+    /// * `session_file` - 主会话 JSONL 文件路径
+    /// * `cursor` - agent 文件的增量读取状态
+    ///
+    /// # 返回
+    /// 返回本次有新节点挂载的子代理会话数量
+    ///
+    /// # 规则
+    /// - 没有 `sessionId` 或 `sessionId` 不是当前会话的 agent 文件不会被拼接
+    /// - 找不到发起调用的 tool_use 节点时不记录偏移量，下次调用重新读取
+    pub fn attach_sidechains(
+        tree: &mut ConversationTree,
+        index: &mut NodeIndex,
+        session_file: &Path,
+        cursor: &mut SidechainCursor,
+    ) -> Result<usize> {
+        let project_dir = match session_file.parent() {
+            Some(dir) => dir,
+            None => return Ok(0),
+        };
+        let session_id = session_file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+
+        // 收集 agent 文件（按文件名排序，保证结果稳定）
+        let mut agent_files: Vec<_> = std::fs::read_dir(project_dir)
+            .with_context(|| format!("读取项目目录失败: {:?}", project_dir))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| detect_session_type_by_filename(path) == SessionFileType::Agent)
+            .filter(|path| !cursor.foreign.contains(path))
+            .collect();
+        agent_files.sort();
+
+        let mut attached = 0;
+
+        for agent_file in agent_files {
+            let (mut parser, known_agent_id) = match cursor.parsers.remove(&agent_file) {
+                Some((parser, agent_id)) => (parser, Some(agent_id)),
+                None => {
+                    // 只拼接明确属于该会话的 agent 文件
+                    match Self::read_agent_session_id(&agent_file) {
+                        Some(agent_session_id) if agent_session_id == session_id => {}
+                        Some(_) => {
+                            cursor.foreign.insert(agent_file);
+                            continue;
+                        }
+                        // 还没有写入 sessionId 的文件下次再检查
+                        None => continue,
+                    }
+
+                    match JsonlParser::new(agent_file.clone()) {
+                        Ok(parser) => (parser, None),
+                        Err(e) => {
+                            eprintln!("⚠️  打开子代理会话失败 {:?}: {}", agent_file, e);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            let entries = match parser.parse_incremental() {
+                Ok(result) => result.entries,
+                Err(e) => {
+                    eprintln!("⚠️  解析子代理会话失败 {:?}: {}", agent_file, e);
+                    continue;
+                }
+            };
+
+            let was_attached = known_agent_id.is_some();
+            let agent_id = known_agent_id.unwrap_or_else(|| {
+                entries
+                    .iter()
+                    .find_map(|e| e.data.get("agentId").and_then(|v| v.as_str()))
+                    .map(|s| s.to_string())
+                    .or_else(|| {
+                        agent_file
+                            .file_stem()
+                            .and_then(|s| s.to_str())
+                            .and_then(|s| s.strip_prefix("agent-"))
+                            .map(|s| s.to_string())
+                    })
+                    .unwrap_or_default()
+            });
+
+            let merged = Self::attach_sidechain_entries(tree, index, &agent_id, &entries)?;
+            if merged {
+                attached += 1;
+            }
+
+            // 侧链挂载成功后才记录偏移量，否则下次从头重新匹配
+            if merged || was_attached {
+                cursor.parsers.insert(agent_file, (parser, agent_id));
+            }
+        }
+
+        Ok(attached)
+    }
+
+    /// 将单个子代理会话的条目合并到主会话树中
+    ///
+    /// 按节点 ID 合并：已在树中的条目被跳过，父节点已在树中的条目挂载为其子节点，
+    /// 子代理的根条目挂载到发起调用的 tool_use 节点下。因此可以反复传入同一个
+    /// agent 文件新追加的条目。
+    ///
+    /// # 参数
+    /// * `tree` - 主会话的对话树
+    /// * `index` - `tree` 的节点索引，新节点挂载后同步更新
+    /// * `agent_id` - 子代理 ID
+    /// * `entries` - 子代理会话的 JSONL 条目（按文件顺序）
+    ///
+    /// # 返回
+    /// 有新节点挂载时返回 true；找不到发起调用的 tool_use 节点或没有新条目时返回 false
+    ///
+    /// # 匹配规则
+    /// 1. 主会话中 `toolUseResult.agentId` 等于子代理 ID 的 tool_result，取其 `tool_use_id`
+    /// 2. 回退：子代理首条用户消息与 Task 工具的 `input.prompt` 完全一致
+    pub fn attach_sidechain_entries(
+        tree: &mut ConversationTree,
+        index: &mut NodeIndex,
+        agent_id: &str,
+        entries: &[JsonlEntry],
+    ) -> Result<bool> {
+        let mut pending = PendingNodes::default();
+
+        // 发起调用的 tool_use 节点 ID（遇到第一个侧链根节点时确定）
+        let mut target_id: Option<String> = None;
+
+        for entry in entries {
+            // 没有 uuid 的条目（如 summary 行）不参与构建
+            let id = match entry.data.get("uuid").and_then(|v| v.as_str()) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+
+            // 已挂载的节点直接跳过
+            if index.contains(&id) || pending.contains(&id) {
+                continue;
+            }
+
+            let parent_id = entry
+                .data
+                .get("parentUuid")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+
+            let mut node = MessageNode::new(id, parent_id.clone(), entry.data.clone());
+            node.metadata = Some(
+                MetadataExtractor::extract_metadata_from_node(&node)
+                    .context("提取消息元数据失败")?,
+            );
+            node.is_sidechain = true;
+            node.sidechain_agent_id = Some(agent_id.to_string());
+
+            let attach_to = match parent_id {
+                Some(parent) => parent,
+                None => {
+                    // 与 build_from_entries 一致，只保留 User 消息和压缩边界作为根节点
+                    if !node.is_user_message() && !node.is_compact_boundary() {
+                        continue;
+                    }
+                    if target_id.is_none() {
+                        target_id = Self::find_sidechain_target(tree, agent_id, &node);
+                    }
+                    match target_id {
+                        Some(ref target) => target.clone(),
+                        None => continue,
+                    }
+                }
+            };
+
+            pending.push(node, Some(attach_to), index);
+        }
+
+        let (count, roots) = pending.attach(tree, index);
+        let attached = count > 0;
+
+        // 侧链中可能包含已有调用的结果，只需配对新子树
+        if attached {
+            MetadataExtractor::pair_appended_tool_results(tree, index, &roots);
+        }
+
+        Ok(attached)
+    }
+
+    /// 查找侧链根节点应当挂载的 tool_use 节点 ID
+    fn find_sidechain_target(
+        tree: &ConversationTree,
+        agent_id: &str,
+        root: &MessageNode,
+    ) -> Option<String> {
+        let tool_use_id = Self::find_tool_use_id_by_agent(tree, agent_id).or_else(|| {
+            let prompt = Self::node_text(root)?;
+            Self::find_tool_use_id_by_prompt(tree, &prompt)
+        })?;
+
+        tree.find_node_where(|node| node.has_tool_use_id(&tool_use_id))
+            .map(|node| node.id.clone())
+    }
+
+    /// 通过 `toolUseResult.agentId` 查找发起子代理的 tool_use ID
+    fn find_tool_use_id_by_agent(tree: &ConversationTree, agent_id: &str) -> Option<String> {
+        if agent_id.is_empty() {
+            return None;
+        }
+
+        let result_node = tree.find_node_where(|node| {
+            node.message_data
+                .get("toolUseResult")
+                .and_then(|r| r.get("agentId"))
+                .and_then(|v| v.as_str())
+                == Some(agent_id)
+        })?;
+
+        result_node.content_blocks()?.iter().find_map(|block| {
+            if block.get("type").and_then(|v| v.as_str()) == Some("tool_result") {
+                block
+                    .get("tool_use_id")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// 通过 Task 工具的 prompt 查找发起子代理的 tool_use ID
+    fn find_tool_use_id_by_prompt(tree: &ConversationTree, prompt: &str) -> Option<String> {
+        let prompt = prompt.trim();
+        let task_block_id = |block: &Value| -> Option<String> {
+            if block.get("type")?.as_str()? != "tool_use" || block.get("name")?.as_str()? != "Task"
+            {
+                return None;
+            }
+            if block.get("input")?.get("prompt")?.as_str()?.trim() != prompt {
+                return None;
+            }
+            block.get("id")?.as_str().map(|s| s.to_string())
+        };
+
+        let mut stack: Vec<&MessageNode> = tree.roots.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            if let Some(id) = node
+                .content_blocks()
+                .and_then(|blocks| blocks.iter().find_map(&task_block_id))
+            {
+                return Some(id);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        None
+    }
+
+    /// 获取节点的纯文本内容（兼容 `message.content` 字符串格式）
+    fn node_text(node: &MessageNode) -> Option<String> {
+        if let Some(ref text) = node.full_content {
+            return Some(text.clone());
+        }
+        let content = node.message_data.get("message")?.get("content")?;
+        if let Some(text) = content.as_str() {
+            return Some(text.to_string());
+        }
+        let text: Vec<&str> = content
+            .as_array()?
+            .iter()
+            .filter(|b| b.get("type").and_then(|v| v.as_str()) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+            .collect();
+        if text.is_empty() {
+            None
+        } else {
+            Some(text.join("\n"))
+        }
+    }
+
+    /// 读取 agent 文件中记录的所属会话 ID（只检查前几行）
+    pub(crate) fn read_agent_session_id(path: &Path) -> Option<String> {
+        let file = File::open(path).ok()?;
+        BufReader::new(file)
+            .lines()
+            .take(5)
+            .map_while(|line| line.ok())
+            .find_map(|line| {
+                serde_json::from_str::<Value>(&line)
+                    .ok()?
+                    .get("sessionId")?
+                    .as_str()
+                    .map(|s| s.to_string())
+            })
+    }
+
     /// 使用迭代算法构建单棵树（避免递归栈溢出）
     ///
     /// # 参数
@@ -581,6 +1410,233 @@ mod tests {
         assert_eq!(tree.roots[0].children[0].depth, 1);
     }
 
+    #[test]
+    fn test_attach_sidechain_entries() {
+        // 主会话：user1 → assistant1 (Task tool_use) → result1 (tool_result)
+        let main_entries = vec![
+            create_test_entry("user1", None, "user"),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "assistant1",
+                    "parentUuid": "user1",
+                    "role": "assistant",
+                    "type": "message",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": "Task",
+                        "input": {"prompt": "Find the bug"}
+                    }]
+                }),
+            ),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "result1",
+                    "parentUuid": "assistant1",
+                    "role": "user",
+                    "type": "message",
+                    "content": [{"type": "tool_result", "tool_use_id": "toolu_1"}],
+                    "toolUseResult": {"agentId": "abc123"}
+                }),
+            ),
+        ];
+        let mut tree = MessageTreeBuilder::build_from_entries(&main_entries).unwrap();
+        let mut index = NodeIndex::build(&tree);
+
+        // 子代理会话：agent_user1 → agent_assistant1
+        let agent_entries = vec![
+            create_test_entry("agent_user1", None, "user"),
+            create_test_entry("agent_assistant1", Some("agent_user1"), "assistant"),
+        ];
+
+        let attached = MessageTreeBuilder::attach_sidechain_entries(
+            &mut tree,
+            &mut index,
+            "abc123",
+            &agent_entries,
+        )
+        .unwrap();
+        assert!(attached);
+        assert_eq!(index.take_attached(), vec!["agent_user1".to_string()]);
+
+        let assistant = &tree.roots[0].children[0];
+        let sidechain_root = assistant
+            .children
+            .iter()
+            .find(|c| c.id == "agent_user1")
+            .expect("侧链应挂载在 tool_use 节点下");
+        assert!(sidechain_root.is_sidechain);
+        assert_eq!(sidechain_root.sidechain_agent_id.as_deref(), Some("abc123"));
+        assert_eq!(sidechain_root.depth, 2);
+        assert_eq!(sidechain_root.children[0].depth, 3);
+        assert!(!assistant.is_sidechain);
+        assert_eq!(tree.total_count, 5);
+
+        // 重复挂载应被跳过
+        let attached_again = MessageTreeBuilder::attach_sidechain_entries(
+            &mut tree,
+            &mut index,
+            "abc123",
+            &agent_entries,
+        )
+        .unwrap();
+        assert!(!attached_again);
+
+        // 子代理继续追加的条目按节点 ID 合并到已挂载的侧链
+        let mut grown_entries = agent_entries.clone();
+        grown_entries.push(create_test_entry(
+            "agent_user2",
+            Some("agent_assistant1"),
+            "user",
+        ));
+        let merged = MessageTreeBuilder::attach_sidechain_entries(
+            &mut tree,
+            &mut index,
+            "abc123",
+            &grown_entries,
+        )
+        .unwrap();
+        assert!(merged);
+
+        let agent_assistant = &tree.roots[0].children[0].children[1].children[0];
+        assert_eq!(agent_assistant.id, "agent_assistant1");
+        assert_eq!(agent_assistant.children[0].id, "agent_user2");
+        assert!(agent_assistant.children[0].is_sidechain);
+        assert_eq!(agent_assistant.children[0].depth, 4);
+        assert_eq!(tree.total_count, 6);
+        assert_eq!(tree.max_depth, 4);
+        assert_eq!(
+            index.get(&tree, "agent_user2").map(|node| node.depth),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn test_attach_sidechains_requires_session_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let session_file = dir.path().join("main-session.jsonl");
+        std::fs::write(&session_file, "").unwrap();
+
+        let main_entries = vec![
+            create_test_entry("user1", None, "user"),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "assistant1",
+                    "parentUuid": "user1",
+                    "role": "assistant",
+                    "type": "message",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_3",
+                        "name": "Task",
+                        "input": {"prompt": "Test message"}
+                    }]
+                }),
+            ),
+        ];
+        let mut tree = MessageTreeBuilder::build_from_entries(&main_entries).unwrap();
+        let mut index = NodeIndex::build(&tree);
+
+        // 没有 sessionId 的 agent 文件不属于任何会话
+        let orphan = json!({"uuid": "orphan1", "role": "user", "type": "message",
+            "content": [{"type": "text", "text": "Test message"}]});
+        std::fs::write(
+            dir.path().join("agent-orphan.jsonl"),
+            format!("{}\n", orphan),
+        )
+        .unwrap();
+
+        let mut cursor = SidechainCursor::default();
+        let attached = MessageTreeBuilder::attach_sidechains(
+            &mut tree,
+            &mut index,
+            &session_file,
+            &mut cursor,
+        )
+        .unwrap();
+        assert_eq!(attached, 0);
+        assert!(!index.contains("orphan1"));
+
+        // 属于该会话的 agent 文件追加后只合并新条目
+        let agent_path = dir.path().join("agent-live.jsonl");
+        let first = json!({"uuid": "live1", "sessionId": "main-session", "role": "user",
+            "type": "message", "content": [{"type": "text", "text": "Test message"}]});
+        std::fs::write(&agent_path, format!("{}\n", first)).unwrap();
+
+        let attached = MessageTreeBuilder::attach_sidechains(
+            &mut tree,
+            &mut index,
+            &session_file,
+            &mut cursor,
+        )
+        .unwrap();
+        assert_eq!(attached, 1);
+        assert!(index.contains("live1"));
+
+        let second = json!({"uuid": "live2", "parentUuid": "live1", "sessionId": "main-session",
+            "role": "assistant", "type": "message", "content": [{"type": "text", "text": "ok"}]});
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&agent_path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, format!("{}\n", second).as_bytes()).unwrap();
+
+        let attached = MessageTreeBuilder::attach_sidechains(
+            &mut tree,
+            &mut index,
+            &session_file,
+            &mut cursor,
+        )
+        .unwrap();
+        assert_eq!(attached, 1);
+        assert!(index.contains("live2"));
+        assert_eq!(tree.total_count, 4);
+    }
+
+    #[test]
+    fn test_attach_sidechain_by_prompt() {
+        let main_entries = vec![
+            create_test_entry("user1", None, "user"),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "assistant1",
+                    "parentUuid": "user1",
+                    "role": "assistant",
+                    "type": "message",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_2",
+                        "name": "Task",
+                        "input": {"prompt": "Test message"}
+                    }]
+                }),
+            ),
+        ];
+        let mut tree = MessageTreeBuilder::build_from_entries(&main_entries).unwrap();
+        let mut index = NodeIndex::build(&tree);
+
+        // 没有 toolUseResult 时，按首条消息与 Task prompt 匹配
+        let agent_entries = vec![create_test_entry("agent_user1", None, "user")];
+        let attached = MessageTreeBuilder::attach_sidechain_entries(
+            &mut tree,
+            &mut index,
+            "unknown",
+            &agent_entries,
+        )
+        .unwrap();
+
+        assert!(attached);
+        assert_eq!(tree.roots[0].children[0].children[0].id, "agent_user1");
+    }
+
     #[test]
     fn test_build_multiple_roots() {
         // 创建多个根节点：
@@ -636,4 +1692,139 @@ mod tests {
         assert!(tree.roots[0].children[0].is_assistant_message());
         assert!(!tree.roots[0].children[0].is_user_message());
     }
+
+    #[test]
+    fn test_append_entries_patches_existing_tree() {
+        let entries = vec![
+            create_test_entry("user1", None, "user"),
+            create_test_entry("assistant1", Some("user1"), "assistant"),
+        ];
+        let mut tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        let mut index = NodeIndex::build(&tree);
+        assert_eq!(tree.total_count, 2);
+
+        let appended = vec![
+            create_test_entry("user2", Some("assistant1"), "user"),
+            create_test_entry("assistant2", Some("user2"), "assistant"),
+            // 重复条目被忽略
+            create_test_entry("assistant1", Some("user1"), "assistant"),
+            // 父节点不存在的条目被丢弃
+            create_test_entry("orphan", Some("missing"), "assistant"),
+            // 新的根节点
+            create_test_entry("user3", None, "user"),
+            // 同一批中较早条目的兄弟节点
+            create_test_entry("assistant2b", Some("user2"), "assistant"),
+        ];
+
+        let count = MessageTreeBuilder::append_entries(&mut tree, &mut index, &appended).unwrap();
+        assert_eq!(count, 4);
+        assert_eq!(tree.roots.len(), 2);
+        assert_eq!(tree.total_count, 6);
+        assert_eq!(tree.max_depth, 3);
+
+        // 每棵新子树只挂载一次
+        assert_eq!(
+            index.take_attached(),
+            vec!["user2".to_string(), "user3".to_string()]
+        );
+        assert_eq!(index.parent_id("user2"), Some("assistant1"));
+        assert_eq!(
+            index.ancestors("assistant2b").collect::<Vec<_>>(),
+            vec!["user2", "assistant1", "user1"]
+        );
+
+        let user2 = index.get(&tree, "user2").unwrap();
+        let children: Vec<&str> = user2.children.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(children, vec!["assistant2", "assistant2b"]);
+
+        let assistant2 = index.get(&tree, "assistant2").unwrap();
+        assert_eq!(assistant2.depth, 3);
+        assert!(assistant2.metadata.is_some());
+        assert!(!index.contains("orphan"));
+    }
+
+    #[test]
+    fn test_append_entries_pairs_existing_tool_call() {
+        let entries = vec![
+            create_test_entry("user1", None, "user"),
+            JsonlEntry::new(
+                0,
+                0,
+                json!({
+                    "uuid": "assistant1",
+                    "parentUuid": "user1",
+                    "role": "assistant",
+                    "type": "message",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": "Bash",
+                        "input": {"command": "ls"}
+                    }]
+                }),
+            ),
+        ];
+        let mut tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        let mut index = NodeIndex::build(&tree);
+        assert_eq!(index.tool_call_node("toolu_1"), Some("assistant1"));
+
+        let call = &tree.roots[0].children[0]
+            .metadata
+            .as_ref()
+            .unwrap()
+            .tool_calls[0];
+        assert_eq!(call.status, "pending");
+
+        let appended = vec![JsonlEntry::new(
+            0,
+            0,
+            json!({
+                "uuid": "result1",
+                "parentUuid": "assistant1",
+                "role": "user",
+                "type": "message",
+                "content": [{
+                    "type": "tool_result",
+                    "tool_use_id": "toolu_1",
+                    "content": "Permission denied",
+                    "is_error": true
+                }]
+            }),
+        )];
+        MessageTreeBuilder::append_entries(&mut tree, &mut index, &appended).unwrap();
+
+        // 结果回填到已有节点上的调用，并记录为已更新节点
+        assert_eq!(index.take_updated(), vec!["assistant1".to_string()]);
+        let metadata = index
+            .get(&tree, "assistant1")
+            .unwrap()
+            .metadata
+            .as_ref()
+            .unwrap();
+        assert_eq!(metadata.tool_calls[0].status, "error");
+        assert_eq!(metadata.tool_calls[0].is_error, Some(true));
+        assert_eq!(
+            metadata
+                .errors
+                .iter()
+                .filter(|e| e.category.is_some())
+                .count(),
+            1
+        );
+
+        // 重复的结果不会再次回填
+        let duplicate = vec![JsonlEntry::new(
+            0,
+            0,
+            json!({
+                "uuid": "result2",
+                "parentUuid": "assistant1",
+                "role": "user",
+                "type": "message",
+                "content": [{"type": "tool_result", "tool_use_id": "toolu_1"}]
+            }),
+        )];
+        MessageTreeBuilder::append_entries(&mut tree, &mut index, &duplicate).unwrap();
+        assert!(index.take_updated().is_empty());
+    }
 }
@@ -0,0 +1,409 @@
+//! Token 用量统计模块
+//!
+//! 从 assistant 条目的 `message.usage` 和 `message.model` 字段读取 Claude API
+//! 返回的真实 token 用量（包括缓存写入和缓存读取），按消息、会话、日期聚合。
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use super::jsonl::{JsonlEntry, JsonlParser};
+use crate::database::ModelPrice;
+
+/// Token 用量
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    /// 输入 token 数
+    pub input_tokens: u64,
+
+    /// 输出 token 数
+    pub output_tokens: u64,
+
+    /// 缓存写入 token 数
+    pub cache_creation_input_tokens: u64,
+
+    /// 缓存读取 token 数
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsage {
+    /// 从 `message.usage` 对象解析用量
+    pub fn from_value(usage: &serde_json::Value) -> Self {
+        let get = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        Self {
+            input_tokens: get("input_tokens"),
+            output_tokens: get("output_tokens"),
+            cache_creation_input_tokens: get("cache_creation_input_tokens"),
+            cache_read_input_tokens: get("cache_read_input_tokens"),
+        }
+    }
+
+    /// 累加另一份用量
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+
+    /// 总 token 数（含缓存）
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens
+            + self.output_tokens
+            + self.cache_creation_input_tokens
+            + self.cache_read_input_tokens
+    }
+}
+
+/// 单条消息的用量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageUsage {
+    /// 消息 UUID
+    pub uuid: Option<String>,
+
+    /// 模型名称
+    pub model: String,
+
+    /// 消息时间戳（RFC3339）
+    pub timestamp: Option<String>,
+
+    /// Token 用量
+    pub usage: TokenUsage,
+}
+
+/// 会话用量汇总
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionUsage {
+    /// 所有消息的用量合计
+    pub total: TokenUsage,
+
+    /// 按模型分组的用量
+    pub by_model: HashMap<String, TokenUsage>,
+
+    /// 按日期（本地时区，YYYY-MM-DD）分组、再按模型分组的用量
+    pub by_day: BTreeMap<String, HashMap<String, TokenUsage>>,
+
+    /// 计入统计的 API 响应数量
+    pub response_count: usize,
+}
+
+/// 用量提取器
+pub struct UsageExtractor;
+
+impl UsageExtractor {
+    /// 从 JSONL 条目中提取每条消息的用量
+    ///
+    /// Claude Code 会把一次 API 响应的多个内容块拆成多条 assistant 条目，
+    /// 它们携带相同的 `message.id` 和相同的 `usage`，这里按 `message.id`
+    /// 去重，避免重复计数。
+    pub fn extract_from_entries(entries: &[JsonlEntry]) -> Vec<MessageUsage> {
+        Self::extract_unseen(entries, &mut HashSet::new())
+    }
+
+    /// 从 JSONL 条目中提取 `seen_ids` 中尚未出现过的消息用量
+    ///
+    /// 去重键为 `message.id`（缺失时使用 `requestId`），提取到的键会加入 `seen_ids`。
+    /// 恢复或分叉的会话会把之前的 assistant 条目复制到新文件中，
+    /// 跨会话统计时共用同一个 `seen_ids`，每次 API 响应只计费一次。
+    pub fn extract_unseen(
+        entries: &[JsonlEntry],
+        seen_ids: &mut HashSet<String>,
+    ) -> Vec<MessageUsage> {
+        let mut usages = Vec::new();
+
+        for entry in entries {
+            let message = match entry.data.get("message") {
+                Some(message) => message,
+                None => continue,
+            };
+            let usage = match message.get("usage") {
+                Some(usage) if usage.is_object() => usage,
+                _ => continue,
+            };
+
+            let dedup_key = message
+                .get("id")
+                .or_else(|| entry.data.get("requestId"))
+                .and_then(|v| v.as_str());
+            if let Some(id) = dedup_key {
+                if !seen_ids.insert(id.to_string()) {
+                    continue;
+                }
+            }
+
+            let model = message
+                .get("model")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+                .to_string();
+
+            // 合成消息（如 API 错误提示）不产生费用
+            if model == "<synthetic>" {
+                continue;
+            }
+
+            usages.push(MessageUsage {
+                uuid: entry
+                    .data
+                    .get("uuid")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                model,
+                timestamp: entry
+                    .data
+                    .get("timestamp")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                usage: TokenUsage::from_value(usage),
+            });
+        }
+
+        usages
+    }
+
+    /// 汇总消息用量
+    pub fn aggregate(usages: &[MessageUsage]) -> SessionUsage {
+        let mut session = SessionUsage::default();
+
+        for message in usages {
+            session.total.add(&message.usage);
+            session
+                .by_model
+                .entry(message.model.clone())
+                .or_default()
+                .add(&message.usage);
+
+            if let Some(day) = message.timestamp.as_deref().and_then(Self::local_day) {
+                session
+                    .by_day
+                    .entry(day)
+                    .or_default()
+                    .entry(message.model.clone())
+                    .or_default()
+                    .add(&message.usage);
+            }
+
+            session.response_count += 1;
+        }
+
+        session
+    }
+
+    /// 读取会话文件并汇总用量
+    pub fn from_file(file_path: &Path) -> Result<SessionUsage> {
+        Self::from_file_unseen(file_path, &mut HashSet::new())
+    }
+
+    /// 读取会话文件并汇总 `seen_ids` 中尚未出现过的消息用量（跨会话统计使用）
+    pub fn from_file_unseen(
+        file_path: &Path,
+        seen_ids: &mut HashSet<String>,
+    ) -> Result<SessionUsage> {
+        let mut parser = JsonlParser::new(file_path.to_path_buf())?;
+        let entries = parser.parse_all()?;
+        Ok(Self::aggregate(&Self::extract_unseen(&entries, seen_ids)))
+    }
+
+    /// 将 RFC3339 时间戳转换为本地日期（YYYY-MM-DD）
+    fn local_day(timestamp: &str) -> Option<String> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|dt| dt.with_timezone(&Local).format("%Y-%m-%d").to_string())
+    }
+}
+
+/// 用量成本
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageCost {
+    /// 用量合计
+    pub usage: TokenUsage,
+
+    /// 总成本（美元）
+    pub cost_usd: f64,
+
+    /// 按模型分组的成本（美元）
+    pub cost_by_model: HashMap<String, f64>,
+
+    /// 价格表中找不到价格的模型（未计入成本）
+    pub unpriced_models: Vec<String>,
+}
+
+/// 模型价格表
+///
+/// 模型名包含价格模式即视为匹配，多个模式匹配时取最长的一个，
+/// 因此 `claude-opus-4-5` 会优先于 `claude-opus-4`。
+pub struct PriceTable {
+    prices: Vec<ModelPrice>,
+}
+
+impl PriceTable {
+    /// 创建价格表
+    pub fn new(prices: Vec<ModelPrice>) -> Self {
+        Self { prices }
+    }
+
+    /// 查找模型对应的价格
+    pub fn find(&self, model: &str) -> Option<&ModelPrice> {
+        self.prices
+            .iter()
+            .filter(|price| model.contains(price.model_pattern.as_str()))
+            .max_by_key(|price| price.model_pattern.len())
+    }
+
+    /// 计算单个模型用量的成本（美元）
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        let price = self.find(model)?;
+        let per_token = |tokens: u64, price_per_mtok: f64| tokens as f64 * price_per_mtok / 1e6;
+
+        Some(
+            per_token(usage.input_tokens, price.input_price)
+                + per_token(usage.output_tokens, price.output_price)
+                + per_token(usage.cache_creation_input_tokens, price.cache_write_price)
+                + per_token(usage.cache_read_input_tokens, price.cache_read_price),
+        )
+    }
+
+    /// 计算按模型分组用量的成本
+    pub fn price_usage(&self, by_model: &HashMap<String, TokenUsage>) -> UsageCost {
+        let mut result = UsageCost::default();
+
+        for (model, usage) in by_model {
+            result.usage.add(usage);
+            match self.cost(model, usage) {
+                Some(cost) => {
+                    result.cost_usd += cost;
+                    *result.cost_by_model.entry(model.clone()).or_default() += cost;
+                }
+                None => result.unpriced_models.push(model.clone()),
+            }
+        }
+
+        result.unpriced_models.sort();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn assistant_entry(message_id: &str, model: &str, input: u64, output: u64) -> JsonlEntry {
+        JsonlEntry::new(
+            0,
+            0,
+            json!({
+                "type": "assistant",
+                "uuid": format!("uuid-{}-{}", message_id, input),
+                "timestamp": "2025-01-01T12:00:00.000Z",
+                "message": {
+                    "id": message_id,
+                    "model": model,
+                    "usage": {
+                        "input_tokens": input,
+                        "output_tokens": output,
+                        "cache_creation_input_tokens": 100,
+                        "cache_read_input_tokens": 1000
+                    }
+                }
+            }),
+        )
+    }
+
+    #[test]
+    fn test_extract_deduplicates_by_message_id() {
+        let entries = vec![
+            assistant_entry("msg_1", "claude-sonnet-4-20250514", 10, 20),
+            // 同一响应的第二个内容块
+            assistant_entry("msg_1", "claude-sonnet-4-20250514", 10, 20),
+            assistant_entry("msg_2", "claude-opus-4-20250514", 5, 7),
+            JsonlEntry::new(0, 0, json!({"type": "user", "message": {"content": "hi"}})),
+        ];
+
+        let usages = UsageExtractor::extract_from_entries(&entries);
+        assert_eq!(usages.len(), 2);
+
+        let session = UsageExtractor::aggregate(&usages);
+        assert_eq!(session.response_count, 2);
+        assert_eq!(session.total.input_tokens, 15);
+        assert_eq!(session.total.output_tokens, 27);
+        assert_eq!(session.total.cache_read_input_tokens, 2000);
+        assert_eq!(session.by_model.len(), 2);
+        assert_eq!(session.by_day.len(), 1);
+    }
+
+    #[test]
+    fn test_from_file_unseen_deduplicates_across_sessions() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, entries: &[JsonlEntry]| {
+            let path = temp_dir.path().join(name);
+            let lines: Vec<String> = entries.iter().map(|e| e.data.to_string()).collect();
+            std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+            path
+        };
+        let original = write(
+            "original.jsonl",
+            &[assistant_entry("msg_1", "claude-sonnet-4-20250514", 10, 20)],
+        );
+        // 恢复的会话复制了原会话的 assistant 条目
+        let resumed = write(
+            "resumed.jsonl",
+            &[
+                assistant_entry("msg_1", "claude-sonnet-4-20250514", 10, 20),
+                assistant_entry("msg_2", "claude-sonnet-4-20250514", 3, 4),
+            ],
+        );
+
+        let mut seen_ids = HashSet::new();
+        let first = UsageExtractor::from_file_unseen(&original, &mut seen_ids).unwrap();
+        let second = UsageExtractor::from_file_unseen(&resumed, &mut seen_ids).unwrap();
+        assert_eq!(first.response_count, 1);
+        assert_eq!(second.response_count, 1);
+        assert_eq!(first.total.input_tokens + second.total.input_tokens, 13);
+
+        // 单独统计时仍包含复制的条目
+        assert_eq!(
+            UsageExtractor::from_file(&resumed).unwrap().response_count,
+            2
+        );
+    }
+
+    #[test]
+    fn test_price_table_prefers_longest_pattern() {
+        let price = |pattern: &str, input: f64| ModelPrice {
+            id: None,
+            model_pattern: pattern.to_string(),
+            input_price: input,
+            output_price: 0.0,
+            cache_write_price: 0.0,
+            cache_read_price: 0.0,
+            updated_at: String::new(),
+        };
+        let table = PriceTable::new(vec![
+            price("claude-opus-4", 15.0),
+            price("claude-opus-4-5", 5.0),
+        ]);
+
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            ..Default::default()
+        };
+        assert_eq!(table.cost("claude-opus-4-5-20251101", &usage), Some(5.0));
+        assert_eq!(table.cost("claude-opus-4-1-20250805", &usage), Some(15.0));
+        assert_eq!(table.cost("gpt-4o", &usage), None);
+
+        let mut by_model = HashMap::new();
+        by_model.insert("claude-opus-4-5-20251101".to_string(), usage.clone());
+        by_model.insert("gpt-4o".to_string(), usage);
+        let cost = table.price_usage(&by_model);
+        assert_eq!(cost.cost_usd, 5.0);
+        assert_eq!(cost.unpriced_models, vec!["gpt-4o".to_string()]);
+        assert_eq!(cost.usage.input_tokens, 2_000_000);
+    }
+}
@@ -19,6 +19,8 @@
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::collections::HashMap;
 use std::fmt;
 
 use crate::database::models::Message;
@@ -129,6 +131,9 @@ pub struct QAPair {
     pub answer: Option<Message>,
     /// 问答对的时间戳（使用问题的时间戳）
     pub timestamp: String,
+    /// 问题所在分支是否已被回退或编辑取代（不在当前对话路径上）
+    #[serde(default)]
+    pub superseded: bool,
 }
 
 /// 视图等级错误类型
@@ -167,12 +172,260 @@ impl From<std::io::Error> for ViewLevelError {
     }
 }
 
+// ==================== 自定义等级 ====================
+
+/// 消息谓词
+///
+/// 自定义等级由谓词组合而成，序列化为带 `kind` 标签的 JSON，例如
+/// "用户提问 + Edit 工具调用"：
+///
+/// ```json
+/// {"kind": "any", "predicates": [
+///     {"kind": "role", "values": ["user"]},
+///     {"kind": "tool_name", "values": ["Edit"]}
+/// ]}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MessagePredicate {
+    /// 消息类型（user / assistant / system）
+    MsgType { values: Vec<String> },
+    /// 内容类型（text / tool_use / tool_result / thinking，字符串内容视为 text）
+    ContentType { values: Vec<String> },
+    /// 调用了指定工具（tool_use 块的 name）
+    ToolName { values: Vec<String> },
+    /// 内容包含指定文本
+    ContainsText {
+        text: String,
+        #[serde(default)]
+        case_sensitive: bool,
+    },
+    /// 包含错误（is_error 的 tool_result 或 <tool_use_error>）
+    HasError,
+    /// 角色：user（用户输入，不含工具结果）、assistant、tool（工具调用与结果）、system
+    Role { values: Vec<String> },
+    /// 所有子谓词都满足
+    All { predicates: Vec<MessagePredicate> },
+    /// 任一子谓词满足
+    Any { predicates: Vec<MessagePredicate> },
+    /// 子谓词不满足
+    Not { predicate: Box<MessagePredicate> },
+}
+
+impl MessagePredicate {
+    /// 判断消息是否满足谓词
+    ///
+    /// summary 中的消息 JSON 在整个谓词树求值期间最多解析一次
+    pub fn matches(&self, message: &Message) -> bool {
+        self.matches_with(message, &OnceCell::new())
+    }
+
+    /// 使用共享的内容块缓存判断消息是否满足谓词
+    fn matches_with(
+        &self,
+        message: &Message,
+        blocks_cache: &OnceCell<Vec<serde_json::Value>>,
+    ) -> bool {
+        let blocks = || blocks_cache.get_or_init(|| message_blocks(message));
+        match self {
+            MessagePredicate::MsgType { values } => contains_ignore_case(values, &message.msg_type),
+            MessagePredicate::ContentType { values } => {
+                contains_ignore_case(values, message.content_type.as_deref().unwrap_or("text"))
+            }
+            MessagePredicate::ToolName { values } => blocks()
+                .iter()
+                .filter(|block| block.get("type").and_then(|v| v.as_str()) == Some("tool_use"))
+                .filter_map(|block| block.get("name").and_then(|v| v.as_str()))
+                .any(|name| contains_ignore_case(values, name)),
+            MessagePredicate::ContainsText {
+                text,
+                case_sensitive,
+            } => {
+                let haystack = message
+                    .content
+                    .as_deref()
+                    .or(message.summary.as_deref())
+                    .unwrap_or("");
+                if *case_sensitive {
+                    haystack.contains(text.as_str())
+                } else {
+                    haystack.to_lowercase().contains(&text.to_lowercase())
+                }
+            }
+            MessagePredicate::HasError => {
+                blocks()
+                    .iter()
+                    .any(|block| block.get("is_error").and_then(|v| v.as_bool()) == Some(true))
+                    || message
+                        .summary
+                        .as_deref()
+                        .is_some_and(|s| s.contains("<tool_use_error>"))
+            }
+            MessagePredicate::Role { values } => {
+                contains_ignore_case(values, message_role(message))
+            }
+            MessagePredicate::All { predicates } => predicates
+                .iter()
+                .all(|p| p.matches_with(message, blocks_cache)),
+            MessagePredicate::Any { predicates } => predicates
+                .iter()
+                .any(|p| p.matches_with(message, blocks_cache)),
+            MessagePredicate::Not { predicate } => !predicate.matches_with(message, blocks_cache),
+        }
+    }
+
+    /// 校验谓词（组合谓词不能为空）
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            MessagePredicate::All { predicates } | MessagePredicate::Any { predicates } => {
+                if predicates.is_empty() {
+                    return Err("组合谓词至少需要一个子谓词".to_string());
+                }
+                predicates.iter().try_for_each(|p| p.validate())
+            }
+            MessagePredicate::Not { predicate } => predicate.validate(),
+            MessagePredicate::ContainsText { text, .. } if text.is_empty() => {
+                Err("contains_text 的文本不能为空".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 自定义等级
+///
+/// 由用户定义的消息谓词组合，保存在数据库中，可以按会话选择
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomViewLevel {
+    /// 等级名称（唯一，不能与预设等级重名）
+    pub name: String,
+
+    /// 描述说明
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// 消息满足该谓词时被包含
+    pub predicate: MessagePredicate,
+}
+
+impl CustomViewLevel {
+    /// 校验等级定义
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("自定义等级名称不能为空".to_string());
+        }
+        if ViewLevel::from_str(&self.name).is_ok() {
+            return Err(format!("自定义等级名称不能与预设等级重名: {}", self.name));
+        }
+        self.predicate.validate()
+    }
+}
+
+/// 忽略大小写判断列表是否包含值
+fn contains_ignore_case(values: &[String], value: &str) -> bool {
+    values.iter().any(|v| v.eq_ignore_ascii_case(value))
+}
+
+/// 消息的内容块（从 summary 中的 message JSON 解析）
+fn message_blocks(message: &Message) -> Vec<serde_json::Value> {
+    message
+        .summary
+        .as_deref()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+        .and_then(|v| v.get("content").and_then(|c| c.as_array()).cloned())
+        .unwrap_or_default()
+}
+
+/// 消息的角色
+fn message_role(message: &Message) -> &'static str {
+    match message.content_type.as_deref() {
+        Some("tool_use") | Some("tool_result") => return "tool",
+        _ => {}
+    }
+    match message.msg_type.as_str() {
+        "user" => "user",
+        "system" => "system",
+        "tool_use" | "tool_result" => "tool",
+        _ => "assistant",
+    }
+}
+
+/// 问答配对使用的消息树索引
+///
+/// 通过 parentUuid 把消息连接成树（以下标表示），构建与遍历均为线性时间。
+/// parentUuid 缺失或指向列表外的消息时视为根节点。
+struct QATreeIndex {
+    /// 子节点下标（按文件顺序）
+    children: Vec<Vec<usize>>,
+    /// 根节点下标（按文件顺序）
+    roots: Vec<usize>,
+}
+
+impl QATreeIndex {
+    /// 基于原始消息列表建立索引
+    fn build(messages: &[Message]) -> Self {
+        let mut index: HashMap<&str, usize> = HashMap::with_capacity(messages.len());
+        for (i, msg) in messages.iter().enumerate() {
+            index.entry(msg.uuid.as_str()).or_insert(i);
+        }
+
+        let mut children = vec![Vec::new(); messages.len()];
+        let mut roots = Vec::new();
+        for (i, msg) in messages.iter().enumerate() {
+            match msg
+                .parent_uuid
+                .as_deref()
+                .and_then(|parent_uuid| index.get(parent_uuid))
+            {
+                Some(&parent) if parent != i => children[parent].push(i),
+                _ => roots.push(i),
+            }
+        }
+
+        Self { children, roots }
+    }
+
+    /// 在同一轮对话内前进一步：取文件中最新的非问题子节点
+    fn next_in_turn(&self, node: usize, is_question: &[bool]) -> Option<usize> {
+        self.children[node]
+            .iter()
+            .rev()
+            .copied()
+            .find(|&child| !is_question[child])
+    }
+
+    /// 标记被取代的节点
+    ///
+    /// 同一父节点下的多个问题中，只有文件中最后一个在当前分支上，
+    /// 其余问题及其整个子树都被标记为已取代。
+    fn superseded(&self, is_question: &[bool]) -> Vec<bool> {
+        let mut superseded = vec![false; self.children.len()];
+        let mut stack = self.roots.clone();
+
+        while let Some(node) = stack.pop() {
+            let live = self.children[node]
+                .iter()
+                .rev()
+                .copied()
+                .find(|&child| is_question[child]);
+            for &child in &self.children[node] {
+                superseded[child] = superseded[node] || (is_question[child] && Some(child) != live);
+                stack.push(child);
+            }
+        }
+
+        superseded
+    }
+}
+
 /// 消息过滤器
 ///
-/// 根据选择的 ViewLevel 对消息列表进行过滤。
+/// 根据选择的 ViewLevel（或自定义等级）对消息列表进行过滤。
 /// 支持流式处理，在解析阶段直接应用过滤逻辑。
 pub struct MessageFilter {
     view_level: ViewLevel,
+    custom_level: Option<CustomViewLevel>,
 }
 
 impl MessageFilter {
@@ -182,7 +435,20 @@ impl MessageFilter {
     ///
     /// - `view_level`: 日志读取等级
     pub fn new(view_level: ViewLevel) -> Self {
-        Self { view_level }
+        Self {
+            view_level,
+            custom_level: None,
+        }
+    }
+
+    /// 使用自定义等级创建消息过滤器
+    ///
+    /// 自定义等级基于完整消息列表求值，`view_level()` 返回 Full
+    pub fn with_custom_level(custom_level: CustomViewLevel) -> Self {
+        Self {
+            view_level: ViewLevel::Full,
+            custom_level: Some(custom_level),
+        }
     }
 
     /// 判断消息是否应该被包含
@@ -203,7 +469,12 @@ impl MessageFilter {
     /// - QAPairs 等级总是返回 false，因为它需要特殊的配对逻辑
     /// - 其他等级根据消息的 msg_type 字段进行判断
     /// - UserOnly 等级会额外过滤掉 type 为 tool_result 的消息
+    /// - 设置了自定义等级时只按其谓词判断
     pub fn should_include(&self, message: &Message) -> bool {
+        if let Some(ref custom_level) = self.custom_level {
+            return custom_level.predicate.matches(message);
+        }
+
         match self.view_level {
             ViewLevel::Full => true,
             ViewLevel::Conversation => {
@@ -246,15 +517,41 @@ impl MessageFilter {
 
     /// 提取问答对
     ///
-    /// 从消息列表中提取问答配对。
+    /// 从消息列表中提取问答配对，只返回找到答案的问题。
+    /// 设置了自定义等级时，只保留问题或答案满足其谓词的问答对。
+    /// 算法说明见 [`MessageFilter::extract_qa_turns`]。
+    pub fn extract_qa_pairs(&self, messages: Vec<Message>) -> Vec<QAPair> {
+        let mut qa_pairs = self.extract_qa_turns(messages);
+        qa_pairs.retain(|pair| pair.answer.is_some());
+        if let Some(ref custom_level) = self.custom_level {
+            qa_pairs.retain(|pair| {
+                custom_level.predicate.matches(&pair.question)
+                    || pair
+                        .answer
+                        .as_ref()
+                        .is_some_and(|answer| custom_level.predicate.matches(answer))
+            });
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            eprintln!("🔍 [extract_qa_pairs] 输出问答对数量: {}", qa_pairs.len());
+        }
+
+        qa_pairs
+    }
+
+    /// 提取问答轮次
+    ///
+    /// 与 [`MessageFilter::extract_qa_pairs`] 相同，但保留没有答案的问题（answer 为 None）。
     ///
     /// # 算法
     ///
     /// **步骤 1: 预过滤**
     ///
-    /// 首先移除不适合问答对的消息：
-    /// - user 类型的 tool_result 消息
-    /// - assistant 类型的 tool_use 消息
+    /// 首先找出适合问答对的消息：
+    /// - user 类型的 tool_result 消息不作为问题
+    /// - assistant 类型的 tool_use 消息不作为答案
     /// - 包含 <tool_use_error> 或 <system-reminder> 的消息
     /// - system 类型的消息
     ///
@@ -265,31 +562,36 @@ impl MessageFilter {
     /// - 合并策略：将技能内容追加到用户消息，用分隔符间隔
     /// - 合并后从列表中移除技能消息
     ///
-    /// **步骤 3: 从后向前扫描配对**
+    /// **步骤 3: 沿 parentUuid 链路配对**
     ///
-    /// 1. 从后向前扫描合并后的消息列表
-    /// 2. 遇到 user 时，向后查找第一个 assistant 作为答案
-    /// 3. 如果遇到另一个 user，停止查找（该 user 没有答案）
+    /// 1. 基于原始消息（包括工具往返等中间节点）建立父子索引
+    /// 2. 从每个问题出发沿子节点前进，跳过中间节点，直到遇到下一个问题或链路末端
+    /// 3. 途经的最后一条 assistant 文本消息即为答案
+    /// 4. 节点有多个非问题子节点时（如重新生成），沿文件中最新的子节点前进
+    ///
+    /// **步骤 4: 标记被取代的分支**
+    ///
+    /// 同一父节点下有多个问题时（用户编辑或回退后重新提问），只有文件中最后一个问题
+    /// 在当前分支上，其余问题及其后续问题的 `superseded` 为 true。
+    ///
+    /// **步骤 5: 回退到文件顺序**
+    ///
+    /// 链路中途断开（末端节点没有任何子节点，例如缺少 parentUuid）且没有找到答案时，按文件顺序
+    /// 向后查找：取紧随其后的连续 assistant 中的最后一条，遇到下一个问题即停止。
     ///
     /// # 示例
     ///
     /// ```text
-    /// 原始消息序列:
-    /// 1:  user (text)              → 保留
-    /// 2:  assistant (text)         → 保留，配对给 1
-    /// 3:  assistant (tool_use)     → 过滤
-    /// 4:  user (tool_result)       → 过滤
-    /// 5:  assistant (text)         → 保留，但没有对应的 question，忽略
-    /// 6:  assistant (tool_use)     → 过滤
-    /// 7:  user (tool_result)       → 过滤
-    /// 8:  assistant (tool_use)     → 过滤
-    /// 9:  user (text)              → 保留
-    /// 10: user (tool_result)       → 过滤
-    /// 11: assistant (text)         → 保留，配对给 9
-    /// 12: user (text)              → 保留，无答案
-    ///
-    /// 过滤后序列: [1(user), 2(assistant), 5(assistant), 9(user), 11(assistant), 12(user)]
-    /// 问答对: [(1, 2), (9, 11), (12, None)]
+    /// u1 (text)
+    /// └─ a1 (tool_use)
+    ///    └─ r1 (tool_result)
+    ///       └─ a2 (text)          → u1 的答案
+    ///          ├─ u2 (text)       → 被 u2' 取代
+    ///          │  └─ a3 (text)    → u2 的答案
+    ///          └─ u2' (text)      → 当前分支
+    ///             └─ a4 (text)    → u2' 的答案
+    ///
+    /// 问答轮次: [(u1, a2), (u2, a3, 已取代), (u2', a4)]
     /// ```
     ///
     /// # 参数
@@ -298,79 +600,111 @@ impl MessageFilter {
     ///
     /// # 返回
     ///
-    /// 问答对列表（按原始对话顺序）
+    /// 问答轮次列表（按问题在文件中的顺序）
     ///
     /// # 复杂度
     ///
-    /// - 时间复杂度: O(n²)，n 为消息数量（最坏情况）
-    /// - 空间复杂度: O(m)，m 为问答对数量
-    pub fn extract_qa_pairs(&self, messages: Vec<Message>) -> Vec<QAPair> {
-        // 步骤 1: 预过滤，移除不适合问答对的消息
-        let filtered_messages = self.pre_filter_for_qa(messages);
+    /// - 时间复杂度: O(n)，每条消息最多被一个问题的链路访问一次
+    /// - 空间复杂度: O(n)
+    pub fn extract_qa_turns(&self, messages: Vec<Message>) -> Vec<QAPair> {
+        // 步骤 1: 预过滤，找出适合问答对的消息
+        let filtered_messages = self.pre_filter_for_qa(&messages);
 
         // 步骤 2: 合并用户消息与技能消息
         let merged_messages = self.merge_user_with_skill_messages(filtered_messages);
 
-        let mut qa_pairs = Vec::new();
-
-        // 步骤 3: 从后向前扫描，配对 user 和 assistant
-        let mut i = merged_messages.len();
-        while i > 0 {
-            i -= 1;
-            let msg = &merged_messages[i];
-
-            match msg.msg_type.as_str() {
-                "user" => {
-                    // 找到一个 user，向后查找第一个 assistant 作为答案
-                    let mut answer: Option<Message> = None;
-
-                    // 从当前 user 之后开始向后找 assistant
-                    let mut j = i + 1;
-                    while j < merged_messages.len() {
-                        let next_msg = &merged_messages[j];
-                        match next_msg.msg_type.as_str() {
-                            "assistant" => {
-                                // 找到 assistant，记录为答案
-                                answer = Some(next_msg.clone());
-                                // 继续查找，可能有更合适的 assistant
-                                j += 1;
-                            }
-                            "user" => {
-                                // 遇到新的 user，停止查找
-                                break;
-                            }
-                            _ => {
-                                // 遇到其他类型，停止查找
-                                break;
-                            }
-                        }
-                    }
-
-                    // 只有当找到答案时才创建问答对
-                    if answer.is_some() {
-                        qa_pairs.push(QAPair {
-                            question: msg.clone(),
-                            answer,
-                            timestamp: msg.timestamp.clone(),
-                        });
-                    }
+        // 原始消息在合并列表中的位置（工具往返、技能消息等中间节点为 None）
+        let position: HashMap<&str, usize> = merged_messages
+            .iter()
+            .enumerate()
+            .map(|(pos, msg)| (msg.uuid.as_str(), pos))
+            .collect();
+        let merged_pos: Vec<Option<usize>> = messages
+            .iter()
+            .map(|msg| position.get(msg.uuid.as_str()).copied())
+            .collect();
+        let is_question: Vec<bool> = merged_pos
+            .iter()
+            .map(|pos| pos.is_some_and(|p| merged_messages[p].msg_type == "user"))
+            .collect();
+
+        // 步骤 3: 沿 parentUuid 链路查找答案
+        let tree = QATreeIndex::build(&messages);
+        let mut answers: Vec<Option<usize>> = vec![None; merged_messages.len()];
+        let mut claimed = vec![false; merged_messages.len()];
+        let mut needs_fallback = vec![false; merged_messages.len()];
+        let mut visited = vec![false; messages.len()];
+
+        for start in (0..messages.len()).filter(|&i| is_question[i]) {
+            let question_pos = merged_pos[start].expect("问题必定在合并列表中");
+            let mut node = start;
+            let mut answer = None;
+            visited[start] = true;
+
+            while let Some(next) = tree.next_in_turn(node, &is_question) {
+                // parentUuid 成环时停止
+                if visited[next] {
+                    break;
                 }
-                _ => {
-                    // 其他类型（如 assistant），跳过
-                    // 这些消息会在找到对应的 user 时作为答案被处理
+                visited[next] = true;
+                if let Some(pos) = merged_pos[next] {
+                    answer = Some(pos);
                 }
+                node = next;
+            }
+
+            if let Some(pos) = answer {
+                answers[question_pos] = Some(pos);
+                claimed[pos] = true;
+            } else {
+                needs_fallback[question_pos] = tree.children[node].is_empty();
             }
         }
 
-        // 从后向前扫描得到的结果是倒序的，需要反转回来
-        qa_pairs.reverse();
+        // 步骤 4: 标记被取代的分支
+        let superseded = tree.superseded(&is_question);
+
+        // 步骤 5: 链路断开的问题按文件顺序回退
+        for pos in (0..merged_messages.len()).filter(|&pos| needs_fallback[pos]) {
+            let answer = merged_messages[pos + 1..]
+                .iter()
+                .take_while(|msg| msg.msg_type == "assistant")
+                .count();
+            if answer > 0 && !claimed[pos + answer] {
+                answers[pos] = Some(pos + answer);
+                claimed[pos + answer] = true;
+            }
+        }
+
+        let mut question_superseded = vec![false; merged_messages.len()];
+        for (i, pos) in merged_pos.iter().enumerate() {
+            if let Some(pos) = *pos {
+                question_superseded[pos] = superseded[i];
+            }
+        }
+
+        let qa_turns: Vec<QAPair> = merged_messages
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| msg.msg_type == "user")
+            .map(|(pos, msg)| QAPair {
+                question: msg.clone(),
+                answer: answers[pos].map(|answer| merged_messages[answer].clone()),
+                timestamp: msg.timestamp.clone(),
+                superseded: question_superseded[pos],
+            })
+            .collect();
 
         #[cfg(debug_assertions)]
         {
-            eprintln!("🔍 [extract_qa_pairs] 输出问答对数量: {}", qa_pairs.len());
+            eprintln!(
+                "🔍 [extract_qa_turns] 输出问答轮次: {}，其中已取代: {}",
+                qa_turns.len(),
+                qa_turns.iter().filter(|pair| pair.superseded).count()
+            );
         }
 
-        qa_pairs
+        qa_turns
     }
 
     /// 获取当前等级
@@ -474,14 +808,14 @@ impl MessageFilter {
     ///
     /// # 返回
     ///
-    /// 过滤后的消息列表，只包含适合问答对的消息
-    fn pre_filter_for_qa(&self, messages: Vec<Message>) -> Vec<Message> {
+    /// 过滤后的消息列表（副本），只包含适合问答对的消息
+    fn pre_filter_for_qa(&self, messages: &[Message]) -> Vec<Message> {
         use crate::filter_config::FilterConfigManager;
 
         // 在迭代器之前创建一次 FilterConfigManager，避免为每条消息重复创建
         let filter_mgr = FilterConfigManager::with_default_path().ok();
 
-        messages.into_iter()
+        messages.iter()
             .filter(|msg| {
                 // ========== 内容过滤检查（集成 FilterConfigManager）==========
                 // 如果消息有 summary，先应用内容过滤规则
@@ -518,6 +852,7 @@ impl MessageFilter {
                 // 过滤掉其他类型（system, tool_use, tool_result 等）
                 false
             })
+            .cloned()
             .collect()
     }
 
@@ -529,8 +864,8 @@ impl MessageFilter {
     ///
     /// # 算法
     ///
-    /// 1. 遍历消息列表
-    /// 2. 当遇到 user 消息时，查找其直系技能子消息（parentUuid == user.uuid）
+    /// 1. 按 parentUuid 建立技能消息索引
+    /// 2. 遍历消息列表，当遇到 user 消息时，取出其直系技能子消息（parentUuid == user.uuid）
     /// 3. 如果找到技能消息，合并其内容到用户消息
     /// 4. 从列表中移除已合并的技能消息
     ///
@@ -549,6 +884,19 @@ impl MessageFilter {
             eprintln!("🔗 [merge_user_with_skill_messages] 开始合并，原始消息数: {}", messages.len());
         }
 
+        // 父消息 UUID -> 技能子消息，避免为每条用户消息重新扫描整个列表
+        let mut skill_children: HashMap<&str, Vec<&Message>> = HashMap::new();
+        for msg in &messages {
+            if let Some(ref parent_uuid) = msg.parent_uuid {
+                if self.is_skill_invocation_message(msg) {
+                    skill_children
+                        .entry(parent_uuid.as_str())
+                        .or_default()
+                        .push(msg);
+                }
+            }
+        }
+
         let mut result = Vec::new();
         let mut merged_uuids = HashSet::new();
 
@@ -559,20 +907,9 @@ impl MessageFilter {
             }
 
             if msg.msg_type == "user" {
-                // 查找该用户消息的直系技能子消息
-                let skill_children: Vec<&Message> = messages
-                    .iter()
-                    .filter(|m| {
-                        // parentUuid 指向当前用户消息
-                        m.parent_uuid.as_ref() == Some(&msg.uuid)
-                        // 且是技能消息
-                        && self.is_skill_invocation_message(m)
-                    })
-                    .collect();
-
-                if !skill_children.is_empty() {
+                // 取出该用户消息的直系技能子消息
+                if let Some(mut sorted_skills) = skill_children.remove(msg.uuid.as_str()) {
                     // 按原始顺序（timestamp）排序技能消息
-                    let mut sorted_skills = skill_children;
                     sorted_skills.sort_by_key(|m| &m.timestamp);
 
                     // 合并内容
@@ -868,6 +1205,99 @@ mod tests {
         assert!(filter.should_include(&thinking_msg));
     }
 
+    #[test]
+    fn test_custom_level_user_prompts_and_edits() {
+        let level: CustomViewLevel = serde_json::from_value(serde_json::json!({
+            "name": "review",
+            "predicate": {"kind": "any", "predicates": [
+                {"kind": "role", "values": ["user"]},
+                {"kind": "tool_name", "values": ["Edit"]}
+            ]}
+        }))
+        .unwrap();
+        assert!(level.validate().is_ok());
+        let filter = MessageFilter::with_custom_level(level);
+
+        let prompt = create_test_message("user", "uuid1", None);
+        let answer = create_test_message("assistant", "uuid2", Some("uuid1"));
+
+        let mut edit = create_test_message_with_summary(
+            "assistant",
+            "uuid3",
+            r#"{"role":"assistant","content":[{"type":"tool_use","name":"Edit","input":{}}]}"#,
+        );
+        edit.content_type = Some("tool_use".to_string());
+
+        let mut bash = create_test_message_with_summary(
+            "assistant",
+            "uuid4",
+            r#"{"role":"assistant","content":[{"type":"tool_use","name":"Bash","input":{}}]}"#,
+        );
+        bash.content_type = Some("tool_use".to_string());
+
+        let mut result = create_test_message_with_summary(
+            "user",
+            "uuid5",
+            r#"{"role":"user","content":[{"type":"tool_result","is_error":true,"content":"x"}]}"#,
+        );
+        result.content_type = Some("tool_result".to_string());
+
+        assert!(filter.should_include(&prompt));
+        assert!(!filter.should_include(&answer));
+        assert!(filter.should_include(&edit));
+        assert!(!filter.should_include(&bash));
+        assert!(!filter.should_include(&result));
+        assert!(MessagePredicate::HasError.matches(&result));
+    }
+
+    #[test]
+    fn test_custom_level_filters_qa_pairs() {
+        let level = CustomViewLevel {
+            name: "errors".to_string(),
+            description: None,
+            predicate: MessagePredicate::ContainsText {
+                text: "panic".to_string(),
+                case_sensitive: false,
+            },
+        };
+        let filter = MessageFilter::with_custom_level(level);
+
+        let mut question1 = create_test_message("user", "uuid1", None);
+        question1.content = Some("why does it panic?".to_string());
+        let answer1 = create_test_message("assistant", "uuid2", Some("uuid1"));
+        let question2 = create_test_message("user", "uuid3", Some("uuid2"));
+        let mut answer2 = create_test_message("assistant", "uuid4", Some("uuid3"));
+        answer2.content = Some("Panic comes from unwrap".to_string());
+        let question3 = create_test_message("user", "uuid5", Some("uuid4"));
+        let answer3 = create_test_message("assistant", "uuid6", Some("uuid5"));
+
+        let qa_pairs = filter.extract_qa_pairs(vec![
+            question1, answer1, question2, answer2, question3, answer3,
+        ]);
+        let questions: Vec<&str> = qa_pairs
+            .iter()
+            .map(|pair| pair.question.uuid.as_str())
+            .collect();
+        assert_eq!(questions, vec!["uuid1", "uuid3"]);
+    }
+
+    #[test]
+    fn test_custom_level_validation() {
+        let preset_name = CustomViewLevel {
+            name: "full".to_string(),
+            description: None,
+            predicate: MessagePredicate::HasError,
+        };
+        assert!(preset_name.validate().is_err());
+
+        let empty_any = CustomViewLevel {
+            name: "errors".to_string(),
+            description: None,
+            predicate: MessagePredicate::Any { predicates: vec![] },
+        };
+        assert!(empty_any.validate().is_err());
+    }
+
     #[test]
     fn test_filter_conversation_level() {
         let filter = MessageFilter::new(ViewLevel::Conversation);
@@ -1133,6 +1563,56 @@ mod tests {
         assert_eq!(qa_pairs[1].answer.as_ref().unwrap().uuid, assistant2.uuid);
     }
 
+    #[test]
+    fn test_extract_qa_turns_follows_parent_chain() {
+        let filter = MessageFilter::new(ViewLevel::QAPairs);
+        let with_type = |msg_type: &str, uuid: &str, parent: &str, content_type: &str| Message {
+            content_type: Some(content_type.to_string()),
+            ..create_test_message(msg_type, uuid, Some(parent))
+        };
+
+        // u1 的工具往返与被编辑的 u2 分支在文件中交错出现
+        let messages = vec![
+            create_test_message("user", "u1", None),
+            with_type("assistant", "a1", "u1", "tool_use"),
+            with_type("user", "r1", "a1", "tool_result"),
+            with_type("assistant", "a2", "r1", "text"),
+            create_test_message("user", "u2", Some("a2")),
+            with_type("assistant", "a3", "u2", "text"),
+            create_test_message("user", "u3", Some("a3")),
+            create_test_message("user", "u2-edit", Some("a2")),
+            with_type("assistant", "a4", "u2-edit", "tool_use"),
+            with_type("user", "r4", "a4", "tool_result"),
+            with_type("assistant", "a5", "r4", "text"),
+        ];
+
+        let turns = filter.extract_qa_turns(messages.clone());
+        let summary: Vec<(&str, Option<&str>, bool)> = turns
+            .iter()
+            .map(|pair| {
+                (
+                    pair.question.uuid.as_str(),
+                    pair.answer.as_ref().map(|a| a.uuid.as_str()),
+                    pair.superseded,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("u1", Some("a2"), false),
+                ("u2", Some("a3"), true),
+                ("u3", None, true),
+                ("u2-edit", Some("a5"), false),
+            ]
+        );
+
+        // extract_qa_pairs 只保留有答案的问题
+        let pairs = filter.extract_qa_pairs(messages);
+        assert_eq!(pairs.len(), 3);
+        assert!(pairs.iter().all(|pair| pair.answer.is_some()));
+    }
+
     #[test]
     fn test_message_order_preservation() {
         let filter = MessageFilter::new(ViewLevel::Conversation);
@@ -1,10 +1,14 @@
-//! Windows 路径转换器
+//! 路径转换器
 //!
-//! 将 Windows 路径转换为 Claude Code projects 文件夹名称
+//! 将项目路径转换为 Claude Code projects 文件夹名称
 //!
-//! 示例：
+//! Windows 示例：
 //! - C:\software\Java\project → C--software-Java-project
 //! - D:\Projects\My App\v2.0 → D--Projects-My App-v2.0
+//!
+//! Linux / macOS 示例：
+//! - /home/dev/prism-forge → -home-dev-prism-forge
+//! - /Users/dev/my_app/.worktrees → -Users-dev-my-app--worktrees
 
 use super::{PathConverter, PathResolveError};
 use std::path::Path;
@@ -117,6 +121,67 @@ impl PathConverter for WindowsPathConverter {
     }
 }
 
+/// Linux / macOS 路径转换器
+///
+/// 与 Claude Code 的编码一致：斜杠、点和下划线都替换为连字符。
+/// 编码是有损的，`folder_name_to_path` 只能把连字符一律还原为斜杠，
+/// 需要真实路径时应使用会话条目中的 `cwd`（见 `ClaudePathResolver::project_path_for_folder`）。
+#[derive(Default)]
+pub struct PosixPathConverter;
+
+impl PosixPathConverter {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl PathConverter for PosixPathConverter {
+    fn path_to_folder_name(&self, path: &Path) -> Result<String, PathResolveError> {
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| PathResolveError::InvalidPath("路径包含无效字符".to_string()))?;
+
+        let folder_name: String = path_str
+            .chars()
+            .map(|c| match c {
+                '/' | '.' | '_' => '-',
+                c => c,
+            })
+            .collect();
+
+        #[cfg(debug_assertions)]
+        eprintln!("[PathConverter] {} → {}", path_str, folder_name);
+
+        Ok(folder_name)
+    }
+
+    fn folder_name_to_path(
+        &self,
+        folder_name: &str,
+    ) -> Result<std::path::PathBuf, PathResolveError> {
+        // 绝对路径编码后必然以连字符开头
+        if !folder_name.starts_with('-') {
+            return Err(PathResolveError::InvalidFolderName(
+                "文件夹名称必须以连字符开头".to_string(),
+            ));
+        }
+
+        Ok(std::path::PathBuf::from(folder_name.replace('-', "/")))
+    }
+}
+
+/// 获取当前平台的路径转换器
+pub fn platform_converter() -> Box<dyn PathConverter + Send + Sync> {
+    #[cfg(windows)]
+    {
+        Box::new(WindowsPathConverter::new())
+    }
+    #[cfg(not(windows))]
+    {
+        Box::new(PosixPathConverter::new())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -161,4 +226,24 @@ mod tests {
         let result = converter.path_to_folder_name(path).unwrap();
         assert_eq!(result, "C--软件-项目-测试");
     }
+
+    #[test]
+    fn test_posix_path() {
+        let converter = PosixPathConverter::new();
+        let path = Path::new("/home/dev/full_stack/prism-forge");
+        let result = converter.path_to_folder_name(path).unwrap();
+        assert_eq!(result, "-home-dev-full-stack-prism-forge");
+
+        let path = Path::new("/Users/dev/app/.claude/worktrees/v1.2");
+        let result = converter.path_to_folder_name(path).unwrap();
+        assert_eq!(result, "-Users-dev-app--claude-worktrees-v1-2");
+    }
+
+    #[test]
+    fn test_restore_posix_path() {
+        let converter = PosixPathConverter::new();
+        let result = converter.folder_name_to_path("-home-dev-project").unwrap();
+        assert_eq!(result, Path::new("/home/dev/project"));
+        assert!(converter.folder_name_to_path("C--software").is_err());
+    }
 }
@@ -1,14 +1,15 @@
 //! 路径解析模块
 //!
-//! 将 Windows 文件系统路径转换为 Claude Code projects 目录下的文件夹名称
+//! 将项目路径（Windows、Linux、macOS）转换为 Claude Code projects 目录下的文件夹名称，
+//! 并根据会话记录的 `cwd` 反查真实项目路径
 
 pub mod converter;
 pub mod resolver;
 
-pub use converter::WindowsPathConverter;
+pub use converter::{platform_converter, PosixPathConverter, WindowsPathConverter};
 pub use resolver::{
-    list_session_files, resolve_session_directory, session_directory_exists, ClaudePathResolver,
-    PathConverter, PathResolveError, PathResolver, SessionFileInfo,
+    find_project_cwd, list_session_files, resolve_session_directory, session_directory_exists,
+    ClaudePathResolver, PathConverter, PathResolveError, PathResolver, SessionFileInfo,
 };
 
 /// 重新导出常用类型
@@ -2,16 +2,20 @@
 //!
 //! 根据项目路径解析对应的会话目录
 
-use super::converter::WindowsPathConverter;
+use super::converter::platform_converter;
 use crate::session_type_detector::{detect_session_type_by_filename, SessionFileType};
 use dirs::home_dir;
 use std::fs;
+use std::io::{BufRead, BufReader};
 use std::path::{Component, Path, PathBuf};
 
+/// 查找 `cwd` 时每个会话文件最多读取的行数
+const CWD_SCAN_LINES: usize = 100;
+
 /// 路径解析错误
 #[derive(Debug, thiserror::Error)]
 pub enum PathResolveError {
-    #[error("无效的 Windows 路径: {0}")]
+    #[error("无效的路径: {0}")]
     InvalidPath(String),
 
     #[error("无法获取用户主目录")]
@@ -29,7 +33,7 @@ pub enum PathResolveError {
 
 /// 路径转换器 Trait
 pub trait PathConverter {
-    /// 将 Windows 路径转换为文件夹名称
+    /// 将项目路径转换为文件夹名称
     fn path_to_folder_name(&self, path: &Path) -> Result<String, PathResolveError>;
 
     /// 从文件夹名称还原原始路径
@@ -70,7 +74,7 @@ pub struct SessionFileInfo {
 
 /// Claude Code 路径解析器
 pub struct ClaudePathResolver {
-    converter: WindowsPathConverter,
+    converter: Box<dyn PathConverter + Send + Sync>,
     projects_base_dir: PathBuf,
 }
 
@@ -85,15 +89,23 @@ impl ClaudePathResolver {
         eprintln!("[PathResolver] Projects 基础目录: {:?}", projects_base_dir);
 
         Ok(Self {
-            converter: WindowsPathConverter::new(),
+            converter: platform_converter(),
             projects_base_dir,
         })
     }
 
     /// 使用自定义基础目录创建解析器（用于测试）
     pub fn with_base_dir(base_dir: PathBuf) -> Self {
+        Self::with_converter(base_dir, platform_converter())
+    }
+
+    /// 使用自定义基础目录和路径转换器创建解析器
+    pub fn with_converter(
+        base_dir: PathBuf,
+        converter: Box<dyn PathConverter + Send + Sync>,
+    ) -> Self {
         Self {
-            converter: WindowsPathConverter::new(),
+            converter,
             projects_base_dir: base_dir,
         }
     }
@@ -103,6 +115,29 @@ impl ClaudePathResolver {
         &self.projects_base_dir
     }
 
+    /// 根据会话目录名称反查真实的项目路径
+    ///
+    /// 文件夹编码是有损的，因此优先使用目录下会话条目记录的 `cwd`
+    /// （编码后与目录名称一致的第一个），找不到时退回转换器的还原结果。
+    pub fn project_path_for_folder(&self, folder_name: &str) -> Result<PathBuf, PathResolveError> {
+        self.validate_folder_name(folder_name)?;
+
+        let session_dir = self.build_session_dir_path(folder_name);
+        if session_dir.is_dir() {
+            for entry in fs::read_dir(&session_dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                    continue;
+                }
+                if let Some(cwd) = find_project_cwd(&path, folder_name, self.converter.as_ref()) {
+                    return Ok(cwd);
+                }
+            }
+        }
+
+        self.converter.folder_name_to_path(folder_name)
+    }
+
     /// 构建会话目录的完整路径
     fn build_session_dir_path(&self, folder_name: &str) -> PathBuf {
         self.projects_base_dir.join(folder_name)
@@ -239,6 +274,32 @@ fn system_time_to_rfc3339(time: std::time::SystemTime) -> Result<String, PathRes
     Ok(datetime.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
 }
 
+/// 从会话文件中查找项目的真实路径
+///
+/// 读取条目中的 `cwd` 字段，返回编码后与 `folder_name` 一致的第一个路径。
+/// 会话中途切换过工作目录时，不一致的 `cwd` 会被跳过。
+pub fn find_project_cwd(
+    session_file: &Path,
+    folder_name: &str,
+    converter: &dyn PathConverter,
+) -> Option<PathBuf> {
+    let file = fs::File::open(session_file).ok()?;
+
+    BufReader::new(file)
+        .lines()
+        .take(CWD_SCAN_LINES)
+        .map_while(|line| line.ok())
+        .filter_map(|line| {
+            let entry: serde_json::Value = serde_json::from_str(&line).ok()?;
+            entry.get("cwd").and_then(|v| v.as_str()).map(PathBuf::from)
+        })
+        .find(|cwd| {
+            converter
+                .path_to_folder_name(cwd)
+                .is_ok_and(|name| name == folder_name)
+        })
+}
+
 /// 便捷函数：解析会话目录
 pub fn resolve_session_directory(project_path: &Path) -> Result<PathBuf, PathResolveError> {
     let resolver = ClaudePathResolver::new()?;
@@ -260,13 +321,21 @@ pub fn list_session_files(project_path: &Path) -> Result<Vec<SessionFileInfo>, P
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::path_resolver::{PosixPathConverter, WindowsPathConverter};
     use std::fs::{self, File};
     use tempfile::TempDir;
 
+    fn windows_resolver(base_dir: &Path) -> ClaudePathResolver {
+        ClaudePathResolver::with_converter(
+            base_dir.to_path_buf(),
+            Box::new(WindowsPathConverter::new()),
+        )
+    }
+
     #[test]
     fn test_resolve_session_dir() {
         let temp_dir = TempDir::new().unwrap();
-        let resolver = ClaudePathResolver::with_base_dir(temp_dir.path().to_path_buf());
+        let resolver = windows_resolver(temp_dir.path());
 
         let project_path = Path::new(r"C:\software\Java\project");
         let session_dir = resolver.resolve_session_dir(project_path).unwrap();
@@ -278,7 +347,7 @@ mod tests {
     #[test]
     fn test_session_dir_not_exists() {
         let temp_dir = TempDir::new().unwrap();
-        let resolver = ClaudePathResolver::with_base_dir(temp_dir.path().to_path_buf());
+        let resolver = windows_resolver(temp_dir.path());
 
         let project_path = Path::new(r"C:\nonexistent\project");
         let exists = resolver.session_dir_exists(project_path).unwrap();
@@ -296,11 +365,48 @@ mod tests {
         File::create(session_dir.join("session-2.jsonl")).unwrap();
         File::create(session_dir.join("other.txt")).unwrap();
 
-        let resolver = ClaudePathResolver::with_base_dir(temp_dir.path().to_path_buf());
+        let resolver = windows_resolver(temp_dir.path());
         let project_path = Path::new(r"C:\software\Java\project");
         let sessions = resolver.list_session_files_sorted(project_path).unwrap();
 
         assert_eq!(sessions.len(), 2);
         assert!(sessions[0].file_name.contains("session"));
     }
+
+    #[test]
+    fn test_project_path_for_folder() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder_name = "-home-dev-my-app";
+        let session_dir = temp_dir.path().join(folder_name);
+        fs::create_dir_all(&session_dir).unwrap();
+
+        // 第二行切换到了子目录，只有编码一致的 cwd 会被采用
+        fs::write(
+            session_dir.join("session-1.jsonl"),
+            concat!(
+                r#"{"type":"summary","summary":"build"}"#,
+                "\n",
+                r#"{"type":"user","cwd":"/home/dev/my_app/src"}"#,
+                "\n",
+                r#"{"type":"user","cwd":"/home/dev/my_app"}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let resolver = ClaudePathResolver::with_converter(
+            temp_dir.path().to_path_buf(),
+            Box::new(PosixPathConverter::new()),
+        );
+        assert_eq!(
+            resolver.project_path_for_folder(folder_name).unwrap(),
+            Path::new("/home/dev/my_app")
+        );
+
+        // 没有会话记录时退回有损还原
+        assert_eq!(
+            resolver.project_path_for_folder("-srv-api").unwrap(),
+            Path::new("/srv/api")
+        );
+    }
 }
@@ -0,0 +1,511 @@
+//! 敏感信息脱敏模块
+//!
+//! 在导出会话或提交给第三方 LLM 之前，替换会话内容中的密钥、令牌、
+//! 邮箱和用户主目录路径。
+//!
+//! 同一个 [`Redactor`] 实例内，相同的原文总是映射到相同的占位符
+//! （如 `[REDACTED_API_KEY_1]`），因此脱敏后的内容仍能看出"这是同一个密钥"。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::database::models::Message;
+use crate::parser::tree::{ConversationTree, MessageNode};
+
+// ==================== 数据结构 ====================
+
+/// 自定义脱敏规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedactionRule {
+    /// 规则名称（唯一标识，也用作占位符类别）
+    pub name: String,
+
+    /// 是否启用
+    pub enabled: bool,
+
+    /// 正则表达式；如有第一个捕获组，则只替换捕获组部分
+    pub pattern: String,
+
+    /// 规则描述
+    pub description: Option<String>,
+}
+
+/// 脱敏配置文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedactionConfig {
+    /// 配置版本
+    pub version: String,
+
+    /// 导出时默认是否脱敏（命令可显式覆盖）
+    pub enabled: bool,
+
+    /// 提交给 LLM 前是否脱敏
+    pub apply_to_llm: bool,
+
+    /// 是否启用内置密钥规则
+    pub builtin_secrets: bool,
+
+    /// 是否将用户主目录替换为 `~`
+    pub anonymize_paths: bool,
+
+    /// 是否脱敏邮箱地址
+    pub redact_emails: bool,
+
+    /// 自定义规则
+    pub custom_rules: Vec<RedactionRule>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            version: "1.0".to_string(),
+            enabled: false,
+            apply_to_llm: true,
+            builtin_secrets: true,
+            anonymize_paths: true,
+            redact_emails: true,
+            custom_rules: Vec::new(),
+        }
+    }
+}
+
+/// 脱敏结果条目（用于预览）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedactionFinding {
+    /// 命中的规则名称
+    pub rule: String,
+
+    /// 替换后的占位符
+    pub placeholder: String,
+
+    /// 原文的遮挡预览（只保留前几个字符）
+    pub preview: String,
+
+    /// 出现次数
+    pub occurrences: usize,
+}
+
+// ==================== 内置规则 ====================
+
+/// 内置密钥规则：(规则名称, 正则)
+///
+/// 顺序敏感：更具体的规则放在前面（如 Anthropic 密钥先于通用 `sk-` 密钥）
+const BUILTIN_SECRET_PATTERNS: &[(&str, &str)] = &[
+    (
+        "private_key",
+        r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+    ),
+    ("anthropic_key", r"sk-ant-[A-Za-z0-9_\-]{20,}"),
+    ("openai_key", r"sk-(?:proj-)?[A-Za-z0-9_\-]{20,}"),
+    ("aws_access_key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+    (
+        "github_token",
+        r"\b(?:gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,})",
+    ),
+    ("slack_token", r"\bxox[abprs]-[A-Za-z0-9\-]{10,}"),
+    ("google_api_key", r"\bAIza[0-9A-Za-z_\-]{35}"),
+    (
+        "jwt",
+        r"\beyJ[A-Za-z0-9_\-]{10,}\.[A-Za-z0-9_\-]{10,}\.[A-Za-z0-9_\-]{10,}",
+    ),
+    (
+        "bearer_token",
+        r"(?i)\bbearer\s+([A-Za-z0-9._~+/\-]{20,}=*)",
+    ),
+    (
+        "secret_assignment",
+        r#"(?i)(?:api[_-]?key|secret|token|password|passwd)["']?\s*[:=]\s*["']?([A-Za-z0-9_\-./+]{8,})"#,
+    ),
+];
+
+/// 邮箱规则
+const EMAIL_PATTERN: &str = r"\b[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}\b";
+
+/// 用户主目录规则（Linux、macOS、Windows）
+const HOME_PATH_PATTERN: &str =
+    r#"(?:/home/[^/\s"'`]+|/Users/[^/\s"'`]+|[A-Za-z]:\\+Users\\+[^\\\s"'`]+)"#;
+
+// ==================== 脱敏引擎 ====================
+
+/// 编译后的规则
+struct CompiledRule {
+    name: String,
+    regex: regex::Regex,
+}
+
+/// 脱敏引擎
+pub struct Redactor {
+    /// 按顺序应用的密钥规则
+    rules: Vec<CompiledRule>,
+
+    /// 主目录规则（替换为 `~`，不进入占位符映射）
+    home_path: Option<regex::Regex>,
+
+    /// 原文 -> 占位符
+    mapping: HashMap<String, String>,
+
+    /// 每个类别已分配的占位符数量
+    counters: HashMap<String, usize>,
+
+    /// 命中记录（按占位符排序）
+    findings: BTreeMap<String, RedactionFinding>,
+}
+
+impl Redactor {
+    /// 根据配置创建脱敏引擎
+    pub fn new(config: &RedactionConfig) -> Result<Self> {
+        let mut rules = Vec::new();
+
+        if config.builtin_secrets {
+            for (name, pattern) in BUILTIN_SECRET_PATTERNS {
+                rules.push(CompiledRule {
+                    name: name.to_string(),
+                    regex: regex::Regex::new(pattern)
+                        .with_context(|| format!("内置规则 {} 编译失败", name))?,
+                });
+            }
+        }
+
+        for rule in config.custom_rules.iter().filter(|r| r.enabled) {
+            rules.push(CompiledRule {
+                name: rule.name.clone(),
+                regex: regex::Regex::new(&rule.pattern)
+                    .with_context(|| format!("规则 {} 的正则表达式无效", rule.name))?,
+            });
+        }
+
+        if config.redact_emails {
+            rules.push(CompiledRule {
+                name: "email".to_string(),
+                regex: regex::Regex::new(EMAIL_PATTERN)?,
+            });
+        }
+
+        let home_path = if config.anonymize_paths {
+            Some(regex::Regex::new(HOME_PATH_PATTERN)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            rules,
+            home_path,
+            mapping: HashMap::new(),
+            counters: HashMap::new(),
+            findings: BTreeMap::new(),
+        })
+    }
+
+    /// 按默认配置文件创建导出用的脱敏引擎
+    ///
+    /// # 参数
+    /// * `redact` - 显式指定是否脱敏；为 None 时使用配置中的 `enabled`
+    ///
+    /// # 返回
+    /// 不需要脱敏时返回 None
+    pub fn for_export(redact: Option<bool>) -> Result<Option<Self>> {
+        let manager = RedactionConfigManager::with_default_path()?;
+        let config = manager.get_config();
+        if redact.unwrap_or(config.enabled) {
+            Ok(Some(Self::new(config)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 按默认配置文件创建提交 LLM 用的脱敏引擎
+    ///
+    /// 配置中关闭 `apply_to_llm` 时返回 None。配置文件无法加载或规则无效时
+    /// 记录错误并回退到默认规则，不阻断 LLM 功能
+    pub fn for_llm() -> Option<Self> {
+        let loaded = RedactionConfigManager::with_default_path().and_then(|manager| {
+            let config = manager.get_config();
+            if config.apply_to_llm {
+                Self::new(config).map(Some)
+            } else {
+                Ok(None)
+            }
+        });
+
+        match loaded {
+            Ok(redactor) => redactor,
+            Err(e) => {
+                eprintln!("⚠️  加载脱敏配置失败，使用默认规则: {}", e);
+                Self::new(&RedactionConfig::default()).ok()
+            }
+        }
+    }
+
+    /// 脱敏一段文本
+    pub fn redact_text(&mut self, text: &str) -> String {
+        let mut output = text.to_string();
+
+        for index in 0..self.rules.len() {
+            if !self.rules[index].regex.is_match(&output) {
+                continue;
+            }
+
+            // 先收集替换区间，再从后向前替换，避免区间偏移
+            let spans: Vec<(usize, usize)> = self.rules[index]
+                .regex
+                .captures_iter(&output)
+                .filter_map(|caps| caps.get(1).or_else(|| caps.get(0)))
+                .filter(|m| !m.as_str().starts_with("[REDACTED_"))
+                .map(|m| (m.start(), m.end()))
+                .collect();
+
+            let rule_name = self.rules[index].name.clone();
+            for (start, end) in spans.into_iter().rev() {
+                let placeholder = self.placeholder_for(&rule_name, &output[start..end]);
+                output.replace_range(start..end, &placeholder);
+            }
+        }
+
+        if let Some(home_path) = self.home_path.clone() {
+            let homes: Vec<String> = home_path
+                .find_iter(&output)
+                .map(|m| m.as_str().to_string())
+                .collect();
+            for home in homes {
+                self.record("home_path", "~", &home);
+            }
+            output = home_path.replace_all(&output, "~").into_owned();
+        }
+
+        output
+    }
+
+    /// 递归脱敏 JSON 值中的所有字符串（不修改键名）
+    pub fn redact_value(&mut self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.redact_text(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            Value::Object(map) => map.values_mut().for_each(|item| self.redact_value(item)),
+            _ => {}
+        }
+    }
+
+    /// 脱敏整棵消息树的原始数据
+    ///
+    /// 应在提取元数据之前调用，使工具调用、代码变更等元数据也基于脱敏后的内容
+    pub fn redact_tree(&mut self, tree: &mut ConversationTree) {
+        fn walk(redactor: &mut Redactor, node: &mut MessageNode) {
+            redactor.redact_value(&mut node.message_data);
+            for child in &mut node.children {
+                walk(redactor, child);
+            }
+        }
+
+        for root in &mut tree.roots {
+            walk(self, root);
+        }
+    }
+
+    /// 脱敏消息的内容和摘要
+    pub fn redact_message(&mut self, message: &mut Message) {
+        if let Some(content) = message.content.as_mut() {
+            *content = self.redact_text(content);
+        }
+        if let Some(summary) = message.summary.as_mut() {
+            *summary = self.redact_text(summary);
+        }
+    }
+
+    /// 获取命中记录（按占位符排序）
+    pub fn findings(&self) -> Vec<RedactionFinding> {
+        self.findings.values().cloned().collect()
+    }
+
+    /// 获取原文对应的占位符，不存在时分配新的
+    fn placeholder_for(&mut self, rule: &str, original: &str) -> String {
+        let placeholder = match self.mapping.get(original) {
+            Some(placeholder) => placeholder.clone(),
+            None => {
+                let category = rule
+                    .to_uppercase()
+                    .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+                let counter = self.counters.entry(category.clone()).or_insert(0);
+                *counter += 1;
+                let placeholder = format!("[REDACTED_{}_{}]", category, counter);
+                self.mapping
+                    .insert(original.to_string(), placeholder.clone());
+                placeholder
+            }
+        };
+
+        self.record(rule, &placeholder, original);
+        placeholder
+    }
+
+    /// 记录一次命中
+    fn record(&mut self, rule: &str, placeholder: &str, original: &str) {
+        let key = format!("{}\u{0}{}", placeholder, original);
+        self.findings
+            .entry(key)
+            .or_insert_with(|| RedactionFinding {
+                rule: rule.to_string(),
+                placeholder: placeholder.to_string(),
+                preview: mask(original),
+                occurrences: 0,
+            })
+            .occurrences += 1;
+    }
+}
+
+/// 遮挡原文，只保留前 4 个字符和长度
+fn mask(original: &str) -> String {
+    let prefix: String = original.chars().take(4).collect();
+    format!("{}… ({} chars)", prefix, original.chars().count())
+}
+
+// ==================== 配置管理器 ====================
+
+/// 脱敏配置管理器
+pub struct RedactionConfigManager {
+    /// 配置文件路径
+    config_path: PathBuf,
+
+    /// 当前配置
+    config: RedactionConfig,
+}
+
+impl RedactionConfigManager {
+    /// 创建配置管理器
+    ///
+    /// # 参数
+    /// * `config_path` - 配置文件路径
+    pub fn new(config_path: PathBuf) -> Result<Self> {
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).context("创建配置目录失败")?;
+        }
+
+        let config = if config_path.exists() {
+            let content = fs::read_to_string(&config_path).context("读取脱敏配置失败")?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("解析脱敏配置失败: {}", config_path.display()))?
+        } else {
+            RedactionConfig::default()
+        };
+
+        Ok(Self {
+            config_path,
+            config,
+        })
+    }
+
+    /// 使用默认路径创建配置管理器
+    ///
+    /// 与过滤规则放在同一目录：`<配置目录>/prism-forge/redaction-rules.json`
+    pub fn with_default_path() -> Result<Self> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("无法获取配置目录"))?
+            .join("prism-forge");
+        Self::new(config_dir.join("redaction-rules.json"))
+    }
+
+    /// 获取配置
+    pub fn get_config(&self) -> &RedactionConfig {
+        &self.config
+    }
+
+    /// 更新配置
+    pub fn update_config(&mut self, config: RedactionConfig) -> Result<()> {
+        Self::validate_config(&config)?;
+
+        let json = serde_json::to_string_pretty(&config).context("序列化配置失败")?;
+        fs::write(&self.config_path, json).context("写入配置文件失败")?;
+
+        self.config = config;
+        Ok(())
+    }
+
+    /// 验证配置
+    fn validate_config(config: &RedactionConfig) -> Result<()> {
+        let mut names = std::collections::HashSet::new();
+        for rule in &config.custom_rules {
+            if !names.insert(&rule.name) {
+                anyhow::bail!("规则名称重复: {}", rule.name);
+            }
+            if rule.pattern.is_empty() {
+                anyhow::bail!("规则 {} 的匹配模式为空", rule.name);
+            }
+            regex::Regex::new(&rule.pattern)
+                .with_context(|| format!("规则 {} 的正则表达式无效", rule.name))?;
+        }
+        Ok(())
+    }
+}
+
+// ==================== 单元测试 ====================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redaction_is_deterministic() {
+        let mut redactor = Redactor::new(&RedactionConfig::default()).unwrap();
+
+        let key = "sk-ant-REDACTED";
+        let first = redactor.redact_text(&format!("export ANTHROPIC_API_KEY={}", key));
+        let second = redactor.redact_text(&format!("key is {}", key));
+
+        assert!(!first.contains(key));
+        assert!(first.contains("[REDACTED_ANTHROPIC_KEY_1]"));
+        assert_eq!(second, "key is [REDACTED_ANTHROPIC_KEY_1]");
+
+        let findings = redactor.findings();
+        let finding = findings.iter().find(|f| f.rule == "anthropic_key").unwrap();
+        assert_eq!(finding.occurrences, 2);
+        assert!(!finding.preview.contains(key));
+    }
+
+    #[test]
+    fn test_paths_emails_and_custom_rules() {
+        let config = RedactionConfig {
+            custom_rules: vec![RedactionRule {
+                name: "customer_id".to_string(),
+                enabled: true,
+                pattern: r"CUST-\d{6}".to_string(),
+                description: None,
+            }],
+            ..Default::default()
+        };
+        let mut redactor = Redactor::new(&config).unwrap();
+
+        let mut value = json!({
+            "cwd": "/home/alice/project",
+            "content": [{"type": "text", "text": "Mail bob@example.com about CUST-123456 in C:\\Users\\bob\\repo"}]
+        });
+        redactor.redact_value(&mut value);
+
+        assert_eq!(value["cwd"], "~/project");
+        let text = value["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("[REDACTED_EMAIL_1]"));
+        assert!(text.contains("[REDACTED_CUSTOMER_ID_1]"));
+        assert!(text.ends_with("~\\repo"));
+    }
+
+    #[test]
+    fn test_invalid_custom_rule_is_rejected() {
+        let config = RedactionConfig {
+            custom_rules: vec![RedactionRule {
+                name: "broken".to_string(),
+                enabled: true,
+                pattern: "(".to_string(),
+                description: None,
+            }],
+            ..Default::default()
+        };
+        assert!(Redactor::new(&config).is_err());
+        assert!(RedactionConfigManager::validate_config(&config).is_err());
+    }
+}
@@ -0,0 +1,605 @@
+//! 会话文件压缩归档
+//!
+//! 将长期未更新的会话文件用 gzip 压缩到应用数据目录（`~/.prism-forge/archive`），
+//! 在 `session_archives` 表中记录清单后删除原文件，以节省 `~/.claude/projects` 的磁盘空间。
+//!
+//! 归档文件按相对 `~/.claude/projects` 的完整路径加 `.gz` 存放（子代理等嵌套文件不会冲突），
+//! 因此无需查询数据库即可由原路径定位。
+//! 读取时 [`resolve_session_path`] 会把归档解压到缓存目录，`JsonlParser`、
+//! `SessionDisplayName` 和导出命令据此透明地读取已归档的会话（字节偏移与原文件一致）。
+//! 缓存总大小超过 [`CACHE_MAX_BYTES`] 时按最近使用时间淘汰。
+//! 恢复时解压回原路径并删除归档。
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use crate::database::{SessionArchiveEntry, SessionArchiveRepository};
+use crate::monitor::scanner::SessionMetadata;
+use crate::parser::tree::MessageTreeBuilder;
+use crate::session_type_detector::SessionFileType;
+
+/// 默认归档年龄（天）：超过该天数未更新的会话会被归档
+pub const DEFAULT_ARCHIVE_AGE_DAYS: u32 = 90;
+
+/// 应用数据目录下的归档目录名
+const ARCHIVE_DIR_NAME: &str = "archive";
+
+/// 归档目录下的解压缓存目录名（项目目录名不会以 `.` 开头）
+const CACHE_DIR_NAME: &str = ".cache";
+
+/// 解压缓存的总大小上限（字节）
+pub const CACHE_MAX_BYTES: u64 = 512 * 1024 * 1024;
+
+/// 临时文件序号，避免并发解压写入同一个临时文件
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 批量归档结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveReport {
+    /// 本次归档的会话
+    pub archived: Vec<SessionArchiveEntry>,
+    /// 因仍活跃或未达到归档年龄而跳过的会话数
+    pub skipped: usize,
+    /// 归档失败的会话（文件路径和错误信息）
+    pub failed: Vec<String>,
+    /// 节省的磁盘空间（字节）
+    pub bytes_saved: u64,
+}
+
+/// 会话归档
+pub struct SessionArchive {
+    /// 归档目录
+    root: PathBuf,
+    /// 归档清单
+    manifest: SessionArchiveRepository,
+}
+
+impl SessionArchive {
+    /// 创建归档实例
+    pub fn new(root: PathBuf, manifest: SessionArchiveRepository) -> Self {
+        Self { root, manifest }
+    }
+
+    /// 使用默认归档目录和数据库创建归档实例
+    pub fn from_default() -> Result<Self> {
+        Ok(Self::new(
+            default_archive_root()?,
+            SessionArchiveRepository::from_default_db()?,
+        ))
+    }
+
+    /// 压缩归档单个会话文件
+    ///
+    /// 压缩后校验解压大小与原文件一致，写入清单后才删除原文件
+    pub fn archive(&self, session_id: &str, original: &Path) -> Result<SessionArchiveEntry> {
+        let archive_path = archive_path_in(&self.root, original);
+        if archive_path.exists() {
+            anyhow::bail!("归档文件已存在: {:?}", archive_path);
+        }
+
+        let original_size = fs::metadata(original)
+            .with_context(|| format!("无法读取文件元数据: {:?}", original))?
+            .len();
+        compress_file(original, &archive_path)?;
+
+        let restored_size = decompressed_size(&archive_path)?;
+        if restored_size != original_size {
+            let _ = fs::remove_file(&archive_path);
+            anyhow::bail!(
+                "归档校验失败: 解压后 {} 字节，原文件 {} 字节",
+                restored_size,
+                original_size
+            );
+        }
+
+        let entry = SessionArchiveEntry {
+            session_id: session_id.to_string(),
+            original_path: original.to_string_lossy().to_string(),
+            archive_path: archive_path.to_string_lossy().to_string(),
+            original_size,
+            compressed_size: fs::metadata(&archive_path)?.len(),
+            archived_at: Utc::now().to_rfc3339(),
+        };
+        self.manifest.upsert(&entry)?;
+
+        fs::remove_file(original).with_context(|| format!("无法删除原文件: {:?}", original))?;
+        let _ = fs::remove_file(cache_path_in(&self.root, original));
+
+        Ok(entry)
+    }
+
+    /// 归档超过指定天数未更新的会话（活跃会话始终跳过）
+    ///
+    /// 子代理会话由 `attach_sidechains` 在主会话所在目录中查找，
+    /// 因此所属主会话文件仍在原位置时不归档 agent 文件；
+    /// 主会话先于 agent 文件处理，同一批次归档的主会话不会阻止其 agent 文件归档
+    pub fn archive_older_than(
+        &self,
+        sessions: &[SessionMetadata],
+        max_age_days: u32,
+    ) -> ArchiveReport {
+        let cutoff = Utc::now() - Duration::days(max_age_days as i64);
+        let mut report = ArchiveReport::default();
+
+        let (agents, mains): (Vec<_>, Vec<_>) = sessions
+            .iter()
+            .partition(|session| session.file_type == SessionFileType::Agent);
+        for session in mains.into_iter().chain(agents) {
+            let is_old = DateTime::parse_from_rfc3339(&session.updated_at)
+                .map(|updated| updated.with_timezone(&Utc) < cutoff)
+                .unwrap_or(false);
+            let parent_is_live =
+                session.file_type == SessionFileType::Agent && has_live_parent(&session.file_path);
+            if session.is_active || !is_old || parent_is_live {
+                report.skipped += 1;
+                continue;
+            }
+
+            match self.archive(&session.session_id, &session.file_path) {
+                Ok(entry) => {
+                    report.bytes_saved += entry.original_size.saturating_sub(entry.compressed_size);
+                    report.archived.push(entry);
+                }
+                Err(e) => report
+                    .failed
+                    .push(format!("{}: {}", session.file_path.display(), e)),
+            }
+        }
+
+        report
+    }
+
+    /// 将已归档的会话解压回原路径，并删除归档文件和清单条目
+    ///
+    /// # 参数
+    /// - `original`: 归档前的会话文件路径（清单条目的 `original_path`）
+    ///
+    /// # 返回
+    /// 返回被恢复的清单条目
+    pub fn restore(&self, original: &Path) -> Result<SessionArchiveEntry> {
+        let entry = self
+            .manifest
+            .get(&original.to_string_lossy())?
+            .ok_or_else(|| anyhow::anyhow!("会话未压缩归档: {:?}", original))?;
+        let original = PathBuf::from(&entry.original_path);
+        if original.exists() {
+            anyhow::bail!("原文件已存在: {:?}", original);
+        }
+
+        decompress_file(Path::new(&entry.archive_path), &original)?;
+        let restored_size = fs::metadata(&original)?.len();
+        if restored_size != entry.original_size {
+            anyhow::bail!(
+                "恢复校验失败: 解压后 {} 字节，原文件 {} 字节",
+                restored_size,
+                entry.original_size
+            );
+        }
+
+        fs::remove_file(&entry.archive_path)
+            .with_context(|| format!("无法删除归档文件: {}", entry.archive_path))?;
+        let _ = fs::remove_file(cache_path_in(&self.root, &original));
+        self.manifest.remove(&entry.original_path)?;
+
+        Ok(entry)
+    }
+
+    /// 获取全部归档条目
+    pub fn list(&self) -> Result<Vec<SessionArchiveEntry>> {
+        self.manifest.list()
+    }
+}
+
+/// agent 文件所属的主会话文件是否仍在原位置
+///
+/// 无法读取所属会话 ID 时保守地视为仍在原位置
+fn has_live_parent(agent_file: &Path) -> bool {
+    match (
+        agent_file.parent(),
+        MessageTreeBuilder::read_agent_session_id(agent_file),
+    ) {
+        (Some(dir), Some(session_id)) => dir.join(format!("{}.jsonl", session_id)).exists(),
+        _ => true,
+    }
+}
+
+/// 默认归档目录（与数据库位于同一应用数据目录）
+///
+/// 只计算路径，不创建目录：解析不存在的会话文件时不应在磁盘上留下任何内容，
+/// 归档写入时再按需创建
+pub fn default_archive_root() -> Result<PathBuf> {
+    Ok(crate::database::get_app_dir()?.join(ARCHIVE_DIR_NAME))
+}
+
+/// 解析会话文件的可读路径
+///
+/// 原文件存在时直接返回；原文件已被压缩归档时解压到缓存目录并返回缓存文件路径；
+/// 都不存在时原样返回，由调用方报告文件不存在。
+pub fn resolve_session_path(path: &Path) -> Result<PathBuf> {
+    if path.exists() {
+        return Ok(path.to_path_buf());
+    }
+    resolve_in(&default_archive_root()?, path)
+}
+
+/// 会话文件是否已被压缩归档
+pub fn is_archived(path: &Path) -> bool {
+    default_archive_root()
+        .map(|root| archive_path_in(&root, path).exists())
+        .unwrap_or(false)
+}
+
+/// 在指定归档目录中解析会话文件的可读路径
+fn resolve_in(root: &Path, path: &Path) -> Result<PathBuf> {
+    let archive_path = archive_path_in(root, path);
+    if !archive_path.exists() {
+        return Ok(path.to_path_buf());
+    }
+
+    let cache_path = cache_path_in(root, path);
+    if cache_path.exists() {
+        // 更新修改时间作为最近使用时间
+        if let Err(e) = File::options()
+            .write(true)
+            .open(&cache_path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+        {
+            eprintln!("⚠️  更新解压缓存时间失败 {:?}: {}", cache_path, e);
+        }
+    } else {
+        decompress_file(&archive_path, &cache_path)?;
+        if let Err(e) = evict_cache(&root.join(CACHE_DIR_NAME), CACHE_MAX_BYTES, &cache_path) {
+            eprintln!("⚠️  清理解压缓存失败: {}", e);
+        }
+    }
+    Ok(cache_path)
+}
+
+/// 淘汰最久未使用的解压缓存，直到总大小不超过 `max_bytes`
+///
+/// `keep` 为刚解压、即将被读取的文件，不会被淘汰
+///
+/// # 返回
+/// 返回删除的文件数
+fn evict_cache(cache_dir: &Path, max_bytes: u64, keep: &Path) -> Result<usize> {
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = Vec::new();
+    let mut stack = vec![cache_dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        for entry in fs::read_dir(&dir)?.flatten() {
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() {
+                stack.push(entry.path());
+            } else {
+                let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((used, metadata.len(), entry.path()));
+            }
+        }
+    }
+
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort_by_key(|(used, _, _)| *used);
+
+    let mut removed = 0;
+    for (_, size, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        if path == keep {
+            continue;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total = total.saturating_sub(size);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// 归档文件在归档目录中的相对位置
+///
+/// 位于 `~/.claude/projects` 下的文件保留相对该目录的完整路径，
+/// 其他位置的文件保留绝对路径的全部普通路径段
+fn relative_location(original: &Path) -> PathBuf {
+    let projects_dir = crate::monitor::scanner::get_claude_projects_dir().ok();
+    relative_location_in(projects_dir.as_deref(), original)
+}
+
+/// 相对指定项目根目录计算归档文件的相对位置
+fn relative_location_in(projects_dir: Option<&Path>, original: &Path) -> PathBuf {
+    let relative = projects_dir
+        .and_then(|dir| original.strip_prefix(dir).ok())
+        .unwrap_or(original);
+    relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 归档文件路径
+fn archive_path_in(root: &Path, original: &Path) -> PathBuf {
+    let mut path = root.join(relative_location(original)).into_os_string();
+    path.push(".gz");
+    PathBuf::from(path)
+}
+
+/// 解压缓存路径
+fn cache_path_in(root: &Path, original: &Path) -> PathBuf {
+    root.join(CACHE_DIR_NAME).join(relative_location(original))
+}
+
+/// 在目标文件旁生成唯一的临时文件路径
+fn temp_path_for(target: &Path) -> PathBuf {
+    let mut path = target.as_os_str().to_os_string();
+    path.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    PathBuf::from(path)
+}
+
+/// 写入临时文件后重命名为目标文件，失败时删除临时文件
+fn write_atomically<F>(target: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+    }
+
+    let temp_path = temp_path_for(target);
+    let result = File::create(&temp_path)
+        .with_context(|| format!("无法创建文件: {:?}", temp_path))
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        })
+        .and_then(|_| {
+            fs::rename(&temp_path, target).with_context(|| format!("无法写入文件: {:?}", target))
+        });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// gzip 压缩文件
+fn compress_file(source: &Path, target: &Path) -> Result<()> {
+    let mut reader =
+        BufReader::new(File::open(source).with_context(|| format!("无法打开文件: {:?}", source))?);
+    write_atomically(target, |writer| {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    })
+}
+
+/// 解压 gzip 文件
+fn decompress_file(source: &Path, target: &Path) -> Result<()> {
+    let mut decoder = GzDecoder::new(BufReader::new(
+        File::open(source).with_context(|| format!("无法打开归档文件: {:?}", source))?,
+    ));
+    write_atomically(target, |writer| {
+        io::copy(&mut decoder, writer).context("解压归档文件失败")?;
+        Ok(())
+    })
+}
+
+/// 计算 gzip 文件解压后的字节数
+fn decompressed_size(source: &Path) -> Result<u64> {
+    let mut decoder = GzDecoder::new(BufReader::new(
+        File::open(source).with_context(|| format!("无法打开归档文件: {:?}", source))?,
+    ));
+    io::copy(&mut decoder, &mut io::sink()).context("解压归档文件失败")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::migrations;
+    use crate::parser::jsonl::JsonlParser;
+    use rusqlite::Connection;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    fn create_archive(root: PathBuf) -> SessionArchive {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate_v30(&mut conn).unwrap();
+        let manifest = SessionArchiveRepository::with_conn(Arc::new(Mutex::new(conn)));
+        SessionArchive::new(root, manifest)
+    }
+
+    fn session(file_path: PathBuf, updated_at: &str, is_active: bool) -> SessionMetadata {
+        SessionMetadata {
+            session_id: file_path.file_stem().unwrap().to_string_lossy().to_string(),
+            project_path: "/home/dev/app".to_string(),
+            project_name: "app".to_string(),
+            file_path,
+            created_at: "2024-01-01T00:00:00+00:00".to_string(),
+            updated_at: updated_at.to_string(),
+            message_count: 2,
+            is_active,
+            display_name: None,
+            first_timestamp: None,
+            last_timestamp: None,
+            file_type: SessionFileType::Main,
+        }
+    }
+
+    #[test]
+    fn test_archive_resolve_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_dir = temp_dir.path().join("projects").join("-home-dev-app");
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let content =
+            "{\"type\":\"user\",\"uuid\":\"u1\"}\n{\"type\":\"assistant\",\"uuid\":\"a1\"}\n"
+                .repeat(50);
+        let old = project_dir.join("s-old.jsonl");
+        let recent = project_dir.join("s-recent.jsonl");
+        let active = project_dir.join("s-active.jsonl");
+        for path in [&old, &recent, &active] {
+            fs::write(path, &content).unwrap();
+        }
+
+        let root = temp_dir.path().join("archive");
+        let archive = create_archive(root.clone());
+        let report = archive.archive_older_than(
+            &[
+                session(old.clone(), "2024-01-01T00:00:00+00:00", false),
+                session(recent.clone(), &Utc::now().to_rfc3339(), false),
+                session(active.clone(), "2024-01-01T00:00:00+00:00", true),
+            ],
+            DEFAULT_ARCHIVE_AGE_DAYS,
+        );
+
+        assert_eq!(report.archived.len(), 1);
+        assert_eq!(report.skipped, 2);
+        assert!(report.failed.is_empty());
+        assert!(report.bytes_saved > 0);
+        assert!(!old.exists());
+        assert!(recent.exists() && active.exists());
+        assert!(archive_path_in(&root, &old).exists());
+
+        // 透明读取：解压到缓存，偏移与原文件一致
+        let readable = resolve_in(&root, &old).unwrap();
+        assert_eq!(readable, cache_path_in(&root, &old));
+        assert_eq!(fs::read_to_string(&readable).unwrap(), content);
+        let parser = JsonlParser::new(readable).unwrap();
+        let value = parser.parse_entry_at_offset(28, 32).unwrap();
+        assert_eq!(value["uuid"], "a1");
+
+        // 恢复原文件
+        assert_eq!(archive.list().unwrap().len(), 1);
+        let restored = archive.restore(&old).unwrap();
+        assert_eq!(restored.session_id, "s-old");
+        assert_eq!(PathBuf::from(restored.original_path), old);
+        assert_eq!(fs::read_to_string(&old).unwrap(), content);
+        assert!(!archive_path_in(&root, &old).exists());
+        assert!(!cache_path_in(&root, &old).exists());
+        assert!(archive.list().unwrap().is_empty());
+        assert!(archive.restore(&old).is_err());
+    }
+
+    #[test]
+    fn test_agent_files_follow_their_parent() {
+        let temp_dir = TempDir::new().unwrap();
+        let project_dir = temp_dir.path().join("projects").join("-home-dev-app");
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let old = "2024-01-01T00:00:00+00:00";
+        let live_parent = project_dir.join("s-live.jsonl");
+        let old_parent = project_dir.join("s-old.jsonl");
+        let live_agent = project_dir.join("agent-live.jsonl");
+        let old_agent = project_dir.join("agent-old.jsonl");
+        for (path, session_id) in [
+            (&live_parent, "s-live"),
+            (&old_parent, "s-old"),
+            (&live_agent, "s-live"),
+            (&old_agent, "s-old"),
+        ] {
+            let line = format!("{{\"type\":\"user\",\"sessionId\":\"{}\"}}\n", session_id);
+            fs::write(path, line).unwrap();
+        }
+        let agent = |path: &PathBuf| SessionMetadata {
+            file_type: SessionFileType::Agent,
+            ..session(path.clone(), old, false)
+        };
+
+        let root = temp_dir.path().join("archive");
+        let archive = create_archive(root.clone());
+        // agent 文件排在主会话之前，仍在主会话归档之后处理
+        let report = archive.archive_older_than(
+            &[
+                agent(&live_agent),
+                agent(&old_agent),
+                session(live_parent.clone(), &Utc::now().to_rfc3339(), false),
+                session(old_parent.clone(), old, false),
+            ],
+            DEFAULT_ARCHIVE_AGE_DAYS,
+        );
+
+        assert!(report.failed.is_empty());
+        assert_eq!(report.archived.len(), 2);
+        assert_eq!(report.skipped, 2);
+        assert!(live_parent.exists() && live_agent.exists());
+        assert!(!old_parent.exists() && !old_agent.exists());
+        assert!(archive_path_in(&root, &old_agent).exists());
+    }
+
+    #[test]
+    fn test_relative_location_keeps_nested_paths() {
+        let projects = Path::new("/home/dev/.claude/projects");
+        let main = projects.join("-home-dev-app").join("s1.jsonl");
+        let agent_a = projects
+            .join("-home-dev-app")
+            .join("s1")
+            .join("subagents")
+            .join("agent-1.jsonl");
+        let agent_b = projects
+            .join("-home-dev-app")
+            .join("s2")
+            .join("subagents")
+            .join("agent-1.jsonl");
+
+        assert_eq!(
+            relative_location_in(Some(projects), &main),
+            Path::new("-home-dev-app").join("s1.jsonl")
+        );
+        assert_ne!(
+            relative_location_in(Some(projects), &agent_a),
+            relative_location_in(Some(projects), &agent_b)
+        );
+        assert_eq!(
+            relative_location_in(Some(projects), Path::new("/srv/codex/rollout.jsonl")),
+            Path::new("srv").join("codex").join("rollout.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_evict_cache_removes_least_recently_used() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_dir = temp_dir.path().join(CACHE_DIR_NAME);
+        fs::create_dir_all(cache_dir.join("project")).unwrap();
+
+        let now = SystemTime::now();
+        let files: Vec<PathBuf> = (0..3)
+            .map(|i| cache_dir.join("project").join(format!("s{}.jsonl", i)))
+            .collect();
+        for (i, path) in files.iter().enumerate() {
+            fs::write(path, vec![b'x'; 100]).unwrap();
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(now - std::time::Duration::from_secs(100 - i as u64 * 10))
+                .unwrap();
+        }
+
+        // s0 最久未使用但正在读取，保留；淘汰 s1 后总大小降到上限以内
+        assert_eq!(evict_cache(&cache_dir, 200, &files[0]).unwrap(), 1);
+        assert!(files[0].exists());
+        assert!(!files[1].exists());
+        assert!(files[2].exists());
+    }
+}
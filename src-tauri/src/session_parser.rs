@@ -13,6 +13,7 @@
 //! │                   SessionParserService                   │
 //! ├─────────────────────────────────────────────────────────┤
 //! │  1. parse_file      → JsonlParser 解析 JSONL 文件        │
+//! │     （按 segment_scope 截取压缩分段）                    │
 //! │  2. convert_messages → 转换为 Message + 内容过滤         │
 //! │  3. apply_view_level_filter → 视图等级过滤               │
 //! └─────────────────────────────────────────────────────────┘
@@ -22,12 +23,14 @@
 //!
 //! ```no_run
 //! use crate::session_parser::{SessionParserService, SessionParserConfig};
+//! use crate::parser::segment::SegmentScope;
 //! use crate::parser::view_level::ViewLevel;
 //!
 //! let config = SessionParserConfig {
 //!     enable_content_filter: true,
 //!     view_level: ViewLevel::Full,
 //!     debug: true,
+//!     segment_scope: SegmentScope::All,
 //! };
 //!
 //! let parser = SessionParserService::new(config);
@@ -44,6 +47,7 @@ use serde::{Deserialize, Serialize};
 // 导入现有类型
 use crate::database::models::Message;
 use crate::parser::jsonl::JsonlParser;
+use crate::parser::segment::{SegmentDetector, SegmentScope};
 use crate::parser::view_level::{MessageFilter, ViewLevel};
 
 // ==================== 配置 ====================
@@ -59,6 +63,12 @@ pub struct SessionParserConfig {
 
     /// 是否包含调试日志
     pub debug: bool,
+
+    /// 压缩分段范围（全部分段或仅最后一次压缩之后）
+    ///
+    /// 增量解析时只能在新追加的条目内截取；如果新条目中出现压缩边界，
+    /// 结果从该边界开始并设置 `reset`，调用方应当丢弃已有消息
+    pub segment_scope: SegmentScope,
 }
 
 impl Default for SessionParserConfig {
@@ -67,6 +77,7 @@ impl Default for SessionParserConfig {
             enable_content_filter: true,
             view_level: ViewLevel::Full,
             debug: cfg!(debug_assertions),
+            segment_scope: SegmentScope::All,
         }
     }
 }
@@ -81,6 +92,17 @@ pub struct SessionParseResult {
 
     /// 统计信息
     pub stats: ParseStats,
+
+    /// 已解析到的字节偏移量（增量解析时作为下次的起点）
+    pub next_offset: u64,
+
+    /// `next_offset` 之前若干字节的指纹，与 `next_offset` 一起作为下次增量解析的起点，
+    /// 用于发现被重写为相同或更大长度的文件（全量解析和其他来源的会话为 None）
+    pub next_fingerprint: Option<u64>,
+
+    /// 增量解析时文件已被截断或重写，消息为从头解析的结果；
+    /// 或范围为 `Current` 时新条目开启了新的压缩分段，消息为新分段的内容
+    pub reset: bool,
 }
 
 /// 解析统计信息
@@ -136,8 +158,106 @@ impl SessionParserService {
     pub fn parse_session(&self, file_path: &str, session_id: &str) -> Result<SessionParseResult> {
         // 1. 使用 JsonlParser 解析文件
         let entries = self.parse_file(file_path)?;
+        let next_offset = entries
+            .last()
+            .map(|entry| entry.offset + entry.length as u64)
+            .unwrap_or(0);
+
+        self.process_entries(entries, session_id, next_offset)
+    }
+
+    /// 增量解析会话文件
+    ///
+    /// 只解析 `from_offset` 之后追加的条目，返回的消息可直接追加到已有列表。
+    /// 视图等级中除 QAPairs 外均为逐条过滤，因此结果与全量解析后截取的尾部一致。
+    ///
+    /// # 参数
+    /// - `file_path`: 会话文件路径
+    /// - `session_id`: 会话 ID
+    /// - `from_offset`: 上次解析返回的 `next_offset`
+    /// - `from_fingerprint`: 上次解析返回的 `next_fingerprint`
+    ///
+    /// # 返回
+    /// 仅包含新消息的解析结果；如果文件被截断或重写（或其他来源的会话中已返回的消息块
+    /// 在之后继续增长），则从头解析并设置 `reset`。
+    /// 范围为 `Current` 且新条目中出现压缩边界时同样设置 `reset`
+    pub fn parse_session_from_offset(
+        &self,
+        file_path: &str,
+        session_id: &str,
+        from_offset: u64,
+        from_fingerprint: Option<u64>,
+    ) -> Result<SessionParseResult> {
+        let path = crate::session_archive::resolve_session_path(std::path::Path::new(file_path))?;
+        if !path.exists() {
+            anyhow::bail!("会话文件不存在: {}", file_path);
+        }
+
+        // 其他来源的会话需要整体转换，转换后按原文件偏移截取新条目
+        let adapter = crate::parser::source::adapter_for_path(&path);
+        if adapter.kind() != crate::parser::source::SessionSourceKind::ClaudeCode {
+            let end_offset = std::fs::metadata(&path)?.len();
+            let entries = adapter.read_entries(&path)?;
+            // 偏移量超出文件长度说明文件被截断或重写，从头解析；
+            // 跨多行的条目（如 Aider 的消息块）在 from_offset 之前开始、之后仍在增长时，
+            // 已返回的消息不完整，同样从头返回全部消息
+            let reset = from_offset > end_offset
+                || entries.iter().any(|entry| {
+                    entry.offset < from_offset && entry.offset + entry.length as u64 > from_offset
+                });
+            let start_offset = if reset { 0 } else { from_offset };
+            let entries: Vec<_> = entries
+                .into_iter()
+                .filter(|entry| entry.offset >= start_offset)
+                .collect();
+            let new_segment = self.starts_new_segment(&entries);
+            let mut parsed = self.process_entries(entries, session_id, end_offset)?;
+            parsed.reset = reset || new_segment;
+            return Ok(parsed);
+        }
+
+        let mut parser = match from_fingerprint {
+            Some(fingerprint) => JsonlParser::with_checkpoint(path, from_offset, fingerprint)?,
+            None => JsonlParser::with_offset(path, from_offset)?,
+        };
+        let result = parser.parse_incremental()?;
+
+        if self.config.debug && result.reset {
+            eprintln!(
+                "[SessionParser] 文件已被截断或重写，从头解析: {}",
+                file_path
+            );
+        }
+
+        let new_segment = self.starts_new_segment(&result.entries);
+        let mut parsed = self.process_entries(result.entries, session_id, result.end_offset)?;
+        parsed.next_fingerprint = Some(result.end_fingerprint);
+        parsed.reset = result.reset || new_segment;
+        Ok(parsed)
+    }
+
+    /// 范围为 `Current` 时，新追加的条目是否开启了新的压缩分段
+    ///
+    /// 此时截取后的消息从新分段开始，已有消息都属于旧分段，需要整体替换
+    fn starts_new_segment(&self, entries: &[crate::parser::jsonl::JsonlEntry]) -> bool {
+        self.config.segment_scope == SegmentScope::Current
+            && entries
+                .iter()
+                .any(|entry| crate::parser::segment::starts_segment(&entry.data))
+    }
+
+    /// 处理已解析的条目（转换 + 过滤 + 统计）
+    fn process_entries(
+        &self,
+        entries: Vec<crate::parser::jsonl::JsonlEntry>,
+        session_id: &str,
+        next_offset: u64,
+    ) -> Result<SessionParseResult> {
         let total_entries = entries.len();
 
+        // 按压缩分段范围截取
+        let entries = SegmentDetector::apply_scope(entries, self.config.segment_scope);
+
         // 2. 转换为 Message 对象
         let (messages, content_filtered) = self.convert_messages(entries, session_id)?;
 
@@ -158,22 +278,25 @@ impl SessionParserService {
             eprintln!("[SessionParser] 解析统计: {:?}", stats);
         }
 
-        Ok(SessionParseResult { messages, stats })
+        Ok(SessionParseResult {
+            messages,
+            stats,
+            next_offset,
+            next_fingerprint: None,
+            reset: false,
+        })
     }
 
     /// 解析文件（步骤 1）
     ///
-    /// 从 JSONL 文件中读取所有条目
+    /// 从会话文件中读取所有条目（其他来源的会话由适配器转换为兼容条目）
     fn parse_file(&self, file_path: &str) -> Result<Vec<crate::parser::jsonl::JsonlEntry>> {
-        let path = std::path::PathBuf::from(file_path);
+        let path = crate::session_archive::resolve_session_path(std::path::Path::new(file_path))?;
         if !path.exists() {
             anyhow::bail!("会话文件不存在: {}", file_path);
         }
 
-        let mut parser = JsonlParser::new(path)?;
-        let entries = parser.parse_all()?;
-
-        Ok(entries)
+        crate::parser::source::read_entries(&path)
     }
 
     /// 转换消息（步骤 2）
@@ -481,6 +604,7 @@ mod tests {
             enable_content_filter: false,
             view_level: ViewLevel::Full,
             debug: false,
+            segment_scope: SegmentScope::All,
         }
     }
 
@@ -582,6 +706,7 @@ mod integration_tests {
             enable_content_filter: false,
             view_level: ViewLevel::Full,
             debug: false,
+            segment_scope: SegmentScope::All,
         };
 
         let parser = SessionParserService::new(config);
@@ -619,6 +744,7 @@ mod integration_tests {
             enable_content_filter: true, // 启用内容过滤
             view_level: ViewLevel::Full,
             debug: false,
+            segment_scope: SegmentScope::All,
         };
 
         let parser = SessionParserService::new(config);
@@ -650,6 +776,7 @@ mod integration_tests {
             enable_content_filter: false,
             view_level: ViewLevel::Conversation, // 对话模式
             debug: false,
+            segment_scope: SegmentScope::All,
         };
 
         let parser = SessionParserService::new(config);
@@ -680,6 +807,7 @@ mod integration_tests {
             enable_content_filter: true,
             view_level: ViewLevel::Conversation,
             debug: false,
+            segment_scope: SegmentScope::All,
         };
 
         let parser = SessionParserService::new(config);
@@ -764,6 +892,265 @@ mod integration_tests {
         assert_eq!(timestamps, sorted_timestamps);
     }
 
+    #[test]
+    fn test_parse_session_from_offset() {
+        let temp_dir = std::env::temp_dir();
+        let test_file_path = temp_dir.join("test_session_incremental_parse.jsonl");
+
+        let content = create_test_jsonl_content();
+        let mut lines = content.lines();
+        {
+            let mut file = std::fs::File::create(&test_file_path).unwrap();
+            for line in lines.by_ref().take(2) {
+                writeln!(file, "{}", line).unwrap();
+            }
+        }
+
+        let file_path = test_file_path.to_str().unwrap();
+        let config = SessionParserConfig {
+            enable_content_filter: false,
+            view_level: ViewLevel::Full,
+            debug: false,
+            segment_scope: SegmentScope::All,
+        };
+        let parser = SessionParserService::new(config);
+
+        let first = parser.parse_session(file_path, "test_session").unwrap();
+        assert_eq!(first.messages.len(), 2);
+
+        // 追加剩余的行
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&test_file_path)
+                .unwrap();
+            for line in lines {
+                writeln!(file, "{}", line).unwrap();
+            }
+        }
+
+        let second = parser
+            .parse_session_from_offset(file_path, "test_session", first.next_offset, None)
+            .unwrap();
+
+        let _ = std::fs::remove_file(&test_file_path);
+
+        assert_eq!(second.messages.len(), 4);
+        assert_eq!(second.messages[0].uuid, "msg-003");
+        assert!(second.next_offset > first.next_offset);
+    }
+
+    #[test]
+    fn test_parse_session_from_offset_reset_on_truncate() {
+        let temp_dir = std::env::temp_dir();
+        let test_file_path = temp_dir.join("test_session_incremental_reset.jsonl");
+
+        let content = create_test_jsonl_content();
+        std::fs::write(&test_file_path, &content).unwrap();
+
+        let file_path = test_file_path.to_str().unwrap();
+        let config = SessionParserConfig {
+            enable_content_filter: false,
+            view_level: ViewLevel::Full,
+            debug: false,
+            segment_scope: SegmentScope::All,
+        };
+        let parser = SessionParserService::new(config);
+
+        let first = parser.parse_session(file_path, "test_session").unwrap();
+        assert!(!first.reset);
+
+        // 文件被重写为更短的内容
+        let first_line = content.lines().next().unwrap();
+        std::fs::write(&test_file_path, format!("{}\n", first_line)).unwrap();
+
+        let second = parser
+            .parse_session_from_offset(file_path, "test_session", first.next_offset, None)
+            .unwrap();
+
+        let _ = std::fs::remove_file(&test_file_path);
+
+        assert!(second.reset);
+        assert_eq!(second.messages.len(), 1);
+        assert!(second.next_offset < first.next_offset);
+    }
+
+    #[test]
+    fn test_parse_session_from_offset_reset_on_same_size_rewrite() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let test_file_path = temp_dir.path().join("session.jsonl");
+        let line = |uuid: &str| {
+            format!(
+                r#"{{"timestamp":"2025-01-19T12:00:00Z","type":"user","uuid":"{}","message":"Hello"}}"#,
+                uuid
+            ) + "\n"
+        };
+        std::fs::write(&test_file_path, line("msg-a01")).unwrap();
+
+        let file_path = test_file_path.to_str().unwrap();
+        let config = SessionParserConfig {
+            enable_content_filter: false,
+            view_level: ViewLevel::Full,
+            debug: false,
+            segment_scope: SegmentScope::All,
+        };
+        let parser = SessionParserService::new(config);
+
+        let first = parser
+            .parse_session_from_offset(file_path, "test_session", 0, None)
+            .unwrap();
+        assert_eq!(first.messages.len(), 1);
+        assert!(first.next_fingerprint.is_some());
+
+        // 文件被重写为相同长度的其他内容后继续追加
+        std::fs::write(&test_file_path, line("msg-b01") + &line("msg-b02")).unwrap();
+
+        let second = parser
+            .parse_session_from_offset(
+                file_path,
+                "test_session",
+                first.next_offset,
+                first.next_fingerprint,
+            )
+            .unwrap();
+
+        assert!(second.reset);
+        let uuids: Vec<&str> = second.messages.iter().map(|m| m.uuid.as_str()).collect();
+        assert_eq!(uuids, vec!["msg-b01", "msg-b02"]);
+
+        // 未重写时只返回新追加的消息
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&test_file_path)
+                .unwrap();
+            file.write_all(line("msg-b03").as_bytes()).unwrap();
+        }
+        let third = parser
+            .parse_session_from_offset(
+                file_path,
+                "test_session",
+                second.next_offset,
+                second.next_fingerprint,
+            )
+            .unwrap();
+        assert!(!third.reset);
+        assert_eq!(third.messages.len(), 1);
+        assert_eq!(third.messages[0].uuid, "msg-b03");
+    }
+
+    #[test]
+    fn test_parse_session_from_offset_reset_on_compact_boundary() {
+        let temp_dir = std::env::temp_dir();
+        let test_file_path = temp_dir.join("test_session_incremental_compact.jsonl");
+
+        let content = create_test_jsonl_content();
+        std::fs::write(&test_file_path, &content).unwrap();
+
+        let file_path = test_file_path.to_str().unwrap();
+        let config = SessionParserConfig {
+            enable_content_filter: false,
+            view_level: ViewLevel::Full,
+            debug: false,
+            segment_scope: SegmentScope::Current,
+        };
+        let parser = SessionParserService::new(config);
+
+        let first = parser.parse_session(file_path, "test_session").unwrap();
+        assert_eq!(first.messages.len(), 6);
+
+        // 追加一次压缩边界和压缩后的消息
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&test_file_path)
+                .unwrap();
+            writeln!(
+                file,
+                r#"{{"timestamp":"2025-01-19T12:00:06Z","type":"user","uuid":"msg-007","message":"Before compaction","parentUuid":"msg-006"}}"#
+            )
+            .unwrap();
+            writeln!(
+                file,
+                r#"{{"timestamp":"2025-01-19T12:00:07Z","type":"system","subtype":"compact_boundary","uuid":"msg-008","message":"Conversation compacted","parentUuid":null}}"#
+            )
+            .unwrap();
+            writeln!(
+                file,
+                r#"{{"timestamp":"2025-01-19T12:00:08Z","type":"user","uuid":"msg-009","message":"After compaction","parentUuid":"msg-008"}}"#
+            )
+            .unwrap();
+        }
+
+        let second = parser
+            .parse_session_from_offset(file_path, "test_session", first.next_offset, None)
+            .unwrap();
+
+        let _ = std::fs::remove_file(&test_file_path);
+
+        assert!(second.reset);
+        let uuids: Vec<&str> = second.messages.iter().map(|m| m.uuid.as_str()).collect();
+        assert_eq!(uuids, vec!["msg-008", "msg-009"]);
+    }
+
+    #[test]
+    fn test_parse_session_from_offset_reset_on_grown_aider_block() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let test_file_path = temp_dir.path().join(".aider.chat.history.md");
+        std::fs::write(
+            &test_file_path,
+            "# aider chat started at 2025-03-10 09:00:00\n\n#### add a greet function\n\nHere is the change:\n",
+        )
+        .unwrap();
+
+        let file_path = test_file_path.to_str().unwrap();
+        let config = SessionParserConfig {
+            enable_content_filter: false,
+            view_level: ViewLevel::Full,
+            debug: false,
+            segment_scope: SegmentScope::All,
+        };
+        let parser = SessionParserService::new(config);
+
+        let first = parser.parse_session(file_path, "test_session").unwrap();
+        assert_eq!(first.messages.len(), 2);
+
+        // 模型回复在同一个消息块中继续写入
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&test_file_path)
+                .unwrap();
+            writeln!(file, "\nhello.py\ndef greet(name): ...").unwrap();
+        }
+
+        let second = parser
+            .parse_session_from_offset(file_path, "test_session", first.next_offset, None)
+            .unwrap();
+
+        assert!(second.reset);
+        assert_eq!(second.messages.len(), 2);
+        assert_eq!(second.messages[1].uuid, first.messages[1].uuid);
+        assert!(second.messages[1].length > first.messages[1].length);
+
+        // 追加新的消息块时只返回新消息
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&test_file_path)
+                .unwrap();
+            writeln!(file, "\n#### thanks").unwrap();
+        }
+
+        let third = parser
+            .parse_session_from_offset(file_path, "test_session", second.next_offset, None)
+            .unwrap();
+
+        assert!(!third.reset);
+        assert_eq!(third.messages.len(), 1);
+        assert_eq!(third.messages[0].msg_type, "user");
+    }
+
     #[test]
     fn test_error_handling_file_not_found() {
         let config = SessionParserConfig::default();
@@ -0,0 +1,804 @@
+//! 会话查询语言
+//!
+//! 用一行查询筛选会话，例如：
+//!
+//! ```text
+//! project:prism date:last-week tool:Bash error:true file:*.rs
+//! (tag:bugfix OR tag:refactor) -model:haiku rating:>=4 "race condition"
+//! ```
+//!
+//! - 字段：`project`、`date`、`tool`、`file`、`role`、`error`、`tag`、`rating`、`model`
+//! - 相邻的条件之间为 AND，支持 `OR`、`NOT`（或 `-` 前缀）和括号
+//! - 不带字段的词或 `"短语"` 在消息全文索引中搜索
+//! - 值中含 `*` / `?` 时按通配符匹配；`project`、`file`、`model` 默认为包含匹配，
+//!   其余字段为不区分大小写的相等匹配
+//! - `date` 支持 `today`、`yesterday`、`last-week`、`last-month`、`7d` / `2w` / `3m`、
+//!   `2025-01`、`2025-01-01..2025-01-31` 以及 `>`、`>=`、`<`、`<=` 比较
+//! - `rating` 支持数字比较和 `none`；`error` 支持 `true` / `false` 或工具名
+//!
+//! 查询被编译为 `sessions` 表上的 SQL。工具、文件、错误、模型、角色和活跃日期
+//! 来自 `session_facts` 表，由 `sync_session_facts` 通过 `MetadataExtractor` 提取。
+//! 尚未同步的会话没有任何事实，因此不会匹配依赖索引的否定条件（如 `-error:true`、
+//! `error:false`）。
+
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDate};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::database::search_repository::fts_expression;
+use crate::database::{SessionQueryRepository, ToolErrorRepository};
+use crate::parser::tool_error::collect_tool_errors;
+use crate::parser::tree::{ConversationTree, MessageNode, MessageTreeBuilder};
+
+/// 会话事实类别：使用过的工具
+pub const FACT_TOOL: &str = "tool";
+/// 会话事实类别：读写过的文件
+pub const FACT_FILE: &str = "file";
+/// 会话事实类别：出错的工具
+pub const FACT_ERROR: &str = "error";
+/// 会话事实类别：助手使用的模型
+pub const FACT_MODEL: &str = "model";
+/// 会话事实类别：出现过的角色（user / assistant / tool）
+pub const FACT_ROLE: &str = "role";
+/// 会话事实类别：有消息的日期（YYYY-MM-DD）
+pub const FACT_DAY: &str = "day";
+
+/// 没有相关工具时的错误事实值
+const UNKNOWN_ERROR_SOURCE: &str = "unknown";
+
+/// 会话已同步事实的条件
+const FACTS_SYNCED: &str =
+    "EXISTS (SELECT 1 FROM session_facts f WHERE f.session_id = s.session_id)";
+
+/// 查询字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Project,
+    Date,
+    Tool,
+    File,
+    Role,
+    Error,
+    Tag,
+    Rating,
+    Model,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "project" => Some(Self::Project),
+            "date" => Some(Self::Date),
+            "tool" => Some(Self::Tool),
+            "file" => Some(Self::File),
+            "role" => Some(Self::Role),
+            "error" => Some(Self::Error),
+            "tag" => Some(Self::Tag),
+            "rating" => Some(Self::Rating),
+            "model" => Some(Self::Model),
+            _ => None,
+        }
+    }
+}
+
+/// 比较运算符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// 查询表达式
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryExpr {
+    And(Box<QueryExpr>, Box<QueryExpr>),
+    Or(Box<QueryExpr>, Box<QueryExpr>),
+    Not(Box<QueryExpr>),
+    /// 全文搜索
+    Text(String),
+    /// 字段条件
+    Field {
+        field: Field,
+        op: CompareOp,
+        value: String,
+    },
+}
+
+/// 编译后的查询
+#[derive(Debug, Clone)]
+pub struct CompiledQuery {
+    /// `sessions s` 上的 WHERE 子句
+    pub where_clause: String,
+    /// 按顺序绑定的参数
+    pub params: Vec<SqlValue>,
+}
+
+/// 词法单元
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Word(String),
+    Phrase(String),
+    Field(String, String),
+}
+
+/// 解析查询
+///
+/// 空查询返回 None（匹配所有会话）
+pub fn parse_query(input: &str) -> Result<Option<QueryExpr>> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_or()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        bail!("查询语法错误: 意外的 {:?}", token);
+    }
+    Ok(Some(expr))
+}
+
+/// 编译查询
+///
+/// # 参数
+/// - `expr`: 查询表达式（None 匹配所有会话）
+/// - `today`: 解析相对日期时使用的"今天"
+pub fn compile_query(expr: Option<&QueryExpr>, today: NaiveDate) -> Result<CompiledQuery> {
+    let mut params = Vec::new();
+    let where_clause = match expr {
+        Some(expr) => compile_expr(expr, today, &mut params)?,
+        None => "1 = 1".to_string(),
+    };
+    Ok(CompiledQuery {
+        where_clause,
+        params,
+    })
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        match c {
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '"' => {
+                let (text, next) = read_quoted(&chars, i)?;
+                tokens.push(Token::Phrase(text));
+                i = next;
+            }
+            '-' if chars.get(i + 1).is_some_and(|c| !c.is_whitespace()) => {
+                tokens.push(Token::Not);
+                i += 1;
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '(' | ')')
+                {
+                    if chars[i] == ':' {
+                        break;
+                    }
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                // 字段条件：name:value 或 name:"quoted value"
+                if i < chars.len() && chars[i] == ':' {
+                    i += 1;
+                    let value = if chars.get(i) == Some(&'"') {
+                        let (text, next) = read_quoted(&chars, i)?;
+                        i = next;
+                        text
+                    } else {
+                        let value_start = i;
+                        while i < chars.len()
+                            && !chars[i].is_whitespace()
+                            && !matches!(chars[i], '(' | ')')
+                        {
+                            i += 1;
+                        }
+                        chars[value_start..i].iter().collect()
+                    };
+                    if value.is_empty() {
+                        bail!("查询语法错误: 字段 {} 缺少值", word);
+                    }
+                    tokens.push(Token::Field(word, value));
+                    continue;
+                }
+
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// 读取以 `chars[start]` 处双引号开始的字符串，返回内容和下一个位置
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize)> {
+    let mut i = start + 1;
+    let mut text = String::new();
+    while i < chars.len() && chars[i] != '"' {
+        text.push(chars[i]);
+        i += 1;
+    }
+    if i >= chars.len() {
+        bail!("查询语法错误: 引号未闭合");
+    }
+    Ok((text, i + 1))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<QueryExpr> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = QueryExpr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<QueryExpr> {
+        let mut left = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Or) | Some(Token::RParen) | None => break,
+                _ => {}
+            }
+            let right = self.parse_unary()?;
+            left = QueryExpr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<QueryExpr> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(QueryExpr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<QueryExpr> {
+        let token = match self.tokens.get(self.pos).cloned() {
+            Some(token) => token,
+            None => bail!("查询语法错误: 表达式不完整"),
+        };
+        self.pos += 1;
+
+        match token {
+            Token::LParen => {
+                let expr = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    bail!("查询语法错误: 缺少右括号");
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Token::Word(word) => Ok(QueryExpr::Text(word)),
+            Token::Phrase(phrase) => Ok(QueryExpr::Text(format!("\"{}\"", phrase))),
+            Token::Field(name, value) => {
+                let field = match Field::parse(&name) {
+                    Some(field) => field,
+                    None => bail!("未知的查询字段: {}", name),
+                };
+                let (op, value) = split_operator(&value);
+                if op != CompareOp::Eq && !matches!(field, Field::Date | Field::Rating) {
+                    bail!("字段 {} 不支持比较运算", name);
+                }
+                Ok(QueryExpr::Field {
+                    field,
+                    op,
+                    value: value.to_string(),
+                })
+            }
+            other => bail!("查询语法错误: 意外的 {:?}", other),
+        }
+    }
+}
+
+/// 拆出值前面的比较运算符
+fn split_operator(value: &str) -> (CompareOp, &str) {
+    for (prefix, op) in [
+        (">=", CompareOp::Ge),
+        ("<=", CompareOp::Le),
+        (">", CompareOp::Gt),
+        ("<", CompareOp::Lt),
+        ("=", CompareOp::Eq),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (op, rest);
+        }
+    }
+    (CompareOp::Eq, value)
+}
+
+fn compile_expr(expr: &QueryExpr, today: NaiveDate, params: &mut Vec<SqlValue>) -> Result<String> {
+    Ok(match expr {
+        QueryExpr::And(left, right) => format!(
+            "({} AND {})",
+            compile_expr(left, today, params)?,
+            compile_expr(right, today, params)?
+        ),
+        QueryExpr::Or(left, right) => format!(
+            "({} OR {})",
+            compile_expr(left, today, params)?,
+            compile_expr(right, today, params)?
+        ),
+        QueryExpr::Not(inner) => {
+            let negated = format!("(NOT {})", compile_expr(inner, today, params)?);
+            if depends_on_index(inner) {
+                format!("({} AND {})", FACTS_SYNCED, negated)
+            } else {
+                negated
+            }
+        }
+        QueryExpr::Text(text) => {
+            let expression = match fts_expression(text) {
+                Some(expression) => expression,
+                None => bail!("无效的搜索词: {}", text),
+            };
+            params.push(SqlValue::Text(expression));
+            "s.session_id IN (SELECT session_id FROM message_fts WHERE message_fts MATCH ?)"
+                .to_string()
+        }
+        QueryExpr::Field { field, op, value } => compile_field(*field, *op, value, today, params)?,
+    })
+}
+
+fn compile_field(
+    field: Field,
+    op: CompareOp,
+    value: &str,
+    today: NaiveDate,
+    params: &mut Vec<SqlValue>,
+) -> Result<String> {
+    Ok(match field {
+        Field::Project => {
+            let name = text_match("s.project_name", value, true, params);
+            let path = text_match("s.project_path", value, true, params);
+            format!("({} OR {})", name, path)
+        }
+        Field::Tag => format!(
+            "EXISTS (SELECT 1 FROM json_each(s.tags) t WHERE {})",
+            text_match("t.value", value, false, params)
+        ),
+        Field::Rating => {
+            if value.eq_ignore_ascii_case("none") {
+                return Ok("s.rating IS NULL".to_string());
+            }
+            let rating: i64 = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("无效的评分: {}", value))?;
+            params.push(SqlValue::Integer(rating));
+            format!("s.rating {} ?", sql_operator(op))
+        }
+        Field::Error => match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" => fact_exists(FACT_ERROR, None, params),
+            "false" | "no" | "none" => format!(
+                "({} AND NOT {})",
+                FACTS_SYNCED,
+                fact_exists(FACT_ERROR, None, params)
+            ),
+            _ => fact_exists(FACT_ERROR, Some((value, false)), params),
+        },
+        Field::Tool => fact_exists(FACT_TOOL, Some((value, false)), params),
+        Field::Role => fact_exists(FACT_ROLE, Some((value, false)), params),
+        Field::File => fact_exists(FACT_FILE, Some((value, true)), params),
+        Field::Model => fact_exists(FACT_MODEL, Some((value, true)), params),
+        Field::Date => compile_date(op, value, today, params)?,
+    })
+}
+
+/// 表达式是否依赖全文索引同步的数据（会话事实或消息全文）
+fn depends_on_index(expr: &QueryExpr) -> bool {
+    match expr {
+        QueryExpr::And(left, right) | QueryExpr::Or(left, right) => {
+            depends_on_index(left) || depends_on_index(right)
+        }
+        QueryExpr::Not(inner) => depends_on_index(inner),
+        QueryExpr::Text(_) => true,
+        QueryExpr::Field { field, .. } => {
+            !matches!(field, Field::Project | Field::Tag | Field::Rating)
+        }
+    }
+}
+
+/// 会话事实存在条件
+///
+/// `matcher` 为（值, 是否包含匹配），None 表示该类别有任意事实即可
+fn fact_exists(kind: &str, matcher: Option<(&str, bool)>, params: &mut Vec<SqlValue>) -> String {
+    params.push(SqlValue::Text(kind.to_string()));
+    let mut sql = "EXISTS (SELECT 1 FROM session_facts f \
+                   WHERE f.session_id = s.session_id AND f.kind = ?"
+        .to_string();
+    if let Some((value, contains)) = matcher {
+        sql.push_str(" AND ");
+        sql.push_str(&text_match("f.value", value, contains, params));
+    }
+    sql.push(')');
+    sql
+}
+
+/// 文本匹配条件：含通配符时用 GLOB，否则为包含匹配或不区分大小写的相等匹配
+fn text_match(column: &str, value: &str, contains: bool, params: &mut Vec<SqlValue>) -> String {
+    if value.contains(['*', '?']) {
+        params.push(SqlValue::Text(value.to_string()));
+        format!("{} GLOB ?", column)
+    } else if contains {
+        let escaped = value
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        params.push(SqlValue::Text(format!("%{}%", escaped)));
+        format!("{} LIKE ? ESCAPE '\\'", column)
+    } else {
+        params.push(SqlValue::Text(value.to_string()));
+        format!("{} = ? COLLATE NOCASE", column)
+    }
+}
+
+fn sql_operator(op: CompareOp) -> &'static str {
+    match op {
+        CompareOp::Eq => "=",
+        CompareOp::Gt => ">",
+        CompareOp::Ge => ">=",
+        CompareOp::Lt => "<",
+        CompareOp::Le => "<=",
+    }
+}
+
+/// 日期条件：会话在范围内的某一天有消息
+fn compile_date(
+    op: CompareOp,
+    value: &str,
+    today: NaiveDate,
+    params: &mut Vec<SqlValue>,
+) -> Result<String> {
+    // (起始, 结束)，均为包含边界的日期前缀
+    let (from, to): (Option<String>, Option<String>) = match op {
+        CompareOp::Eq => {
+            if let Some((start, end)) = value.split_once("..") {
+                (
+                    (!start.is_empty())
+                        .then(|| absolute_date(start))
+                        .transpose()?,
+                    (!end.is_empty()).then(|| absolute_date(end)).transpose()?,
+                )
+            } else if let Some(days) = relative_days(value)? {
+                let start = today - Duration::days(days - 1);
+                (Some(start.format("%Y-%m-%d").to_string()), None)
+            } else if value.eq_ignore_ascii_case("yesterday") {
+                let day = (today - Duration::days(1)).format("%Y-%m-%d").to_string();
+                (Some(day.clone()), Some(day))
+            } else {
+                let day = absolute_date(value)?;
+                (Some(day.clone()), Some(day))
+            }
+        }
+        CompareOp::Ge => (Some(absolute_date(value)?), None),
+        CompareOp::Le => (None, Some(absolute_date(value)?)),
+        // "~" 大于数字和 "-"，使 ">2025-01" 排除整个一月
+        CompareOp::Gt => (Some(format!("{}~", absolute_date(value)?)), None),
+        CompareOp::Lt => {
+            params.push(SqlValue::Text(FACT_DAY.to_string()));
+            params.push(SqlValue::Text(absolute_date(value)?));
+            return Ok("EXISTS (SELECT 1 FROM session_facts f \
+                       WHERE f.session_id = s.session_id AND f.kind = ? AND f.value < ?)"
+                .to_string());
+        }
+    };
+
+    params.push(SqlValue::Text(FACT_DAY.to_string()));
+    let mut sql = "EXISTS (SELECT 1 FROM session_facts f \
+                   WHERE f.session_id = s.session_id AND f.kind = ?"
+        .to_string();
+    if let Some(from) = from {
+        sql.push_str(" AND f.value >= ?");
+        params.push(SqlValue::Text(from));
+    }
+    if let Some(to) = to {
+        sql.push_str(" AND f.value <= ?");
+        params.push(SqlValue::Text(format!("{}~", to)));
+    }
+    sql.push(')');
+    Ok(sql)
+}
+
+/// 相对日期（包含今天的天数）
+fn relative_days(value: &str) -> Result<Option<i64>> {
+    let lower = value.to_ascii_lowercase();
+    let days = match lower.as_str() {
+        "today" => 1,
+        "last-week" | "this-week" => 7,
+        "last-month" | "this-month" => 30,
+        "last-year" | "this-year" => 365,
+        _ => {
+            let (number, unit) = lower.split_at(lower.len().saturating_sub(1));
+            let multiplier = match unit {
+                "d" => 1,
+                "w" => 7,
+                "m" => 30,
+                "y" => 365,
+                _ => return Ok(None),
+            };
+            match number.parse::<i64>() {
+                Ok(n) if n > 0 => n * multiplier,
+                _ => return Ok(None),
+            }
+        }
+    };
+    Ok(Some(days))
+}
+
+/// 校验绝对日期（`YYYY`、`YYYY-MM` 或 `YYYY-MM-DD`）
+fn absolute_date(value: &str) -> Result<String> {
+    let valid = match value.len() {
+        4 => value.chars().all(|c| c.is_ascii_digit()),
+        7 => NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d").is_ok(),
+        10 => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+        _ => false,
+    };
+    if !valid {
+        bail!("无效的日期: {}", value);
+    }
+    Ok(value.to_string())
+}
+
+/// 会话事实（类别, 值）
+pub type SessionFact = (String, String);
+
+/// 从已提取元数据的消息树中提取可查询的事实
+///
+/// 工具、文件和错误来自 `MetadataExtractor` 的工具调用、代码变更与错误提取结果
+pub fn session_facts_from_tree(tree: &ConversationTree) -> Vec<SessionFact> {
+    let mut facts: BTreeSet<SessionFact> = BTreeSet::new();
+    let mut add = |kind: &str, value: &str| {
+        if !value.is_empty() {
+            facts.insert((kind.to_string(), value.to_string()));
+        }
+    };
+
+    let mut stack: Vec<&MessageNode> = tree.roots.iter().collect();
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.iter());
+        let data = &node.message_data;
+
+        if let Some(day) = data
+            .get("timestamp")
+            .and_then(|v| v.as_str())
+            .and_then(|ts| ts.get(..10))
+        {
+            add(FACT_DAY, day);
+        }
+
+        match data.get("type").and_then(|v| v.as_str()) {
+            Some("assistant") => {
+                add(FACT_ROLE, "assistant");
+                if let Some(model) = data
+                    .get("message")
+                    .and_then(|m| m.get("model"))
+                    .and_then(|v| v.as_str())
+                {
+                    add(FACT_MODEL, model);
+                }
+            }
+            Some("user") => {
+                let is_tool_result = data
+                    .get("message")
+                    .and_then(|m| m.get("content"))
+                    .and_then(|c| c.as_array())
+                    .is_some_and(|blocks| {
+                        blocks
+                            .iter()
+                            .any(|b| b.get("type").and_then(|v| v.as_str()) == Some("tool_result"))
+                    });
+                add(FACT_ROLE, if is_tool_result { "tool" } else { "user" });
+            }
+            _ => {}
+        }
+
+        let metadata = match node.metadata {
+            Some(ref metadata) => metadata,
+            None => continue,
+        };
+        for call in &metadata.tool_calls {
+            add(FACT_TOOL, &call.name);
+            if call.is_error == Some(true) {
+                add(FACT_ERROR, &call.name);
+            }
+            for key in ["file_path", "notebook_path", "path"] {
+                if let Some(Value::String(path)) = call.input.get(key) {
+                    add(FACT_FILE, path);
+                }
+            }
+        }
+        for change in &metadata.code_changes {
+            add(FACT_FILE, &change.file_path);
+        }
+        for error in &metadata.errors {
+            add(
+                FACT_ERROR,
+                error
+                    .related_tool
+                    .as_deref()
+                    .unwrap_or(UNKNOWN_ERROR_SOURCE),
+            );
+        }
+    }
+
+    facts.into_iter().collect()
+}
+
+/// 重新提取会话事实和工具错误分类
+///
+/// 需要完整解析会话文件并构建消息树：全量同步时对有变化的会话调用，
+/// 文件监控中则等会话停止写入一段时间后再调用，而不是每次追加消息都重新提取
+pub fn sync_session_facts(
+    conn: Arc<Mutex<Connection>>,
+    session_id: &str,
+    project_path: &str,
+    file_path: &Path,
+) -> Result<()> {
+    let entries = crate::parser::source::read_entries(file_path)?;
+    let tree = MessageTreeBuilder::build_from_entries(&entries)?;
+
+    SessionQueryRepository::with_conn(conn.clone())
+        .replace_facts(session_id, &session_facts_from_tree(&tree))?;
+    ToolErrorRepository::with_conn(conn).replace_errors(
+        session_id,
+        project_path,
+        &collect_tool_errors(&tree),
+    )?;
+    Ok(())
+}
+
+/// 删除会话事实和工具错误分类
+pub fn remove_session_facts(conn: Arc<Mutex<Connection>>, session_id: &str) -> Result<()> {
+    SessionQueryRepository::with_conn(conn.clone()).remove_facts(session_id)?;
+    ToolErrorRepository::with_conn(conn).remove_errors(session_id)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::jsonl::JsonlEntry;
+    use serde_json::json;
+
+    fn field(field: Field, op: CompareOp, value: &str) -> QueryExpr {
+        QueryExpr::Field {
+            field,
+            op,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let expr =
+            parse_query("project:prism (tool:Bash OR tag:\"bug fix\") -error:true rating:>=4")
+                .unwrap()
+                .unwrap();
+        let expected = QueryExpr::And(
+            Box::new(QueryExpr::And(
+                Box::new(QueryExpr::And(
+                    Box::new(field(Field::Project, CompareOp::Eq, "prism")),
+                    Box::new(QueryExpr::Or(
+                        Box::new(field(Field::Tool, CompareOp::Eq, "Bash")),
+                        Box::new(field(Field::Tag, CompareOp::Eq, "bug fix")),
+                    )),
+                )),
+                Box::new(QueryExpr::Not(Box::new(field(
+                    Field::Error,
+                    CompareOp::Eq,
+                    "true",
+                )))),
+            )),
+            Box::new(field(Field::Rating, CompareOp::Ge, "4")),
+        );
+        assert_eq!(expr, expected);
+
+        assert!(parse_query("   ").unwrap().is_none());
+        assert!(parse_query("owner:me").is_err());
+        assert!(parse_query("tool:>Bash").is_err());
+        assert!(parse_query("(tool:Bash").is_err());
+        assert!(parse_query("\"unclosed").is_err());
+    }
+
+    #[test]
+    fn test_compile_relative_date() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let expr = parse_query("date:last-week").unwrap();
+        let compiled = compile_query(expr.as_ref(), today).unwrap();
+        assert_eq!(
+            compiled.params,
+            vec![
+                SqlValue::Text("day".to_string()),
+                SqlValue::Text("2025-03-04".to_string()),
+            ]
+        );
+
+        assert!(compile_query(parse_query("date:2025-13").unwrap().as_ref(), today).is_err());
+    }
+
+    #[test]
+    fn test_session_facts_from_tree() {
+        let entries: Vec<JsonlEntry> = [
+            json!({"uuid": "u1", "type": "user", "timestamp": "2025-03-09T10:00:00Z",
+                   "message": {"role": "user", "content": "fix the build"}}),
+            json!({"uuid": "a1", "parentUuid": "u1", "type": "assistant",
+                   "timestamp": "2025-03-10T10:00:00Z",
+                   "message": {"role": "assistant", "model": "claude-sonnet-4", "content": [
+                       {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "cargo build"}},
+                       {"type": "tool_use", "id": "t2", "name": "Edit",
+                        "input": {"file_path": "/src/main.rs", "old_string": "a", "new_string": "b"}}
+                   ]}}),
+            json!({"uuid": "r1", "parentUuid": "a1", "type": "user",
+                   "message": {"role": "user", "content": [
+                       {"type": "tool_result", "tool_use_id": "t1", "content": "exit 101", "is_error": true}
+                   ]}}),
+        ]
+        .into_iter()
+        .map(|data| JsonlEntry::new(0, 0, data))
+        .collect();
+
+        let tree = MessageTreeBuilder::build_from_entries(&entries).unwrap();
+        let facts = session_facts_from_tree(&tree);
+        let has = |kind: &str, value: &str| {
+            facts
+                .iter()
+                .any(|(k, v)| k.as_str() == kind && v.as_str() == value)
+        };
+        assert!(has(FACT_TOOL, "Bash"));
+        assert!(has(FACT_TOOL, "Edit"));
+        assert!(has(FACT_FILE, "/src/main.rs"));
+        assert!(has(FACT_ERROR, "Bash"));
+        assert!(has(FACT_MODEL, "claude-sonnet-4"));
+        assert!(has(FACT_ROLE, "tool"));
+        assert!(has(FACT_DAY, "2025-03-09"));
+        assert!(has(FACT_DAY, "2025-03-10"));
+    }
+}
@@ -192,6 +192,11 @@ impl SessionDisplayName {
         let file_path = file_path.as_ref();
         let session_id = extract_session_id(file_path)?;
 
+        // 已压缩归档的会话读取解压后的缓存文件
+        let resolved = crate::session_archive::resolve_session_path(file_path)
+            .unwrap_or_else(|_| file_path.to_path_buf());
+        let file_path = resolved.as_path();
+
         // 策略 1: 优先从 summary 读取
         if let Ok(name) = Self::try_read_summary(file_path, &session_id).await {
             #[cfg(debug_assertions)]
@@ -32,6 +32,24 @@ impl SessionFileType {
     pub fn is_agent(&self) -> bool {
         matches!(self, Self::Agent)
     }
+
+    /// 类型标识（与序列化值一致，用于数据库存储）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Main => "main",
+            Self::Agent => "agent",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// 从类型标识解析，无法识别时为 `Unknown`
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "main" => Self::Main,
+            "agent" => Self::Agent,
+            _ => Self::Unknown,
+        }
+    }
 }
 
 // ==================== 类型检测函数 ====================
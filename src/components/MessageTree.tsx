@@ -57,7 +57,7 @@ export function MessageTree({ filePath, lazy = false, onTreeLoaded, className }:
 
     try {
       const result = await invoke<{
-        tree: ConversationTree;
+        tree: ConversationTree | null;
         parse_duration_ms: number;
         message_count: number;
         max_depth: number;
@@ -65,11 +65,14 @@ export function MessageTree({ filePath, lazy = false, onTreeLoaded, className }:
         filePath,
       });
 
-      setTree(result.tree);
+      // 非增量解析总是返回完整的消息树
+      if (result.tree) {
+        setTree(result.tree);
 
-      // 调用回调通知父组件
-      if (onTreeLoaded) {
-        onTreeLoaded(result.tree);
+        // 调用回调通知父组件
+        if (onTreeLoaded) {
+          onTreeLoaded(result.tree);
+        }
       }
     } catch (err) {
       const errorMessage = err instanceof Error ? err.message : String(err);
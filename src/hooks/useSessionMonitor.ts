@@ -27,6 +27,31 @@ export interface SessionChangeEvent {
   timestamp: string;
 }
 
+/**
+ * 结构化会话事件（`session-event`）
+ *
+ * 由后端增量读取会话文件生成，可直接追加到会话视图而无需重新解析
+ */
+export type SessionLiveEvent =
+  | { kind: 'session_created'; sessionId: string; path: string }
+  | {
+      kind: 'messages_appended';
+      sessionId: string;
+      path: string;
+      /** 新增消息数量 */
+      count: number;
+      /** 最后一条新增消息的 UUID */
+      lastUuid: string | null;
+      /** 新增消息（数量过多时为空，需要重新加载） */
+      messages: Record<string, unknown>[];
+      /** 文件被重写，需要丢弃已有消息 */
+      reset: boolean;
+      /** 无法解析而跳过的行数 */
+      skipped: number;
+    }
+  | { kind: 'summary_updated'; sessionId: string; path: string; summary: string }
+  | { kind: 'session_deleted'; sessionId: string; path: string };
+
 /**
  * useSessionMonitor Hook 配置
  */
@@ -227,3 +252,50 @@ export function useAutoRefresh() {
     triggerRefresh,
   };
 }
+
+/**
+ * 监听结构化会话事件
+ *
+ * @example
+ * useSessionEvents((event) => {
+ *   if (event.kind === 'messages_appended' && event.sessionId === currentId) {
+ *     appendMessages(event.messages);
+ *   }
+ * });
+ */
+export function useSessionEvents(
+  onEvent: (event: SessionLiveEvent) => void,
+  enabled = true
+) {
+  const onEventRef = useRef(onEvent);
+
+  useEffect(() => {
+    onEventRef.current = onEvent;
+  }, [onEvent]);
+
+  useEffect(() => {
+    if (!enabled) return;
+
+    let unlisten: (() => void) | null = null;
+    let cancelled = false;
+
+    listen<SessionLiveEvent>('session-event', (event) => {
+      onEventRef.current(event.payload);
+    })
+      .then((unlistenFn) => {
+        if (cancelled) {
+          unlistenFn();
+        } else {
+          unlisten = unlistenFn;
+        }
+      })
+      .catch((error) => {
+        console.error('设置会话事件监听失败:', error);
+      });
+
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, [enabled]);
+}
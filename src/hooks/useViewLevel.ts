@@ -138,12 +138,23 @@ export function useExportSessionByLevel() {
       viewLevel,
       format,
       filePath,
+      customLevel,
     }: {
       sessionId: string;
       viewLevel: ViewLevel;
       format: ExportFormat;
       filePath?: string;
-    }) => exportSessionByLevel(sessionId, viewLevel, format, filePath),
+      customLevel?: string;
+    }) =>
+      exportSessionByLevel(
+        sessionId,
+        viewLevel,
+        format,
+        filePath,
+        undefined,
+        undefined,
+        customLevel
+      ),
   });
 }
 
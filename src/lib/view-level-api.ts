@@ -13,7 +13,35 @@ import { ViewLevel, type Message, type QAPair } from '@/types/viewLevel';
 /**
  * 导出格式类型
  */
-export type ExportFormat = 'markdown' | 'json';
+export type ExportFormat = 'markdown' | 'json' | 'openai' | 'sharegpt' | 'anthropic';
+
+/**
+ * 压缩分段范围：全部分段或仅最后一次压缩之后
+ */
+export type SegmentScope = 'all' | 'current';
+
+/**
+ * 消息谓词（自定义等级由谓词组合而成）
+ */
+export type MessagePredicate =
+  | { kind: 'msg_type'; values: string[] }
+  | { kind: 'content_type'; values: string[] }
+  | { kind: 'tool_name'; values: string[] }
+  | { kind: 'contains_text'; text: string; case_sensitive?: boolean }
+  | { kind: 'has_error' }
+  | { kind: 'role'; values: string[] }
+  | { kind: 'all'; predicates: MessagePredicate[] }
+  | { kind: 'any'; predicates: MessagePredicate[] }
+  | { kind: 'not'; predicate: MessagePredicate };
+
+/**
+ * 自定义等级
+ */
+export interface CustomViewLevel {
+  name: string;
+  description?: string | null;
+  predicate: MessagePredicate;
+}
 
 // ==================== API 函数 ====================
 
@@ -23,18 +51,24 @@ export type ExportFormat = 'markdown' | 'json';
  * @param sessionId - 会话 ID
  * @param viewLevel - 视图等级
  * @param filePath - 可选的文件路径（如果提供，直接使用文件而不从数据库查询）
+ * @param segmentScope - 可选的压缩分段范围（默认 all）
+ * @param customLevel - 可选的自定义等级名称（提供时忽略 viewLevel）
  * @returns 过滤后的消息列表
  */
 export async function getMessagesByLevel(
   sessionId: string,
   viewLevel: ViewLevel,
-  filePath?: string
+  filePath?: string,
+  segmentScope?: SegmentScope,
+  customLevel?: string
 ): Promise<Message[]> {
   try {
     const messages = await invoke<Message[]>('cmd_get_messages_by_level', {
       sessionId,
       viewLevel,
       filePath,
+      segmentScope,
+      customLevel,
     });
 
     return messages;
@@ -44,24 +78,81 @@ export async function getMessagesByLevel(
   }
 }
 
+/**
+ * 增量获取消息的结果
+ */
+export interface AppendedMessages {
+  /** 新追加的消息（reset 为 true 时为从头解析的全部消息） */
+  messages: Message[];
+  /** 下次调用的起始偏移量 */
+  nextOffset: number;
+  /** 起始偏移量之前内容的指纹，下次调用时与 nextOffset 一起传回（其他来源的会话为 null） */
+  nextFingerprint: string | null;
+  /** 文件已被截断或重写，应丢弃已有消息并用 messages 替换 */
+  reset: boolean;
+}
+
+/**
+ * 根据视图等级获取 fromOffset 之后追加的消息
+ *
+ * @param sessionId - 会话 ID
+ * @param viewLevel - 视图等级
+ * @param fromOffset - 上次返回的 nextOffset（首次传 0）
+ * @param fromFingerprint - 上次返回的 nextFingerprint（首次传 null）
+ * @param filePath - 可选的文件路径
+ * @param segmentScope - 可选的压缩分段范围（默认 all）
+ * @param customLevel - 可选的自定义等级名称
+ * @returns 新追加的消息、下次的起始偏移量和指纹、重置标记
+ */
+export async function getAppendedMessagesByLevel(
+  sessionId: string,
+  viewLevel: ViewLevel,
+  fromOffset: number,
+  fromFingerprint: string | null,
+  filePath?: string,
+  segmentScope?: SegmentScope,
+  customLevel?: string
+): Promise<AppendedMessages> {
+  try {
+    return await invoke<AppendedMessages>('cmd_get_appended_messages_by_level', {
+      sessionId,
+      viewLevel,
+      filePath,
+      fromOffset,
+      fromFingerprint,
+      segmentScope,
+      customLevel,
+    });
+  } catch (error) {
+    const message = getErrorMessage(error);
+    throw new Error(`获取新增消息失败: ${message}`);
+  }
+}
+
 /**
  * 根据视图等级提取问答对
  *
  * @param sessionId - 会话 ID
  * @param viewLevel - 视图等级（必须是 QAPairs）
  * @param filePath - 可选的文件路径（如果提供，直接使用文件而不从数据库查询）
+ * @param segmentScope - 可选的压缩分段范围（默认 all）
+ * @param customLevel - 可选的自定义等级名称（只保留问题或答案满足该等级的问答对）
  * @returns 问答对列表
  */
 export async function getQAPairsByLevel(
   sessionId: string,
   viewLevel: ViewLevel,
-  filePath?: string
+  filePath?: string,
+  segmentScope?: SegmentScope,
+  customLevel?: string
 ): Promise<QAPair[]> {
   try {
     const qaPairs = await invoke<QAPair[]>('cmd_get_qa_pairs_by_level', {
       sessionId,
       viewLevel,
       filePath,
+      segmentScope,
+      customLevel,
     });
 
     return qaPairs;
@@ -76,15 +167,18 @@ export async function getQAPairsByLevel(
  *
  * @param sessionId - 会话 ID
  * @param viewLevel - 视图等级
+ * @param customLevel - 可选的自定义等级名称
  */
 export async function saveViewLevelPreference(
   sessionId: string,
-  viewLevel: ViewLevel
+  viewLevel: ViewLevel,
+  customLevel?: string
 ): Promise<void> {
   try {
     await invoke('cmd_save_view_level_preference', {
       sessionId,
       viewLevel,
+      customLevel,
     });
   } catch (error) {
     const message = getErrorMessage(error);
@@ -112,6 +206,60 @@ export async function getViewLevelPreference(
   }
 }
 
+/**
+ * 获取会话选择的自定义等级
+ *
+ * @param sessionId - 会话 ID
+ * @returns 自定义等级名称（使用预设等级时为 null）
+ */
+export async function getCustomViewLevelPreference(
+  sessionId: string
+): Promise<string | null> {
+  try {
+    return await invoke<string | null>('cmd_get_custom_view_level_preference', {
+      sessionId,
+    });
+  } catch (error) {
+    return null;
+  }
+}
+
+/**
+ * 获取所有自定义等级
+ */
+export async function listCustomViewLevels(): Promise<CustomViewLevel[]> {
+  try {
+    return await invoke<CustomViewLevel[]>('cmd_list_custom_view_levels');
+  } catch (error) {
+    const message = getErrorMessage(error);
+    throw new Error(`获取自定义等级失败: ${message}`);
+  }
+}
+
+/**
+ * 保存自定义等级（同名等级会被覆盖）
+ */
+export async function saveCustomViewLevel(level: CustomViewLevel): Promise<void> {
+  try {
+    await invoke('cmd_save_custom_view_level', { level });
+  } catch (error) {
+    const message = getErrorMessage(error);
+    throw new Error(`保存自定义等级失败: ${message}`);
+  }
+}
+
+/**
+ * 删除自定义等级
+ */
+export async function deleteCustomViewLevel(name: string): Promise<boolean> {
+  try {
+    return await invoke<boolean>('cmd_delete_custom_view_level', { name });
+  } catch (error) {
+    const message = getErrorMessage(error);
+    throw new Error(`删除自定义等级失败: ${message}`);
+  }
+}
+
 /**
  * 导出会话（按视图等级过滤）
  *
@@ -119,13 +267,19 @@ export async function getViewLevelPreference(
  * @param viewLevel - 视图等级
  * @param format - 导出格式（markdown 或 json）
  * @param filePath - 可选的文件路径（如果提供，直接使用文件而不从数据库查询）
+ * @param redact - 可选，是否脱敏（默认使用脱敏配置）
+ * @param segmentScope - 可选的压缩分段范围（默认 all）
+ * @param customLevel - 可选的自定义等级名称（提供时忽略 viewLevel）
  * @returns 导出的内容字符串
  */
 export async function exportSessionByLevel(
   sessionId: string,
   viewLevel: ViewLevel,
   format: ExportFormat,
-  filePath?: string
+  filePath?: string,
+  redact?: boolean,
+  segmentScope?: SegmentScope,
+  customLevel?: string
 ): Promise<string> {
   try {
     const content = await invoke<string>('cmd_export_session_by_level', {
@@ -133,6 +287,9 @@ export async function exportSessionByLevel(
       viewLevel,
       format,
       filePath,
+      redact,
+      segmentScope,
+      customLevel,
     });
     return content;
   } catch (error) {
@@ -47,6 +47,11 @@ function parseError(error: unknown): string {
 
 // ==================== 类型定义 ====================
 
+/**
+ * 会话来源（与 Rust 后端 SessionSourceKind 对应）
+ */
+export type SessionSourceKind = 'claude_code' | 'codex_cli' | 'aider';
+
 /**
  * 监控目录接口（与 Rust 后端 MonitoredDirectory 模型对应）
  */
@@ -55,6 +60,7 @@ export interface MonitoredDirectory {
   path: string;
   name: string;
   is_active: boolean;
+  source?: SessionSourceKind;
   created_at: string;
   updated_at: string;
 }
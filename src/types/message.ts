@@ -118,6 +118,18 @@ export interface MessageNode {
    * 线程 ID（支持多线程对话）
    */
   thread_id: string | null;
+  /**
+   * 是否为侧链消息（来自 agent-*.jsonl 的子代理会话）
+   */
+  sidechain?: boolean;
+  /**
+   * 侧链所属的子代理 ID
+   */
+  sidechainAgentId?: string;
+  /**
+   * 分支标记（编辑、回退或重试产生的兄弟分支的首个节点）
+   */
+  branch?: BranchLabel;
   /**
    * 提取的元数据
    */
@@ -128,6 +140,22 @@ export interface MessageNode {
   [key: string]: any;
 }
 
+/**
+ * 分支标记
+ */
+export interface BranchLabel {
+  /** 分叉点（共同父节点）ID，首条提示词被编辑时为 null */
+  branchPointId: string | null;
+  /** 分支类型：edit（编辑/回退）或 retry（重试） */
+  kind: 'edit' | 'retry';
+  /** 分支序号（按时间顺序） */
+  index: number;
+  /** 分叉点的分支总数 */
+  count: number;
+  /** 是否为存活分支 */
+  surviving: boolean;
+}
+
 /**
  * 对话树
  */
@@ -70,6 +70,8 @@ export interface QAPair {
   answer: Message | null;
   /** 问答对的时间戳 */
   timestamp: string;
+  /** 问题所在分支是否已被回退或编辑取代 */
+  superseded: boolean;
 }
 
 /**
@@ -108,6 +110,12 @@ export enum ExportFormatType {
   Markdown = 'markdown',
   /** JSON 格式 */
   Json = 'json',
+  /** OpenAI chat messages JSONL */
+  OpenAi = 'openai',
+  /** ShareGPT 对话 JSON */
+  ShareGpt = 'sharegpt',
+  /** Anthropic Messages JSONL */
+  Anthropic = 'anthropic',
 }
 
 /**